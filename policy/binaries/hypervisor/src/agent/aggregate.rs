@@ -0,0 +1,146 @@
+//! Aggregations over arrays of JSON records, used by tools to return
+//! L2-compliant summaries (counts, totals, averages, ranges) instead of raw dumps
+//!
+//! Fields are `json_path` paths, so nested values such as `sentiment.score` can be
+//! aggregated too.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::json_path;
+
+/// Summary of a numeric field across records
+///
+/// Records without the field, or with a non-numeric value, are left out,
+/// so `count` may be lower than the number of records.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FieldSummary {
+    pub count: usize,
+    pub sum: f64,
+    pub average: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Number of records
+pub fn count(records: &[Value]) -> usize {
+    records.len()
+}
+
+/// Numeric values of `field` across records
+fn values<'a>(records: &'a [Value], field: &'a str) -> impl Iterator<Item = f64> + 'a {
+    records.iter().filter_map(move |r| json_path::get_f64(r, field))
+}
+
+/// Total of `field` across records, 0 if none has it
+pub fn sum(records: &[Value], field: &str) -> f64 {
+    values(records, field).sum()
+}
+
+/// Mean of `field` across the records that have it
+pub fn average(records: &[Value], field: &str) -> Option<f64> {
+    let (count, sum) = values(records, field).fold((0, 0.0), |(n, s), v| (n + 1, s + v));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// `(min, max)` of `field` across the records that have it
+pub fn range(records: &[Value], field: &str) -> Option<(f64, f64)> {
+    values(records, field).fold(None, |range, v| match range {
+        None => Some((v, v)),
+        Some((min, max)) => Some((f64::min(min, v), f64::max(max, v))),
+    })
+}
+
+/// Count, total, mean and range of `field` across records
+pub fn summarize(records: &[Value], field: &str) -> FieldSummary {
+    let range = range(records, field);
+
+    FieldSummary {
+        count: values(records, field).count(),
+        sum: sum(records, field),
+        average: average(records, field),
+        min: range.map(|(min, _)| min),
+        max: range.map(|(_, max)| max),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({ "score": 0.5, "mentions": 10 }),
+            json!({ "score": 0.9, "mentions": 30 }),
+            json!({ "score": 0.1 }),
+            json!({ "score": "n/a", "mentions": 20 }),
+        ]
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(count(&records()), 4);
+        assert_eq!(count(&[]), 0);
+    }
+
+    #[test]
+    fn test_sum() {
+        assert_eq!(sum(&records(), "mentions"), 60.0);
+        assert_eq!(sum(&records(), "missing"), 0.0);
+        assert_eq!(sum(&[], "mentions"), 0.0);
+    }
+
+    #[test]
+    fn test_average() {
+        assert_eq!(average(&records(), "mentions"), Some(20.0));
+        let score = average(&records(), "score").unwrap();
+        assert!((score - 0.5).abs() < 1e-9);
+        assert_eq!(average(&records(), "missing"), None);
+        assert_eq!(average(&[], "score"), None);
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(range(&records(), "score"), Some((0.1, 0.9)));
+        assert_eq!(range(&records(), "missing"), None);
+        assert_eq!(range(&[], "score"), None);
+    }
+
+    #[test]
+    fn test_nested_fields() {
+        let records = [
+            json!({ "sentiment": { "score": 0.2 }, "sources": [{ "mentions": 5 }] }),
+            json!({ "sentiment": { "score": 0.6 }, "sources": [{ "mentions": 7 }] }),
+            json!({ "sentiment": null }),
+        ];
+        assert_eq!(range(&records, "sentiment.score"), Some((0.2, 0.6)));
+        assert_eq!(sum(&records, "sources[0].mentions"), 12.0);
+        assert_eq!(summarize(&records, "sentiment.score").count, 2);
+    }
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(
+            summarize(&records(), "mentions"),
+            FieldSummary {
+                count: 3,
+                sum: 60.0,
+                average: Some(20.0),
+                min: Some(10.0),
+                max: Some(30.0),
+            }
+        );
+        assert_eq!(
+            summarize(&[], "mentions"),
+            FieldSummary {
+                count: 0,
+                sum: 0.0,
+                average: None,
+                min: None,
+                max: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,123 @@
+/// Blockchains the agent's tools accept by default
+pub const DEFAULT_SUPPORTED_CHAINS: [&str; 3] = ["ethereum", "solana", "bitcoin"];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ChainError {
+    #[error("UnsupportedChain: '{chain}' is not supported (supported: {})", supported.join(", "))]
+    UnsupportedChain {
+        chain: String,
+        supported: Vec<String>,
+    },
+}
+
+/// Configured list of blockchains, validated uniformly by every chain-aware tool
+#[derive(Debug, Clone)]
+pub struct SupportedChains(Vec<String>);
+
+impl SupportedChains {
+    pub fn new(chains: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        Self(
+            chains
+                .into_iter()
+                .map(|c| c.as_ref().to_lowercase())
+                .collect(),
+        )
+    }
+
+    pub fn list(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Check a `blockchain` argument against the list
+    /// Returns the lowercased chain name
+    pub fn resolve(&self, chain: &str) -> Result<String, ChainError> {
+        let chain = chain.to_lowercase();
+
+        if !self.0.contains(&chain) {
+            return Err(ChainError::UnsupportedChain {
+                chain,
+                supported: self.0.clone(),
+            });
+        }
+
+        Ok(chain)
+    }
+}
+
+impl Default for SupportedChains {
+    fn default() -> Self {
+        Self::new(DEFAULT_SUPPORTED_CHAINS)
+    }
+}
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Canonical form of a blockchain address, None if `s` isn't one
+///
+/// EVM addresses (`0x` + 40 hex digits, checksummed or not) are lowercased;
+/// Solana addresses (32 to 44 base58 characters) are case-sensitive and kept as is.
+pub fn normalize_address(s: &str) -> Option<String> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return (hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| format!("0x{}", hex.to_lowercase()));
+    }
+
+    ((32..=44).contains(&s.len()) && s.chars().all(|c| BASE58_ALPHABET.contains(c)))
+        .then(|| s.to_string())
+}
+
+/// Text with addresses in canonical form, other words lowercased and whitespace collapsed
+///
+/// Used before phrase matching, so the same address is seen the same way in every spelling.
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            // Keep punctuation around an address out of it, e.g. "0xabc...?"
+            let is_punctuation = |c: char| !c.is_ascii_alphanumeric();
+            let core = word.trim_matches(is_punctuation);
+            let start = word.len() - word.trim_start_matches(is_punctuation).len();
+            match normalize_address(core) {
+                Some(address) => format!(
+                    "{}{address}{}",
+                    &word[..start],
+                    &word[start + core.len()..]
+                ),
+                None => word.to_lowercase(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVM: &str = "0x52908400098527886E0F7030069857D2E4169EE7";
+    const SOLANA: &str = "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T";
+
+    #[test]
+    fn test_normalize_address() {
+        assert_eq!(normalize_address(EVM), normalize_address(&EVM.to_lowercase()));
+        assert_eq!(
+            normalize_address(&EVM.replace("0x", "0X")).unwrap(),
+            EVM.to_lowercase()
+        );
+        assert_eq!(normalize_address(SOLANA).unwrap(), SOLANA);
+
+        // Too short, not hex, or outside the base58 alphabet
+        assert_eq!(normalize_address("0x1234"), None);
+        assert_eq!(normalize_address(&EVM.replace('E', "G")), None);
+        assert_eq!(normalize_address(&SOLANA.replace('N', "0")), None);
+        assert_eq!(normalize_address("bitcoin"), None);
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        let text = format!("Who  owns {EVM}? And\t{SOLANA}.");
+        assert_eq!(
+            normalize_text(&text),
+            format!("who owns {}? and {SOLANA}.", EVM.to_lowercase())
+        );
+    }
+}
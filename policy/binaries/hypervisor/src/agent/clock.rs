@@ -0,0 +1,57 @@
+//! Time source of the agent, its tools, compliance checks and quotes
+//!
+//! Everything stamping a time reads it from an injected `Clock`, so tests can pin
+//! timestamps and move time across freshness windows.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Source of the current time
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> SystemTime;
+}
+
+/// Clock shared by the agent and the components it builds
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The system's wall clock, used in production
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock standing still at a set time until it's moved
+#[derive(Debug)]
+pub struct MockClock(Mutex<SystemTime>);
+
+impl MockClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self(Mutex::new(now))
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.0.lock().expect("clock lock poisoned") = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().expect("clock lock poisoned") += by;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().expect("clock lock poisoned")
+    }
+}
+
+/// The system clock, shared
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
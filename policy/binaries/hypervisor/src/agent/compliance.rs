@@ -1,7 +1,17 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use crate::agent::types::{AgentPlan, ComplianceResult, ToolCall};
+use crate::agent::{
+    chains::normalize_text,
+    clock::{system_clock, SharedClock},
+    json_path,
+    metrics::{rule_metrics, RuleOutcome, SharedRecorder},
+    replay::Replay,
+    types::{AgentPlan, ComplianceResult, ToolCall},
+};
+use crate::utils::models;
 
 /// Compliance checking method
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -26,6 +36,96 @@ impl LLMComplianceResult {
     pub fn is_compliant(&self) -> bool {
         self.compliant
     }
+
+    /// Err(reason) when the rule was violated
+    fn verdict(&self) -> Result<(), String> {
+        if !self.is_compliant() {
+            return Err(format!("LLM compliance check failed: {}", self.explanation));
+        }
+        Ok(())
+    }
+}
+
+/// Base URL of the chat completion API used for LLM rules by default
+const DEFAULT_LLM_API_BASE: &str = "https://api.openai.com/v1";
+
+/// Model answering LLM rules that don't name their own
+const DEFAULT_LLM_MODEL: &str = "gpt-4o";
+
+/// Temperature of LLM rules that don't set their own
+const DEFAULT_LLM_TEMPERATURE: f32 = 0.0;
+
+/// System prompt of a single-rule LLM compliance check
+const RULE_SYSTEM_PROMPT: &str = "You are a compliance checker that examines whether the tool use of the LLM agent complies with the policy set by the tool owner. Respond with a JSON object containing 'compliant' (boolean) and 'explanation' (string).";
+
+/// System prompt of a batched LLM compliance check
+const BATCH_SYSTEM_PROMPT: &str = "You are a compliance checker that examines whether the tool use of the LLM agent complies with the policies set by the tool owners. Respond with a JSON object containing 'verdicts', an array with one {'index', 'compliant', 'explanation'} object per numbered check.";
+
+/// LLM answers to (tool call, rule) pairs graded in one batched request, keyed by
+/// tool name, arguments, policy ID and rule ID
+#[derive(Debug, Clone, Default)]
+pub struct LlmVerdicts(HashMap<(String, String, String, String), LLMComplianceResult>);
+
+impl LlmVerdicts {
+    fn get(
+        &self,
+        tool_name: &str,
+        tool_arguments: &str,
+        policy_id: &str,
+        rule_id: &str,
+    ) -> Option<&LLMComplianceResult> {
+        let key = (
+            tool_name.to_string(),
+            tool_arguments.to_string(),
+            policy_id.to_string(),
+            rule_id.to_string(),
+        );
+        self.0.get(&key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A numbered check of a batched LLM compliance request
+#[derive(Debug, Serialize)]
+struct BatchedCheck<'a> {
+    index: usize,
+    tool: &'a str,
+    arguments: &'a str,
+    policy: &'a str,
+    check: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchedAnswer {
+    verdicts: Vec<BatchedVerdict>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchedVerdict {
+    index: usize,
+    #[serde(flatten)]
+    result: LLMComplianceResult,
+}
+
+impl BatchedAnswer {
+    /// Results in check order, None unless each of the `checks` got exactly one
+    fn ordered(self, checks: usize) -> Option<Vec<LLMComplianceResult>> {
+        let mut graded = vec![None; checks];
+        for verdict in self.verdicts {
+            match graded.get_mut(verdict.index) {
+                Some(slot @ None) => *slot = Some(verdict.result),
+                _ => return None,
+            }
+        }
+        graded.into_iter().collect()
+    }
 }
 
 /// A policy that defines acceptable agent behavior
@@ -37,10 +137,26 @@ pub struct Policy {
     pub name: String,
     /// Policy text/description
     pub text: String,
+    /// Whether the policy is enforced; a disabled one keeps its definition but its rules
+    /// are skipped (default: true)
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Also check the session's earlier agent queries together with the current one, so a
+    /// request spread over turns that each pass is caught (default: false)
+    #[serde(default)]
+    pub cross_turn: bool,
+    /// Log violations and report them as the tool result's `would_reject` instead of
+    /// rejecting the tool call, to observe a new policy before enforcing it (default: false)
+    #[serde(default)]
+    pub report_only: bool,
     /// Compliance checking methods for this policy
     pub methods: Vec<PolicyMethod>,
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
 /// A compliance checking method for a policy
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PolicyMethod {
@@ -58,6 +174,7 @@ pub struct PolicyRule {
     /// Rule type
     pub rule_type: PolicyRuleType,
     /// Rule parameters
+    #[serde(default)]
     pub parameters: serde_json::Value,
 }
 
@@ -69,6 +186,9 @@ pub enum PolicyRuleType {
     ProhibitedKeywords { keywords: Vec<String> },
     /// Require certain patterns to be absent
     RequiredAbsentPatterns { patterns: Vec<String> },
+    /// Restrict a tool argument, at a `json_path` path, to listed values (case-insensitive);
+    /// calls without the argument pass
+    AllowedValues { field: String, values: Vec<String> },
     /// Limit output size or raw data dumps
     OutputRestriction {
         max_raw_items: Option<usize>,
@@ -86,14 +206,130 @@ pub enum PolicyRuleType {
     /// Custom LLM-based compliance check
     LLMCompliance {
         check_prompt: String,
+        /// Guard model of this rule, instead of the checker's default
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        model: Option<String>,
+        /// Sampling temperature of this rule, instead of the checker's default
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temperature: Option<f32>,
     },
 }
 
+/// A rule that was not evaluated by a check
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SkippedRule {
+    pub policy_id: String,
+    pub rule_id: String,
+    /// Method of the skipped rule
+    pub method: ComplianceMethod,
+}
+
+/// Outcome of a tool call let through by its compliance check
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolApproval {
+    /// Rules not evaluated (disabled methods, or LLM rules without LLM compliance)
+    pub skipped_rules: Vec<SkippedRule>,
+    /// Violations of report-only policies, which would have rejected the call
+    pub would_reject: Vec<String>,
+}
+
+impl ToolApproval {
+    /// Pass a policy's `verdict` on, unless the policy is report-only: its violation is
+    /// then logged and recorded instead
+    fn admit(&mut self, policy: &Policy, verdict: Result<(), String>) -> Result<(), String> {
+        match verdict {
+            Err(reason) if policy.report_only => {
+                tracing::warn!(
+                    policy_id = %policy.id,
+                    reason = %reason,
+                    "report-only policy would reject the tool call"
+                );
+                self.would_reject.push(reason);
+                Ok(())
+            }
+            verdict => verdict,
+        }
+    }
+}
+
+impl SkippedRule {
+    fn all<'a>(
+        policy: &Policy,
+        method: &'a PolicyMethod,
+    ) -> impl Iterator<Item = SkippedRule> + 'a {
+        let policy_id = policy.id.clone();
+        let kind = method.method.clone();
+        method.rules.iter().map(move |rule| SkippedRule {
+            policy_id: policy_id.clone(),
+            rule_id: rule.id.clone(),
+            method: kind.clone(),
+        })
+    }
+}
+
+/// Compliance methods switched off at runtime, globally or per policy
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisabledMethods {
+    /// Methods disabled for every policy
+    pub global: Vec<ComplianceMethod>,
+    /// Methods disabled for individual policies, by policy ID
+    pub per_policy: std::collections::HashMap<String, Vec<ComplianceMethod>>,
+}
+
+impl DisabledMethods {
+    pub fn is_disabled(&self, policy_id: &str, method: &ComplianceMethod) -> bool {
+        self.global.contains(method)
+            || self
+                .per_policy
+                .get(policy_id)
+                .is_some_and(|methods| methods.contains(method))
+    }
+}
+
+/// Outcome of running a corpus of example queries through the deterministic checks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorpusReport {
+    pub total: usize,
+    /// Cases whose decision matched the expectation
+    pub passed: usize,
+    pub failed: usize,
+    pub mismatches: Vec<CorpusMismatch>,
+}
+
+/// A corpus case decided differently than expected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusMismatch {
+    pub query: String,
+    pub expected_compliant: bool,
+    /// Violation found, when the query was rejected
+    pub reason: String,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Policy hashes computed on the current thread, used to assert the hash is cached
+    pub(crate) static HASH_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 /// Compliance checker for agent executions
+#[derive(Clone)]
 pub struct ComplianceChecker {
     policies: Vec<Policy>,
+    /// Hash of `policies`, computed once as they never change after construction
+    policy_hash: [u8; 32],
     /// Many-to-many mapping: tool_name -> list of policy IDs
     tool_policy_map: std::collections::HashMap<String, Vec<String>>,
+    /// Methods whose rules are skipped
+    disabled_methods: DisabledMethods,
+    /// Recorded answers to LLM rules, used instead of calling the LLM
+    replay: Option<Arc<Replay>>,
+    /// Base URL of the chat completion API answering LLM rules
+    llm_api_base: String,
+    /// Time source of the decisions' timestamps
+    clock: SharedClock,
+    /// Sink of rule outcomes and timings
+    metrics: SharedRecorder,
 }
 
 impl ComplianceChecker {
@@ -101,21 +337,85 @@ impl ComplianceChecker {
     pub fn new(
         policies: Vec<Policy>,
         tool_policy_map: std::collections::HashMap<String, Vec<String>>,
+    ) -> Self {
+        let policy_hash =
+            hash_policies(&policies, &tool_policy_map, &DisabledMethods::default());
+        Self::with_policy_hash(policy_hash, policies, tool_policy_map)
+    }
+
+    /// Checker of `policies`, whose hash is already known to be `policy_hash`
+    fn with_policy_hash(
+        policy_hash: [u8; 32],
+        policies: Vec<Policy>,
+        tool_policy_map: std::collections::HashMap<String, Vec<String>>,
     ) -> Self {
         Self {
+            policy_hash,
             policies,
             tool_policy_map,
+            disabled_methods: DisabledMethods::default(),
+            replay: None,
+            llm_api_base: DEFAULT_LLM_API_BASE.to_string(),
+            clock: system_clock(),
+            metrics: rule_metrics(),
+        }
+    }
+
+    /// Send LLM rules to this chat completion API instead of OpenAI's
+    pub fn with_llm_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.llm_api_base = api_base.into();
+        self
+    }
+
+    /// Answer LLM rules from the transcript being replayed instead of calling the LLM
+    pub fn with_replay(mut self, replay: Arc<Replay>) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    /// Read the time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record rule evaluations into `metrics` instead of the process-wide counters
+    pub fn with_metrics(mut self, metrics: SharedRecorder) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Skip the rules of the given methods, reporting them in the decision
+    pub fn with_disabled_methods(mut self, disabled_methods: DisabledMethods) -> Self {
+        self.disabled_methods = disabled_methods;
+        self
+    }
+
+    /// Enable or disable LLM-based rules entirely, e.g. for environments without LLM access
+    /// When disabled, `check_tool_compliance_async` evaluates deterministic rules only
+    pub fn with_llm_enabled(mut self, enabled: bool) -> Self {
+        let global = &mut self.disabled_methods.global;
+        global.retain(|m| *m != ComplianceMethod::LLMBased);
+        if !enabled {
+            global.push(ComplianceMethod::LLMBased);
         }
+        self
+    }
+
+    pub fn llm_enabled(&self) -> bool {
+        !self.disabled_methods.global.contains(&ComplianceMethod::LLMBased)
     }
 
     /// Create a default compliance checker with the new L1-L4 policies and T1-T4 tool mappings
     pub fn default_crypto_policy() -> Self {
-        let registry = super::policy_registry::PolicyRegistry::default_crypto_policy();
+        Self::from_registry(&super::policy_registry::PolicyRegistry::default_crypto_policy())
+    }
+
+    /// Create a compliance checker from the policies and mapping of a registry
+    pub fn from_registry(registry: &super::policy_registry::PolicyRegistry) -> Self {
         let (policies, tool_policy_map) = registry.clone_data();
-        Self {
-            policies,
-            tool_policy_map,
-        }
+        Self::with_policy_hash(registry.policy_hash(), policies, tool_policy_map)
+            .with_disabled_methods(registry.disabled_methods().clone())
     }
 
     /// Get policy IDs for a given tool
@@ -134,20 +434,22 @@ impl ComplianceChecker {
         // Hash the policies
         let policy_hash = self.hash_policies();
 
-        // Check each policy
-        for policy in &self.policies {
+        // Check each enabled policy
+        for policy in self.policies.iter().filter(|policy| policy.enabled) {
             for method in &policy.methods {
                 // Only check deterministic methods in this function
                 // LLM-based checks would be done separately
                 if method.method == ComplianceMethod::Deterministic {
                     for rule in &method.rules {
-                        if let Err(reason) = self.check_rule(rule, plan, None) {
+                        if let Err(reason) = self.check_rule(&policy.id, rule, plan, None) {
                             return Ok(ComplianceResult {
                                 compliant: false,
                                 reason: format!("Policy '{}' ({}) rule '{}' violated: {}",
                                     policy.id, policy.name, rule.id, reason),
+                                no_tools: plan.intended_tool_calls.is_empty(),
                                 policy_hash: const_hex::encode(policy_hash),
                                 plan_hash: const_hex::encode(plan_hash),
+                                skipped_rules: Vec::new(),
                             });
                         }
                     }
@@ -158,126 +460,286 @@ impl ComplianceChecker {
         Ok(ComplianceResult {
             compliant: true,
             reason: "All policy checks passed".to_string(),
+            no_tools: plan.intended_tool_calls.is_empty(),
             policy_hash: const_hex::encode(policy_hash),
             plan_hash: const_hex::encode(plan_hash),
+            skipped_rules: Vec::new(),
         })
     }
 
+    /// Whether an enabled policy is checked across turns, see `check_conversation`
+    pub fn has_cross_turn_policies(&self) -> bool {
+        self.policies.iter().any(|policy| policy.enabled && policy.cross_turn)
+    }
+
+    /// Check a session's queries, oldest first and ending with the current one, together
+    /// against the deterministic rules of the `cross_turn` policies
+    /// Returns Err(reason) if the conversation as a whole violates one; violations of
+    /// report-only policies are only logged
+    pub fn check_conversation(&self, turns: &[String]) -> Result<(), String> {
+        let plan = AgentPlan {
+            system_prompt: String::new(),
+            user_query: turns.join("\n"),
+            history: Vec::new(),
+            thought_process: vec![],
+            intended_tool_calls: vec![],
+        };
+
+        for policy in self.policies.iter().filter(|policy| policy.cross_turn) {
+            for method in &policy.methods {
+                if method.method != ComplianceMethod::Deterministic
+                    || self.is_skipped(policy, method)
+                {
+                    continue;
+                }
+
+                for rule in &method.rules {
+                    if let Err(reason) = self.check_rule(&policy.id, rule, &plan, None) {
+                        let reason = format!(
+                            "Policy '{}' ({}) rule '{}' violated across {} turns: {}",
+                            policy.id,
+                            policy.name,
+                            rule.id,
+                            turns.len(),
+                            reason
+                        );
+                        if !policy.report_only {
+                            return Err(reason);
+                        }
+                        tracing::warn!(
+                            policy_id = %policy.id,
+                            reason = %reason,
+                            "report-only policy would reject the conversation"
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run example queries through the deterministic rules of every policy, comparing
+    /// each decision with whether the query is expected to be compliant
+    pub fn evaluate_corpus(&self, cases: &[(impl AsRef<str>, bool)]) -> CorpusReport {
+        let mut report = CorpusReport {
+            total: cases.len(),
+            ..Default::default()
+        };
+
+        for (query, expected_compliant) in cases {
+            let plan = AgentPlan {
+                system_prompt: String::new(),
+                user_query: query.as_ref().to_string(),
+                history: Vec::new(),
+                thought_process: vec![],
+                intended_tool_calls: vec![],
+            };
+            let result = self
+                .check_compliance(&plan)
+                .expect("deterministic checks don't fail");
+
+            if result.compliant == *expected_compliant {
+                report.passed += 1;
+            } else {
+                report.failed += 1;
+                report.mismatches.push(CorpusMismatch {
+                    query: plan.user_query,
+                    expected_compliant: *expected_compliant,
+                    reason: result.reason,
+                });
+            }
+        }
+
+        report
+    }
+
     /// Check compliance for a specific tool call against its policies
     /// Returns Ok(()) if compliant, Err(reason) if not
+    /// Only deterministic rules are evaluated; see `check_tool_compliance_deterministic_only`
+    /// for the LLM rules this leaves out
     pub fn check_tool_compliance(
         &self,
         tool_name: &str,
         user_query: &str,
         tool_arguments: &str,
     ) -> Result<(), String> {
-        // Get policy IDs for this tool
-        let policy_ids = self.get_policy_ids_for_tool(tool_name);
-
-        if policy_ids.is_empty() {
-            // No policies for this tool, allow it
-            return Ok(());
-        }
+        self.check_tool_compliance_deterministic_only(tool_name, user_query, tool_arguments)
+            .map(|_| ())
+    }
 
-        // Check each policy
-        for policy_id in &policy_ids {
-            let policy = self
-                .policies
-                .iter()
-                .find(|p| p.id == *policy_id)
-                .ok_or_else(|| format!("Policy '{}' not found for tool '{}'", policy_id, tool_name))?;
+    /// Check a tool call against the deterministic rules of its policies, without network
+    /// Returns the skipped LLM-based and disabled rules and the report-only violations if
+    /// compliant, Err(reason) if not
+    pub fn check_tool_compliance_deterministic_only(
+        &self,
+        tool_name: &str,
+        user_query: &str,
+        tool_arguments: &str,
+    ) -> Result<ToolApproval, String> {
+        let mut approval = ToolApproval::default();
 
-            // Check each method (only deterministic for now)
+        for policy in self.tool_policies(tool_name)? {
+            let mut verdict = Ok(());
             for method in &policy.methods {
-                if method.method == ComplianceMethod::Deterministic {
-                    for rule in &method.rules {
-                        // Create temporary plan with just this tool call
-                        let temp_plan = AgentPlan {
-                            system_prompt: String::new(),
-                            user_query: user_query.to_string(),
-                            thought_process: vec![],
-                            intended_tool_calls: vec![ToolCall {
-                                id: uuid::Uuid::now_v7(),
-                                tool_name: tool_name.to_string(),
-                                arguments: tool_arguments.to_string(),
-                                timestamp: std::time::SystemTime::now(),
-                                compliance_quote: None,
-                            }],
-                        };
-
-                        if let Err(reason) = self.check_rule(rule, &temp_plan, None) {
-                            return Err(format!(
-                                "Tool '{}' policy '{}' ({}) rule '{}' violated: {}",
-                                tool_name, policy.id, policy.name, rule.id, reason
-                            ));
-                        }
+                if self.is_skipped(policy, method) {
+                    approval.skipped_rules.extend(SkippedRule::all(policy, method));
+                    continue;
+                }
+
+                match method.method {
+                    ComplianceMethod::Deterministic => {
+                        verdict = verdict.and_then(|()| {
+                            self.check_deterministic_method(
+                                policy,
+                                method,
+                                tool_name,
+                                user_query,
+                                tool_arguments,
+                            )
+                        })
+                    }
+                    ComplianceMethod::LLMBased => {
+                        approval.skipped_rules.extend(SkippedRule::all(policy, method))
                     }
                 }
             }
+            approval.admit(policy, verdict)?;
         }
 
-        Ok(())
+        Ok(approval)
     }
 
-    /// Check compliance for a specific tool call against its policies (with LLM support)
-    /// Returns Ok(()) if compliant, Err(reason) if not
-    pub async fn check_tool_compliance_async(
+    /// Whether a method's rules are skipped: its policy is disabled, or the method is
+    fn is_skipped(&self, policy: &Policy, method: &PolicyMethod) -> bool {
+        !policy.enabled || self.disabled_methods.is_disabled(&policy.id, &method.method)
+    }
+
+    /// Policies that apply to a tool (none means the tool is allowed)
+    fn tool_policies(&self, tool_name: &str) -> Result<Vec<&Policy>, String> {
+        self.get_policy_ids_for_tool(tool_name)
+            .iter()
+            .map(|policy_id| {
+                self.policies
+                    .iter()
+                    .find(|p| p.id == *policy_id)
+                    .ok_or_else(|| format!("Policy '{}' not found for tool '{}'", policy_id, tool_name))
+            })
+            .collect()
+    }
+
+    /// Check a tool call against the rules of a deterministic method
+    fn check_deterministic_method(
         &self,
+        policy: &Policy,
+        method: &PolicyMethod,
         tool_name: &str,
         user_query: &str,
         tool_arguments: &str,
-        openai_api_key: Option<&str>,
     ) -> Result<(), String> {
-        // Get policy IDs for this tool
-        let policy_ids = self.get_policy_ids_for_tool(tool_name);
+        // Create temporary plan with just this tool call
+        let temp_plan = AgentPlan {
+            system_prompt: String::new(),
+            user_query: user_query.to_string(),
+            history: Vec::new(),
+            thought_process: vec![],
+            intended_tool_calls: vec![ToolCall {
+                id: uuid::Uuid::now_v7(),
+                tool_name: tool_name.to_string(),
+                arguments: tool_arguments.to_string(),
+                timestamp: self.clock.now(),
+                compliance_quote: None,
+                thought_step: None,
+            }],
+        };
 
-        if policy_ids.is_empty() {
-            // No policies for this tool, allow it
-            return Ok(());
+        for rule in &method.rules {
+            if let Err(reason) = self.check_rule(&policy.id, rule, &temp_plan, None) {
+                return Err(format!(
+                    "Tool '{}' policy '{}' ({}) rule '{}' violated: {}",
+                    tool_name, policy.id, policy.name, rule.id, reason
+                ));
+            }
         }
 
-        // Check each policy
-        for policy_id in &policy_ids {
-            let policy = self
-                .policies
-                .iter()
-                .find(|p| p.id == *policy_id)
-                .ok_or_else(|| format!("Policy '{}' not found for tool '{}'", policy_id, tool_name))?;
+        Ok(())
+    }
 
-            // Check each method
-            for method in &policy.methods {
-                match method.method {
-                    ComplianceMethod::Deterministic => {
-                        for rule in &method.rules {
-                            // Create temporary plan with just this tool call
-                            let temp_plan = AgentPlan {
-                                system_prompt: String::new(),
-                                user_query: user_query.to_string(),
-                                thought_process: vec![],
-                                intended_tool_calls: vec![ToolCall {
-                                    id: uuid::Uuid::now_v7(),
-                                    tool_name: tool_name.to_string(),
-                                    arguments: tool_arguments.to_string(),
-                                    timestamp: std::time::SystemTime::now(),
-                                    compliance_quote: None,
-                                }],
-                            };
+    /// Check compliance for a specific tool call against its policies (with LLM support)
+    /// Returns the skipped rules and the report-only violations if compliant, Err(reason)
+    /// if not
+    /// Rules of disabled methods are skipped, and LLM rules too without an API key
+    pub async fn check_tool_compliance_async(
+        &self,
+        tool_name: &str,
+        user_query: &str,
+        tool_arguments: &str,
+        openai_api_key: Option<&str>,
+    ) -> Result<ToolApproval, String> {
+        self.check_tool_compliance_graded(
+            tool_name,
+            user_query,
+            tool_arguments,
+            openai_api_key,
+            &LlmVerdicts::default(),
+        )
+        .await
+    }
 
-                            if let Err(reason) = self.check_rule(rule, &temp_plan, None) {
-                                return Err(format!(
-                                    "Tool '{}' policy '{}' ({}) rule '{}' violated: {}",
-                                    tool_name, policy.id, policy.name, rule.id, reason
-                                ));
-                            }
-                        }
+    /// `check_tool_compliance_async`, taking the LLM rules graded by `grade_llm_rules`
+    /// from `verdicts` and asking the LLM only for the others
+    pub async fn check_tool_compliance_graded(
+        &self,
+        tool_name: &str,
+        user_query: &str,
+        tool_arguments: &str,
+        openai_api_key: Option<&str>,
+        verdicts: &LlmVerdicts,
+    ) -> Result<ToolApproval, String> {
+        let mut approval = ToolApproval::default();
+
+        for policy in self.tool_policies(tool_name)? {
+            let skipped = &mut approval.skipped_rules;
+            let verdict = async {
+                // Check each method
+                for method in &policy.methods {
+                    if self.is_skipped(policy, method) {
+                        skipped.extend(SkippedRule::all(policy, method));
+                        continue;
                     }
-                    ComplianceMethod::LLMBased => {
-                        if let Some(api_key) = openai_api_key {
+
+                    match method.method {
+                        ComplianceMethod::Deterministic => self.check_deterministic_method(
+                            policy,
+                            method,
+                            tool_name,
+                            user_query,
+                            tool_arguments,
+                        )?,
+                        ComplianceMethod::LLMBased => {
+                            let Some(api_key) = openai_api_key else {
+                                // If no API key provided, skip LLM checks
+                                skipped.extend(SkippedRule::all(policy, method));
+                                continue;
+                            };
                             for rule in &method.rules {
-                                if let Err(reason) = self
-                                    .check_llm_rule(rule, &policy.text, tool_name, user_query, tool_arguments, api_key)
-                                    .await
-                                {
+                                let graded =
+                                    verdicts.get(tool_name, tool_arguments, &policy.id, &rule.id);
+                                let verdict = match graded {
+                                    Some(result) => result.verdict(),
+                                    None => {
+                                        self.check_llm_rule(
+                                            policy,
+                                            rule,
+                                            tool_name,
+                                            user_query,
+                                            tool_arguments,
+                                            api_key,
+                                        )
+                                        .await
+                                    }
+                                };
+                                if let Err(reason) = verdict {
                                     return Err(format!(
                                         "Tool '{}' policy '{}' ({}) LLM rule '{}' violated: {}",
                                         tool_name, policy.id, policy.name, rule.id, reason
@@ -285,7 +747,187 @@ impl ComplianceChecker {
                                 }
                             }
                         }
-                        // If no API key provided, skip LLM checks
+                    }
+                }
+
+                Ok(())
+            }
+            .await;
+            approval.admit(policy, verdict)?;
+        }
+
+        Ok(approval)
+    }
+
+    /// Grade the LLM rules of every tool call in the plan with one request, instead of
+    /// one per tool and rule
+    ///
+    /// Returns no verdicts when fewer than two rules apply, when replaying, or when the
+    /// answer is malformed or incomplete; each rule is then checked on its own.
+    pub async fn grade_llm_rules(&self, plan: &AgentPlan, openai_api_key: &str) -> LlmVerdicts {
+        use tracing::{debug, info, warn};
+
+        if self.replay.is_some() {
+            return LlmVerdicts::default();
+        }
+
+        let mut keys = Vec::new();
+        let mut checks = Vec::new();
+        for tool_call in &plan.intended_tool_calls {
+            let Ok(policies) = self.tool_policies(&tool_call.tool_name) else {
+                continue;
+            };
+            for policy in policies {
+                let methods = policy.methods.iter().filter(|method| {
+                    method.method == ComplianceMethod::LLMBased && !self.is_skipped(policy, method)
+                });
+                for rule in methods.flat_map(|method| &method.rules) {
+                    // Rules with their own model or temperature are asked on their own
+                    let PolicyRuleType::LLMCompliance {
+                        check_prompt,
+                        model: None,
+                        temperature: None,
+                    } = &rule.rule_type
+                    else {
+                        continue;
+                    };
+                    let key = (
+                        tool_call.tool_name.clone(),
+                        tool_call.arguments.clone(),
+                        policy.id.clone(),
+                        rule.id.clone(),
+                    );
+                    if keys.contains(&key) {
+                        continue;
+                    }
+                    keys.push(key);
+                    checks.push(BatchedCheck {
+                        index: checks.len(),
+                        tool: &tool_call.tool_name,
+                        arguments: &tool_call.arguments,
+                        policy: &policy.text,
+                        check: check_prompt,
+                    });
+                }
+            }
+        }
+        if checks.len() < 2 {
+            return LlmVerdicts::default();
+        }
+
+        let full_prompt = format!(
+            "User Query: {}\n\nChecks:\n{}\n\nGrade every check: does the tool call comply with the policy, given the check? Respond in JSON format with one verdict per check:\n\nExample: {{\"verdicts\": [{{\"index\": 0, \"compliant\": true, \"explanation\": \"The call does not violate the policy because...\"}}]}}",
+            plan.user_query,
+            serde_json::to_string_pretty(&checks).expect("checks serialize"),
+        );
+
+        info!(
+            checks = checks.len(),
+            "[LLM_COMPLIANCE_CHECK] Starting batched compliance check call"
+        );
+        debug!(prompt = %full_prompt, "[LLM_COMPLIANCE_CHECK] Full prompt");
+
+        let max_tokens = 100 + 150 * checks.len() as u32;
+        let started = Instant::now();
+        let answer = request_llm_compliance(
+            &self.llm_api_base,
+            DEFAULT_LLM_MODEL,
+            DEFAULT_LLM_TEMPERATURE,
+            BATCH_SYSTEM_PROMPT,
+            &full_prompt,
+            max_tokens,
+            openai_api_key,
+        )
+        .await
+        .and_then(|answer| {
+            debug!(response = %answer, "[LLM_COMPLIANCE_CHECK] Response");
+            serde_json::from_str::<BatchedAnswer>(answer.trim()).map_err(|e| e.to_string())
+        });
+
+        let Some(graded) = answer.ok().and_then(|answer| answer.ordered(checks.len())) else {
+            warn!(
+                checks = checks.len(),
+                "batched compliance answer is malformed, checking rules one by one"
+            );
+            return LlmVerdicts::default();
+        };
+
+        // The verdicts share the batch call's time evenly
+        let elapsed = started.elapsed() / checks.len() as u32;
+        for ((_, _, policy_id, rule_id), result) in keys.iter().zip(&graded) {
+            let outcome = RuleOutcome::of(&result.verdict());
+            self.metrics.record_rule(policy_id, rule_id, outcome, elapsed);
+        }
+
+        LlmVerdicts(keys.into_iter().zip(graded).collect())
+    }
+
+    /// Whether a call's output must be cut down to its summary before use: the call omits
+    /// `address`, asking for the full dump, and an enabled deterministic OutputRestriction
+    /// of the tool's policies (L2) requires aggregation
+    pub fn requires_summary(&self, tool_name: &str, tool_arguments: &str) -> bool {
+        let full_dump = serde_json::from_str::<serde_json::Value>(tool_arguments)
+            .is_ok_and(|args| json_path::get(&args, "address").is_none());
+        if !full_dump {
+            return false;
+        }
+
+        self.tool_policies(tool_name)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|policy| {
+                policy
+                    .methods
+                    .iter()
+                    .filter(|method| method.method == ComplianceMethod::Deterministic)
+                    .filter(|method| !self.is_skipped(policy, method))
+            })
+            .flat_map(|method| &method.rules)
+            .any(|rule| {
+                matches!(
+                    rule.rule_type,
+                    PolicyRuleType::OutputRestriction {
+                        require_aggregation: true,
+                        ..
+                    }
+                )
+            })
+    }
+
+    /// Check a tool's output against the output-scoped deterministic rules of its policies:
+    /// OutputRestriction, NoIdentityInference and RequireAttribution
+    /// Returns Err(reason) if the output must be kept out of the final prompt; violations of
+    /// report-only policies are only logged
+    pub fn check_result_compliance(
+        &self,
+        tool_name: &str,
+        result_json: &str,
+    ) -> Result<(), String> {
+        let result = serde_json::from_str(result_json)
+            .unwrap_or_else(|_| serde_json::Value::String(result_json.to_string()));
+
+        for policy in self.tool_policies(tool_name)? {
+            for method in &policy.methods {
+                if method.method != ComplianceMethod::Deterministic
+                    || self.is_skipped(policy, method)
+                {
+                    continue;
+                }
+
+                for rule in &method.rules {
+                    if let Err(reason) = check_result_rule(rule, &result, result_json) {
+                        let reason = format!(
+                            "Tool '{}' result violates policy '{}' ({}) rule '{}': {}",
+                            tool_name, policy.id, policy.name, rule.id, reason
+                        );
+                        if !policy.report_only {
+                            return Err(reason);
+                        }
+                        tracing::warn!(
+                            policy_id = %policy.id,
+                            reason = %reason,
+                            "report-only policy would reject the tool result"
+                        );
                     }
                 }
             }
@@ -294,8 +936,27 @@ impl ComplianceChecker {
         Ok(())
     }
 
-    /// Check an LLM-based rule
+    /// Check an LLM-based rule, recording its outcome and timing
     async fn check_llm_rule(
+        &self,
+        policy: &Policy,
+        rule: &PolicyRule,
+        tool_name: &str,
+        user_query: &str,
+        tool_arguments: &str,
+        openai_api_key: &str,
+    ) -> Result<(), String> {
+        let started = Instant::now();
+        let result = self
+            .ask_llm_rule(rule, &policy.text, tool_name, user_query, tool_arguments, openai_api_key)
+            .await;
+        let outcome = RuleOutcome::of(&result);
+        self.metrics.record_rule(&policy.id, &rule.id, outcome, started.elapsed());
+
+        result
+    }
+
+    async fn ask_llm_rule(
         &self,
         rule: &PolicyRule,
         policy_text: &str,
@@ -306,7 +967,12 @@ impl ComplianceChecker {
     ) -> Result<(), String> {
         use tracing::{info, debug};
         
-        if let PolicyRuleType::LLMCompliance { check_prompt } = &rule.rule_type {
+        if let PolicyRuleType::LLMCompliance {
+            check_prompt,
+            model,
+            temperature,
+        } = &rule.rule_type
+        {
             let context = format!(
                 "Tool: {}\nUser Query: {}\nTool Arguments: {}",
                 tool_name, user_query, tool_arguments
@@ -320,75 +986,67 @@ impl ComplianceChecker {
             info!("[LLM_COMPLIANCE_CHECK] Starting OpenAI compliance check call");
             debug!("[LLM_COMPLIANCE_CHECK] Rule ID: {}", rule.id);
             debug!("[LLM_COMPLIANCE_CHECK] Tool: {}", tool_name);
-            debug!("[LLM_COMPLIANCE_CHECK] Full prompt: {}", full_prompt);
-
-            // Call OpenAI API
-            let client = reqwest::Client::new();
-            let request_body = serde_json::json!({
-                "model": "gpt-4o",
-                "messages": [
-                    {
-                        "role": "system",
-                        "content": "You are a compliance checker that examines whether the tool use of the LLM agent complies with the policy set by the tool owner. Respond with a JSON object containing 'compliant' (boolean) and 'explanation' (string)."
-                    },
-                    {
-                        "role": "user",
-                        "content": full_prompt
-                    }
-                ],
-                "temperature": 0.0,
-                "max_tokens": 150,
-                "response_format": { "type": "json_object" }
-            });
-
-            let response = client
-                .post("https://api.openai.com/v1/chat/completions")
-                .header("Authorization", format!("Bearer {}", openai_api_key))
-                .header("Content-Type", "application/json")
-                .json(&request_body)
-                .send()
-                .await
-                .map_err(|e| format!("Failed to call OpenAI API: {}", e))?;
-
-            if !response.status().is_success() {
-                let error_text = response.text().await.unwrap_or_default();
-                info!("[LLM_COMPLIANCE_CHECK] API error: {}", error_text);
-                return Err(format!("OpenAI API error: {}", error_text));
-            }
-
-            let openai_response: serde_json::Value = response
-                .json()
-                .await
-                .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
-
-            let llm_result = openai_response["choices"][0]["message"]["content"]
-                .as_str()
-                .ok_or_else(|| "Invalid OpenAI response format".to_string())?
-                .trim();
+            debug!(prompt = %full_prompt, "[LLM_COMPLIANCE_CHECK] Full prompt");
+
+            let llm_result = match &self.replay {
+                Some(replay) => replay.next_compliance()?,
+                None => {
+                    request_llm_compliance(
+                        &self.llm_api_base,
+                        model.as_deref().unwrap_or(DEFAULT_LLM_MODEL),
+                        temperature.unwrap_or(DEFAULT_LLM_TEMPERATURE),
+                        RULE_SYSTEM_PROMPT,
+                        &full_prompt,
+                        150,
+                        openai_api_key,
+                    )
+                    .await?
+                }
+            };
+            let llm_result = llm_result.trim();
 
             info!("[LLM_COMPLIANCE_CHECK] Response received ({} chars)", llm_result.len());
-            debug!("[LLM_COMPLIANCE_CHECK] Response: {}", llm_result);
+            debug!(response = %llm_result, "[LLM_COMPLIANCE_CHECK] Response");
 
-            // Parse the JSON result
+            // Parse the JSON result; the response is only logged, as it may quote the query
             let compliance_result: LLMComplianceResult = serde_json::from_str(llm_result)
-                .map_err(|e| format!("Failed to parse LLM compliance result: {}. Response: {}", e, llm_result))?;
+                .map_err(|e| format!("Failed to parse LLM compliance result: {}", e))?;
 
-            info!("[LLM_COMPLIANCE_CHECK] Compliance result: compliant={}, explanation='{}'", 
-                  compliance_result.compliant, compliance_result.explanation);
-
-            if !compliance_result.is_compliant() {
-                return Err(format!("LLM compliance check failed: {}", compliance_result.explanation));
-            }
+            info!(
+                compliant = compliance_result.compliant,
+                explanation = %compliance_result.explanation,
+                "[LLM_COMPLIANCE_CHECK] Compliance result"
+            );
 
-            Ok(())
+            compliance_result.verdict()
         } else {
             Ok(())
         }
     }
 
+    /// Check a single rule of `policy_id` against plan, recording its outcome and timing
+    fn check_rule(
+        &self,
+        policy_id: &str,
+        rule: &PolicyRule,
+        plan: &AgentPlan,
+        response: Option<&str>,
+    ) -> Result<(), String> {
+        let started = Instant::now();
+        let result = self.evaluate_rule(rule, plan, response);
+        self.metrics.record_rule(policy_id, &rule.id, RuleOutcome::of(&result), started.elapsed());
+
+        result
+    }
+
     /// Check a single rule against plan
     /// Optional response parameter for checking output-related rules
-    fn check_rule(&self, rule: &PolicyRule, plan: &AgentPlan, response: Option<&str>) -> Result<(), String> {
+    fn evaluate_rule(
+        &self,
+        rule: &PolicyRule,
+        plan: &AgentPlan,
+        response: Option<&str>,
+    ) -> Result<(), String> {
         match &rule.rule_type {
             PolicyRuleType::ProhibitedKeywords { keywords } => {
                 let query_lower = plan.user_query.to_lowercase();
@@ -430,6 +1088,23 @@ impl ComplianceChecker {
                 }
                 Ok(())
             }
+            PolicyRuleType::AllowedValues { field, values } => {
+                for tool_call in &plan.intended_tool_calls {
+                    let Ok(args) = serde_json::from_str::<serde_json::Value>(&tool_call.arguments)
+                    else {
+                        continue;
+                    };
+                    let value = match json_path::get(&args, field) {
+                        None | Some(serde_json::Value::Null) => continue,
+                        Some(serde_json::Value::String(value)) => value.clone(),
+                        Some(value) => value.to_string(),
+                    };
+                    if !values.iter().any(|allowed| allowed.eq_ignore_ascii_case(&value)) {
+                        return Err(format!("Value '{value}' of '{field}' is not allowed"));
+                    }
+                }
+                Ok(())
+            }
             PolicyRuleType::OutputRestriction { max_raw_items: _, require_aggregation } => {
                 // This check would typically be done on the response
                 // For now, we'll just validate the rule exists
@@ -450,21 +1125,30 @@ impl ComplianceChecker {
                 Ok(())
             }
             PolicyRuleType::NoIdentityInference { prohibited_terms } => {
-                let query_lower = plan.user_query.to_lowercase();
+                // Addresses are normalized so every spelling of one is matched alike
+                let query = normalize_text(&plan.user_query);
+                let terms: Vec<_> = prohibited_terms.iter().map(|t| normalize_text(t)).collect();
 
-                for term in prohibited_terms {
-                    let term_lower = term.to_lowercase();
-                    if query_lower.contains(&term_lower) {
+                for (term, normalized) in prohibited_terms.iter().zip(&terms) {
+                    if query.contains(normalized.as_str()) {
                         return Err(format!("Identity inference term '{}' found", term));
                     }
+
+                    for tool_call in &plan.intended_tool_calls {
+                        if normalize_text(&tool_call.arguments).contains(normalized.as_str()) {
+                            return Err(format!(
+                                "Identity inference term '{}' found in tool arguments",
+                                term
+                            ));
+                        }
+                    }
                 }
 
                 // Also check in response if provided
                 if let Some(resp) = response {
-                    let resp_lower = resp.to_lowercase();
-                    for term in prohibited_terms {
-                        let term_lower = term.to_lowercase();
-                        if resp_lower.contains(&term_lower) {
+                    let resp = normalize_text(resp);
+                    for (term, normalized) in prohibited_terms.iter().zip(&terms) {
+                        if resp.contains(normalized.as_str()) {
                             return Err(format!("Identity inference term '{}' found in response", term));
                         }
                     }
@@ -476,14 +1160,8 @@ impl ComplianceChecker {
                 if let Some(resp) = response {
                     let resp_lower = resp.to_lowercase();
 
-                    if *require_source {
-                        let has_source = resp_lower.contains("according to")
-                            || resp_lower.contains("source:")
-                            || resp_lower.contains("from");
-
-                        if !has_source {
-                            return Err("Response must include source attribution".to_string());
-                        }
+                    if *require_source && !has_source_attribution(resp) {
+                        return Err("Response must include source attribution".to_string());
                     }
 
                     if *require_timestamp {
@@ -529,38 +1207,246 @@ impl ComplianceChecker {
     }
 
     /// Hash policies for attestation
-    fn hash_policies(&self) -> [u8; 32] {
-        let mut hasher = blake3::Hasher::new();
+    /// Hash of the policies checked against (hex-encoded), as reported in decisions
+    pub fn policy_hash(&self) -> String {
+        const_hex::encode(self.policy_hash)
+    }
 
-        for policy in &self.policies {
-            hasher.update(policy.id.as_bytes());
-            hasher.update(policy.name.as_bytes());
-            hasher.update(policy.text.as_bytes());
+    /// Hash of the policies checked against, attested at `GET /verifiable/policy`
+    pub fn hash_policies(&self) -> [u8; 32] {
+        self.policy_hash
+    }
 
-            for method in &policy.methods {
-                let method_json = serde_json::to_string(&method.method).unwrap_or_default();
-                hasher.update(method_json.as_bytes());
+    /// Get the policies
+    pub fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
+}
 
-                for rule in &method.rules {
-                    hasher.update(rule.id.as_bytes());
-                    let rule_json = serde_json::to_string(&rule.rule_type).unwrap_or_default();
-                    hasher.update(rule_json.as_bytes());
-                }
+/// Hash of `policies`, the tools they apply to and the methods switched off for them,
+/// covering everything that changes a decision
+pub(crate) fn hash_policies(
+    policies: &[Policy],
+    tool_policy_map: &HashMap<String, Vec<String>>,
+    disabled_methods: &DisabledMethods,
+) -> [u8; 32] {
+    #[cfg(test)]
+    HASH_COUNT.with(|count| count.set(count.get() + 1));
+
+    let mut hasher = blake3::Hasher::new();
+
+    for policy in policies {
+        hasher.update(policy.id.as_bytes());
+        hasher.update(policy.name.as_bytes());
+        hasher.update(policy.text.as_bytes());
+        // Only marked when disabled, so hashes of fully enabled sets are unchanged
+        if !policy.enabled {
+            hasher.update(b"disabled");
+        }
+        if policy.cross_turn {
+            hasher.update(b"cross_turn");
+        }
+        if policy.report_only {
+            hasher.update(b"report_only");
+        }
+
+        for method in &policy.methods {
+            let method_json = serde_json::to_string(&method.method).unwrap_or_default();
+            hasher.update(method_json.as_bytes());
+
+            for rule in &method.rules {
+                hasher.update(rule.id.as_bytes());
+                let rule_json = serde_json::to_string(&rule.rule_type).unwrap_or_default();
+                hasher.update(rule_json.as_bytes());
             }
         }
+    }
 
-        hasher.finalize().into()
+    // Unmapping a policy from a tool switches it off for the tool's calls
+    let tool_policies: std::collections::BTreeMap<_, _> = tool_policy_map.iter().collect();
+    hasher.update(b"tool_policies");
+    hasher.update(serde_json::to_string(&tool_policies).unwrap_or_default().as_bytes());
+
+    // Skipping a method's rules switches them off as surely as removing them; only marked
+    // when some are skipped, so hashes of fully enabled sets are unchanged
+    let method_json = |method| serde_json::to_string(method).unwrap_or_default();
+    let mut disabled: Vec<_> = disabled_methods
+        .global
+        .iter()
+        .map(|method| (String::new(), method_json(method)))
+        .chain(disabled_methods.per_policy.iter().flat_map(|(id, methods)| {
+            methods.iter().map(|method| (id.clone(), method_json(method)))
+        }))
+        .collect();
+    disabled.sort();
+    disabled.dedup();
+    if !disabled.is_empty() {
+        hasher.update(b"disabled_methods");
+        hasher.update(serde_json::to_string(&disabled).unwrap_or_default().as_bytes());
     }
 
-    /// Get the policies
-    pub fn policies(&self) -> &[Policy] {
-        &self.policies
+    hasher.finalize().into()
+}
+
+/// Check a rule against a tool's output, `result` being `raw` parsed (a JSON string if it
+/// isn't JSON); rules about the query or plan don't apply to outputs
+fn check_result_rule(
+    rule: &PolicyRule,
+    result: &serde_json::Value,
+    raw: &str,
+) -> Result<(), String> {
+    match &rule.rule_type {
+        PolicyRuleType::OutputRestriction {
+            max_raw_items,
+            require_aggregation,
+        } => {
+            let items = largest_array(result);
+            if let Some(max) = max_raw_items.filter(|max| items > *max) {
+                return Err(format!("raw dump of {items} items, more than {max}"));
+            }
+            if *require_aggregation && items > 0 && !has_key(result, "summary") {
+                return Err(format!("{items} raw items without an aggregated summary"));
+            }
+            Ok(())
+        }
+        PolicyRuleType::NoIdentityInference { prohibited_terms } => {
+            let raw = normalize_text(raw);
+            match prohibited_terms
+                .iter()
+                .find(|term| raw.contains(normalize_text(term).as_str()))
+            {
+                Some(term) => Err(format!("identity inference term '{term}' found in result")),
+                None => Ok(()),
+            }
+        }
+        PolicyRuleType::RequireAttribution {
+            require_source,
+            require_timestamp,
+        } => {
+            let field = |path: &str| {
+                json_path::get_str(result, path).is_some_and(|v| !v.trim().is_empty())
+            };
+            if *require_source && !field("source") {
+                return Err("result has no source".to_string());
+            }
+            if *require_timestamp && !field("timestamp") {
+                return Err("result has no timestamp".to_string());
+            }
+            Ok(())
+        }
+        PolicyRuleType::ProhibitedKeywords { .. }
+        | PolicyRuleType::RequiredAbsentPatterns { .. }
+        | PolicyRuleType::AllowedValues { .. }
+        | PolicyRuleType::LLMCompliance { .. } => Ok(()),
+    }
+}
+
+/// Whether `response` attributes its data to a source, e.g. "according to ..."
+pub fn has_source_attribution(response: &str) -> bool {
+    let response = response.to_lowercase();
+    ["according to", "source:", "from"]
+        .iter()
+        .any(|phrase| response.contains(phrase))
+}
+
+/// Whether `response` attributes data to `source` by name, ignoring case and spacing
+pub fn cites_source(response: &str, source: &str) -> bool {
+    let source = normalize_text(source);
+    !source.is_empty()
+        && has_source_attribution(response)
+        && normalize_text(response).contains(&source)
+}
+
+/// Length of the longest array in a JSON value, at any depth
+fn largest_array(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(largest_array)
+            .fold(items.len(), usize::max),
+        serde_json::Value::Object(fields) => fields.values().map(largest_array).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Whether an object at any depth of a JSON value has the key
+fn has_key(value: &serde_json::Value, key: &str) -> bool {
+    match value {
+        serde_json::Value::Array(items) => items.iter().any(|item| has_key(item, key)),
+        serde_json::Value::Object(fields) => {
+            fields.contains_key(key) || fields.values().any(|field| has_key(field, key))
+        }
+        _ => false,
+    }
+}
+
+/// Ask `model` whether tool calls comply with rules, returning its JSON answer
+async fn request_llm_compliance(
+    api_base: &str,
+    model: &str,
+    temperature: f32,
+    system_prompt: &str,
+    full_prompt: &str,
+    max_tokens: u32,
+    openai_api_key: &str,
+) -> Result<String, String> {
+    use tracing::info;
+
+    // Call OpenAI API
+    let client = reqwest::Client::new();
+    let request_body = serde_json::json!({
+        "model": model,
+        "messages": [
+            {
+                "role": "system",
+                "content": system_prompt
+            },
+            {
+                "role": "user",
+                "content": full_prompt
+            }
+        ],
+        "temperature": temperature,
+        "max_tokens": max_tokens,
+        "response_format": { "type": "json_object" }
+    });
+
+    let response = client
+        .post(format!("{api_base}/chat/completions"))
+        .header("Authorization", format!("Bearer {}", openai_api_key))
+        .header("Content-Type", "application/json")
+        .json(&request_body)
+        .timeout(std::time::Duration::from_secs(models::DEFAULT_TIMEOUT_SECS))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to call OpenAI API: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text =
+            models::read_error_text(response, models::DEFAULT_MAX_RESPONSE_BYTES).await;
+        info!("[LLM_COMPLIANCE_CHECK] API error: {}", error_text);
+        return Err(format!("OpenAI API error: {status}"));
     }
+
+    let openai_response = models::read_json(response, models::DEFAULT_MAX_RESPONSE_BYTES)
+        .await
+        .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
+
+    models::parse_completion(&openai_response)
+        .map(|completion| completion.content)
+        .map_err(|e| format!("Invalid OpenAI response: {e}"))
 }
 
 #[cfg(test)]
 mod tests {
+    use axum::http::StatusCode;
+
     use super::*;
+    use crate::{
+        agent::{clock::SystemClock, types::ToolOutput},
+        test_utils::{chat_completion, MockOpenAI},
+    };
 
     #[test]
     fn test_default_policy_structure() {
@@ -598,6 +1484,47 @@ mod tests {
         assert_eq!(t4_policies, vec!["L1", "L2", "L3", "L4"]);
     }
 
+    #[test]
+    fn test_result_compliance() {
+        let checker = ComplianceChecker::default_crypto_policy();
+        let output = |data: serde_json::Value| {
+            ToolOutput::new("OnChainHistoryTool", "On-Chain Data Provider", data, &SystemClock)
+                .to_json()
+        };
+        let transactions = |n: usize| vec![serde_json::json!({ "value_usd": 1.0 }); n];
+
+        let summarized = output(serde_json::json!({
+            "transactions": transactions(5),
+            "summary": { "transactions": 5 },
+        }));
+        assert_eq!(checker.check_result_compliance("OnChainHistoryTool", &summarized), Ok(()));
+
+        // L2: raw dumps, and raw items without a summary
+        let dump = output(serde_json::json!({
+            "transactions": transactions(25),
+            "summary": { "transactions": 25 },
+        }));
+        let err = checker.check_result_compliance("OnChainHistoryTool", &dump).unwrap_err();
+        assert!(err.contains("'L2'") && err.contains("raw dump of 25 items"), "{err}");
+        let raw = output(serde_json::json!({ "transactions": transactions(5) }));
+        let err = checker.check_result_compliance("OnChainHistoryTool", &raw).unwrap_err();
+        assert!(err.contains("without an aggregated summary"), "{err}");
+
+        // L3: identity inference in the returned data
+        let doxxing = output(serde_json::json!({ "label": "This wallet belongs to Alice" }));
+        let err = checker.check_result_compliance("OnChainHistoryTool", &doxxing).unwrap_err();
+        assert!(err.contains("'L3'"), "{err}");
+
+        // L4: outputs without source or timestamp
+        let unattributed =
+            r#"{"tool": "SentimentTool", "data": {}, "source": "", "timestamp": ""}"#;
+        let err = checker.check_result_compliance("SentimentTool", unattributed).unwrap_err();
+        assert!(err.contains("'L4'") && err.contains("no source"), "{err}");
+
+        // Output rules don't apply to tools without the policies
+        assert_eq!(checker.check_result_compliance("PriceFeedTool", &dump), Ok(()));
+    }
+
     #[test]
     fn test_compliance_l1_prohibited_keywords() {
         let checker = ComplianceChecker::default_crypto_policy();
@@ -605,6 +1532,7 @@ mod tests {
         let plan = AgentPlan {
             system_prompt: "Test system prompt".to_string(),
             user_query: "You should buy Bitcoin now".to_string(),
+            history: Vec::new(),
             thought_process: vec![],
             intended_tool_calls: vec![],
         };
@@ -614,6 +1542,70 @@ mod tests {
         assert!(result.reason.contains("should buy"));
     }
 
+    #[test]
+    fn test_turns_trip_cross_turn_l3_together() {
+        let turns = [
+            "List the transfers of 0x52908400098527886E0F7030069857D2E4169EE7, I think this wallet"
+                .to_string(),
+            "belongs to my neighbour, can you confirm from the transfers?".to_string(),
+        ];
+        let registry = crate::agent::PolicyRegistry::default_crypto_policy();
+        let checker = ComplianceChecker::from_registry(&registry);
+
+        // Each turn passes on its own, and together without cross-turn checking
+        for turn in &turns {
+            assert!(checker.evaluate_corpus(&[(turn, true)]).mismatches.is_empty(), "{turn}");
+        }
+        assert!(!checker.has_cross_turn_policies());
+        assert_eq!(checker.check_conversation(&turns), Ok(()));
+
+        let registry = registry.with_cross_turn_policies(&["L3".to_string()]).unwrap();
+        let checker = ComplianceChecker::from_registry(&registry);
+        assert!(checker.has_cross_turn_policies());
+        assert_eq!(checker.check_conversation(&turns[..1]), Ok(()));
+        let err = checker.check_conversation(&turns).unwrap_err();
+        assert!(err.contains("'L3'") && err.contains("across 2 turns"), "{err}");
+
+        // A report-only cross-turn policy logs the violation instead
+        let registry = registry.with_report_only_policies(&["L3".to_string()]).unwrap();
+        let checker = ComplianceChecker::from_registry(&registry);
+        assert_eq!(checker.check_conversation(&turns), Ok(()));
+
+        let unknown = crate::agent::PolicyRegistry::default_crypto_policy()
+            .with_cross_turn_policies(&["L9".to_string()]);
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn test_rejected_rule_is_counted() {
+        let metrics = Arc::new(crate::agent::RuleMetrics::default());
+        let checker = ComplianceChecker::default_crypto_policy().with_metrics(metrics.clone());
+        let rule = "no_investment_advice_keywords";
+
+        let plan = AgentPlan {
+            system_prompt: String::new(),
+            user_query: "You should buy Bitcoin now".to_string(),
+            history: Vec::new(),
+            thought_process: vec![],
+            intended_tool_calls: vec![],
+        };
+        assert!(!checker.check_compliance(&plan).unwrap().compliant);
+        assert_eq!(metrics.count("L1", rule, RuleOutcome::Rejected), 1);
+        assert_eq!(metrics.count("L1", rule, RuleOutcome::Passed), 0);
+
+        // Tool calls are checked rule by rule too
+        let args = r#"{"symbol": "BTC"}"#;
+        checker.check_tool_compliance("PriceFeedTool", "What is BTC?", args).unwrap();
+        assert_eq!(metrics.count("L1", rule, RuleOutcome::Passed), 1);
+
+        let rendered = metrics.render();
+        let labels = format!(r#"policy="L1",rule="{rule}""#);
+        assert!(rendered.contains(&format!(
+            r#"compliance_rule_evaluations_total{{{labels},outcome="rejected"}} 1"#
+        )));
+        assert!(rendered.contains(&format!("compliance_rule_seconds_total{{{labels}}} ")));
+    }
+
     #[test]
     fn test_compliance_l3_identity_inference() {
         let checker = ComplianceChecker::default_crypto_policy();
@@ -621,6 +1613,7 @@ mod tests {
         let plan = AgentPlan {
             system_prompt: "Test system prompt".to_string(),
             user_query: "This wallet belongs to Satoshi".to_string(),
+            history: Vec::new(),
             thought_process: vec![],
             intended_tool_calls: vec![],
         };
@@ -630,6 +1623,68 @@ mod tests {
         assert!(result.reason.contains("belongs to"));
     }
 
+    #[test]
+    fn test_identity_inference_across_address_casings() {
+        let checker = ComplianceChecker::default_crypto_policy();
+        let checksummed = "0x52908400098527886E0F7030069857D2E4169EE7";
+
+        for address in [checksummed.to_string(), checksummed.to_lowercase()] {
+            let result = checker.check_tool_compliance(
+                "OnChainHistoryTool",
+                &format!("Tell me who {address} probably belongs to"),
+                &format!(r#"{{"address": "{address}", "blockchain": "ethereum"}}"#),
+            );
+            assert!(result.unwrap_err().contains("probably belongs to"));
+
+            // A prohibited phrase naming the address, in the other casing
+            let checker = ComplianceChecker::new(
+                vec![Policy {
+                    id: "L3".to_string(),
+                    name: "No deanonymization".to_string(),
+                    text: String::new(),
+                    enabled: true,
+                    cross_turn: false,
+                    report_only: false,
+                    methods: vec![PolicyMethod {
+                        method: ComplianceMethod::Deterministic,
+                        rules: vec![PolicyRule {
+                            id: "no_identity_inference".to_string(),
+                            rule_type: PolicyRuleType::NoIdentityInference {
+                                prohibited_terms: vec![format!("who owns {checksummed}")],
+                            },
+                            parameters: serde_json::json!({}),
+                        }],
+                    }],
+                }],
+                [("OnChainHistoryTool".to_string(), vec!["L3".to_string()])].into(),
+            );
+            let result = checker.check_tool_compliance(
+                "OnChainHistoryTool",
+                &format!("Who owns {address}?"),
+                r#"{"blockchain": "ethereum"}"#,
+            );
+            assert!(result.is_err(), "{address}");
+        }
+    }
+
+    #[test]
+    fn test_evaluate_corpus_flags_mismatch() {
+        let checker = ComplianceChecker::default_crypto_policy();
+        let cases = [
+            ("What is the price of BTC?", true),
+            ("You should buy Bitcoin now", false),
+            // Wrong expectation: L3 rejects identity inference
+            ("This wallet belongs to Satoshi", true),
+        ];
+
+        let report = checker.evaluate_corpus(&cases);
+        assert_eq!((report.total, report.passed, report.failed), (3, 2, 1));
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].query, "This wallet belongs to Satoshi");
+        assert!(report.mismatches[0].expected_compliant);
+        assert!(report.mismatches[0].reason.contains("L3"), "{}", report.mismatches[0].reason);
+    }
+
     #[test]
     fn test_tool_compliance_check() {
         let checker = ComplianceChecker::default_crypto_policy();
@@ -678,4 +1733,392 @@ mod tests {
 
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_deterministic_only_reports_skipped_llm_rules() {
+        let checker = ComplianceChecker::default_crypto_policy();
+
+        let skipped = checker
+            .check_tool_compliance_deterministic_only(
+                "SentimentTool",
+                "What is the sentiment of BTC?",
+                r#"{"symbol": "BTC"}"#,
+            )
+            .unwrap()
+            .skipped_rules;
+
+        // Every LLM rule of L1 and L4 is reported, none silently passed
+        let expected: Vec<_> = ["L1", "L4"]
+            .iter()
+            .flat_map(|id| checker.policies().iter().find(|p| p.id == *id))
+            .flat_map(|policy| {
+                policy
+                    .methods
+                    .iter()
+                    .filter(|m| m.method == ComplianceMethod::LLMBased)
+                    .flat_map(move |m| m.rules.iter().map(move |r| (policy.id.clone(), r.id.clone())))
+            })
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|r| (r.policy_id.clone(), r.rule_id.clone()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert!(skipped.iter().all(|r| r.method == ComplianceMethod::LLMBased));
+
+        // Deterministic violations are still reported
+        let err = checker
+            .check_tool_compliance_deterministic_only(
+                "SentimentTool",
+                "You should buy BTC",
+                r#"{"symbol": "BTC"}"#,
+            )
+            .unwrap_err();
+        assert!(err.contains("should buy"));
+    }
+
+    #[tokio::test]
+    async fn test_llm_disabled_checker_never_calls_llm() {
+        let checker = ComplianceChecker::default_crypto_policy().with_llm_enabled(false);
+        assert!(!checker.llm_enabled());
+
+        // An API key is given, but no LLM request may be made (it would fail offline)
+        let result = checker
+            .check_tool_compliance_async(
+                "PriceFeedTool",
+                "What is the price of Bitcoin?",
+                r#"{"symbol": "BTC"}"#,
+                Some("test-key"),
+            )
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_globally_disabled_llm_methods_are_skipped() {
+        let registry = crate::agent::PolicyRegistry::default_crypto_policy()
+            .with_disabled_methods(DisabledMethods {
+                global: vec![ComplianceMethod::LLMBased],
+                ..Default::default()
+            })
+            .unwrap();
+        let checker = ComplianceChecker::from_registry(&registry);
+
+        // With an API key, LLM rules would run (and fail offline) unless disabled
+        let skipped = checker
+            .check_tool_compliance_async(
+                "SentimentTool",
+                "What is the sentiment of BTC?",
+                r#"{"symbol": "BTC"}"#,
+                Some("test-key"),
+            )
+            .await
+            .unwrap()
+            .skipped_rules;
+        assert!(!skipped.is_empty());
+        assert!(skipped.iter().all(|r| r.method == ComplianceMethod::LLMBased));
+        assert!(skipped.iter().any(|r| r.policy_id == "L1"));
+        assert!(skipped.iter().any(|r| r.policy_id == "L4"));
+
+        // Deterministic rules still run
+        let err = checker
+            .check_tool_compliance_async(
+                "SentimentTool",
+                "You should buy BTC",
+                r#"{"symbol": "BTC"}"#,
+                Some("test-key"),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.contains("no_investment_advice_keywords"));
+    }
+
+    #[test]
+    fn test_per_policy_disabled_method() {
+        let checker = ComplianceChecker::default_crypto_policy().with_disabled_methods(
+            DisabledMethods {
+                per_policy: std::collections::HashMap::from([(
+                    "L1".to_string(),
+                    vec![ComplianceMethod::Deterministic],
+                )]),
+                ..Default::default()
+            },
+        );
+
+        // L1's keyword rule no longer rejects, and is reported instead
+        let skipped = checker
+            .check_tool_compliance_deterministic_only(
+                "PriceFeedTool",
+                "You should buy BTC",
+                r#"{"symbol": "BTC"}"#,
+            )
+            .unwrap()
+            .skipped_rules;
+        assert!(skipped.contains(&SkippedRule {
+            policy_id: "L1".to_string(),
+            rule_id: "no_investment_advice_keywords".to_string(),
+            method: ComplianceMethod::Deterministic,
+        }));
+    }
+
+    #[test]
+    fn test_disabled_policy_is_skipped_and_hashed() {
+        let enabled = ComplianceChecker::default_crypto_policy();
+        let (mut policies, tool_policy_map) =
+            crate::agent::PolicyRegistry::default_crypto_policy().clone_data();
+        policies.iter_mut().find(|p| p.id == "L1").unwrap().enabled = false;
+        let disabled = ComplianceChecker::new(policies, tool_policy_map);
+
+        let plan = AgentPlan {
+            system_prompt: String::new(),
+            user_query: "You should buy Bitcoin now".to_string(),
+            history: Vec::new(),
+            thought_process: vec![],
+            intended_tool_calls: vec![],
+        };
+        let before = enabled.check_compliance(&plan).unwrap();
+        assert!(!before.compliant);
+        let after = disabled.check_compliance(&plan).unwrap();
+        assert!(after.compliant, "{}", after.reason);
+        assert_ne!(after.policy_hash, before.policy_hash);
+
+        // Tool calls skip its rules, reporting them
+        let skipped = disabled
+            .check_tool_compliance_deterministic_only(
+                "PriceFeedTool",
+                "You should buy BTC",
+                r#"{"symbol": "BTC"}"#,
+            )
+            .unwrap()
+            .skipped_rules;
+        assert!(skipped.iter().any(|rule| rule.policy_id == "L1"));
+    }
+
+    #[test]
+    fn test_policy_hash_comes_from_registry() {
+        let registry = crate::agent::PolicyRegistry::default_crypto_policy();
+        let (policies, tool_policy_map) = registry.clone_data();
+        assert_eq!(
+            registry.policy_hash(),
+            hash_policies(&policies, &tool_policy_map, registry.disabled_methods())
+        );
+
+        // Neither building checkers nor checking plans hashes the policies again
+        let hashes = HASH_COUNT.with(|count| count.get());
+        for _ in 0..2 {
+            let checker = ComplianceChecker::from_registry(&registry);
+            assert_eq!(checker.hash_policies(), registry.policy_hash());
+            for plan in [two_tool_plan(), two_tool_plan()] {
+                let result = checker.check_compliance(&plan).unwrap();
+                assert_eq!(result.policy_hash, checker.policy_hash());
+            }
+        }
+        assert_eq!(HASH_COUNT.with(|count| count.get()), hashes);
+
+        // Changing the policies drops the cached hash
+        let cached = registry.policy_hash();
+        let report_only = registry.with_report_only_policies(&["L1".to_string()]).unwrap();
+        assert_ne!(report_only.policy_hash(), cached);
+        assert_eq!(
+            report_only.policy_hash(),
+            hash_policies(
+                report_only.policies(),
+                report_only.tool_policy_map(),
+                report_only.disabled_methods()
+            )
+        );
+    }
+
+    fn two_tool_plan() -> AgentPlan {
+        let call = |tool_name: &str| ToolCall {
+            id: uuid::Uuid::now_v7(),
+            tool_name: tool_name.to_string(),
+            arguments: r#"{"symbol": "BTC"}"#.to_string(),
+            timestamp: std::time::SystemTime::now(),
+            compliance_quote: None,
+            thought_step: None,
+        };
+        AgentPlan {
+            system_prompt: String::new(),
+            user_query: "What are the price and sentiment of BTC?".to_string(),
+            history: Vec::new(),
+            thought_process: vec![],
+            intended_tool_calls: vec![call("PriceFeedTool"), call("SentimentTool")],
+        }
+    }
+
+    /// Number of LLM rules applying to the calls of `plan`
+    fn llm_rule_count(checker: &ComplianceChecker, plan: &AgentPlan) -> usize {
+        plan.intended_tool_calls
+            .iter()
+            .flat_map(|call| checker.tool_policies(&call.tool_name).unwrap())
+            .flat_map(|policy| &policy.methods)
+            .filter(|method| method.method == ComplianceMethod::LLMBased)
+            .map(|method| method.rules.len())
+            .sum()
+    }
+
+    async fn check_plan(
+        checker: &ComplianceChecker,
+        plan: &AgentPlan,
+        verdicts: &LlmVerdicts,
+    ) -> Vec<Result<Vec<SkippedRule>, String>> {
+        let mut results = Vec::new();
+        for call in &plan.intended_tool_calls {
+            let result = checker
+                .check_tool_compliance_graded(
+                    &call.tool_name,
+                    &plan.user_query,
+                    &call.arguments,
+                    Some("test-key"),
+                    verdicts,
+                )
+                .await;
+            results.push(result.map(|approval| approval.skipped_rules));
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn test_llm_rules_of_plan_are_graded_in_one_request() {
+        let plan = two_tool_plan();
+        let checks = llm_rule_count(&ComplianceChecker::default_crypto_policy(), &plan);
+        assert!(checks >= 2);
+
+        // The last check belongs to SentimentTool
+        let backend = MockOpenAI::spawn(move |_| {
+            let verdicts: Vec<_> = (0..checks)
+                .map(|index| {
+                    serde_json::json!({
+                        "index": index,
+                        "compliant": index + 1 < checks,
+                        "explanation": format!("graded {index}"),
+                    })
+                })
+                .collect();
+            let answer = serde_json::json!({ "verdicts": verdicts });
+            (StatusCode::OK, chat_completion(&answer.to_string()))
+        })
+        .await;
+        let metrics = Arc::new(crate::agent::RuleMetrics::default());
+        let checker = ComplianceChecker::default_crypto_policy()
+            .with_llm_api_base(&backend.base_url)
+            .with_metrics(metrics.clone());
+
+        let verdicts = checker.grade_llm_rules(&plan, "test-key").await;
+        assert_eq!(verdicts.len(), checks);
+
+        let results = check_plan(&checker, &plan, &verdicts).await;
+        assert!(results[0].is_ok(), "{:?}", results[0]);
+        let err = results[1].as_ref().unwrap_err();
+        assert!(err.starts_with("Tool 'SentimentTool'"), "{err}");
+        assert!(err.contains(&format!("graded {}", checks - 1)), "{err}");
+
+        // Each batched verdict is recorded once, when graded
+        let rules: std::collections::BTreeSet<_> =
+            verdicts.0.keys().map(|(_, _, policy, rule)| (policy, rule)).collect();
+        let total = |outcome| {
+            rules.iter().map(|(policy, rule)| metrics.count(policy, rule, outcome)).sum::<u64>()
+        };
+        assert_eq!(total(RuleOutcome::Passed), checks as u64 - 1);
+        assert_eq!(total(RuleOutcome::Rejected), 1);
+
+        let requests = backend.requests();
+        assert_eq!(requests.len(), 1);
+        let system_prompt = requests[0]["messages"][0]["content"].as_str().unwrap();
+        assert!(system_prompt.contains("verdicts"));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_batch_falls_back_to_per_rule_requests() {
+        let backend = MockOpenAI::spawn(|body| {
+            let system_prompt = body["messages"][0]["content"].as_str().unwrap_or_default();
+            let answer = if system_prompt.contains("verdicts") {
+                r#"{"verdicts": [{"index": 0}]}"#
+            } else {
+                r#"{"compliant": true, "explanation": "Lookup only"}"#
+            };
+            (StatusCode::OK, chat_completion(answer))
+        })
+        .await;
+        let checker =
+            ComplianceChecker::default_crypto_policy().with_llm_api_base(&backend.base_url);
+        let plan = two_tool_plan();
+
+        let verdicts = checker.grade_llm_rules(&plan, "test-key").await;
+        assert!(verdicts.is_empty());
+
+        let results = check_plan(&checker, &plan, &verdicts).await;
+        assert!(results.iter().all(Result::is_ok), "{results:?}");
+        assert_eq!(backend.requests().len(), 1 + llm_rule_count(&checker, &plan));
+    }
+
+    #[tokio::test]
+    async fn test_rule_level_model_override() {
+        let llm_rule = |id: &str, model: Option<&str>, temperature: Option<f32>| PolicyRule {
+            id: id.to_string(),
+            rule_type: PolicyRuleType::LLMCompliance {
+                check_prompt: format!("Check {id}"),
+                model: model.map(ToString::to_string),
+                temperature,
+            },
+            parameters: serde_json::json!({}),
+        };
+        let policy = Policy {
+            id: "P1".to_string(),
+            name: "Guarded".to_string(),
+            text: "No personalized advice".to_string(),
+            enabled: true,
+            cross_turn: false,
+            report_only: false,
+            methods: vec![PolicyMethod {
+                method: ComplianceMethod::LLMBased,
+                rules: vec![
+                    llm_rule("strict", Some("gpt-4o-mini"), Some(0.2)),
+                    llm_rule("default", None, None),
+                ],
+            }],
+        };
+        let backend = MockOpenAI::spawn(|_| {
+            let answer = r#"{"compliant": true, "explanation": "Lookup only"}"#;
+            (StatusCode::OK, chat_completion(answer))
+        })
+        .await;
+        let tool_policy_map = [("PriceFeedTool".to_string(), vec!["P1".to_string()])].into();
+        let checker = ComplianceChecker::new(vec![policy], tool_policy_map)
+            .with_llm_api_base(&backend.base_url);
+
+        checker
+            .check_tool_compliance_async(
+                "PriceFeedTool",
+                "What is the price of BTC?",
+                r#"{"symbol": "BTC"}"#,
+                Some("test-key"),
+            )
+            .await
+            .unwrap();
+
+        let requests = backend.requests();
+        assert_eq!(requests.len(), 2);
+        let sent = |check: &str| {
+            requests
+                .iter()
+                .find(|r| r["messages"][1]["content"].as_str().unwrap().contains(check))
+                .map(|r| (r["model"].clone(), r["temperature"].as_f64().unwrap()))
+                .unwrap()
+        };
+        let (model, temperature) = sent("Check strict");
+        assert_eq!(model, "gpt-4o-mini");
+        assert!((temperature - 0.2).abs() < 1e-6);
+        assert_eq!(sent("Check default"), (serde_json::json!("gpt-4o"), 0.0));
+
+        // A rule with its own model isn't graded in a batch with the others
+        let plan = two_tool_plan();
+        assert!(checker.grade_llm_rules(&plan, "test-key").await.is_empty());
+        assert_eq!(backend.requests().len(), 2);
+    }
 }
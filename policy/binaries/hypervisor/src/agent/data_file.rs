@@ -0,0 +1,170 @@
+//! JSON data files backing the file-based tools, optionally reloaded when they change on disk
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde_json::Value;
+use tracing::{info, warn};
+
+/// Modification time and size identifying a version of the file
+type Version = (Option<SystemTime>, u64);
+
+struct Loaded {
+    value: Arc<Value>,
+    version: Version,
+    checked_at: Instant,
+}
+
+/// A tool's parsed JSON data file
+///
+/// With a reload interval set, `get` checks the file at most once per interval and swaps in
+/// the new contents when it changed. A file caught mid-write (changing while read, or not
+/// parsing) is skipped and the previous contents kept until the next check.
+pub struct DataFile {
+    path: PathBuf,
+    /// What the data is, for error messages, e.g. "price feed"
+    what: &'static str,
+    reload: Option<Duration>,
+    loaded: RwLock<Loaded>,
+}
+
+impl DataFile {
+    /// Read and parse the file, without reloading
+    pub fn load(path: impl AsRef<Path>, what: &'static str) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let (value, version) = read(&path, what)?;
+
+        Ok(Self {
+            path,
+            what,
+            reload: None,
+            loaded: RwLock::new(Loaded {
+                value: Arc::new(value),
+                version,
+                checked_at: Instant::now(),
+            }),
+        })
+    }
+
+    /// Check the file for changes at most once per `interval`, `None` to never reload
+    pub fn with_reload(mut self, interval: Option<Duration>) -> Self {
+        self.reload = interval;
+        self
+    }
+
+    /// Current contents, reloaded first if the file changed since the last check
+    pub fn get(&self) -> Arc<Value> {
+        if let Some(interval) = self.reload {
+            self.refresh(interval);
+        }
+
+        self.loaded
+            .read()
+            .expect("data file lock poisoned")
+            .value
+            .clone()
+    }
+
+    fn refresh(&self, interval: Duration) {
+        {
+            let loaded = self.loaded.read().expect("data file lock poisoned");
+            if loaded.checked_at.elapsed() < interval {
+                return;
+            }
+        }
+
+        let mut loaded = self.loaded.write().expect("data file lock poisoned");
+        if loaded.checked_at.elapsed() < interval {
+            return;
+        }
+        loaded.checked_at = Instant::now();
+
+        match version(&self.path) {
+            Ok(version) if version == loaded.version => return,
+            Ok(_) => {}
+            Err(e) => {
+                warn!(path = %self.path.display(), error = %e, "Failed to check data file");
+                return;
+            }
+        }
+
+        match read(&self.path, self.what) {
+            Ok((value, version)) => {
+                info!(path = %self.path.display(), "Reloaded {} data", self.what);
+                loaded.value = Arc::new(value);
+                loaded.version = version;
+            }
+            Err(e) => warn!(error = %e, "Keeping previous {} data", self.what),
+        }
+    }
+}
+
+fn version(path: &Path) -> std::io::Result<Version> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.modified().ok(), metadata.len()))
+}
+
+/// Read and parse the file, failing if it changed while being read
+fn read(path: &Path, what: &str) -> Result<(Value, Version), String> {
+    let read_error =
+        |e: std::io::Error| format!("Failed to read {} data {}: {}", what, path.display(), e);
+
+    let before = version(path).map_err(read_error)?;
+    let data_str = fs::read_to_string(path).map_err(read_error)?;
+    if version(path).map_err(read_error)? != before {
+        return Err(format!(
+            "{} data {} changed while being read",
+            what,
+            path.display()
+        ));
+    }
+
+    let value = serde_json::from_str(&data_str)
+        .map_err(|e| format!("Failed to parse {} data {}: {}", what, path.display(), e))?;
+
+    Ok((value, before))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("data_file_{}.json", uuid::Uuid::now_v7()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_reload_keeps_previous_data_on_partial_write() {
+        let path = temp_file(r#"{"price": 1}"#);
+        let file = DataFile::load(&path, "test")
+            .unwrap()
+            .with_reload(Some(Duration::ZERO));
+        assert_eq!(file.get()["price"], 1);
+
+        fs::write(&path, r#"{"price": 2, "vol"#).unwrap();
+        assert_eq!(file.get()["price"], 1);
+
+        fs::write(&path, r#"{"price": 2, "volume": 10}"#).unwrap();
+        assert_eq!(file.get()["price"], 2);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(file.get()["price"], 2);
+    }
+
+    #[test]
+    fn test_without_reload_data_is_fixed() {
+        let path = temp_file(r#"{"price": 1}"#);
+        let file = DataFile::load(&path, "test").unwrap();
+
+        fs::write(&path, r#"{"price": 2, "volume": 10}"#).unwrap();
+        assert_eq!(file.get()["price"], 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+/// Failure of an agent run, by the phase that failed
+#[derive(Debug, thiserror::Error)]
+pub enum AgentError {
+    /// The LLM answered, but its plan could not be used
+    #[error("planning failed: {0}")]
+    PlanningFailed(String),
+
+    /// The OpenAI request could not be sent or returned an error status
+    #[error("LLM request failed: {0}")]
+    LlmRequest(String),
+
+    /// The OpenAI response did not have the expected shape
+    #[error("malformed LLM response: {0}")]
+    LlmParse(String),
+
+    /// The completion was withheld by the content filter or refused by the model
+    #[error("LLM declined to answer: {0}")]
+    LlmRefused(String),
+
+    #[error("tool execution failed: {0}")]
+    ToolExecution(String),
+
+    #[error("compliance check failed: {0}")]
+    Compliance(String),
+}
+
+impl AgentError {
+    /// Name of the failed phase, loggable without the reason's user content
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::PlanningFailed(_) => "planning_failed",
+            Self::LlmRequest(_) => "llm_request_failed",
+            Self::LlmParse(_) => "llm_parse_failed",
+            Self::LlmRefused(_) => "llm_refused",
+            Self::ToolExecution(_) => "tool_execution_failed",
+            Self::Compliance(_) => "compliance_failed",
+        }
+    }
+}
+
+/// Failure of a tool call on its arguments, before the tool reads any data
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ToolError {
+    /// The arguments don't deserialize into the tool's typed params
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(String),
+}
+
+impl From<ToolError> for String {
+    fn from(err: ToolError) -> Self {
+        err.to_string()
+    }
+}
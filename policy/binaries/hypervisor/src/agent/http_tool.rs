@@ -0,0 +1,427 @@
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tracing::debug;
+
+use super::clock::{system_clock, SharedClock};
+use super::policy_registry::{PolicyInfo, PolicyRegistry};
+use super::tools::check_compliance_quote;
+use super::types::{parse_argument_object, ComplianceQuote, Tool, ToolOutput};
+
+/// Upstream settings for an HTTP-backed tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpToolConfig {
+    /// Upstream endpoint, queried with the tool arguments as query parameters
+    pub url: String,
+    /// Output field (under `data`) -> JSON pointer into the upstream response
+    #[serde(default)]
+    pub response_mapping: BTreeMap<String, String>,
+    /// Request timeout in milliseconds
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// How long an upstream response is served from cache, in seconds
+    #[serde(default = "default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    /// Maximum number of cached responses
+    #[serde(default = "default_cache_capacity")]
+    pub cache_capacity: usize,
+}
+
+fn default_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    30
+}
+
+fn default_cache_capacity() -> usize {
+    64
+}
+
+impl HttpToolConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            response_mapping: BTreeMap::new(),
+            timeout_ms: default_timeout_ms(),
+            cache_ttl_secs: default_cache_ttl_secs(),
+            cache_capacity: default_cache_capacity(),
+        }
+    }
+}
+
+/// Tool answering from a live HTTP upstream instead of a static fixture
+///
+/// Arguments are validated against the parameter schema, sent as query parameters,
+/// and the JSON response is reshaped through `response_mapping`. Mapped results are
+/// cached per argument set for `cache_ttl_secs`.
+///
+/// `execute` blocks on the request, so it must run on a multi-threaded Tokio runtime
+/// (or outside of any runtime).
+pub struct HttpTool {
+    name: String,
+    description: String,
+    parameters_schema: Value,
+    config: HttpToolConfig,
+    client: reqwest::Client,
+    cache: Mutex<HashMap<String, (Instant, String)>>,
+    policies: Arc<PolicyRegistry>,
+    clock: SharedClock,
+}
+
+impl HttpTool {
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters_schema: Value,
+        config: HttpToolConfig,
+        policies: Arc<PolicyRegistry>,
+    ) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+        Ok(Self {
+            name: name.into(),
+            description: description.into(),
+            parameters_schema,
+            config,
+            client,
+            cache: Mutex::new(HashMap::new()),
+            policies,
+            clock: system_clock(),
+        })
+    }
+
+    /// Stamp outputs with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Check the arguments against the parameter schema and turn them into query pairs
+    fn query_params(&self, args: &Map<String, Value>) -> Result<Vec<(String, String)>, String> {
+        let required = self.parameters_schema["required"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for name in required.iter().filter_map(Value::as_str) {
+            if !args.contains_key(name) {
+                return Err(format!("Missing {} parameter", name));
+            }
+        }
+
+        let mut params = Vec::new();
+        if let Some(properties) = self.parameters_schema["properties"].as_object() {
+            for (name, schema) in properties {
+                let Some(value) = args.get(name) else {
+                    continue;
+                };
+                let value = match (schema["type"].as_str(), value) {
+                    (Some("string"), Value::String(s)) => s.clone(),
+                    (Some("number" | "integer"), Value::Number(n)) => n.to_string(),
+                    (Some("boolean"), Value::Bool(b)) => b.to_string(),
+                    _ => return Err(format!("Invalid {} parameter", name)),
+                };
+                params.push((name.clone(), value));
+            }
+        }
+
+        Ok(params)
+    }
+
+    fn cached(&self, key: &str) -> Option<String> {
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        let cache = self.cache.lock().expect("http tool cache poisoned");
+        cache
+            .get(key)
+            .filter(|(stored_at, _)| stored_at.elapsed() < ttl)
+            .map(|(_, output)| output.clone())
+    }
+
+    fn store(&self, key: String, output: String) {
+        if self.config.cache_capacity == 0 {
+            return;
+        }
+
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        let mut cache = self.cache.lock().expect("http tool cache poisoned");
+        cache.retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+        if cache.len() >= self.config.cache_capacity {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, (stored_at, _))| *stored_at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(key, (Instant::now(), output));
+    }
+
+    async fn fetch(&self, params: &[(String, String)]) -> Result<Value, String> {
+        self.client
+            .get(&self.config.url)
+            .query(params)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| format!("Upstream request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid upstream response: {}", e))
+    }
+
+    fn map_response(&self, params: &[(String, String)], body: &Value) -> Result<String, String> {
+        let mut data = json!({});
+        for (name, value) in params {
+            data[name] = json!(value);
+        }
+        for (field, pointer) in &self.config.response_mapping {
+            let value = body
+                .pointer(pointer)
+                .ok_or_else(|| format!("Upstream response missing '{}'", pointer))?;
+            data[field] = value.clone();
+        }
+
+        Ok(ToolOutput::new(&self.name, &self.config.url, data, &*self.clock).to_json())
+    }
+}
+
+impl Tool for HttpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters_schema(&self) -> Value {
+        self.parameters_schema.clone()
+    }
+
+    fn execute(&self, arguments: &str, compliance_quote: Option<&ComplianceQuote>) -> Result<String, String> {
+        check_compliance_quote(self.name(), compliance_quote)?;
+
+        let args = parse_argument_object(arguments)?;
+        let params = self.query_params(&args)?;
+
+        let cache_key = serde_json::to_string(&params).expect("query params serialize");
+        if let Some(output) = self.cached(&cache_key) {
+            debug!("Serving {} from cache", self.name);
+            return Ok(output);
+        }
+
+        let body = block_on(self.fetch(&params))?;
+        let output = self.map_response(&params, &body)?;
+        self.store(cache_key, output.clone());
+
+        Ok(output)
+    }
+
+    fn policy_ids(&self) -> Vec<String> {
+        self.policies.get_policy_ids_for_tool(self.name())
+    }
+
+    fn policy_info(&self) -> Vec<PolicyInfo> {
+        self.policies.get_policy_info_for_tool(self.name())
+    }
+}
+
+/// Drive a future to completion from the synchronous `Tool::execute`
+fn block_on<F: Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build tokio runtime")
+            .block_on(future),
+    }
+}
+
+// =============================================================================
+// T1 (live): PriceFeedHttpTool - Policy: L1
+// =============================================================================
+
+/// Live variant of `PriceFeedTool`, registered under the same name and policies
+pub struct PriceFeedHttpTool(HttpTool);
+
+impl PriceFeedHttpTool {
+    /// Create the tool; an empty `response_mapping` expects the fixture's price fields
+    pub fn new(mut config: HttpToolConfig, policies: Arc<PolicyRegistry>) -> Result<Self, String> {
+        if config.response_mapping.is_empty() {
+            config.response_mapping = [
+                "price_usd",
+                "market_cap",
+                "24h_volume",
+                "24h_change_pct",
+                "last_updated",
+            ]
+            .into_iter()
+            .map(|field| (field.to_string(), format!("/{}", field)))
+            .collect();
+        }
+
+        let tool = HttpTool::new(
+            "PriceFeedTool",
+            "Get current cryptocurrency prices in USD. Returns real-time price data with timestamp.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "symbol": {
+                        "type": "string",
+                        "description": "The cryptocurrency symbol (e.g., BTC, ETH, SOL)"
+                    }
+                },
+                "required": ["symbol"]
+            }),
+            config,
+            policies,
+        )?;
+
+        Ok(Self(tool))
+    }
+
+    /// Stamp outputs with `clock` instead of the system clock
+    pub fn with_clock(self, clock: SharedClock) -> Self {
+        Self(self.0.with_clock(clock))
+    }
+}
+
+impl Tool for PriceFeedHttpTool {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn description(&self) -> &str {
+        self.0.description()
+    }
+
+    fn parameters_schema(&self) -> Value {
+        self.0.parameters_schema()
+    }
+
+    fn execute(&self, arguments: &str, compliance_quote: Option<&ComplianceQuote>) -> Result<String, String> {
+        // Symbols are matched case-insensitively, like the fixture-backed tool
+        let mut args = parse_argument_object(arguments)?;
+        if let Some(symbol) = args.get("symbol").and_then(Value::as_str) {
+            args["symbol"] = json!(symbol.to_uppercase());
+        }
+
+        self.0.execute(&Value::Object(args).to_string(), compliance_quote)
+    }
+
+    fn policy_ids(&self) -> Vec<String> {
+        self.0.policy_ids()
+    }
+
+    fn policy_info(&self) -> Vec<PolicyInfo> {
+        self.0.policy_info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{extract::Query, routing::get, Json, Router};
+
+    use super::*;
+    use crate::test_utils::serve;
+
+    async fn mock_upstream(hits: Arc<AtomicUsize>) -> String {
+        let app = Router::new().route(
+            "/price",
+            get(move |Query(query): Query<HashMap<String, String>>| async move {
+                hits.fetch_add(1, Ordering::SeqCst);
+                Json(json!({
+                    "symbol": query["symbol"],
+                    "price_usd": 67500.5,
+                    "market_cap": 1320000000000u64,
+                    "24h_volume": 32000000000u64,
+                    "24h_change_pct": 2.3,
+                    "last_updated": "2025-11-20T10:00:00Z"
+                }))
+            }),
+        );
+
+        serve(app).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_price_feed_http_tool_maps_and_caches() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let base_url = mock_upstream(hits.clone()).await;
+        let tool = PriceFeedHttpTool::new(
+            HttpToolConfig::new(format!("{base_url}/price")),
+            Arc::default(),
+        )
+        .unwrap();
+
+        let output = tool.execute(r#"{"symbol": "btc"}"#, None).unwrap();
+        let output: ToolOutput = serde_json::from_str(&output).unwrap();
+        assert_eq!(output.tool, "PriceFeedTool");
+        assert_eq!(output.source, format!("{base_url}/price"));
+        assert_eq!(output.data["symbol"], "BTC");
+        assert_eq!(output.data["price_usd"], 67500.5);
+        assert_eq!(output.data["last_updated"], "2025-11-20T10:00:00Z");
+        assert_eq!(tool.policy_ids(), ["L1"]);
+
+        // The second call is answered from cache
+        tool.execute(r#"{"symbol": "BTC"}"#, None).unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // Invalid arguments never reach the upstream
+        assert!(tool.execute(r#"{}"#, None).is_err());
+        assert!(tool.execute(r#"{"symbol": 42}"#, None).is_err());
+        let err = tool.execute(r#"["BTC"]"#, None).unwrap_err();
+        assert_eq!(err, "Invalid arguments: expected a JSON object, got an array");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_http_tool_cache_shared_across_requests() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let base_url = mock_upstream(hits.clone()).await;
+        let mut config = crate::Config::default();
+        config.agent.data_dir = crate::test_utils::data_dir();
+        config.agent.price_feed_upstream = Some(HttpToolConfig::new(format!("{base_url}/price")));
+        let state = crate::types::HypervisorState::new(config).unwrap();
+
+        // Each request runs the tools it loads from the state
+        for _ in 0..2 {
+            let tools = state.tool_registry();
+            let tool = tools.get_tool("PriceFeedTool").unwrap();
+            tool.execute(r#"{"symbol": "BTC"}"#, None).unwrap();
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // A reload builds new tools, with an empty cache
+        state.reload_policies().unwrap();
+        let tools = state.tool_registry();
+        let tool = tools.get_tool("PriceFeedTool").unwrap();
+        tool.execute(r#"{"symbol": "BTC"}"#, None).unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_http_tool_missing_field_is_an_error() {
+        let base_url = mock_upstream(Arc::default()).await;
+        let mut config = HttpToolConfig::new(format!("{base_url}/price"));
+        config
+            .response_mapping
+            .insert("supply".to_string(), "/circulating_supply".to_string());
+        let tool = PriceFeedHttpTool::new(config, Arc::default()).unwrap();
+
+        let err = tool.execute(r#"{"symbol": "ETH"}"#, None).unwrap_err();
+        assert!(err.contains("/circulating_supply"));
+    }
+}
@@ -0,0 +1,76 @@
+/// Phrases of tool output trying to instruct the final-response model
+pub const DEFAULT_INJECTION_MARKERS: [&str; 10] = [
+    "ignore previous instructions",
+    "ignore all previous instructions",
+    "ignore the above",
+    "disregard previous instructions",
+    "disregard all prior instructions",
+    "forget your instructions",
+    "new instructions:",
+    "you are now",
+    "system prompt:",
+    "<|im_start|>",
+];
+
+/// Tag wrapping flagged tool results in the final prompt
+const UNTRUSTED_TAG: &str = "untrusted_data";
+
+/// Configured injection markers, matched against tool results before final synthesis
+///
+/// Matching ignores case and collapses runs of whitespace, so a marker split across
+/// lines still matches.
+#[derive(Debug, Clone)]
+pub struct InjectionMarkers(Vec<String>);
+
+impl InjectionMarkers {
+    pub fn new(markers: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        Self(
+            markers
+                .into_iter()
+                .map(|m| normalize(m.as_ref()))
+                .filter(|m| !m.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Markers found in `text`, in configured order
+    pub fn scan(&self, text: &str) -> Vec<String> {
+        let text = normalize(text);
+        self.0
+            .iter()
+            .filter(|marker| text.contains(marker.as_str()))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for InjectionMarkers {
+    fn default() -> Self {
+        Self::new(DEFAULT_INJECTION_MARKERS)
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Wrap `text` in `<untrusted_data>` tags for the prompt, defusing closing tags inside it
+pub fn wrap_untrusted(text: &str) -> String {
+    let closing = format!("</{UNTRUSTED_TAG}");
+    // ASCII lowercasing keeps byte offsets, so matches index into `text` too
+    let lowered = text.to_ascii_lowercase();
+    let mut wrapped = format!("<{UNTRUSTED_TAG}>");
+    let mut last = 0;
+    for (start, _) in lowered.match_indices(&closing) {
+        wrapped.push_str(&text[last..start]);
+        wrapped.push_str(&format!("<\\/{UNTRUSTED_TAG}"));
+        last = start + closing.len();
+    }
+    wrapped.push_str(&text[last..]);
+    wrapped.push_str(&format!("</{UNTRUSTED_TAG}>"));
+
+    wrapped
+}
@@ -0,0 +1,122 @@
+//! Lookups in nested JSON by path, shared by compliance rules and aggregations
+//!
+//! A path is dot-separated keys and array indices, indices also written in brackets:
+//! `holdings.0.value_usd` and `holdings[0].value_usd` are the same path. The empty path
+//! is the value itself. Missing keys, out-of-range indices and malformed paths give `None`.
+
+use serde_json::Value;
+
+/// Value at `path`
+pub fn get<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+
+    path.split('.').try_fold(value, |value, segment| {
+        let (key, indices) = match segment.find('[') {
+            Some(open) => segment.split_at(open),
+            None => (segment, ""),
+        };
+        let mut value = match key {
+            "" if !indices.is_empty() => value,
+            key => step(value, key)?,
+        };
+
+        let mut indices = indices;
+        while !indices.is_empty() {
+            let close = indices.find(']')?;
+            let index = indices.strip_prefix('[')?[..close - 1].parse::<usize>().ok()?;
+            value = value.as_array()?.get(index)?;
+            indices = &indices[close + 1..];
+        }
+
+        Some(value)
+    })
+}
+
+/// Key of an object, or index of an array
+fn step<'a>(value: &'a Value, segment: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(fields) => fields.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+/// Number at `path`
+pub fn get_f64(value: &Value, path: &str) -> Option<f64> {
+    get(value, path)?.as_f64()
+}
+
+/// Non-negative integer at `path`
+pub fn get_u64(value: &Value, path: &str) -> Option<u64> {
+    get(value, path)?.as_u64()
+}
+
+/// String at `path`
+pub fn get_str<'a>(value: &'a Value, path: &str) -> Option<&'a str> {
+    get(value, path)?.as_str()
+}
+
+/// Boolean at `path`
+pub fn get_bool(value: &Value, path: &str) -> Option<bool> {
+    get(value, path)?.as_bool()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn portfolio() -> Value {
+        json!({
+            "owner": { "name": "treasury", "verified": true },
+            "holdings": [
+                { "symbol": "BTC", "value_usd": 67500.0, "lots": [[1, 2], [3]] },
+                { "symbol": "ETH", "value_usd": 3200.5, "amount": 4 }
+            ]
+        })
+    }
+
+    #[test]
+    fn test_nested_objects() {
+        let value = portfolio();
+        assert_eq!(get_str(&value, "owner.name"), Some("treasury"));
+        assert_eq!(get_bool(&value, "owner.verified"), Some(true));
+        assert_eq!(get(&value, ""), Some(&value));
+        assert_eq!(get(&value, "owner"), Some(&value["owner"]));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let value = portfolio();
+        assert_eq!(get_f64(&value, "holdings.0.value_usd"), Some(67500.0));
+        assert_eq!(get_f64(&value, "holdings[1].value_usd"), Some(3200.5));
+        assert_eq!(get_u64(&value, "holdings.1.amount"), Some(4));
+        assert_eq!(get_u64(&value, "holdings[0].lots[0][1]"), Some(2));
+        assert_eq!(get_u64(&value, "holdings.0.lots.1.0"), Some(3));
+
+        let array = json!([{ "score": 0.5 }]);
+        assert_eq!(get_f64(&array, "[0].score"), Some(0.5));
+        assert_eq!(get_f64(&array, "0.score"), Some(0.5));
+    }
+
+    #[test]
+    fn test_missing_paths() {
+        let value = portfolio();
+        assert_eq!(get(&value, "owner.address"), None);
+        assert_eq!(get(&value, "holdings.2.value_usd"), None);
+        assert_eq!(get(&value, "holdings[-1]"), None);
+        assert_eq!(get(&value, "owner[0]"), None);
+        assert_eq!(get(&value, "owner.name.first"), None);
+        // Present, but not of the requested type
+        assert_eq!(get_f64(&value, "holdings.0.symbol"), None);
+        assert_eq!(get_str(&value, "holdings.0.value_usd"), None);
+        // Malformed paths
+        assert_eq!(get(&value, "holdings[0"), None);
+        assert_eq!(get(&value, "holdings[x]"), None);
+        assert_eq!(get(&value, "holdings..0"), None);
+        assert_eq!(get(&value, "holdings[0]x"), None);
+    }
+}
@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::types::ToolResult;
+
+/// Domain prefix for leaf hashes (guards against second-preimage attacks)
+const LEAF_PREFIX: u8 = 0x00;
+/// Domain prefix for interior node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+/// Root of a tree without any tool results
+pub const EMPTY_ROOT: [u8; 32] = [0u8; 32];
+
+/// Merkle inclusion proof for a single tool result
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MerkleProof {
+    /// ID of the tool call whose result this proof covers
+    pub call_id: Uuid,
+    /// Position of the result in `AgentExecution::tool_results`
+    pub leaf_index: usize,
+    /// Total number of leaves in the tree
+    pub leaf_count: usize,
+    /// Sibling hashes from leaf to root (hex-encoded)
+    pub siblings: Vec<String>,
+}
+
+/// Merkle tree over the hashes of an execution's tool results
+///
+/// Leaves are `hash(0x00 || call_id || success || result)` and interior nodes are
+/// `hash(0x01 || left || right)`. A node without a sibling is promoted to the next
+/// level unchanged, so no leaf is ever duplicated.
+#[derive(Debug, Clone)]
+pub struct ToolResultsMerkleTree {
+    call_ids: Vec<Uuid>,
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl ToolResultsMerkleTree {
+    /// Build the tree from tool results, preserving their order
+    pub fn build(results: &[ToolResult]) -> Self {
+        let call_ids = results.iter().map(|r| r.call_id).collect();
+        let mut levels = vec![results.iter().map(hash_tool_result).collect::<Vec<_>>()];
+
+        while levels.last().map(|l| l.len() > 1).unwrap_or(false) {
+            let next = levels
+                .last()
+                .expect("non-empty levels")
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Self { call_ids, levels }
+    }
+
+    /// Root hash committed to in the execution hash
+    pub fn root(&self) -> [u8; 32] {
+        self.levels
+            .last()
+            .and_then(|l| l.first())
+            .copied()
+            .unwrap_or(EMPTY_ROOT)
+    }
+
+    /// Inclusion proof for the result of the given tool call
+    pub fn proof(&self, call_id: Uuid) -> Option<MerkleProof> {
+        let leaf_index = self.call_ids.iter().position(|id| *id == call_id)?;
+
+        let mut siblings = Vec::new();
+        let mut index = leaf_index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                siblings.push(const_hex::encode(level[sibling]));
+            }
+            index /= 2;
+        }
+
+        Some(MerkleProof {
+            call_id,
+            leaf_index,
+            leaf_count: self.call_ids.len(),
+            siblings,
+        })
+    }
+
+    /// Inclusion proofs for every tool result, in result order
+    pub fn proofs(&self) -> Vec<MerkleProof> {
+        self.call_ids
+            .iter()
+            .filter_map(|id| self.proof(*id))
+            .collect()
+    }
+}
+
+/// Hash a single tool result as a Merkle leaf
+///
+/// Covers the report-only verdict too, so the execution hash attests a call that ran
+/// only because its violated policies were report-only.
+pub fn hash_tool_result(result: &ToolResult) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(result.call_id.as_bytes());
+    hasher.update(&[result.success as u8]);
+    hasher.update(&[result.would_reject as u8]);
+    hasher.update(&(result.report_only_violations.len() as u64).to_le_bytes());
+    for violation in &result.report_only_violations {
+        hasher.update(&(violation.len() as u64).to_le_bytes());
+        hasher.update(violation.as_bytes());
+    }
+    hasher.update(result.result.as_bytes());
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Verify a single tool result against an attested Merkle root
+pub fn verify_tool_result_proof(result: &ToolResult, proof: &MerkleProof, root: &[u8; 32]) -> bool {
+    if result.call_id != proof.call_id || proof.leaf_index >= proof.leaf_count {
+        return false;
+    }
+
+    let mut hash = hash_tool_result(result);
+    let mut index = proof.leaf_index;
+    let mut width = proof.leaf_count;
+    let mut siblings = proof.siblings.iter();
+
+    while width > 1 {
+        let is_promoted = index == width - 1 && width % 2 == 1;
+        if !is_promoted {
+            let Some(sibling) = siblings
+                .next()
+                .and_then(|s| const_hex::decode_to_array::<_, 32>(s).ok())
+            else {
+                return false;
+            };
+
+            hash = if index.is_multiple_of(2) {
+                hash_node(&hash, &sibling)
+            } else {
+                hash_node(&sibling, &hash)
+            };
+        }
+
+        index /= 2;
+        width = width.div_ceil(2);
+    }
+
+    siblings.next().is_none() && hash == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_results(n: usize) -> Vec<ToolResult> {
+        (0..n)
+            .map(|i| ToolResult {
+                call_id: Uuid::now_v7(),
+                success: i % 2 == 0,
+                result: format!(r#"{{"tool": "PriceFeedTool", "index": {}}}"#, i),
+                error: None,
+                quote_verified: false,
+                compliance_quote: None,
+                result_hash: None,
+                injection_markers: Vec::new(),
+                would_reject: false,
+                report_only_violations: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_proof_for_one_of_several_results() {
+        let results = sample_results(5);
+        let tree = ToolResultsMerkleTree::build(&results);
+        let root = tree.root();
+
+        let target = &results[2];
+        let proof = tree.proof(target.call_id).unwrap();
+        assert_eq!(proof.leaf_index, 2);
+        assert_eq!(proof.leaf_count, 5);
+        assert!(verify_tool_result_proof(target, &proof, &root));
+
+        // Every result in the tree (including the promoted odd leaf) verifies
+        for (result, proof) in results.iter().zip(tree.proofs()) {
+            assert!(verify_tool_result_proof(result, &proof, &root));
+        }
+    }
+
+    #[test]
+    fn test_tampered_result_fails_verification() {
+        let results = sample_results(4);
+        let tree = ToolResultsMerkleTree::build(&results);
+        let root = tree.root();
+
+        let mut tampered = results[1].clone();
+        let proof = tree.proof(tampered.call_id).unwrap();
+        tampered.result.push_str("tampered");
+        assert!(!verify_tool_result_proof(&tampered, &proof, &root));
+
+        // Hiding that a report-only policy would have rejected the call breaks the proof
+        let mut flagged = results.clone();
+        flagged[1].report_violations(vec!["L2 would reject".to_string()]);
+        let tree = ToolResultsMerkleTree::build(&flagged);
+        let proof = tree.proof(results[1].call_id).unwrap();
+        assert!(verify_tool_result_proof(&flagged[1], &proof, &tree.root()));
+        assert!(!verify_tool_result_proof(&results[1], &proof, &tree.root()));
+
+        // A valid result cannot be presented with another call's proof
+        let tree = ToolResultsMerkleTree::build(&results);
+        let other_proof = tree.proof(results[0].call_id).unwrap();
+        assert!(!verify_tool_result_proof(&results[1], &other_proof, &root));
+    }
+
+    #[test]
+    fn test_single_and_empty_trees() {
+        assert_eq!(ToolResultsMerkleTree::build(&[]).root(), EMPTY_ROOT);
+
+        let results = sample_results(1);
+        let tree = ToolResultsMerkleTree::build(&results);
+        assert_eq!(tree.root(), hash_tool_result(&results[0]));
+
+        let proof = tree.proof(results[0].call_id).unwrap();
+        assert!(proof.siblings.is_empty());
+        assert!(verify_tool_result_proof(&results[0], &proof, &tree.root()));
+    }
+}
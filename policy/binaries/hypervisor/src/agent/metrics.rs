@@ -0,0 +1,131 @@
+//! Outcome counters and timings of compliance rule evaluations, by policy and rule
+//!
+//! Checkers record into the process-wide `RuleMetrics` served at `GET /metrics`, unless
+//! given another recorder, e.g. one kept by a test.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+/// Whether an evaluated rule let the plan or tool call through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleOutcome {
+    Passed,
+    Rejected,
+}
+
+impl RuleOutcome {
+    pub fn of<T, E>(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Self::Passed,
+            Err(_) => Self::Rejected,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+/// Sink of rule evaluations
+pub trait MetricsRecorder: Send + Sync + fmt::Debug {
+    fn record_rule(&self, policy_id: &str, rule_id: &str, outcome: RuleOutcome, elapsed: Duration);
+}
+
+/// Recorder shared by the checkers of every request
+pub type SharedRecorder = Arc<dyn MetricsRecorder>;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct RuleStats {
+    passed: u64,
+    rejected: u64,
+    seconds: f64,
+}
+
+/// In-memory rule counters, rendered in the Prometheus text format
+#[derive(Debug, Default)]
+pub struct RuleMetrics(Mutex<BTreeMap<(String, String), RuleStats>>);
+
+impl MetricsRecorder for RuleMetrics {
+    fn record_rule(&self, policy_id: &str, rule_id: &str, outcome: RuleOutcome, elapsed: Duration) {
+        let mut rules = self.0.lock().expect("rule metrics poisoned");
+        let stats = rules
+            .entry((policy_id.to_string(), rule_id.to_string()))
+            .or_default();
+        match outcome {
+            RuleOutcome::Passed => stats.passed += 1,
+            RuleOutcome::Rejected => stats.rejected += 1,
+        }
+        stats.seconds += elapsed.as_secs_f64();
+    }
+}
+
+impl RuleMetrics {
+    /// Evaluations of a rule ending in `outcome`
+    pub fn count(&self, policy_id: &str, rule_id: &str, outcome: RuleOutcome) -> u64 {
+        let rules = self.0.lock().expect("rule metrics poisoned");
+        let key = (policy_id.to_string(), rule_id.to_string());
+        rules.get(&key).map_or(0, |stats| match outcome {
+            RuleOutcome::Passed => stats.passed,
+            RuleOutcome::Rejected => stats.rejected,
+        })
+    }
+
+    /// Counters and total evaluation time of every rule evaluated so far
+    pub fn render(&self) -> String {
+        let labelled: Vec<_> = self
+            .0
+            .lock()
+            .expect("rule metrics poisoned")
+            .iter()
+            .map(|((policy, rule), stats)| {
+                (format!("policy=\"{}\",rule=\"{}\"", escape(policy), escape(rule)), *stats)
+            })
+            .collect();
+        let mut out = String::new();
+
+        out.push_str("# HELP compliance_rule_evaluations_total Compliance rule evaluations\n");
+        out.push_str("# TYPE compliance_rule_evaluations_total counter\n");
+        for (labels, stats) in &labelled {
+            for (outcome, count) in [
+                (RuleOutcome::Passed, stats.passed),
+                (RuleOutcome::Rejected, stats.rejected),
+            ] {
+                let outcome = outcome.label();
+                let _ = writeln!(
+                    out,
+                    "compliance_rule_evaluations_total{{{labels},outcome=\"{outcome}\"}} {count}"
+                );
+            }
+        }
+
+        out.push_str("# HELP compliance_rule_seconds_total Time spent in compliance rules\n");
+        out.push_str("# TYPE compliance_rule_seconds_total counter\n");
+        for (labels, stats) in &labelled {
+            let _ = writeln!(out, "compliance_rule_seconds_total{{{labels}}} {}", stats.seconds);
+        }
+
+        out
+    }
+}
+
+/// Label value with backslashes, quotes and newlines escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+static RULE_METRICS: LazyLock<Arc<RuleMetrics>> = LazyLock::new(Arc::default);
+
+/// The process-wide rule counters, served at `GET /metrics`
+pub fn rule_metrics() -> Arc<RuleMetrics> {
+    RULE_METRICS.clone()
+}
@@ -1,15 +1,41 @@
+pub mod aggregate;
+pub mod chains;
+pub mod clock;
 pub mod compliance;
 pub mod crypto_agent;
+pub mod data_file;
+pub mod error;
+pub mod http_tool;
+pub mod injection;
+pub mod json_path;
+pub mod merkle;
+pub mod metrics;
 pub mod policy_registry;
 pub mod quote_utils;
+pub mod replay;
 pub mod tools;
 pub mod types;
 
+pub use chains::{ChainError, SupportedChains};
+pub use clock::{Clock, MockClock, SharedClock, SystemClock};
 pub use compliance::{
-    ComplianceChecker, ComplianceMethod, LLMComplianceResult, Policy, PolicyMethod, PolicyRule,
-    PolicyRuleType,
+    ComplianceChecker, ComplianceMethod, CorpusMismatch, CorpusReport, DisabledMethods,
+    LLMComplianceResult, LlmVerdicts, Policy, PolicyMethod, PolicyRule, PolicyRuleType,
+    SkippedRule, ToolApproval,
 };
 pub use crypto_agent::CryptoAgent;
-pub use policy_registry::{PolicyInfo, PolicyRegistry};
-pub use quote_utils::{generate_compliance_quote, verify_compliance_quote_dummy};
-pub use types::{AgentPlan, AgentExecution, ComplianceQuote, ComplianceResult, Tool, ToolCall, ToolResult};
+pub use error::{AgentError, ToolError};
+pub use http_tool::{HttpTool, HttpToolConfig, PriceFeedHttpTool};
+pub use injection::InjectionMarkers;
+pub use merkle::{verify_tool_result_proof, MerkleProof, ToolResultsMerkleTree};
+pub use metrics::{MetricsRecorder, RuleMetrics, RuleOutcome};
+pub use policy_registry::{PolicyConflict, PolicyInfo, PolicyRegistry};
+pub use quote_utils::{
+    compliance_quote_binds_call, compliance_quote_matches, generate_compliance_quote,
+    verify_compliance_quote_dummy,
+};
+pub use replay::Transcript;
+pub use types::{
+    parse_argument_object, parse_arguments, AgentEvent, AgentExecution, AgentPlan,
+    ComplianceQuote, ComplianceResult, Tool, ToolCall, ToolOutput, ToolResult,
+};
@@ -1,8 +1,14 @@
 /// Central policy registry - single source of truth for policies and tool-policy mappings
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, fs, path::Path, sync::OnceLock};
 
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use super::chains::normalize_text;
+use super::crypto_agent::CryptoAgentConfig;
 use super::compliance::{
-    ComplianceMethod, Policy, PolicyMethod, PolicyRule, PolicyRuleType,
+    hash_policies, ComplianceMethod, DisabledMethods, Policy, PolicyMethod, PolicyRule,
+    PolicyRuleType,
 };
 
 /// Policy information with ID and name
@@ -21,21 +27,68 @@ impl PolicyInfo {
     }
 }
 
+/// Two rules governing the same tool that can't both be satisfied, found by
+/// `PolicyRegistry::lint`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyConflict {
+    pub tool_name: String,
+    /// The conflicting rules, as `policy_id/rule_id`
+    pub rules: [String; 2],
+    pub reason: String,
+}
+
+impl fmt::Display for PolicyConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [first, second] = &self.rules;
+        write!(
+            f,
+            "tool '{}': rules '{first}' and '{second}' conflict: {}",
+            self.tool_name, self.reason
+        )
+    }
+}
+
+/// Contents of `policy_file`
+#[derive(Debug, Deserialize)]
+struct PolicyFile {
+    policies: Vec<Policy>,
+    /// Per-tool policy IDs replacing the compiled mapping of the tools listed
+    #[serde(default)]
+    tool_policies: HashMap<String, Vec<String>>,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Registries built on the current thread, used to assert the registry is shared
+    pub(crate) static BUILD_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 /// Central registry for all policies and tool-policy mappings
+#[derive(Debug)]
 pub struct PolicyRegistry {
     policies: Vec<Policy>,
+    /// Hash of `policies`, `tool_policy_map` and `disabled_methods`, computed on first use;
+    /// reset whenever any of them changes
+    policy_hash: OnceLock<[u8; 32]>,
     tool_policy_map: HashMap<String, Vec<String>>,
+    disabled_methods: DisabledMethods,
 }
 
 impl PolicyRegistry {
     /// Create the default crypto policy registry with L1-L4 policies and T1-T4 tool mappings
     pub fn default_crypto_policy() -> Self {
+        #[cfg(test)]
+        BUILD_COUNT.with(|count| count.set(count.get() + 1));
+
         let policies = vec![
             // L1: No personalized investment advice
             Policy {
                 id: "L1".to_string(),
                 name: "No personalized investment advice".to_string(),
                 text: "The agent must not give personalized investment advice. It may explain concepts and describe markets in general terms, but it must not recommend what a specific user should buy/sell/hold, how to allocate their portfolio, or what concrete trades they should execute, given their personal situation or holdings.".to_string(),
+                enabled: true,
+                cross_turn: false,
+                report_only: false,
                 methods: vec![
                     PolicyMethod {
                         method: ComplianceMethod::Deterministic,
@@ -67,6 +120,8 @@ impl PolicyRegistry {
                                 id: "llm_check_personalized_advice".to_string(),
                                 rule_type: PolicyRuleType::LLMCompliance {
                                     check_prompt: "Does this query ask for personalized investment advice specific to a user's situation?".to_string(),
+                                    model: None,
+                                    temperature: None,
                                 },
                                 parameters: serde_json::json!({}),
                             },
@@ -80,6 +135,9 @@ impl PolicyRegistry {
                 id: "L2".to_string(),
                 name: "Aggregated outputs only (no raw dumps)".to_string(),
                 text: "The agent may use raw tool data internally, but user-facing outputs must be aggregated or summarized (e.g., totals, averages, ranges, counts, small illustrative snippets). It must not return large raw dumps such as full tick-by-tick feeds, long transaction lists, or full order books.".to_string(),
+                enabled: true,
+                cross_turn: false,
+                report_only: false,
                 methods: vec![
                     PolicyMethod {
                         method: ComplianceMethod::Deterministic,
@@ -101,6 +159,8 @@ impl PolicyRegistry {
                                 id: "llm_check_raw_dump".to_string(),
                                 rule_type: PolicyRuleType::LLMCompliance {
                                     check_prompt: "Does this query ask for raw data instead of aggregated/summarized information?".to_string(),
+                                    model: None,
+                                    temperature: None,
                                 },
                                 parameters: serde_json::json!({}),
                             },
@@ -114,6 +174,9 @@ impl PolicyRegistry {
                 id: "L3".to_string(),
                 name: "No deanonymization / doxxing of wallets".to_string(),
                 text: "The agent must not attempt to infer or assert real-world identities behind wallet addresses, nor encourage harassment or targeting of specific wallets. It may use labels explicitly provided by tools (e.g., \"this is a known centralized exchange hot wallet\") but must not guess that an address belongs to a named person or organization unless that information is explicitly and legitimately public and provided.".to_string(),
+                enabled: true,
+                cross_turn: false,
+                report_only: false,
                 methods: vec![
                     PolicyMethod {
                         method: ComplianceMethod::Deterministic,
@@ -142,6 +205,8 @@ impl PolicyRegistry {
                                 id: "llm_check_doxxing".to_string(),
                                 rule_type: PolicyRuleType::LLMCompliance {
                                     check_prompt: "Does this query attempt to infer or assert real-world identities behind wallet addresses without explicit public information?".to_string(),
+                                    model: None,
+                                    temperature: None,
                                 },
                                 parameters: serde_json::json!({}),
                             },
@@ -155,6 +220,9 @@ impl PolicyRegistry {
                 id: "L4".to_string(),
                 name: "Source attribution & timestamp".to_string(),
                 text: "Whenever the agent uses data from a tool in its answer, it must clearly attribute the source and include a time reference. For example: \"According to PriceFeedTool (data as of 2025-11-20 10:00 UTC), BTC's price is …\". Attribution must be present for each distinct tool whose data is used.".to_string(),
+                enabled: true,
+                cross_turn: false,
+                report_only: false,
                 methods: vec![
                     PolicyMethod {
                         method: ComplianceMethod::Deterministic,
@@ -176,6 +244,8 @@ impl PolicyRegistry {
                                 id: "llm_check_attribution".to_string(),
                                 rule_type: PolicyRuleType::LLMCompliance {
                                     check_prompt: "This policy can be enforced post-hoc. Simply return True".to_string(),
+                                    model: None,
+                                    temperature: None,
                                 },
                                 parameters: serde_json::json!({}),
                             },
@@ -208,15 +278,139 @@ impl PolicyRegistry {
 
         Self {
             policies,
+            policy_hash: OnceLock::new(),
             tool_policy_map,
+            disabled_methods: DisabledMethods::default(),
         }
     }
 
+    /// Replace the policy mapping of the given tools, keeping the compiled
+    /// default for every tool not mentioned in `overrides`
+    ///
+    /// Fails if an override references a policy that is not registered.
+    pub fn with_tool_policy_overrides(
+        mut self,
+        overrides: &HashMap<String, Vec<String>>,
+    ) -> Result<Self> {
+        for (tool_name, policy_ids) in overrides {
+            if let Some(unknown) = policy_ids.iter().find(|id| self.get_policy(id).is_none()) {
+                bail!("unknown policy '{unknown}' in override for tool '{tool_name}'");
+            }
+            self.tool_policy_map.insert(tool_name.clone(), policy_ids.clone());
+        }
+        self.policy_hash = OnceLock::new();
+
+        Ok(self)
+    }
+
+    /// Registry with the policies of a TOML file instead of the compiled L1-L4
+    ///
+    /// Tools the file doesn't map keep the compiled mapping, so every policy it references
+    /// must still be defined. Fails on duplicate policy IDs and unknown mapped policies.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("read policy file {}", path.display()))?;
+        let file: PolicyFile = toml::from_str(&data)
+            .with_context(|| format!("parse policy file {}", path.display()))?;
+
+        for (i, policy) in file.policies.iter().enumerate() {
+            if file.policies[..i].iter().any(|p| p.id == policy.id) {
+                bail!("duplicate policy '{}' in {}", policy.id, path.display());
+            }
+        }
+
+        let registry = Self {
+            policies: file.policies,
+            ..Self::default_crypto_policy()
+        }
+        .with_tool_policy_overrides(&file.tool_policies)?;
+
+        if let Some((tool_name, policy_id)) = registry.unknown_mapped_policies().first() {
+            bail!(
+                "tool '{tool_name}' is mapped to policy '{policy_id}', not defined in {}",
+                path.display()
+            );
+        }
+
+        Ok(registry)
+    }
+
+    /// Registry of the agent config's policy file, or the default one, with the config's
+    /// tool-policy overrides and disabled methods
+    pub fn from_agent_config(config: &CryptoAgentConfig) -> Result<Self> {
+        let registry = match &config.policy_file {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default_crypto_policy(),
+        };
+
+        registry
+            .with_tool_policy_overrides(&config.tool_policies)?
+            .with_cross_turn_policies(&config.cross_turn_policies)?
+            .with_report_only_policies(&config.report_only_policies)?
+            .with_disabled_methods(config.disabled_compliance_methods.clone())
+    }
+
+    /// Check the given policies across turns too, see `ComplianceChecker::check_conversation`
+    pub fn with_cross_turn_policies(mut self, policy_ids: &[String]) -> Result<Self> {
+        for id in policy_ids {
+            let Some(policy) = self.policies.iter_mut().find(|p| &p.id == id) else {
+                bail!("unknown policy '{id}' in cross-turn policies");
+            };
+            policy.cross_turn = true;
+        }
+        self.policy_hash = OnceLock::new();
+
+        Ok(self)
+    }
+
+    /// Only log and report violations of the given policies, see `Policy::report_only`
+    pub fn with_report_only_policies(mut self, policy_ids: &[String]) -> Result<Self> {
+        for id in policy_ids {
+            let Some(policy) = self.policies.iter_mut().find(|p| &p.id == id) else {
+                bail!("unknown policy '{id}' in report-only policies");
+            };
+            policy.report_only = true;
+        }
+        self.policy_hash = OnceLock::new();
+
+        Ok(self)
+    }
+
+    /// Switch compliance methods off globally or per policy
+    pub fn with_disabled_methods(mut self, disabled_methods: DisabledMethods) -> Result<Self> {
+        if let Some(unknown) = disabled_methods
+            .per_policy
+            .keys()
+            .find(|id| self.get_policy(id).is_none())
+        {
+            bail!("unknown policy '{unknown}' in disabled compliance methods");
+        }
+        self.disabled_methods = disabled_methods;
+        self.policy_hash = OnceLock::new();
+
+        Ok(self)
+    }
+
+    /// Compliance methods switched off at runtime
+    pub fn disabled_methods(&self) -> &DisabledMethods {
+        &self.disabled_methods
+    }
+
     /// Get all policies
     pub fn policies(&self) -> &[Policy] {
         &self.policies
     }
 
+    /// Hash of the policies, their tool mapping and disabled methods, computed once per load
+    /// and reused by every checker built from this registry
+    pub fn policy_hash(&self) -> [u8; 32] {
+        *self
+            .policy_hash
+            .get_or_init(|| {
+                hash_policies(&self.policies, &self.tool_policy_map, &self.disabled_methods)
+            })
+    }
+
     /// Get a policy by ID
     pub fn get_policy(&self, id: &str) -> Option<&Policy> {
         self.policies.iter().find(|p| p.id == id)
@@ -242,6 +436,75 @@ impl PolicyRegistry {
             .collect()
     }
 
+    /// Tool-policy mappings that reference an unregistered policy, as `(tool, policy_id)`
+    pub fn unknown_mapped_policies(&self) -> Vec<(String, String)> {
+        let mut unknown: Vec<_> = self
+            .tool_policy_map
+            .iter()
+            .flat_map(|(tool_name, policy_ids)| {
+                policy_ids
+                    .iter()
+                    .filter(|id| self.get_policy(id).is_none())
+                    .map(move |id| (tool_name.clone(), id.clone()))
+            })
+            .collect();
+        unknown.sort();
+
+        unknown
+    }
+
+    /// Obvious conflicts between the enforced deterministic rules of each tool's policies:
+    /// allowed values rejected by a prohibited keyword or identity term, and allowlists
+    /// of one argument with no value in common
+    pub fn lint(&self) -> Vec<PolicyConflict> {
+        let mut tool_names: Vec<_> = self.tool_policy_map.keys().collect();
+        tool_names.sort();
+
+        let mut conflicts = Vec::new();
+        for tool_name in tool_names {
+            let rules: Vec<_> = self
+                .get_policy_ids_for_tool(tool_name)
+                .iter()
+                .filter_map(|id| self.get_policy(id))
+                .filter(|policy| policy.enabled)
+                .flat_map(|policy| {
+                    policy
+                        .methods
+                        .iter()
+                        .filter(|method| {
+                            method.method == ComplianceMethod::Deterministic
+                                && !self.disabled_methods.is_disabled(&policy.id, &method.method)
+                        })
+                        .flat_map(|method| &method.rules)
+                        .map(|rule| (format!("{}/{}", policy.id, rule.id), &rule.rule_type))
+                })
+                .collect();
+
+            for (i, (allowed_at, allowed)) in rules.iter().enumerate() {
+                let PolicyRuleType::AllowedValues { field, values } = allowed else {
+                    continue;
+                };
+                for (j, (other_at, other)) in rules.iter().enumerate() {
+                    // Pairs of allowlists are reported once
+                    let seen = j < i && matches!(other, PolicyRuleType::AllowedValues { .. });
+                    if j == i || seen {
+                        continue;
+                    }
+                    let Some(reason) = allowlist_conflict(field, values, other) else {
+                        continue;
+                    };
+                    conflicts.push(PolicyConflict {
+                        tool_name: tool_name.clone(),
+                        rules: [allowed_at.clone(), other_at.clone()],
+                        reason,
+                    });
+                }
+            }
+        }
+
+        conflicts
+    }
+
     /// Get the tool-policy map
     pub fn tool_policy_map(&self) -> &HashMap<String, Vec<String>> {
         &self.tool_policy_map
@@ -252,3 +515,226 @@ impl PolicyRegistry {
         (self.policies.clone(), self.tool_policy_map.clone())
     }
 }
+
+/// Why the allowlist of `field` conflicts with `other`, if it does
+fn allowlist_conflict(field: &str, values: &[String], other: &PolicyRuleType) -> Option<String> {
+    // Both rules scan the call's whole arguments, so an allowed value containing a
+    // prohibited term is always rejected
+    let rejected = |terms: &[String], normalize: fn(&str) -> String, kind: &str| {
+        values.iter().find_map(|value| {
+            let value_norm = normalize(value);
+            let term = terms
+                .iter()
+                .find(|term| value_norm.contains(normalize(term).as_str()))?;
+            Some(format!("allowed value '{value}' of '{field}' contains {kind} '{term}'"))
+        })
+    };
+
+    match other {
+        PolicyRuleType::ProhibitedKeywords { keywords } => {
+            rejected(keywords, |text| text.to_lowercase(), "prohibited keyword")
+        }
+        PolicyRuleType::NoIdentityInference { prohibited_terms } => {
+            rejected(prohibited_terms, normalize_text, "identity inference term")
+        }
+        PolicyRuleType::AllowedValues {
+            field: other_field,
+            values: other_values,
+        } if other_field == field => {
+            let shared = values
+                .iter()
+                .any(|value| other_values.iter().any(|other| other.eq_ignore_ascii_case(value)));
+            (!shared).then(|| format!("no value of '{field}' is allowed by both"))
+        }
+        _ => None,
+    }
+}
+
+impl Default for PolicyRegistry {
+    fn default() -> Self {
+        Self::default_crypto_policy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_policy_override_applies() {
+        let overrides = HashMap::from([(
+            "PriceFeedTool".to_string(),
+            vec!["L1".to_string(), "L4".to_string()],
+        )]);
+        let registry = PolicyRegistry::default_crypto_policy()
+            .with_tool_policy_overrides(&overrides)
+            .unwrap();
+
+        assert_eq!(registry.get_policy_ids_for_tool("PriceFeedTool"), ["L1", "L4"]);
+        // Tools without an override keep the compiled default
+        assert_eq!(registry.get_policy_ids_for_tool("SentimentTool"), ["L1", "L4"]);
+        assert_eq!(
+            registry.get_policy_ids_for_tool("OnChainHistoryTool"),
+            ["L1", "L2", "L3"]
+        );
+    }
+
+    #[test]
+    fn test_tool_policy_override_rejects_unknown_policy() {
+        let overrides = HashMap::from([("PriceFeedTool".to_string(), vec!["L9".to_string()])]);
+        let err = PolicyRegistry::default_crypto_policy()
+            .with_tool_policy_overrides(&overrides)
+            .unwrap_err();
+        assert!(err.to_string().contains("L9"));
+    }
+
+    #[test]
+    fn test_policy_file_replaces_compiled_policies() {
+        let path = std::env::temp_dir().join(format!("policies_{}.toml", uuid::Uuid::now_v7()));
+        let policy = |id: &str| {
+            format!(
+                r#"
+                [[policies]]
+                id = "{id}"
+                name = "No hype"
+                text = "No price predictions"
+                [[policies.methods]]
+                method = "Deterministic"
+                [[policies.methods.rules]]
+                id = "no_moon"
+                rule_type = {{ type = "ProhibitedKeywords", keywords = ["to the moon"] }}
+                "#
+            )
+        };
+        let mapping = r#"
+            [tool_policies]
+            PriceFeedTool = ["H1"]
+            OnChainHistoryTool = ["H1"]
+            SentimentTool = ["H1"]
+            PortfolioTool = ["H1"]
+        "#;
+
+        fs::write(&path, policy("H1") + mapping).unwrap();
+        let registry = PolicyRegistry::from_file(&path).unwrap();
+        assert_eq!(registry.policies().len(), 1);
+        assert_eq!(registry.get_policy_ids_for_tool("SentimentTool"), ["H1"]);
+
+        // Tools left on the compiled mapping reference L1-L4, which the file dropped
+        fs::write(&path, policy("H1")).unwrap();
+        let err = PolicyRegistry::from_file(&path).unwrap_err();
+        assert!(err.to_string().contains("not defined"), "{err}");
+
+        fs::write(&path, policy("H1") + &policy("H1") + mapping).unwrap();
+        let err = PolicyRegistry::from_file(&path).unwrap_err();
+        assert!(err.to_string().contains("duplicate policy 'H1'"), "{err}");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_lint_flags_conflicting_rules() {
+        let policy = |id: &str, rule_type: PolicyRuleType| Policy {
+            id: id.to_string(),
+            name: id.to_string(),
+            text: String::new(),
+            enabled: true,
+            cross_turn: false,
+            report_only: false,
+            methods: vec![PolicyMethod {
+                method: ComplianceMethod::Deterministic,
+                rules: vec![PolicyRule {
+                    id: "rule".to_string(),
+                    rule_type,
+                    parameters: serde_json::json!({}),
+                }],
+            }],
+        };
+        let allowed = |values: &[&str]| PolicyRuleType::AllowedValues {
+            field: "symbol".to_string(),
+            values: values.iter().map(ToString::to_string).collect(),
+        };
+        let registry = PolicyRegistry {
+            policies: vec![
+                policy("A", allowed(&["BTC", "ETH", "SHIB"])),
+                policy(
+                    "B",
+                    PolicyRuleType::ProhibitedKeywords {
+                        keywords: vec!["shib".to_string()],
+                    },
+                ),
+                policy("C", allowed(&["DOGE"])),
+            ],
+            policy_hash: OnceLock::new(),
+            tool_policy_map: HashMap::from([
+                ("PriceFeedTool".to_string(), vec!["A".to_string(), "B".to_string()]),
+                ("SentimentTool".to_string(), vec!["A".to_string(), "C".to_string()]),
+            ]),
+            disabled_methods: DisabledMethods::default(),
+        };
+
+        let conflicts = registry.lint();
+        assert_eq!(conflicts.len(), 2, "{conflicts:?}");
+        assert_eq!(conflicts[0].tool_name, "PriceFeedTool");
+        assert_eq!(conflicts[0].rules, ["A/rule", "B/rule"]);
+        assert!(conflicts[0].reason.contains("'SHIB'"), "{}", conflicts[0]);
+        assert_eq!(conflicts[1].tool_name, "SentimentTool");
+        assert_eq!(conflicts[1].rules, ["A/rule", "C/rule"]);
+        assert!(conflicts[1].to_string().contains("no value of 'symbol'"));
+
+        // The allowed value is indeed rejected
+        let checker = crate::agent::ComplianceChecker::from_registry(&registry);
+        let check = |symbol: &str| {
+            let args = serde_json::json!({ "symbol": symbol }).to_string();
+            checker.check_tool_compliance("PriceFeedTool", "price?", &args)
+        };
+        assert!(check("btc").is_ok());
+        assert!(check("SHIB").unwrap_err().contains("Prohibited keyword"));
+        assert!(check("XRP").unwrap_err().contains("not allowed"));
+
+        // Disabling either rule resolves the conflict
+        let registry = registry
+            .with_disabled_methods(DisabledMethods {
+                per_policy: HashMap::from([(
+                    "B".to_string(),
+                    vec![ComplianceMethod::Deterministic],
+                )]),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(registry.lint().len(), 1);
+        assert!(PolicyRegistry::default_crypto_policy().lint().is_empty());
+    }
+    #[test]
+    fn test_disabled_methods_change_the_hash() {
+        let registry = PolicyRegistry::default_crypto_policy();
+        let enforced = registry.policy_hash();
+        let disabled = |disabled_methods| {
+            PolicyRegistry::default_crypto_policy()
+                .with_disabled_methods(disabled_methods)
+                .unwrap()
+                .policy_hash()
+        };
+
+        let global = disabled(DisabledMethods {
+            global: vec![ComplianceMethod::LLMBased],
+            ..Default::default()
+        });
+        let per_policy = disabled(DisabledMethods {
+            per_policy: HashMap::from([("L1".to_string(), vec![ComplianceMethod::LLMBased])]),
+            ..Default::default()
+        });
+        assert_ne!(global, enforced);
+        assert_ne!(per_policy, enforced);
+        assert_ne!(per_policy, global);
+        assert_eq!(disabled(DisabledMethods::default()), enforced);
+
+        // The hash cached before the methods were disabled is dropped
+        let registry = registry
+            .with_disabled_methods(DisabledMethods {
+                global: vec![ComplianceMethod::LLMBased],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(registry.policy_hash(), global);
+    }
+}
@@ -1,8 +1,14 @@
 use anyhow::{Context, Result};
-use std::time::SystemTime;
+use attest::types::RawReport;
 use tracing::{debug, info};
+use uuid::Uuid;
 
-use super::types::ComplianceQuote;
+use super::clock::Clock;
+use super::types::{ComplianceQuote, ToolCall};
+use crate::utils::{
+    attest::{ReportDataBuilder, COMPLIANCE_DOMAIN},
+    verify::{verify_quote, VerifyOutcome},
+};
 
 /// Generate a real TEE attestation quote for a compliance check result
 /// 
@@ -13,20 +19,20 @@ use super::types::ComplianceQuote;
 /// - Certificate chain for verification
 pub fn generate_compliance_quote(
     tool_name: &str,
+    call_id: Uuid,
+    session_id: Uuid,
     compliant: bool,
     policy_ids: &[String],
     user_query: &str,
     arguments: &str,
+    clock: &dyn Clock,
 ) -> Result<ComplianceQuote> {
     // Generate a deterministic hash of the compliance check inputs
     // This hash will be embedded in the TEE attestation quote's report_data
-    let compliance_hash = hash_compliance_data(
-        tool_name,
-        compliant,
-        policy_ids,
-        user_query,
-        arguments,
-    );
+    let context_hash = hash_decision_context(policy_ids, user_query);
+    let compliance_report =
+        hash_compliance_data(tool_name, call_id, session_id, compliant, &context_hash, arguments);
+    let compliance_hash = compliance_report.digest();
 
     debug!(
         tool_name = %tool_name,
@@ -35,14 +41,33 @@ pub fn generate_compliance_quote(
         "Generating TEE attestation quote for compliance check"
     );
 
-    // Generate the raw report with the compliance hash
-    let raw_report = crate::utils::attest::generate_raw_report_from_hash(compliance_hash);
-
     // Get the actual TEE attestation quote (TDX/SGX)
-    let quote = attest::get_quote(raw_report)
-        .context("Failed to generate TEE attestation quote for compliance check")?;
+    quote_compliance_report(
+        tool_name,
+        call_id,
+        session_id,
+        compliant,
+        context_hash,
+        &compliance_report,
+        clock,
+        |report| Ok(attest::get_quote(report)?.to_bytes()),
+    )
+}
 
-    let quote_bytes = quote.to_bytes();
+/// Quote over the report_data of a compliance decision from `get_quote`, timestamped
+/// from `clock`
+fn quote_compliance_report(
+    tool_name: &str,
+    call_id: Uuid,
+    session_id: Uuid,
+    compliant: bool,
+    context_hash: [u8; 32],
+    compliance_report: &ReportDataBuilder,
+    clock: &dyn Clock,
+    get_quote: impl FnOnce(RawReport) -> Result<Vec<u8>>,
+) -> Result<ComplianceQuote> {
+    let quote_bytes = get_quote(compliance_report.build())
+        .context("Failed to generate TEE attestation quote for compliance check")?;
 
     info!(
         tool_name = %tool_name,
@@ -53,49 +78,108 @@ pub fn generate_compliance_quote(
 
     Ok(ComplianceQuote {
         tool_name: tool_name.to_string(),
+        call_id,
+        session_id,
         compliant,
         quote_bytes,
-        compliance_hash,
-        timestamp: SystemTime::now(),
+        compliance_hash: compliance_report.digest(),
+        context_hash,
+        timestamp: clock.now(),
     })
 }
 
+/// Hash what a compliance decision was made against: the policies evaluated and the
+/// user query
+///
+/// Carried by the quote as `context_hash`, so the call a quote approves can be checked
+/// without them, see `compliance_quote_binds_call`.
+pub(crate) fn hash_decision_context(policy_ids: &[String], user_query: &str) -> [u8; 32] {
+    // Policy IDs are sorted for determinism
+    let mut sorted_policies = policy_ids.to_vec();
+    sorted_policies.sort();
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&(sorted_policies.len() as u64).to_le_bytes());
+    for policy_id in &sorted_policies {
+        hasher.update(&(policy_id.len() as u64).to_le_bytes());
+        hasher.update(policy_id.as_bytes());
+    }
+    hasher.update(user_query.as_bytes());
+
+    hasher.finalize().into()
+}
+
 /// Hash the compliance check data
 /// 
-/// Creates a deterministic hash that represents the compliance check decision.
-/// This hash is embedded in the TEE attestation quote's report_data field.
-fn hash_compliance_data(
+/// Creates a deterministic digest that represents the compliance check decision.
+/// The digest is the report_data (`compliance` domain) of the TEE attestation quote.
+/// The call and session IDs bind it to one call, so it can't approve another call
+/// of the same tool.
+pub(crate) fn hash_compliance_data(
     tool_name: &str,
+    call_id: Uuid,
+    session_id: Uuid,
     compliant: bool,
+    context_hash: &[u8; 32],
+    arguments: &str,
+) -> ReportDataBuilder {
+    ReportDataBuilder::new(COMPLIANCE_DOMAIN)
+        .field(tool_name)
+        .field(call_id.as_bytes())
+        .field(session_id.as_bytes())
+        .field([compliant as u8])
+        .field(context_hash)
+        .field(arguments)
+}
+
+/// Check that a compliance quote attests this decision on these inputs
+///
+/// Recomputes the compliance hash from the quote's tool name, call and decision, so a client
+/// holding a rejection record can prove the hypervisor evaluated and denied the call.
+/// Combine with `verify_compliance_quote_dummy` to also check the quote's report_data.
+pub fn compliance_quote_matches(
+    quote: &ComplianceQuote,
     policy_ids: &[String],
     user_query: &str,
     arguments: &str,
-) -> [u8; 32] {
-    let mut hasher = blake3::Hasher::new();
-    
-    // Hash a version prefix
-    hasher.update(b"COMPLIANCE_V1");
-    
-    // Hash tool name
-    hasher.update(tool_name.as_bytes());
-    
-    // Hash compliance result
-    hasher.update(&[if compliant { 1u8 } else { 0u8 }]);
-    
-    // Hash policy IDs (sorted for determinism)
-    let mut sorted_policies = policy_ids.to_vec();
-    sorted_policies.sort();
-    for policy_id in sorted_policies {
-        hasher.update(policy_id.as_bytes());
+) -> bool {
+    let expected = hash_compliance_data(
+        &quote.tool_name,
+        quote.call_id,
+        quote.session_id,
+        quote.compliant,
+        &hash_decision_context(policy_ids, user_query),
+        arguments,
+    )
+    .digest();
+
+    expected == quote.compliance_hash
+}
+
+/// Check that a compliance quote was issued for the call it accompanies
+///
+/// Recomputes the compliance hash from the call's tool name, ID and arguments, with the
+/// session, decision and decision context the quote claims, and checks it against the
+/// quote's report_data. The quote's own `tool_name`, `call_id` and `compliance_hash` are
+/// not trusted, so editing them can't carry a quote over to another call.
+pub fn compliance_quote_binds_call(quote: &ComplianceQuote, call: &ToolCall) -> bool {
+    let expected = hash_compliance_data(
+        &call.tool_name,
+        call.id,
+        quote.session_id,
+        quote.compliant,
+        &quote.context_hash,
+        &call.arguments,
+    )
+    .digest();
+
+    match verify_quote(&quote.quote_bytes, Some(&expected), &[]) {
+        VerifyOutcome::Verified(_) => true,
+        outcome => {
+            debug!(%outcome, call_id = %call.id, "Compliance quote isn't bound to the call");
+            false
+        }
     }
-    
-    // Hash user query
-    hasher.update(user_query.as_bytes());
-    
-    // Hash arguments
-    hasher.update(arguments.as_bytes());
-    
-    hasher.finalize().into()
 }
 
 /// Verify a compliance quote (dummy implementation for tools)
@@ -129,43 +213,18 @@ pub fn verify_compliance_quote_dummy(
         return Ok(false);
     }
 
-    // In a real implementation, we would:
-    // 1. Parse the quote using dcap-rs or similar
-    // 2. Verify the quote signature
-    // 3. Check the certificate chain
-    // 4. Validate RTMR values against known good measurements
-    // 5. Extract report_data and verify it matches compliance_hash
-    // 6. Check timestamp for freshness
-    
-    // For now, we just parse it to check basic validity
-    let quote_parsed = attest::types::Quote::from_bytes(&quote.quote_bytes);
-    
-    match quote_parsed {
-        Ok(parsed_quote) => {
-            // Extract report_data from the quote
-            let report_data = parsed_quote.report_data();
-            let embedded_hash = &report_data[..32];
-            
-            // Verify the compliance hash matches what's in the quote
-            if embedded_hash != quote.compliance_hash {
-                debug!(
-                    expected = %const_hex::encode(quote.compliance_hash),
-                    actual = %const_hex::encode(embedded_hash),
-                    "Quote verification failed: compliance hash mismatch"
-                );
-                return Ok(false);
-            }
-            
+    match verify_quote(&quote.quote_bytes, Some(&quote.compliance_hash), &[]) {
+        VerifyOutcome::Verified(_) => {
             info!(
                 tool_name = %quote.tool_name,
                 compliant = quote.compliant,
                 "Quote verification PASSED (dummy verification - signature not checked)"
             );
-            
+
             Ok(true)
         }
-        Err(e) => {
-            debug!(error = %e, "Quote verification failed: invalid quote format");
+        outcome => {
+            debug!(%outcome, "Quote verification failed");
             Ok(false)
         }
     }
@@ -174,24 +233,33 @@ pub fn verify_compliance_quote_dummy(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::agent::clock::{MockClock, SystemClock};
+    use crate::test_utils::synthetic_td_quote;
+
+    const CALL_ID: Uuid = Uuid::from_u128(1);
+    const SESSION_ID: Uuid = Uuid::from_u128(2);
 
     #[test]
     fn test_hash_compliance_data() {
         let hash1 = hash_compliance_data(
             "PriceFeedTool",
+            CALL_ID,
+            SESSION_ID,
             true,
-            &["L1".to_string()],
-            "What is the price of BTC?",
+            &hash_decision_context(&["L1".to_string()], "What is the price of BTC?"),
             r#"{"symbol": "BTC"}"#,
-        );
+        )
+        .digest();
 
         let hash2 = hash_compliance_data(
             "PriceFeedTool",
+            CALL_ID,
+            SESSION_ID,
             true,
-            &["L1".to_string()],
-            "What is the price of BTC?",
+            &hash_decision_context(&["L1".to_string()], "What is the price of BTC?"),
             r#"{"symbol": "BTC"}"#,
-        );
+        )
+        .digest();
 
         // Same inputs should produce same hash
         assert_eq!(hash1, hash2);
@@ -199,13 +267,195 @@ mod tests {
         // Different compliance result should produce different hash
         let hash3 = hash_compliance_data(
             "PriceFeedTool",
+            CALL_ID,
+            SESSION_ID,
             false, // changed
-            &["L1".to_string()],
-            "What is the price of BTC?",
+            &hash_decision_context(&["L1".to_string()], "What is the price of BTC?"),
             r#"{"symbol": "BTC"}"#,
-        );
+        )
+        .digest();
 
         assert_ne!(hash1, hash3);
+
+        // Another call of the same tool, with the same inputs, gets its own hash
+        let hash4 = hash_compliance_data(
+            "PriceFeedTool",
+            Uuid::from_u128(3),
+            SESSION_ID,
+            true,
+            &hash_decision_context(&["L1".to_string()], "What is the price of BTC?"),
+            r#"{"symbol": "BTC"}"#,
+        )
+        .digest();
+
+        assert_ne!(hash1, hash4);
+    }
+
+    #[test]
+    fn test_denied_quote_matches_decision() {
+        let policy_ids = ["L1".to_string()];
+        let query = "Should buy BTC now?";
+        let arguments = r#"{"symbol": "BTC"}"#;
+
+        let context_hash = hash_decision_context(&policy_ids, query);
+        let compliance_hash = hash_compliance_data(
+            "PriceFeedTool",
+            CALL_ID,
+            SESSION_ID,
+            false,
+            &context_hash,
+            arguments,
+        )
+        .digest();
+        let denied = ComplianceQuote {
+            tool_name: "PriceFeedTool".to_string(),
+            call_id: CALL_ID,
+            session_id: SESSION_ID,
+            compliant: false,
+            quote_bytes: vec![],
+            compliance_hash,
+            context_hash,
+            timestamp: std::time::SystemTime::now(),
+        };
+        assert!(compliance_quote_matches(&denied, &policy_ids, query, arguments));
+
+        // The same quote can't be passed off as an approval or for other inputs
+        let claimed_approval = ComplianceQuote {
+            compliant: true,
+            ..denied.clone()
+        };
+        assert!(!compliance_quote_matches(&claimed_approval, &policy_ids, query, arguments));
+        assert!(!compliance_quote_matches(&denied, &policy_ids, "What is BTC?", arguments));
+        let other_call = ComplianceQuote {
+            call_id: Uuid::from_u128(3),
+            ..denied.clone()
+        };
+        assert!(!compliance_quote_matches(&other_call, &policy_ids, query, arguments));
+    }
+
+    #[test]
+    fn test_compliance_quote_is_stamped_from_the_clock() {
+        let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_735_689_600);
+        let clock = MockClock::new(now);
+        let policy_ids = ["L1".to_string()];
+        let (query, arguments) = ("What is the price of BTC?", r#"{"symbol": "BTC"}"#);
+        let context_hash = hash_decision_context(&policy_ids, query);
+        let report = hash_compliance_data(
+            "PriceFeedTool",
+            CALL_ID,
+            SESSION_ID,
+            true,
+            &context_hash,
+            arguments,
+        );
+
+        let mock_quote = |report: RawReport| Ok(report.to_bytes().to_vec());
+        let quote = |clock: &MockClock| {
+            quote_compliance_report(
+                "PriceFeedTool",
+                CALL_ID,
+                SESSION_ID,
+                true,
+                context_hash,
+                &report,
+                clock,
+                mock_quote,
+            )
+            .unwrap()
+        };
+        assert_eq!(quote(&clock).timestamp, now);
+        assert_eq!(quote(&clock).timestamp, now);
+        assert!(compliance_quote_matches(&quote(&clock), &policy_ids, query, arguments));
+
+        clock.advance(std::time::Duration::from_secs(5));
+        assert_eq!(quote(&clock).timestamp, now + std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_quote_binds_only_its_call() {
+        let call = ToolCall {
+            id: CALL_ID,
+            tool_name: "PriceFeedTool".to_string(),
+            arguments: r#"{"symbol": "BTC"}"#.to_string(),
+            timestamp: std::time::SystemTime::now(),
+            compliance_quote: None,
+            thought_step: None,
+        };
+        let context_hash = hash_decision_context(&["L1".to_string()], "What is the price of BTC?");
+        let report = hash_compliance_data(
+            &call.tool_name,
+            call.id,
+            SESSION_ID,
+            true,
+            &context_hash,
+            &call.arguments,
+        );
+        let quote = quote_compliance_report(
+            &call.tool_name,
+            call.id,
+            SESSION_ID,
+            true,
+            context_hash,
+            &report,
+            &SystemClock,
+            |report| Ok(synthetic_td_quote(report.to_bytes())),
+        )
+        .unwrap();
+        assert!(compliance_quote_binds_call(&quote, &call));
+
+        // Relabeling the quote doesn't carry it over to another call or other arguments
+        let other_call = ToolCall {
+            id: Uuid::from_u128(3),
+            ..call.clone()
+        };
+        let relabeled = ComplianceQuote {
+            call_id: other_call.id,
+            ..quote.clone()
+        };
+        assert!(!compliance_quote_binds_call(&relabeled, &other_call));
+        let other_arguments = ToolCall {
+            arguments: r#"{"symbol": "ETH"}"#.to_string(),
+            ..call.clone()
+        };
+        assert!(!compliance_quote_binds_call(&quote, &other_arguments));
+
+        // Nor does claiming an approval for a denial, or a hash the quote doesn't attest
+        let flipped = ComplianceQuote {
+            compliant: false,
+            ..quote.clone()
+        };
+        assert!(!compliance_quote_binds_call(&flipped, &call));
+        let unattested = ComplianceQuote {
+            quote_bytes: synthetic_td_quote([0; 64]),
+            ..quote
+        };
+        assert!(!compliance_quote_binds_call(&unattested, &call));
+    }
+
+    #[test]
+    #[ignore] // Requires TEE environment
+    fn test_generate_denied_quote() {
+        let policy_ids = ["L1".to_string()];
+        let quote = generate_compliance_quote(
+            "PriceFeedTool",
+            CALL_ID,
+            SESSION_ID,
+            false,
+            &policy_ids,
+            "Should buy BTC now?",
+            r#"{"symbol": "BTC"}"#,
+            &SystemClock,
+        )
+        .unwrap();
+
+        assert!(!quote.compliant);
+        assert!(compliance_quote_matches(
+            &quote,
+            &policy_ids,
+            "Should buy BTC now?",
+            r#"{"symbol": "BTC"}"#
+        ));
+        assert!(verify_compliance_quote_dummy(&quote, "PriceFeedTool").unwrap());
     }
 
     #[test]
@@ -213,10 +463,13 @@ mod tests {
     fn test_generate_quote() {
         let quote = generate_compliance_quote(
             "PriceFeedTool",
+            CALL_ID,
+            SESSION_ID,
             true,
             &["L1".to_string()],
             "What is the price of BTC?",
             r#"{"symbol": "BTC"}"#,
+            &SystemClock,
         )
         .unwrap();
 
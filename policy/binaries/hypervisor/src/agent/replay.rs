@@ -0,0 +1,88 @@
+//! Replay of recorded LLM responses, so an execution can be reproduced byte for byte
+//!
+//! Golden-file tests of the execution hash and compliance logic run the agent on a
+//! `Transcript` instead of the network. Call IDs, timestamps and the execution time are
+//! fixed too, so the same transcript always yields the same `AgentExecution`.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::types::{ToolOutput, ToolResult};
+use crate::utils::models::DEFAULT_MODEL;
+
+/// LLM responses of one execution, in the order the agent requested them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+    /// Planning response (THOUGHT/TOOL_CALL lines)
+    pub planning: String,
+    /// Responses to the LLM compliance checks
+    #[serde(default)]
+    pub compliance: Vec<String>,
+    /// Final response text
+    pub final_response: String,
+    /// Model recorded as having written the final response
+    #[serde(default = "default_model")]
+    pub model: String,
+}
+
+fn default_model() -> String {
+    DEFAULT_MODEL.to_string()
+}
+
+/// A transcript being replayed, along with the fixed clock and ID sequence of the execution
+#[derive(Debug)]
+pub struct Replay {
+    transcript: Transcript,
+    compliance: Mutex<VecDeque<String>>,
+    next_id: AtomicU64,
+}
+
+impl Replay {
+    /// Time of every timestamp in a replayed execution
+    pub const TIME: SystemTime = UNIX_EPOCH;
+
+    pub fn new(transcript: Transcript) -> Self {
+        Self {
+            compliance: Mutex::new(transcript.compliance.iter().cloned().collect()),
+            transcript,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    /// Next ID of the sequence 1, 2, ..., standing in for random call IDs
+    pub fn next_id(&self) -> Uuid {
+        Uuid::from_u64_pair(0, self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Next recorded LLM compliance response, an error once they run out
+    pub fn next_compliance(&self) -> Result<String, String> {
+        self.compliance
+            .lock()
+            .expect("replay lock poisoned")
+            .pop_front()
+            .ok_or_else(|| "transcript has no more compliance responses".to_string())
+    }
+
+    /// Set the timestamp of a tool's output to `TIME`
+    pub fn restamp(&self, result: &mut ToolResult) {
+        let Ok(mut output) = serde_json::from_str::<ToolOutput>(&result.result) else {
+            return;
+        };
+
+        output.timestamp = chrono::DateTime::<chrono::Utc>::from(Self::TIME).to_rfc3339();
+        result.result = output.to_json();
+    }
+}
@@ -1,10 +1,60 @@
+use serde::Deserialize;
 use serde_json::json;
-use std::fs;
-use tracing::debug;
-
+use std::path::Path;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+use super::aggregate;
+use super::chains::SupportedChains;
+use super::clock::{system_clock, SharedClock};
+use super::crypto_agent::CryptoAgentConfig;
+use super::data_file::DataFile;
+use super::http_tool::PriceFeedHttpTool;
 use super::policy_registry::PolicyRegistry;
-use super::quote_utils::verify_compliance_quote_dummy;
-use super::types::{ComplianceQuote, Tool, ToolCall, ToolResult};
+use super::quote_utils::{compliance_quote_binds_call, verify_compliance_quote_dummy};
+use super::types::{parse_arguments, ComplianceQuote, Tool, ToolCall, ToolOutput, ToolResult};
+
+/// Default directory holding the synthetic tool data, relative to the workspace root
+pub const DEFAULT_DATA_DIR: &str = "binaries/hypervisor/data";
+
+/// Verify the compliance quote (if any) accompanying a call to `tool_name`
+pub(crate) fn check_compliance_quote(
+    tool_name: &str,
+    compliance_quote: Option<&ComplianceQuote>,
+) -> Result<(), String> {
+    if let Some(quote) = compliance_quote {
+        let verified = verify_compliance_quote_dummy(quote, tool_name)
+            .map_err(|e| format!("Quote verification error: {}", e))?;
+
+        if !verified {
+            return Err("Compliance quote verification failed".to_string());
+        }
+
+        if !quote.compliant {
+            return Err("Tool use was rejected by compliance policy".to_string());
+        }
+
+        debug!("Compliance quote verified for {}", tool_name);
+    }
+
+    Ok(())
+}
+
+/// Reject a compliance quote issued for another call, such as one approved for other
+/// arguments to the same tool
+///
+/// Checked against what the quote attests, see `compliance_quote_binds_call`.
+fn check_quote_binds_call(call: &ToolCall) -> Result<(), String> {
+    match &call.compliance_quote {
+        Some(quote) if !compliance_quote_binds_call(quote, call) => {
+            Err(format!("Compliance quote wasn't issued for call {}", call.id))
+        }
+        _ => Ok(()),
+    }
+}
 
 // =============================================================================
 // T1: PriceFeedTool - Policy: L1
@@ -12,17 +62,49 @@ use super::types::{ComplianceQuote, Tool, ToolCall, ToolResult};
 
 /// T1: Price feed tool for cryptocurrency prices
 pub struct PriceFeedTool {
-    data: serde_json::Value,
+    data: DataFile,
+    policies: Arc<PolicyRegistry>,
+    clock: SharedClock,
+}
+
+/// Arguments of `PriceFeedTool`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriceFeedArgs {
+    pub symbol: String,
 }
 
 impl PriceFeedTool {
+    pub const DATA_FILE: &'static str = "price_feed.json";
+
     pub fn new() -> Result<Self, String> {
-        let data_path = "binaries/hypervisor/data/price_feed.json";
-        let data_str = fs::read_to_string(data_path)
-            .map_err(|e| format!("Failed to read price feed data: {}", e))?;
-        let data: serde_json::Value = serde_json::from_str(&data_str)
-            .map_err(|e| format!("Failed to parse price feed data: {}", e))?;
-        Ok(Self { data })
+        Self::from_data_dir(DEFAULT_DATA_DIR, Arc::default())
+    }
+
+    /// Load the tool's data from `price_feed.json` in the given directory,
+    /// resolving its policies through the shared registry
+    pub fn from_data_dir(
+        data_dir: impl AsRef<Path>,
+        policies: Arc<PolicyRegistry>,
+    ) -> Result<Self, String> {
+        let data = DataFile::load(data_dir.as_ref().join(Self::DATA_FILE), "price feed")?;
+        Ok(Self {
+            data,
+            policies,
+            clock: system_clock(),
+        })
+    }
+
+    /// Reload the data file when it changes, checking at most once per `interval`
+    pub fn with_reload(mut self, interval: Option<Duration>) -> Self {
+        self.data = self.data.with_reload(interval);
+        self
+    }
+
+    /// Stamp outputs with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
     }
 }
 
@@ -50,31 +132,14 @@ impl Tool for PriceFeedTool {
 
     fn execute(&self, arguments: &str, compliance_quote: Option<&ComplianceQuote>) -> Result<String, String> {
         // Verify compliance quote (dummy verification)
-        if let Some(quote) = compliance_quote {
-            let verified = verify_compliance_quote_dummy(quote, self.name())
-                .map_err(|e| format!("Quote verification error: {}", e))?;
-            
-            if !verified {
-                return Err("Compliance quote verification failed".to_string());
-            }
-            
-            if !quote.compliant {
-                return Err("Tool use was rejected by compliance policy".to_string());
-            }
-            
-            debug!("Compliance quote verified for {}", self.name());
-        }
+        check_compliance_quote(self.name(), compliance_quote)?;
         
-        let args: serde_json::Value = serde_json::from_str(arguments)
-            .map_err(|e| format!("Invalid arguments: {}", e))?;
-
-        let symbol = args["symbol"]
-            .as_str()
-            .ok_or("Missing symbol parameter")?
-            .to_uppercase();
+        let args: PriceFeedArgs = parse_arguments(arguments)?;
+        let symbol = args.symbol.to_uppercase();
 
         // Load price data from JSON
-        let prices = self.data["prices"]
+        let data = self.data.get();
+        let prices = data["prices"]
             .as_array()
             .ok_or("Invalid price data format")?;
 
@@ -83,25 +148,32 @@ impl Tool for PriceFeedTool {
             .find(|p| p["symbol"].as_str() == Some(&symbol))
             .ok_or_else(|| format!("Unknown cryptocurrency: {}", symbol))?;
 
-        Ok(json!({
-            "tool": "PriceFeedTool",
-            "symbol": symbol,
-            "price_usd": price_data["price_usd"],
-            "market_cap": price_data["market_cap"],
-            "24h_volume": price_data["24h_volume"],
-            "24h_change_pct": price_data["24h_change_pct"],
-            "last_updated": price_data["last_updated"],
-            "source": "Market Data Feed"
-        })
-        .to_string())
+        Ok(ToolOutput::new(
+            self.name(),
+            "Market Data Feed",
+            json!({
+                "symbol": symbol,
+                "price_usd": price_data["price_usd"],
+                "market_cap": price_data["market_cap"],
+                "24h_volume": price_data["24h_volume"],
+                "24h_change_pct": price_data["24h_change_pct"],
+                "last_updated": price_data["last_updated"]
+            }),
+            &*self.clock,
+        )
+        .to_json())
     }
 
     fn policy_ids(&self) -> Vec<String> {
-        vec!["L1".to_string()]
+        self.policies.get_policy_ids_for_tool(self.name())
     }
 
     fn policy_info(&self) -> Vec<super::policy_registry::PolicyInfo> {
-        PolicyRegistry::default_crypto_policy().get_policy_info_for_tool(self.name())
+        self.policies.get_policy_info_for_tool(self.name())
+    }
+
+    fn is_synthetic(&self) -> bool {
+        true
     }
 }
 
@@ -111,17 +183,81 @@ impl Tool for PriceFeedTool {
 
 /// T2: On-chain transaction history tool
 pub struct OnChainHistoryTool {
-    data: serde_json::Value,
+    data: DataFile,
+    policies: Arc<PolicyRegistry>,
+    chains: Arc<SupportedChains>,
+    clock: SharedClock,
+}
+
+/// Arguments of `OnChainHistoryTool`; without an address, every address on the chain
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OnChainHistoryArgs {
+    pub address: Option<String>,
+    pub blockchain: String,
 }
 
 impl OnChainHistoryTool {
+    pub const DATA_FILE: &'static str = "onchain_history.json";
+
     pub fn new() -> Result<Self, String> {
-        let data_path = "binaries/hypervisor/data/onchain_history.json";
-        let data_str = fs::read_to_string(data_path)
-            .map_err(|e| format!("Failed to read on-chain history data: {}", e))?;
-        let data: serde_json::Value = serde_json::from_str(&data_str)
-            .map_err(|e| format!("Failed to parse on-chain history data: {}", e))?;
-        Ok(Self { data })
+        Self::from_data_dir(DEFAULT_DATA_DIR, Arc::default(), Arc::default())
+    }
+
+    /// Load the tool's data from `onchain_history.json` in the given directory,
+    /// resolving its policies through the shared registry and accepting `chains`
+    pub fn from_data_dir(
+        data_dir: impl AsRef<Path>,
+        policies: Arc<PolicyRegistry>,
+        chains: Arc<SupportedChains>,
+    ) -> Result<Self, String> {
+        let data = DataFile::load(data_dir.as_ref().join(Self::DATA_FILE), "on-chain history")?;
+        Ok(Self {
+            data,
+            policies,
+            chains,
+            clock: system_clock(),
+        })
+    }
+
+    /// Reload the data file when it changes, checking at most once per `interval`
+    pub fn with_reload(mut self, interval: Option<Duration>) -> Self {
+        self.data = self.data.with_reload(interval);
+        self
+    }
+
+    /// Stamp outputs with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// L2 summary of a transaction list: count, USD value and gas totals and ranges
+    fn summarize(transactions: &[serde_json::Value]) -> serde_json::Value {
+        json!({
+            "transactions": aggregate::count(transactions),
+            "value_usd": aggregate::summarize(transactions, "value_usd"),
+            "gas_used": aggregate::summarize(transactions, "gas_used"),
+        })
+    }
+
+    /// Transaction count and total USD value moved per address, standing in for the
+    /// raw records when no address is requested
+    fn summarize_addresses(
+        chain_data: &serde_json::Map<String, serde_json::Value>,
+    ) -> serde_json::Value {
+        chain_data
+            .iter()
+            .map(|(address, transactions)| {
+                let records = transactions.as_array().map(Vec::as_slice).unwrap_or_default();
+                let totals = json!({
+                    "transactions": aggregate::count(records),
+                    "value_usd": aggregate::sum(records, "value_usd"),
+                });
+                (address.clone(), totals)
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into()
     }
 }
 
@@ -131,7 +267,7 @@ impl Tool for OnChainHistoryTool {
     }
 
     fn description(&self) -> &str {
-        "Get on-chain transaction history for a wallet address. Returns individual transaction records."
+        "Get on-chain transaction history for a wallet address. Returns individual transaction records for one address, or per-address counts and totals for all of them."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -140,11 +276,12 @@ impl Tool for OnChainHistoryTool {
             "properties": {
                 "address": {
                     "type": "string",
-                    "description": "The wallet address to query (optional - if not provided, returns all addresses)"
+                    "description": "The wallet address to query (optional - if not provided, returns per-address counts and totals)"
                 },
                 "blockchain": {
                     "type": "string",
-                    "description": "The blockchain network (e.g., ethereum, solana, bitcoin)"
+                    "description": "The blockchain network (e.g., ethereum, solana, bitcoin)",
+                    "enum": self.chains.list()
                 }
             },
             "required": ["blockchain"]
@@ -153,34 +290,17 @@ impl Tool for OnChainHistoryTool {
 
     fn execute(&self, arguments: &str, compliance_quote: Option<&ComplianceQuote>) -> Result<String, String> {
         // Verify compliance quote (dummy verification)
-        if let Some(quote) = compliance_quote {
-            let verified = verify_compliance_quote_dummy(quote, self.name())
-                .map_err(|e| format!("Quote verification error: {}", e))?;
-            
-            if !verified {
-                return Err("Compliance quote verification failed".to_string());
-            }
-            
-            if !quote.compliant {
-                return Err("Tool use was rejected by compliance policy".to_string());
-            }
-            
-            debug!("Compliance quote verified for {}", self.name());
-        }
+        check_compliance_quote(self.name(), compliance_quote)?;
         
-        let args: serde_json::Value = serde_json::from_str(arguments)
-            .map_err(|e| format!("Invalid arguments: {}", e))?;
-
-        let address_opt = args["address"].as_str();
-        let blockchain = args["blockchain"]
-            .as_str()
-            .ok_or("Missing blockchain parameter")?
-            .to_lowercase();
+        let args: OnChainHistoryArgs = parse_arguments(arguments)?;
+        let address_opt = args.address.as_deref();
+        let blockchain = self.chains.resolve(&args.blockchain).map_err(|e| e.to_string())?;
 
         // Load transaction history from JSON - returns individual records
-        let chain_data = self.data[&blockchain]
+        let data = self.data.get();
+        let chain_data = data[&blockchain]
             .as_object()
-            .ok_or_else(|| format!("Unsupported blockchain: {}", blockchain))?;
+            .ok_or_else(|| format!("No on-chain history data available for blockchain: {}", blockchain))?;
 
         if let Some(address) = address_opt {
             // Return data for specific address
@@ -188,36 +308,56 @@ impl Tool for OnChainHistoryTool {
                 .get(address)
                 .ok_or_else(|| format!("No transaction history found for address: {}", address))?;
 
-            Ok(json!({
-                "tool": "OnChainHistoryTool",
-                "address": address,
-                "blockchain": blockchain,
-                "transactions": transactions,
-                "count": transactions.as_array().map(|a| a.len()).unwrap_or(0),
-                "timestamp": chrono::Utc::now().to_rfc3339(),
-                "source": "On-Chain Data Provider"
-            })
-            .to_string())
+            let records = transactions.as_array().map(Vec::as_slice).unwrap_or_default();
+
+            Ok(ToolOutput::new(
+                self.name(),
+                "On-Chain Data Provider",
+                json!({
+                    "address": address,
+                    "blockchain": blockchain,
+                    "transactions": transactions,
+                    "count": aggregate::count(records),
+                    "summary": Self::summarize(records)
+                }),
+                &*self.clock,
+            )
+            .to_json())
         } else {
-            // Return data for all addresses
-            Ok(json!({
-                "tool": "OnChainHistoryTool",
-                "blockchain": blockchain,
-                "all_addresses": chain_data,
-                "address_count": chain_data.len(),
-                "timestamp": chrono::Utc::now().to_rfc3339(),
-                "source": "On-Chain Data Provider"
-            })
-            .to_string())
+            // Aggregate every address server-side; raw records are only returned for one
+            let records: Vec<_> = chain_data
+                .values()
+                .filter_map(|txs| txs.as_array())
+                .flatten()
+                .cloned()
+                .collect();
+            let mut summary = Self::summarize(&records);
+            summary["per_address"] = Self::summarize_addresses(chain_data);
+
+            Ok(ToolOutput::new(
+                self.name(),
+                "On-Chain Data Provider",
+                json!({
+                    "blockchain": blockchain,
+                    "address_count": chain_data.len(),
+                    "summary": summary
+                }),
+                &*self.clock,
+            )
+            .to_json())
         }
     }
 
     fn policy_ids(&self) -> Vec<String> {
-        vec!["L1".to_string(), "L2".to_string(), "L3".to_string()]
+        self.policies.get_policy_ids_for_tool(self.name())
     }
 
     fn policy_info(&self) -> Vec<super::policy_registry::PolicyInfo> {
-        PolicyRegistry::default_crypto_policy().get_policy_info_for_tool(self.name())
+        self.policies.get_policy_info_for_tool(self.name())
+    }
+
+    fn is_synthetic(&self) -> bool {
+        true
     }
 }
 
@@ -227,17 +367,65 @@ impl Tool for OnChainHistoryTool {
 
 /// T3: Market sentiment analysis tool
 pub struct SentimentTool {
-    data: serde_json::Value,
+    data: DataFile,
+    policies: Arc<PolicyRegistry>,
+    clock: SharedClock,
+}
+
+/// Arguments of `SentimentTool`; the timeframe defaults to `SentimentTool::DEFAULT_TIMEFRAME`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SentimentArgs {
+    pub symbol: String,
+    pub timeframe: Option<String>,
 }
 
 impl SentimentTool {
+    pub const DATA_FILE: &'static str = "sentiment.json";
+    pub const DEFAULT_TIMEFRAME: &'static str = "24h";
+
     pub fn new() -> Result<Self, String> {
-        let data_path = "binaries/hypervisor/data/sentiment.json";
-        let data_str = fs::read_to_string(data_path)
-            .map_err(|e| format!("Failed to read sentiment data: {}", e))?;
-        let data: serde_json::Value = serde_json::from_str(&data_str)
-            .map_err(|e| format!("Failed to parse sentiment data: {}", e))?;
-        Ok(Self { data })
+        Self::from_data_dir(DEFAULT_DATA_DIR, Arc::default())
+    }
+
+    /// Load the tool's data from `sentiment.json` in the given directory,
+    /// resolving its policies through the shared registry
+    pub fn from_data_dir(
+        data_dir: impl AsRef<Path>,
+        policies: Arc<PolicyRegistry>,
+    ) -> Result<Self, String> {
+        let data = DataFile::load(data_dir.as_ref().join(Self::DATA_FILE), "sentiment")?;
+        Ok(Self {
+            data,
+            policies,
+            clock: system_clock(),
+        })
+    }
+
+    /// Reload the data file when it changes, checking at most once per `interval`
+    pub fn with_reload(mut self, interval: Option<Duration>) -> Self {
+        self.data = self.data.with_reload(interval);
+        self
+    }
+
+    /// Stamp outputs with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Timeframes present in the data for any symbol, sorted
+    pub fn available_timeframes(&self) -> Vec<String> {
+        let data = self.data.get();
+        let timeframes: std::collections::BTreeSet<&String> = data
+            .as_object()
+            .into_iter()
+            .flat_map(|symbols| symbols.values())
+            .filter_map(|symbol_data| symbol_data.as_object())
+            .flat_map(|symbol_data| symbol_data.keys())
+            .collect();
+
+        timeframes.into_iter().cloned().collect()
     }
 }
 
@@ -260,8 +448,9 @@ impl Tool for SentimentTool {
                 },
                 "timeframe": {
                     "type": "string",
-                    "description": "Time period for analysis (e.g., '24h', '7d', '30d')",
-                    "default": "24h"
+                    "description": "Time period for analysis (e.g., '24h')",
+                    "enum": self.available_timeframes(),
+                    "default": Self::DEFAULT_TIMEFRAME
                 }
             },
             "required": ["symbol"]
@@ -270,56 +459,30 @@ impl Tool for SentimentTool {
 
     fn execute(&self, arguments: &str, compliance_quote: Option<&ComplianceQuote>) -> Result<String, String> {
         // Verify compliance quote (dummy verification)
-        if let Some(quote) = compliance_quote {
-            let verified = verify_compliance_quote_dummy(quote, self.name())
-                .map_err(|e| format!("Quote verification error: {}", e))?;
-            
-            if !verified {
-                return Err("Compliance quote verification failed".to_string());
-            }
-            
-            if !quote.compliant {
-                return Err("Tool use was rejected by compliance policy".to_string());
-            }
-            
-            debug!("Compliance quote verified for {}", self.name());
-        }
+        check_compliance_quote(self.name(), compliance_quote)?;
         
-        let args: serde_json::Value = serde_json::from_str(arguments)
-            .map_err(|e| format!("Invalid arguments: {}", e))?;
-
-        let symbol = args["symbol"]
-            .as_str()
-            .ok_or("Missing symbol parameter")?
-            .to_uppercase();
-        let timeframe = args["timeframe"].as_str().unwrap_or("24h");
+        let args: SentimentArgs = parse_arguments(arguments)?;
+        let symbol = args.symbol.to_uppercase();
+        let timeframe = args.timeframe.as_deref().unwrap_or(Self::DEFAULT_TIMEFRAME);
 
         // Load sentiment data from JSON
-        let symbol_data = self.data[&symbol]
+        let data = self.data.get();
+        let symbol_data = data[&symbol]
             .as_object()
             .ok_or_else(|| format!("Sentiment data not available for: {}", symbol))?;
 
-        let timeframe_data = symbol_data
-            .get(timeframe)
-            .ok_or_else(|| format!("No data available for timeframe: {}", timeframe))?;
+        let timeframe_data = symbol_data.get(timeframe).ok_or_else(|| {
+            json!({
+                "error": format!("No data available for timeframe: {}", timeframe),
+                "available_timeframes": symbol_data.keys().collect::<Vec<_>>(),
+            })
+            .to_string()
+        })?;
 
         // Calculate aggregate metrics from individual records
-        let empty_vec = vec![];
-        let records = timeframe_data.as_array().unwrap_or(&empty_vec);
-        let total_mentions: u32 = records
-            .iter()
-            .filter_map(|r| r["mention_count"].as_u64())
-            .map(|n| n as u32)
-            .sum();
-        let avg_score: f64 = if !records.is_empty() {
-            records
-                .iter()
-                .filter_map(|r| r["score"].as_f64())
-                .sum::<f64>()
-                / records.len() as f64
-        } else {
-            0.0
-        };
+        let records = timeframe_data.as_array().map(Vec::as_slice).unwrap_or_default();
+        let total_mentions = aggregate::sum(records, "mention_count") as u64;
+        let avg_score = aggregate::average(records, "score").unwrap_or(0.0);
 
         let sentiment_label = if avg_score >= 0.6 {
             "Positive"
@@ -329,26 +492,32 @@ impl Tool for SentimentTool {
             "Negative"
         };
 
-        Ok(json!({
-            "tool": "SentimentTool",
-            "symbol": symbol,
-            "timeframe": timeframe,
-            "sentiment_score": avg_score,
-            "sentiment_label": sentiment_label,
-            "mentions_count": total_mentions,
-            "records": timeframe_data,
-            "timestamp": chrono::Utc::now().to_rfc3339(),
-            "source": "Social Media & News Analytics"
-        })
-        .to_string())
+        Ok(ToolOutput::new(
+            self.name(),
+            "Social Media & News Analytics",
+            json!({
+                "symbol": symbol,
+                "timeframe": timeframe,
+                "sentiment_score": avg_score,
+                "sentiment_label": sentiment_label,
+                "mentions_count": total_mentions,
+                "records": timeframe_data
+            }),
+            &*self.clock,
+        )
+        .to_json())
     }
 
     fn policy_ids(&self) -> Vec<String> {
-        vec!["L1".to_string(), "L4".to_string()]
+        self.policies.get_policy_ids_for_tool(self.name())
     }
 
     fn policy_info(&self) -> Vec<super::policy_registry::PolicyInfo> {
-        PolicyRegistry::default_crypto_policy().get_policy_info_for_tool(self.name())
+        self.policies.get_policy_info_for_tool(self.name())
+    }
+
+    fn is_synthetic(&self) -> bool {
+        true
     }
 }
 
@@ -358,17 +527,53 @@ impl Tool for SentimentTool {
 
 /// T4: Portfolio analysis tool
 pub struct PortfolioTool {
-    data: serde_json::Value,
+    data: DataFile,
+    policies: Arc<PolicyRegistry>,
+    chains: Arc<SupportedChains>,
+    clock: SharedClock,
+}
+
+/// Arguments of `PortfolioTool`; without an address, every address on the chain
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortfolioArgs {
+    pub address: Option<String>,
+    pub blockchain: String,
 }
 
 impl PortfolioTool {
+    pub const DATA_FILE: &'static str = "portfolio.json";
+
     pub fn new() -> Result<Self, String> {
-        let data_path = "binaries/hypervisor/data/portfolio.json";
-        let data_str = fs::read_to_string(data_path)
-            .map_err(|e| format!("Failed to read portfolio data: {}", e))?;
-        let data: serde_json::Value = serde_json::from_str(&data_str)
-            .map_err(|e| format!("Failed to parse portfolio data: {}", e))?;
-        Ok(Self { data })
+        Self::from_data_dir(DEFAULT_DATA_DIR, Arc::default(), Arc::default())
+    }
+
+    /// Load the tool's data from `portfolio.json` in the given directory,
+    /// resolving its policies through the shared registry and accepting `chains`
+    pub fn from_data_dir(
+        data_dir: impl AsRef<Path>,
+        policies: Arc<PolicyRegistry>,
+        chains: Arc<SupportedChains>,
+    ) -> Result<Self, String> {
+        let data = DataFile::load(data_dir.as_ref().join(Self::DATA_FILE), "portfolio")?;
+        Ok(Self {
+            data,
+            policies,
+            chains,
+            clock: system_clock(),
+        })
+    }
+
+    /// Reload the data file when it changes, checking at most once per `interval`
+    pub fn with_reload(mut self, interval: Option<Duration>) -> Self {
+        self.data = self.data.with_reload(interval);
+        self
+    }
+
+    /// Stamp outputs with `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
     }
 }
 
@@ -391,7 +596,8 @@ impl Tool for PortfolioTool {
                 },
                 "blockchain": {
                     "type": "string",
-                    "description": "The blockchain network (e.g., ethereum, solana)"
+                    "description": "The blockchain network (e.g., ethereum, solana)",
+                    "enum": self.chains.list()
                 }
             },
             "required": ["blockchain"]
@@ -400,34 +606,17 @@ impl Tool for PortfolioTool {
 
     fn execute(&self, arguments: &str, compliance_quote: Option<&ComplianceQuote>) -> Result<String, String> {
         // Verify compliance quote (dummy verification)
-        if let Some(quote) = compliance_quote {
-            let verified = verify_compliance_quote_dummy(quote, self.name())
-                .map_err(|e| format!("Quote verification error: {}", e))?;
-            
-            if !verified {
-                return Err("Compliance quote verification failed".to_string());
-            }
-            
-            if !quote.compliant {
-                return Err("Tool use was rejected by compliance policy".to_string());
-            }
-            
-            debug!("Compliance quote verified for {}", self.name());
-        }
+        check_compliance_quote(self.name(), compliance_quote)?;
         
-        let args: serde_json::Value = serde_json::from_str(arguments)
-            .map_err(|e| format!("Invalid arguments: {}", e))?;
-
-        let address_opt = args["address"].as_str();
-        let blockchain = args["blockchain"]
-            .as_str()
-            .ok_or("Missing blockchain parameter")?
-            .to_lowercase();
+        let args: PortfolioArgs = parse_arguments(arguments)?;
+        let address_opt = args.address.as_deref();
+        let blockchain = self.chains.resolve(&args.blockchain).map_err(|e| e.to_string())?;
 
         // Load portfolio data from JSON - returns individual holdings
-        let chain_data = self.data[&blockchain]
+        let data = self.data.get();
+        let chain_data = data[&blockchain]
             .as_object()
-            .ok_or_else(|| format!("Unsupported blockchain: {}", blockchain))?;
+            .ok_or_else(|| format!("No portfolio data available for blockchain: {}", blockchain))?;
 
         if let Some(address) = address_opt {
             // Return data for specific address
@@ -435,38 +624,61 @@ impl Tool for PortfolioTool {
                 .get(address)
                 .ok_or_else(|| format!("No portfolio data found for address: {}", address))?;
 
-            Ok(json!({
-                "tool": "PortfolioTool",
-                "address": address,
-                "blockchain": blockchain,
-                "holdings": portfolio_data["holdings"],
-                "total_value_usd": portfolio_data["total_value_usd"],
-                "num_tokens": portfolio_data["holdings"].as_array().map(|a| a.len()).unwrap_or(0),
-                "last_updated": portfolio_data["last_updated"],
-                "timestamp": chrono::Utc::now().to_rfc3339(),
-                "source": "Portfolio Analytics Provider"
-            })
-            .to_string())
+            let holdings = portfolio_data["holdings"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+
+            Ok(ToolOutput::new(
+                self.name(),
+                "Portfolio Analytics Provider",
+                json!({
+                    "address": address,
+                    "blockchain": blockchain,
+                    "holdings": portfolio_data["holdings"],
+                    "total_value_usd": portfolio_data["total_value_usd"],
+                    "num_tokens": aggregate::count(holdings),
+                    "summary": {
+                        "value_usd": aggregate::summarize(holdings, "value_usd"),
+                        "unrealized_pnl_usd": aggregate::summarize(holdings, "unrealized_pnl_usd"),
+                        "24h_change_pct": aggregate::summarize(holdings, "24h_change_pct"),
+                    },
+                    "last_updated": portfolio_data["last_updated"]
+                }),
+                &*self.clock,
+            )
+            .to_json())
         } else {
             // Return data for all addresses
-            Ok(json!({
-                "tool": "PortfolioTool",
-                "blockchain": blockchain,
-                "all_portfolios": chain_data,
-                "address_count": chain_data.len(),
-                "timestamp": chrono::Utc::now().to_rfc3339(),
-                "source": "Portfolio Analytics Provider"
-            })
-            .to_string())
+            let portfolios: Vec<_> = chain_data.values().cloned().collect();
+
+            Ok(ToolOutput::new(
+                self.name(),
+                "Portfolio Analytics Provider",
+                json!({
+                    "blockchain": blockchain,
+                    "all_portfolios": chain_data,
+                    "address_count": chain_data.len(),
+                    "summary": {
+                        "total_value_usd": aggregate::summarize(&portfolios, "total_value_usd"),
+                    }
+                }),
+                &*self.clock,
+            )
+            .to_json())
         }
     }
 
     fn policy_ids(&self) -> Vec<String> {
-        vec!["L1".to_string(), "L2".to_string(), "L3".to_string(), "L4".to_string()]
+        self.policies.get_policy_ids_for_tool(self.name())
     }
 
     fn policy_info(&self) -> Vec<super::policy_registry::PolicyInfo> {
-        PolicyRegistry::default_crypto_policy().get_policy_info_for_tool(self.name())
+        self.policies.get_policy_info_for_tool(self.name())
+    }
+
+    fn is_synthetic(&self) -> bool {
+        true
     }
 }
 
@@ -475,21 +687,210 @@ impl Tool for PortfolioTool {
 // =============================================================================
 
 /// Tool registry for managing available tools
+#[derive(Default)]
 pub struct ToolRegistry {
-    tools: Vec<Box<dyn Tool>>,
+    tools: Vec<Arc<dyn Tool>>,
+    /// Tools that failed to initialize, with the reason
+    unavailable: Vec<(String, String)>,
+    /// Largest result in bytes per tool, beyond which it is cut down to its summary
+    result_limits: HashMap<String, usize>,
+    /// Results of the named tools kept for identical calls
+    result_caches: HashMap<String, Arc<ResultCache>>,
+}
+
+/// Results of a tool's successful calls, keyed by their normalized arguments
+///
+/// Only the data is cached: each call's compliance quote is still verified before a
+/// cached result is returned.
+struct ResultCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, String)>>,
+}
+
+impl ResultCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Result of an identical call made within the TTL, else of running `call` on `tool`
+    fn execute(&self, tool: &dyn Tool, call: &ToolCall) -> Result<String, String> {
+        check_compliance_quote(tool.name(), call.compliance_quote.as_ref())?;
+
+        // Objects re-serialize with sorted keys, so key order and spacing don't matter
+        let key = serde_json::from_str::<serde_json::Value>(&call.arguments)
+            .map_or_else(|_| call.arguments.clone(), |args| args.to_string());
+        let cached = {
+            let entries = self.entries.lock().expect("result cache poisoned");
+            entries
+                .get(&key)
+                .filter(|(stored_at, _)| stored_at.elapsed() < self.ttl)
+                .map(|(_, data)| data.clone())
+        };
+        if let Some(data) = cached {
+            debug!(tool_name = %call.tool_name, "cached result");
+            return Ok(data);
+        }
+
+        let data = tool.execute(&call.arguments, call.compliance_quote.as_ref())?;
+        let mut entries = self.entries.lock().expect("result cache poisoned");
+        entries.retain(|_, (stored_at, _)| stored_at.elapsed() < self.ttl);
+        entries.insert(key, (Instant::now(), data.clone()));
+
+        Ok(data)
+    }
 }
 
 impl ToolRegistry {
     /// Create a new tool registry with T1-T4 realistic crypto tools
-    pub fn new_crypto_tools() -> Result<Self, String> {
-        Ok(Self {
-            tools: vec![
-                Box::new(PriceFeedTool::new()?),
-                Box::new(OnChainHistoryTool::new()?),
-                Box::new(SentimentTool::new()?),
-                Box::new(PortfolioTool::new()?),
-            ],
-        })
+    pub fn new_crypto_tools() -> Self {
+        Self::crypto_tools_from_data_dir(
+            DEFAULT_DATA_DIR,
+            Arc::default(),
+            Arc::default(),
+            None,
+            system_clock(),
+        )
+    }
+
+    /// Create the T1-T4 crypto tools with their data loaded from the given directory,
+    /// their policies resolved through `policies` and chain arguments checked against `chains`
+    ///
+    /// With `reload` set, each data file is reloaded when it changes, checked at most that often.
+    /// A tool whose data fails to load is logged and left out, see `unavailable_tools`;
+    /// the others are registered regardless.
+    pub fn crypto_tools_from_data_dir(
+        data_dir: impl AsRef<Path>,
+        policies: Arc<PolicyRegistry>,
+        chains: Arc<SupportedChains>,
+        reload: Option<Duration>,
+        clock: SharedClock,
+    ) -> Self {
+        let data_dir = data_dir.as_ref();
+        let tools = [
+            (
+                "PriceFeedTool",
+                PriceFeedTool::from_data_dir(data_dir, policies.clone())
+                    .map(|tool| {
+                        Arc::new(tool.with_reload(reload).with_clock(clock.clone()))
+                            as Arc<dyn Tool>
+                    }),
+            ),
+            (
+                "OnChainHistoryTool",
+                OnChainHistoryTool::from_data_dir(data_dir, policies.clone(), chains.clone())
+                    .map(|tool| {
+                        Arc::new(tool.with_reload(reload).with_clock(clock.clone()))
+                            as Arc<dyn Tool>
+                    }),
+            ),
+            (
+                "SentimentTool",
+                SentimentTool::from_data_dir(data_dir, policies.clone())
+                    .map(|tool| {
+                        Arc::new(tool.with_reload(reload).with_clock(clock.clone()))
+                            as Arc<dyn Tool>
+                    }),
+            ),
+            (
+                "PortfolioTool",
+                PortfolioTool::from_data_dir(data_dir, policies, chains)
+                    .map(|tool| {
+                        Arc::new(tool.with_reload(reload).with_clock(clock.clone()))
+                            as Arc<dyn Tool>
+                    }),
+            ),
+        ];
+
+        let mut registry = Self::default();
+        for (name, tool) in tools {
+            match tool {
+                Ok(tool) => registry.tools.push(tool),
+                Err(error) => {
+                    warn!(tool = name, %error, "tool unavailable, skipping it");
+                    registry.unavailable.push((name.to_string(), error));
+                }
+            }
+        }
+
+        registry
+    }
+
+    /// Tools of the agent config: the crypto tools of its data directory with its result
+    /// limits and caches, plus the live price feed when an upstream is configured
+    pub fn from_agent_config(
+        config: &CryptoAgentConfig,
+        policies: Arc<PolicyRegistry>,
+    ) -> anyhow::Result<Self> {
+        let chains = Arc::new(SupportedChains::new(&config.supported_chains));
+        let reload = config.data_reload_secs.map(Duration::from_secs);
+        let mut registry = Self::crypto_tools_from_data_dir(
+            &config.data_dir,
+            policies.clone(),
+            chains,
+            reload,
+            config.clock.clone(),
+        )
+        .with_result_limits(config.max_tool_result_bytes.clone())
+        .with_result_caches(
+            config
+                .tool_cache_ttl_secs
+                .iter()
+                .map(|(name, secs)| (name.clone(), Duration::from_secs(*secs)))
+                .collect(),
+        );
+
+        if let Some(upstream) = &config.price_feed_upstream {
+            let tool = PriceFeedHttpTool::new(upstream.clone(), policies)
+                .map_err(|e| anyhow::anyhow!("Failed to initialize live price feed: {}", e))?
+                .with_clock(config.clock.clone());
+            registry.register(Box::new(tool));
+        }
+
+        Ok(registry)
+    }
+
+    /// Load each crypto tool's data from the given directory, collecting every failure
+    /// instead of stopping at the first one
+    pub fn check_crypto_tool_data(data_dir: impl AsRef<Path>) -> Vec<String> {
+        let data_dir = data_dir.as_ref();
+        let policies = Arc::<PolicyRegistry>::default();
+        let chains = Arc::<SupportedChains>::default();
+
+        [
+            PriceFeedTool::from_data_dir(data_dir, policies.clone()).err(),
+            OnChainHistoryTool::from_data_dir(data_dir, policies.clone(), chains.clone()).err(),
+            SentimentTool::from_data_dir(data_dir, policies.clone()).err(),
+            PortfolioTool::from_data_dir(data_dir, policies, chains).err(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Cut results of the named tools larger than the given byte size down to their summary
+    pub fn with_result_limits(mut self, limits: HashMap<String, usize>) -> Self {
+        self.result_limits = limits;
+        self
+    }
+
+    /// Return results of the named tools for identical arguments from a cache, each kept
+    /// for the given TTL
+    pub fn with_result_caches(mut self, ttls: HashMap<String, Duration>) -> Self {
+        self.result_caches = ttls
+            .into_iter()
+            .map(|(name, ttl)| (name, Arc::new(ResultCache::new(ttl))))
+            .collect();
+        self
+    }
+
+    /// Add a tool, replacing any registered tool with the same name
+    pub fn register(&mut self, tool: Box<dyn Tool>) {
+        self.tools.retain(|t| t.name() != tool.name());
+        self.unavailable.retain(|(name, _)| name != tool.name());
+        self.tools.push(Arc::from(tool));
     }
 
     /// Get a tool by name
@@ -498,33 +899,83 @@ impl ToolRegistry {
     }
 
     /// Get all available tools
-    pub fn all_tools(&self) -> &[Box<dyn Tool>] {
+    pub fn all_tools(&self) -> &[Arc<dyn Tool>] {
         &self.tools
     }
 
+    /// Tools that failed to initialize, with the reason
+    pub fn unavailable_tools(&self) -> &[(String, String)] {
+        &self.unavailable
+    }
+
+    /// Why a call to `name` can't run: the tool failed to initialize or doesn't exist
+    pub fn missing_tool_reason(&self, name: &str) -> String {
+        match self.unavailable.iter().find(|(unavailable, _)| unavailable == name) {
+            Some((_, error)) => format!("Tool '{}' is unavailable: {}", name, error),
+            None => format!("Tool '{}' not found", name),
+        }
+    }
+
+    /// Check a planned call names a registered tool and fits its `parameters_schema`
+    pub fn validate_call(&self, name: &str, arguments: &serde_json::Value) -> Result<(), String> {
+        let tool = self.get_tool(name).ok_or_else(|| self.missing_tool_reason(name))?;
+        check_arguments(&tool.parameters_schema(), arguments)
+            .map_err(|e| format!("Invalid arguments for {name}: {e}"))
+    }
+
     /// Execute a tool call with compliance quote verification
     pub fn execute_tool_call(&self, call: &ToolCall) -> ToolResult {
-        let result = self
-            .get_tool(&call.tool_name)
-            .ok_or_else(|| format!("Tool not found: {}", call.tool_name))
-            .and_then(|tool| tool.execute(&call.arguments, call.compliance_quote.as_ref()));
-
-        match result {
-            Ok(data) => ToolResult {
-                call_id: call.id,
-                success: true,
-                result: data,
-                error: None,
-                quote_verified: call.compliance_quote.is_some(), // Quote was present and verified
-            },
-            Err(e) => ToolResult {
-                call_id: call.id,
-                success: false,
-                result: String::new(),
-                error: Some(e),
-                quote_verified: false,
-            },
+        let max_bytes = self.result_limits.get(&call.tool_name).copied();
+        let cache = self.result_caches.get(&call.tool_name).map(|cache| &**cache);
+        run_tool_call(self.get_tool(&call.tool_name), call, max_bytes, cache)
+    }
+
+    /// Execute tool calls with at most `parallelism` of them running at once
+    ///
+    /// `on_result` sees each result as it completes and may rewrite it; the returned results
+    /// are in the order of `calls`, whatever order they completed in.
+    pub async fn execute_tool_calls(
+        &self,
+        calls: &[ToolCall],
+        parallelism: usize,
+        mut on_result: impl FnMut(&mut ToolResult),
+    ) -> Vec<ToolResult> {
+        let mut pending = calls.iter().cloned().enumerate();
+        let mut running = JoinSet::new();
+        let mut positions = HashMap::new();
+        let mut results = Vec::with_capacity(calls.len());
+
+        loop {
+            while running.len() < parallelism.max(1) {
+                let Some((position, call)) = pending.next() else {
+                    break;
+                };
+                let tool = self.tools.iter().find(|t| t.name() == call.tool_name).cloned();
+                let max_bytes = self.result_limits.get(&call.tool_name).copied();
+                let cache = self.result_caches.get(&call.tool_name).cloned();
+                let call_id = call.id;
+                let task = running.spawn_blocking(move || {
+                    run_tool_call(tool.as_deref(), &call, max_bytes, cache.as_deref())
+                });
+                positions.insert(task.id(), (position, call_id));
+            }
+
+            let Some(joined) = running.join_next_with_id().await else {
+                break;
+            };
+            let (position, mut result) = match joined {
+                Ok((id, result)) => (positions[&id].0, result),
+                Err(e) => {
+                    let (position, call_id) = positions[&e.id()];
+                    (position, failed_tool_result(call_id, format!("Tool task failed: {e}")))
+                }
+            };
+            on_result(&mut result);
+            results.push((position, result));
         }
+
+        results.sort_by_key(|(position, _)| *position);
+        results.into_iter().map(|(_, result)| result).collect()
     }
 
     /// Generate tool descriptions for LLM prompt
@@ -548,6 +999,750 @@ impl ToolRegistry {
             ));
         }
 
+        if !self.unavailable.is_empty() {
+            let names: Vec<_> = self.unavailable.iter().map(|(name, _)| name.as_str()).collect();
+            descriptions.push_str(&format!(
+                "Unavailable tools (do not call them): {}\n",
+                names.join(", ")
+            ));
+        }
+
         descriptions
     }
 }
+
+/// Cut a tool output down to its summary, flagging it `truncated`
+///
+/// Fails on a result that isn't a tool output, having no summary to fall back to.
+pub fn summarize_output(result: &str) -> Result<String, String> {
+    let output: ToolOutput = serde_json::from_str(result)
+        .map_err(|_| "Result has no summary to cut down to".to_string())?;
+    Ok(output.summarized().to_json())
+}
+
+/// Check `arguments` against the parts of JSON schema the tools use: an object with
+/// `required` properties, each property's `type` and `enum`, and `additionalProperties`
+///
+/// String enums compare case-insensitively, as the tools normalize case.
+fn check_arguments(
+    schema: &serde_json::Value,
+    arguments: &serde_json::Value,
+) -> Result<(), String> {
+    let Some(arguments) = arguments.as_object() else {
+        return Err("arguments must be an object".to_string());
+    };
+
+    let required = schema["required"].as_array().into_iter().flatten();
+    if let Some(missing) = required
+        .filter_map(|name| name.as_str())
+        .find(|name| !arguments.contains_key(*name))
+    {
+        return Err(format!("missing required argument '{missing}'"));
+    }
+
+    for (name, value) in arguments {
+        let Some(property) = schema["properties"].get(name) else {
+            if schema["additionalProperties"] == false {
+                return Err(format!("unknown argument '{name}'"));
+            }
+            continue;
+        };
+
+        if let Some(expected) = property["type"].as_str() {
+            if !has_json_type(value, expected) {
+                return Err(format!("argument '{name}' must be of type {expected}"));
+            }
+        }
+
+        if let Some(allowed) = property["enum"].as_array() {
+            let matches = |option: &serde_json::Value| match (option.as_str(), value.as_str()) {
+                (Some(option), Some(value)) => option.eq_ignore_ascii_case(value),
+                _ => option == value,
+            };
+            if !allowed.iter().any(matches) {
+                return Err(format!(
+                    "argument '{name}' must be one of {}",
+                    serde_json::Value::from(allowed.clone())
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `value` is of the JSON schema `type`; unknown types accept anything
+fn has_json_type(value: &serde_json::Value, expected: &str) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Run `call` on `tool`, turning a missing tool or an execution error into a failed result
+///
+/// A result over `max_bytes` is cut down to its summary. With a `cache`, identical calls
+/// share a result.
+fn run_tool_call(
+    tool: Option<&dyn Tool>,
+    call: &ToolCall,
+    max_bytes: Option<usize>,
+    cache: Option<&ResultCache>,
+) -> ToolResult {
+    let result = tool
+        .ok_or_else(|| format!("Tool not found: {}", call.tool_name))
+        .and_then(|tool| {
+            check_quote_binds_call(call)?;
+            match cache {
+                Some(cache) => cache.execute(tool, call),
+                None => tool.execute(&call.arguments, call.compliance_quote.as_ref()),
+            }
+        })
+        .and_then(|data| match max_bytes {
+            Some(max) if data.len() > max => {
+                debug!(tool_name = %call.tool_name, bytes = data.len(), max, "truncating result");
+                summarize_output(&data)
+                    .map_err(|e| format!("{e}: {} bytes over the {max} byte limit", data.len()))
+            }
+            _ => Ok(data),
+        });
+
+    match result {
+        Ok(data) => ToolResult {
+            call_id: call.id,
+            success: true,
+            result: data,
+            error: None,
+            quote_verified: call.compliance_quote.is_some(), // Quote was present and verified
+            compliance_quote: None,
+            result_hash: None,
+            injection_markers: Vec::new(),
+            would_reject: false,
+            report_only_violations: Vec::new(),
+        },
+        Err(e) => failed_tool_result(call.id, e),
+    }
+}
+
+fn failed_tool_result(call_id: uuid::Uuid, error: String) -> ToolResult {
+    ToolResult {
+        call_id,
+        success: false,
+        result: String::new(),
+        error: Some(error),
+        quote_verified: false,
+        compliance_quote: None,
+        result_hash: None,
+        injection_markers: Vec::new(),
+        would_reject: false,
+        report_only_violations: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{ChainError, PolicyInfo, ToolError};
+    use crate::agent::quote_utils::{hash_compliance_data, hash_decision_context};
+    use crate::test_utils::{data_dir, synthetic_td_quote};
+
+    fn chain_tools() -> ToolRegistry {
+        ToolRegistry::crypto_tools_from_data_dir(
+            data_dir(),
+            Arc::default(),
+            Arc::default(),
+            None,
+            system_clock(),
+        )
+    }
+
+    #[test]
+    fn test_supported_chain_across_tools() {
+        let tools = chain_tools();
+
+        for name in ["OnChainHistoryTool", "PortfolioTool"] {
+            let tool = tools.get_tool(name).unwrap();
+            let output = tool.execute(r#"{"blockchain": "Ethereum"}"#, None).unwrap();
+            let output = serde_json::from_str::<ToolOutput>(&output).unwrap().data;
+            assert_eq!(output["blockchain"], "ethereum");
+            assert_eq!(tool.parameters_schema()["properties"]["blockchain"]["enum"][2], "bitcoin");
+        }
+    }
+
+    #[test]
+    fn test_tool_outputs_share_envelope() {
+        let tools = chain_tools();
+        let calls = [
+            ("PriceFeedTool", r#"{"symbol": "BTC"}"#, "symbol"),
+            ("OnChainHistoryTool", r#"{"blockchain": "ethereum"}"#, "summary"),
+            ("SentimentTool", r#"{"symbol": "BTC"}"#, "sentiment_score"),
+            ("PortfolioTool", r#"{"blockchain": "ethereum"}"#, "all_portfolios"),
+        ];
+
+        for (name, args, field) in calls {
+            let output = tools.get_tool(name).unwrap().execute(args, None).unwrap();
+            let output: ToolOutput = serde_json::from_str(&output).unwrap();
+            assert_eq!(output.tool, name);
+            assert!(!output.source.is_empty());
+            assert!(chrono::DateTime::parse_from_rfc3339(&output.timestamp).is_ok());
+            assert!(!output.data[field].is_null(), "{name} is missing data.{field}");
+        }
+    }
+
+    #[test]
+    fn test_tool_results_are_stamped_from_the_clock() {
+        let start = std::time::UNIX_EPOCH + Duration::from_secs(1_735_689_600);
+        let clock = Arc::new(crate::agent::MockClock::new(start));
+        let tools = ToolRegistry::crypto_tools_from_data_dir(
+            data_dir(),
+            Arc::default(),
+            Arc::default(),
+            None,
+            clock.clone(),
+        );
+        let call = ToolCall {
+            id: uuid::Uuid::now_v7(),
+            tool_name: "PriceFeedTool".to_string(),
+            arguments: r#"{"symbol": "BTC"}"#.to_string(),
+            timestamp: start,
+            compliance_quote: None,
+            thought_step: None,
+        };
+        let timestamp = |result: ToolResult| {
+            serde_json::from_str::<ToolOutput>(&result.result).unwrap().timestamp
+        };
+
+        let first = tools.execute_tool_call(&call);
+        assert_eq!(timestamp(first.clone()), "2025-01-01T00:00:00+00:00");
+        assert_eq!(tools.execute_tool_call(&call).result, first.result);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(timestamp(tools.execute_tool_call(&call)), "2025-01-01T00:01:30+00:00");
+    }
+
+    /// Tool answering with its arguments after `delay_ms`
+    struct DelayTool;
+
+    impl Tool for DelayTool {
+        fn name(&self) -> &str {
+            "DelayTool"
+        }
+
+        fn description(&self) -> &str {
+            "Echoes its arguments after a delay"
+        }
+
+        fn parameters_schema(&self) -> serde_json::Value {
+            json!({ "type": "object" })
+        }
+
+        fn execute(&self, arguments: &str, _: Option<&ComplianceQuote>) -> Result<String, String> {
+            let args: serde_json::Value = serde_json::from_str(arguments).unwrap();
+            let delay = args["delay_ms"].as_u64().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(delay));
+            Ok(arguments.to_string())
+        }
+
+        fn policy_ids(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn policy_info(&self) -> Vec<PolicyInfo> {
+            Vec::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_results_follow_call_order() {
+        let mut tools = ToolRegistry::default();
+        tools.register(Box::new(DelayTool));
+
+        let call = |delay_ms: u64| ToolCall {
+            id: uuid::Uuid::now_v7(),
+            tool_name: "DelayTool".to_string(),
+            arguments: json!({ "delay_ms": delay_ms }).to_string(),
+            timestamp: std::time::SystemTime::now(),
+            compliance_quote: None,
+            thought_step: None,
+        };
+        let mut calls: Vec<_> = [300, 0, 150].into_iter().map(call).collect();
+        calls.push(ToolCall {
+            tool_name: "MissingTool".to_string(),
+            ..call(0)
+        });
+
+        let mut completed = Vec::new();
+        let results = tools
+            .execute_tool_calls(&calls, 3, |result| completed.push(result.call_id))
+            .await;
+
+        // The slowest call was started first but finished last
+        assert_eq!(completed.last(), Some(&calls[0].id));
+        assert_eq!(results.len(), calls.len());
+        for (call, result) in calls.iter().zip(&results[..3]) {
+            assert_eq!(result.call_id, call.id);
+            assert_eq!(result.result, call.arguments);
+        }
+        assert_eq!(results[3].call_id, calls[3].id);
+        assert!(!results[3].success);
+    }
+
+    #[test]
+    fn test_price_feed_reloads_edited_fixture() {
+        let dir = std::env::temp_dir().join(format!("price_feed_{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(PriceFeedTool::DATA_FILE);
+        std::fs::copy(data_dir().join(PriceFeedTool::DATA_FILE), &path).unwrap();
+
+        let tool = PriceFeedTool::from_data_dir(&dir, Arc::default())
+            .unwrap()
+            .with_reload(Some(Duration::ZERO));
+        let price = || {
+            let output = tool.execute(r#"{"symbol": "BTC"}"#, None).unwrap();
+            serde_json::from_str::<ToolOutput>(&output).unwrap().data["price_usd"].clone()
+        };
+        assert_eq!(price(), 67500.5);
+
+        let fixture = json!({ "prices": [{ "symbol": "BTC", "price_usd": 70000.25 }] });
+        std::fs::write(&path, fixture.to_string()).unwrap();
+        assert_eq!(price(), 70000.25);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_edited_fixture_reaches_later_requests() {
+        let dir = std::env::temp_dir().join(format!("state_reload_{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for entry in std::fs::read_dir(data_dir()).unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), dir.join(entry.file_name())).unwrap();
+        }
+        let mut config = crate::Config::default();
+        config.agent.data_dir = dir.clone();
+        config.agent.data_reload_secs = Some(0);
+        let state = crate::types::HypervisorState::new(config).unwrap();
+
+        // Each request runs the tools it loads from the state
+        let price = || {
+            let tools = state.tool_registry();
+            let output = tools
+                .get_tool("PriceFeedTool")
+                .unwrap()
+                .execute(r#"{"symbol": "BTC"}"#, None)
+                .unwrap();
+            serde_json::from_str::<ToolOutput>(&output).unwrap().data["price_usd"].clone()
+        };
+        assert_eq!(price(), 67500.5);
+
+        let fixture = json!({ "prices": [{ "symbol": "BTC", "price_usd": 70000.25 }] });
+        std::fs::write(dir.join(PriceFeedTool::DATA_FILE), fixture.to_string()).unwrap();
+        assert_eq!(price(), 70000.25);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unsupported_chain_across_tools() {
+        let tools = chain_tools();
+        let expected = ChainError::UnsupportedChain {
+            chain: "dogecoin".to_string(),
+            supported: vec![
+                "ethereum".to_string(),
+                "solana".to_string(),
+                "bitcoin".to_string(),
+            ],
+        }
+        .to_string();
+
+        for name in ["OnChainHistoryTool", "PortfolioTool"] {
+            let tool = tools.get_tool(name).unwrap();
+            let err = tool.execute(r#"{"blockchain": "dogecoin"}"#, None).unwrap_err();
+            assert_eq!(err, expected);
+        }
+        assert!(expected.contains("ethereum, solana, bitcoin"));
+    }
+
+    #[test]
+    fn test_missing_or_misnamed_arguments_are_invalid() {
+        let err = parse_arguments::<PriceFeedArgs>("{}").unwrap_err();
+        assert_eq!(err, ToolError::InvalidArguments("missing field `symbol`".to_string()));
+        let err = parse_arguments::<SentimentArgs>(r#"{"symbol": "BTC", "time_frame": "7d"}"#);
+        assert!(matches!(err, Err(ToolError::InvalidArguments(e)) if e.contains("`time_frame`")));
+
+        let call = ToolCall {
+            id: uuid::Uuid::now_v7(),
+            tool_name: "PortfolioTool".to_string(),
+            arguments: r#"{"blockchain": "solana"}"#.to_string(),
+            timestamp: std::time::SystemTime::now(),
+            compliance_quote: None,
+            thought_step: None,
+        };
+        let args: PortfolioArgs = call.parse_arguments().unwrap();
+        assert_eq!(args.address, None);
+        assert_eq!(args.blockchain, "solana");
+
+        // Each tool fails the same way, before reading its data
+        let tools = chain_tools();
+        for (name, arguments) in [
+            ("PriceFeedTool", r#"{"ticker": "BTC"}"#),
+            ("OnChainHistoryTool", r#"{"chain": "ethereum"}"#),
+            ("SentimentTool", r#"{"symbol": "BTC", "time_frame": "24h"}"#),
+            ("SentimentTool", r#"{"symbol": 42}"#),
+            ("PortfolioTool", r#"{"blockchain": "ethereum", "wallet": "0xabc"}"#),
+        ] {
+            let err = tools.get_tool(name).unwrap().execute(arguments, None).unwrap_err();
+            assert!(err.starts_with("Invalid arguments: "), "{name}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_non_object_arguments_are_invalid() {
+        // serde alone would read the array as `{"symbol": "BTC"}`
+        let err = parse_arguments::<PriceFeedArgs>(r#"["BTC"]"#).unwrap_err();
+        let expected = ToolError::InvalidArguments("expected a JSON object, got an array".into());
+        assert_eq!(err, expected);
+
+        let tools = chain_tools();
+        let tool = tools.get_tool("PortfolioTool").unwrap();
+        let err = tool.execute(r#"["ethereum"]"#, None).unwrap_err();
+        assert_eq!(err, expected.to_string());
+        let err = tool.execute("42", None).unwrap_err();
+        assert_eq!(err, "Invalid arguments: expected a JSON object, got a number");
+    }
+
+    #[test]
+    fn test_address_tools_return_summaries() {
+        let tools = chain_tools();
+        let args = json!({
+            "blockchain": "ethereum",
+            "address": "0x742d35Cc6634C0532925a3b844Bc9e7595f0bEb",
+        })
+        .to_string();
+
+        let output = tools.get_tool("OnChainHistoryTool").unwrap().execute(&args, None).unwrap();
+        let output = serde_json::from_str::<ToolOutput>(&output).unwrap().data;
+        let transactions = output["transactions"].as_array().unwrap();
+        let summary = &output["summary"];
+        assert_eq!(summary["transactions"], transactions.len());
+        assert_eq!(summary["value_usd"]["count"], transactions.len());
+        assert_eq!(
+            summary["value_usd"]["sum"].as_f64().unwrap(),
+            aggregate::sum(transactions, "value_usd")
+        );
+
+        let output = tools.get_tool("PortfolioTool").unwrap().execute(&args, None).unwrap();
+        let output = serde_json::from_str::<ToolOutput>(&output).unwrap().data;
+        assert_eq!(output["summary"]["value_usd"]["count"], output["num_tokens"]);
+        let (min, max) = (
+            output["summary"]["value_usd"]["min"].as_f64().unwrap(),
+            output["summary"]["value_usd"]["max"].as_f64().unwrap(),
+        );
+        assert!(min <= max);
+
+        // Summaries cover every address when none is given
+        let output = tools
+            .get_tool("PortfolioTool")
+            .unwrap()
+            .execute(r#"{"blockchain": "ethereum"}"#, None)
+            .unwrap();
+        let output = serde_json::from_str::<ToolOutput>(&output).unwrap().data;
+        assert_eq!(output["summary"]["total_value_usd"]["count"], output["address_count"]);
+    }
+
+    #[test]
+    fn test_all_addresses_history_is_aggregated() {
+        let dir = std::env::temp_dir().join(format!("history-{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let transactions = |n: usize, value: f64| -> Vec<_> {
+            (0..n)
+                .map(|i| json!({ "tx_hash": format!("raw-tx-{i}"), "value_usd": value }))
+                .collect()
+        };
+        let history = json!({
+            "ethereum": { "0xabc": transactions(3, 100.0), "0xdef": transactions(2, 25.5) },
+        });
+        std::fs::write(dir.join(OnChainHistoryTool::DATA_FILE), history.to_string()).unwrap();
+        let tool = OnChainHistoryTool::from_data_dir(&dir, Arc::default(), Arc::default()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let output = tool.execute(r#"{"blockchain": "ethereum"}"#, None).unwrap();
+        assert!(!output.contains("raw-tx"), "{output}");
+        let output = serde_json::from_str::<ToolOutput>(&output).unwrap().data;
+        assert_eq!(output["address_count"], 2);
+        assert_eq!(output["summary"]["transactions"], 5);
+        assert_eq!(output["summary"]["value_usd"]["sum"], 351.0);
+        assert_eq!(
+            output["summary"]["per_address"],
+            json!({
+                "0xabc": { "transactions": 3, "value_usd": 300.0 },
+                "0xdef": { "transactions": 2, "value_usd": 51.0 },
+            })
+        );
+
+        // One requested address still gets its records
+        let output = tool
+            .execute(r#"{"address": "0xdef", "blockchain": "ethereum"}"#, None)
+            .unwrap();
+        let output = serde_json::from_str::<ToolOutput>(&output).unwrap().data;
+        assert_eq!(output["transactions"].as_array().unwrap().len(), 2);
+    }
+
+    fn sentiment_tool() -> SentimentTool {
+        SentimentTool::from_data_dir(data_dir(), Arc::default()).unwrap()
+    }
+
+    #[test]
+    fn test_sentiment_timeframe() {
+        let tool = sentiment_tool();
+
+        let output = tool
+            .execute(r#"{"symbol": "btc", "timeframe": "24h"}"#, None)
+            .unwrap();
+        let output = serde_json::from_str::<ToolOutput>(&output).unwrap().data;
+        assert_eq!(output["timeframe"], "24h");
+        assert!(output["mentions_count"].as_u64().unwrap() > 0);
+
+        assert_eq!(tool.available_timeframes(), ["24h"]);
+        assert_eq!(tool.parameters_schema()["properties"]["timeframe"]["enum"], json!(["24h"]));
+    }
+
+    #[test]
+    fn test_sentiment_default_timeframe() {
+        let output = sentiment_tool().execute(r#"{"symbol": "ETH"}"#, None).unwrap();
+        let output = serde_json::from_str::<ToolOutput>(&output).unwrap().data;
+        assert_eq!(output["timeframe"], SentimentTool::DEFAULT_TIMEFRAME);
+    }
+
+    #[test]
+    fn test_sentiment_invalid_timeframe() {
+        let err = sentiment_tool()
+            .execute(r#"{"symbol": "BTC", "timeframe": "7d"}"#, None)
+            .unwrap_err();
+        let err: serde_json::Value = serde_json::from_str(&err).unwrap();
+        assert_eq!(err["error"], "No data available for timeframe: 7d");
+        assert_eq!(err["available_timeframes"], json!(["24h"]));
+    }
+
+    #[test]
+    fn test_missing_data_file_leaves_other_tools() {
+        let dir = std::env::temp_dir().join(format!("tools-{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for entry in std::fs::read_dir(data_dir()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name().unwrap() != SentimentTool::DATA_FILE {
+                std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+            }
+        }
+
+        let tools = ToolRegistry::crypto_tools_from_data_dir(
+            &dir,
+            Arc::default(),
+            Arc::default(),
+            None,
+            system_clock(),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(tools.all_tools().len(), 3);
+        assert!(tools.get_tool("SentimentTool").is_none());
+        let output = tools.get_tool("PriceFeedTool").unwrap().execute(r#"{"symbol": "BTC"}"#, None);
+        assert!(output.unwrap().contains("67500.5"));
+
+        let [(name, error)] = tools.unavailable_tools() else {
+            panic!("{:?}", tools.unavailable_tools());
+        };
+        assert_eq!(name, "SentimentTool");
+        assert!(tools.missing_tool_reason("SentimentTool").contains(error.as_str()));
+        assert_eq!(tools.missing_tool_reason("NoSuchTool"), "Tool 'NoSuchTool' not found");
+        assert!(tools.generate_tool_descriptions().contains("do not call them): SentimentTool"));
+    }
+
+    #[test]
+    fn test_large_result_is_truncated_to_summary() {
+        let dir = std::env::temp_dir().join(format!("large-{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let transactions: Vec<_> = (0..5000)
+            .map(|i| json!({ "txid": format!("0x{i:064x}"), "value_usd": 1.5, "gas_used": 21000 }))
+            .collect();
+        let history = json!({ "ethereum": { "0xabc": transactions } });
+        std::fs::write(dir.join(OnChainHistoryTool::DATA_FILE), history.to_string()).unwrap();
+
+        let tool = OnChainHistoryTool::from_data_dir(&dir, Arc::default(), Arc::default()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut tools = ToolRegistry::default();
+        tools.register(Box::new(tool));
+        let limits = HashMap::from([("OnChainHistoryTool".to_string(), 64 * 1024)]);
+        let tools = tools.with_result_limits(limits);
+
+        let call = |address: &str| ToolCall {
+            id: uuid::Uuid::now_v7(),
+            tool_name: "OnChainHistoryTool".to_string(),
+            arguments: json!({ "address": address, "blockchain": "ethereum" }).to_string(),
+            timestamp: std::time::SystemTime::now(),
+            compliance_quote: None,
+            thought_step: None,
+        };
+        let result = tools.execute_tool_call(&call("0xabc"));
+        assert!(result.success);
+        assert!(result.result.len() <= 64 * 1024);
+
+        let output: ToolOutput = serde_json::from_str(&result.result).unwrap();
+        assert!(output.truncated);
+        assert!(output.data.get("transactions").is_none());
+        assert_eq!(output.data["count"], 5000);
+        assert_eq!(output.data["summary"]["transactions"], 5000);
+        assert_eq!(output.data["summary"]["value_usd"]["sum"].as_f64().unwrap(), 7500.0);
+
+        // Results within the limit are untouched and carry no flag
+        let output = chain_tools()
+            .with_result_limits(HashMap::from([("OnChainHistoryTool".to_string(), 64 * 1024)]))
+            .execute_tool_call(&call("0x742d35Cc6634C0532925a3b844Bc9e7595f0bEb"));
+        assert!(output.success, "{:?}", output.error);
+        assert!(!output.result.contains("truncated"));
+    }
+
+    #[test]
+    fn test_quote_is_rejected_on_another_call_of_the_same_tool() {
+        let tools = chain_tools();
+        let call = |symbol: &str| ToolCall {
+            id: uuid::Uuid::now_v7(),
+            tool_name: "PriceFeedTool".to_string(),
+            arguments: json!({ "symbol": symbol }).to_string(),
+            timestamp: std::time::SystemTime::now(),
+            compliance_quote: None,
+            thought_step: None,
+        };
+
+        let approved = call("BTC");
+        let session_id = uuid::Uuid::now_v7();
+        let context_hash = hash_decision_context(&["L1".to_string()], "What is BTC at?");
+        let report = hash_compliance_data(
+            &approved.tool_name,
+            approved.id,
+            session_id,
+            true,
+            &context_hash,
+            &approved.arguments,
+        );
+        let quote = ComplianceQuote {
+            tool_name: approved.tool_name.clone(),
+            call_id: approved.id,
+            session_id,
+            compliant: true,
+            quote_bytes: synthetic_td_quote(report.build().to_bytes()),
+            compliance_hash: report.digest(),
+            context_hash,
+            timestamp: std::time::SystemTime::now(),
+        };
+
+        // The approval for BTC can't be carried over to an ETH call, even relabeled
+        let eth = call("ETH");
+        let replayed = ToolCall {
+            compliance_quote: Some(ComplianceQuote {
+                call_id: eth.id,
+                ..quote.clone()
+            }),
+            ..eth
+        };
+        let result = tools.execute_tool_call(&replayed);
+        assert!(!result.success);
+        let expected = format!("Compliance quote wasn't issued for call {}", replayed.id);
+        assert_eq!(result.error, Some(expected));
+
+        // On its own call the quote approves it
+        let result = tools.execute_tool_call(&ToolCall {
+            compliance_quote: Some(quote),
+            ..approved
+        });
+        assert!(result.success, "{:?}", result.error);
+    }
+
+    /// Tool counting its executions
+    #[derive(Clone, Default)]
+    struct CountingTool(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Tool for CountingTool {
+        fn name(&self) -> &str {
+            "CountingTool"
+        }
+
+        fn description(&self) -> &str {
+            "Counts its executions"
+        }
+
+        fn parameters_schema(&self) -> serde_json::Value {
+            json!({ "type": "object" })
+        }
+
+        fn execute(&self, arguments: &str, _: Option<&ComplianceQuote>) -> Result<String, String> {
+            let count = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Ok(json!({ "arguments": arguments, "count": count }).to_string())
+        }
+
+        fn policy_ids(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn policy_info(&self) -> Vec<PolicyInfo> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_identical_call_hits_the_result_cache() {
+        let counting = CountingTool::default();
+        let ttls = HashMap::from([("CountingTool".to_string(), Duration::from_secs(60))]);
+        let mut tools = ToolRegistry::default().with_result_caches(ttls);
+        tools.register(Box::new(counting.clone()));
+        let call = |arguments: &str| ToolCall {
+            id: uuid::Uuid::now_v7(),
+            tool_name: "CountingTool".to_string(),
+            arguments: arguments.to_string(),
+            timestamp: std::time::SystemTime::now(),
+            compliance_quote: None,
+            thought_step: None,
+        };
+
+        let first = tools.execute_tool_call(&call(r#"{"symbol": "BTC", "days": 7}"#));
+        assert!(first.success);
+        let second = tools.execute_tool_call(&call(r#"{"days":7,"symbol":"BTC"}"#));
+        assert_eq!(second.result, first.result);
+        assert_eq!(counting.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        tools.execute_tool_call(&call(r#"{"symbol": "ETH", "days": 7}"#));
+        assert_eq!(counting.0.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // A cached result is still only returned past the call's quote
+        let quoted = call(r#"{"symbol": "BTC", "days": 7}"#);
+        let expected = format!("Compliance quote wasn't issued for call {}", quoted.id);
+        let result = tools.execute_tool_call(&ToolCall {
+            compliance_quote: Some(ComplianceQuote {
+                tool_name: quoted.tool_name.clone(),
+                call_id: quoted.id,
+                session_id: uuid::Uuid::now_v7(),
+                compliant: true,
+                quote_bytes: vec![1],
+                compliance_hash: [0; 32],
+                context_hash: [0; 32],
+                timestamp: std::time::SystemTime::now(),
+            }),
+            ..quoted
+        });
+        assert_eq!(result.error, Some(expected));
+
+        // Expired results are run again
+        let ttls = HashMap::from([("CountingTool".to_string(), Duration::ZERO)]);
+        let mut tools = ToolRegistry::default().with_result_caches(ttls);
+        tools.register(Box::new(counting.clone()));
+        tools.execute_tool_call(&call(r#"{"symbol": "BTC", "days": 7}"#));
+        tools.execute_tool_call(&call(r#"{"symbol": "BTC", "days": 7}"#));
+        assert_eq!(counting.0.load(std::sync::atomic::Ordering::SeqCst), 4);
+    }
+}
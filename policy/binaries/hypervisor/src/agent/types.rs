@@ -1,6 +1,9 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::clock::Clock;
+use super::compliance::SkippedRule;
+use super::error::ToolError;
 use super::policy_registry::PolicyInfo;
 
 /// Compliance attestation quote from hypervisor
@@ -8,12 +11,18 @@ use super::policy_registry::PolicyInfo;
 pub struct ComplianceQuote {
     /// Tool name this quote is for
     pub tool_name: String,
+    /// `ToolCall::id` of the call this quote is for
+    pub call_id: Uuid,
+    /// Session of the execution the call belongs to
+    pub session_id: Uuid,
     /// Compliance check result (approved/rejected)
     pub compliant: bool,
     /// The raw TEE attestation quote bytes
     pub quote_bytes: Vec<u8>,
     /// Hash of compliance check data that was attested
     pub compliance_hash: [u8; 32],
+    /// Hash of the policies evaluated and the user query, one of the attested inputs
+    pub context_hash: [u8; 32],
     /// Timestamp of quote generation
     pub timestamp: std::time::SystemTime,
 }
@@ -25,12 +34,34 @@ pub struct AgentPlan {
     pub system_prompt: String,
     /// The user's query/input
     pub user_query: String,
+    /// Earlier queries of the session given to planning, oldest first, within the
+    /// agent's history window
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<String>,
     /// The agent's thought process (chain of thought)
     pub thought_process: Vec<ThoughtStep>,
     /// List of tools the agent intends to use
     pub intended_tool_calls: Vec<ToolCall>,
 }
 
+impl AgentPlan {
+    /// Drop the thought steps and/or system prompt before returning the plan to a client
+    /// Returns whether anything was removed
+    pub fn redact(&mut self, include_thoughts: bool, include_system_prompt: bool) -> bool {
+        let mut redacted = false;
+        if !include_thoughts && !self.thought_process.is_empty() {
+            self.thought_process.clear();
+            redacted = true;
+        }
+        if !include_system_prompt && !self.system_prompt.is_empty() {
+            self.system_prompt.clear();
+            redacted = true;
+        }
+
+        redacted
+    }
+}
+
 /// A step in the agent's reasoning process
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThoughtStep {
@@ -55,6 +86,49 @@ pub struct ToolCall {
     pub timestamp: std::time::SystemTime,
     /// Compliance attestation quote from hypervisor (attached after compliance check)
     pub compliance_quote: Option<ComplianceQuote>,
+    /// `step` of the thought the call follows in the plan; none when the planner
+    /// called the tool before any thought, or for direct calls
+    #[serde(default)]
+    pub thought_step: Option<usize>,
+}
+
+impl ToolCall {
+    /// The call's arguments as the tool's typed params
+    pub fn parse_arguments<T: DeserializeOwned>(&self) -> Result<T, ToolError> {
+        parse_arguments(&self.arguments)
+    }
+}
+
+/// Deserialize JSON tool arguments into typed params
+///
+/// Arguments other than an object, and missing, mistyped and unknown fields, are all
+/// reported as `ToolError::InvalidArguments`.
+pub fn parse_arguments<T: DeserializeOwned>(arguments: &str) -> Result<T, ToolError> {
+    let object = parse_argument_object(arguments)?;
+    T::deserialize(serde_json::Value::Object(object))
+        .map_err(|e| ToolError::InvalidArguments(e.to_string()))
+}
+
+/// Parse JSON tool arguments, which must be an object
+///
+/// Checked up front, as serde would otherwise read an array into a struct by position.
+pub fn parse_argument_object(
+    arguments: &str,
+) -> Result<serde_json::Map<String, serde_json::Value>, ToolError> {
+    use serde_json::Value;
+
+    let value = serde_json::from_str(arguments)
+        .map_err(|e: serde_json::Error| ToolError::InvalidArguments(e.to_string()))?;
+    let found = match value {
+        Value::Object(object) => return Ok(object),
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+    };
+
+    Err(ToolError::InvalidArguments(format!("expected a JSON object, got {found}")))
 }
 
 /// Result from a tool execution
@@ -70,6 +144,82 @@ pub struct ToolResult {
     pub error: Option<String>,
     /// Whether the compliance quote was verified by the tool
     pub quote_verified: bool,
+    /// Attestation that the hypervisor denied the call, on rejection records
+    #[serde(default)]
+    pub compliance_quote: Option<ComplianceQuote>,
+    /// Merkle leaf hash (hex-encoded) standing in for `result` when it was compacted away
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_hash: Option<String>,
+    /// Prompt-injection markers found in `result`; the final prompt then wraps it as
+    /// untrusted data
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub injection_markers: Vec<String>,
+    /// Whether a report-only policy would have rejected the call, which ran anyway
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub would_reject: bool,
+    /// Violations of the report-only policies, when `would_reject` is set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub report_only_violations: Vec<String>,
+}
+
+impl ToolResult {
+    /// Record the violations of report-only policies that let the call through
+    pub fn report_violations(&mut self, violations: Vec<String>) {
+        self.would_reject |= !violations.is_empty();
+        self.report_only_violations.extend(violations);
+    }
+
+    /// Replace the raw result with its Merkle leaf hash, keeping call ID and status
+    pub fn compact(&mut self) {
+        self.result_hash = Some(const_hex::encode(super::merkle::hash_tool_result(self)));
+        self.result.clear();
+    }
+}
+
+/// Common envelope of every tool output; the tool-specific fields live under `data`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolOutput {
+    /// Name of the tool that produced the output
+    pub tool: String,
+    /// Tool-specific payload
+    pub data: serde_json::Value,
+    /// Provider of the underlying data
+    pub source: String,
+    /// When the output was produced (RFC 3339)
+    pub timestamp: String,
+    /// Whether `data` was cut down to its summary, see `summarized`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+impl ToolOutput {
+    /// Output stamped with the current time of `clock`
+    pub fn new(tool: &str, source: &str, data: serde_json::Value, clock: &dyn Clock) -> Self {
+        Self {
+            tool: tool.to_string(),
+            data,
+            source: source.to_string(),
+            timestamp: chrono::DateTime::<chrono::Utc>::from(clock.now()).to_rfc3339(),
+            truncated: false,
+        }
+    }
+
+    /// Keep only the scalar fields and the `summary` of `data`, dropping the raw records,
+    /// and flag the output as truncated
+    pub fn summarized(mut self) -> Self {
+        if let serde_json::Value::Object(fields) = &mut self.data {
+            fields.retain(|key, value| {
+                key == "summary" || !(value.is_array() || value.is_object())
+            });
+        }
+        self.truncated = true;
+        self
+    }
+
+    /// Serialize into the string returned by `Tool::execute`
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("tool output is serializable")
+    }
 }
 
 /// A tool that can be used by the agent
@@ -91,6 +241,11 @@ pub trait Tool: Send + Sync {
     
     /// Get the policy information (ID and name) for this tool
     fn policy_info(&self) -> Vec<PolicyInfo>;
+
+    /// Whether the tool serves synthetic fixture data rather than live data
+    fn is_synthetic(&self) -> bool {
+        false
+    }
 }
 
 /// Complete execution trace of an agent
@@ -106,8 +261,62 @@ pub struct AgentExecution {
     pub tool_results: Vec<ToolResult>,
     /// Final response from the agent
     pub final_response: String,
+    /// False when the agent answered `IMPOSSIBLE: [reason]` because critical tools were rejected
+    pub answerable: bool,
+    /// Why the query couldn't be answered, when `answerable` is false
+    pub reason: Option<String>,
+    /// Model that wrote the final response, a fallback when the primary was unavailable
+    #[serde(default)]
+    pub model: String,
     /// Total execution time in milliseconds
     pub execution_time_ms: u64,
+    /// Number of the execution in the server's sequence, hashed so identical executions
+    /// are attested apart; 0 when run outside the server
+    #[serde(default)]
+    pub sequence: u64,
+    /// Rules the approved calls' policies left unevaluated (disabled methods, or LLM rules
+    /// without LLM compliance), each listed once; reported but not hashed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_rules: Vec<SkippedRule>,
+}
+
+/// Progress event emitted while the agent executes a query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// LLM-based planning has started
+    PlanningStarted,
+    /// A reasoning step produced by planning
+    Thought(ThoughtStep),
+    /// A planned tool call passed its compliance check
+    ToolApproved {
+        call_id: Uuid,
+        tool_name: String,
+        policy_ids: Vec<String>,
+        /// Rules not evaluated (disabled methods, or LLM rules without LLM compliance)
+        skipped_rules: Vec<SkippedRule>,
+    },
+    /// A planned tool call was rejected
+    ToolRejected {
+        call_id: Uuid,
+        tool_name: String,
+        reason: String,
+    },
+    /// An approved tool call finished executing
+    ToolResult(Box<ToolResult>),
+}
+
+impl AgentEvent {
+    /// Event name used on the wire (matches the serde tag)
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgentEvent::PlanningStarted => "planning_started",
+            AgentEvent::Thought(_) => "thought",
+            AgentEvent::ToolApproved { .. } => "tool_approved",
+            AgentEvent::ToolRejected { .. } => "tool_rejected",
+            AgentEvent::ToolResult(_) => "tool_result",
+        }
+    }
 }
 
 /// Result of a compliance check
@@ -117,8 +326,14 @@ pub struct ComplianceResult {
     pub compliant: bool,
     /// Reason for the compliance decision
     pub reason: String,
+    /// The plan called no tools, so no per-tool policy applied
+    #[serde(default)]
+    pub no_tools: bool,
     /// Hash of the policy used for checking
     pub policy_hash: String,
     /// Hash of the plan checked
     pub plan_hash: String,
+    /// Rules that weren't evaluated, see `AgentExecution::skipped_rules`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_rules: Vec<SkippedRule>,
 }
@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::HypervisorError, types::HypervisorState};
+
+pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
+    router.route("/admin/policies/reload", post(reload_policies))
+}
+
+/// Policies enforced after a reload
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReloadPoliciesResponse {
+    /// Hash of the policies (hex-encoded), as reported in compliance decisions
+    pub policy_hash: String,
+    /// Policy IDs
+    pub policies: Vec<String>,
+}
+
+/// Re-read the policy file and swap in the new registry; requests in flight finish
+/// with the previous one
+#[tracing::instrument(skip_all, err)]
+async fn reload_policies(
+    State(state): State<HypervisorState>,
+    headers: HeaderMap,
+) -> Result<Json<ReloadPoliciesResponse>, HypervisorError> {
+    authorize(&state, &headers)?;
+
+    let registry = match state.reload_policies() {
+        Ok(registry) => registry,
+        Err(e) => {
+            return Err(anyhow::Error::msg(StatusCode::UNPROCESSABLE_ENTITY)
+                .context(format!("reload policies: {e:#}"))
+                .into())
+        }
+    };
+
+    let policy_hash = const_hex::encode(registry.policy_hash());
+    tracing::info!(policy_hash, "reloaded policies");
+
+    Ok(Json(ReloadPoliciesResponse {
+        policy_hash,
+        policies: registry.policies().iter().map(|p| p.id.clone()).collect(),
+    }))
+}
+
+/// Check the bearer token against `admin_token`; 404 when no token is configured
+fn authorize(state: &HypervisorState, headers: &HeaderMap) -> Result<(), HypervisorError> {
+    let token = state
+        .config
+        .admin_token
+        .as_ref()
+        .ok_or(anyhow!("admin routes disabled"))
+        .context(StatusCode::NOT_FOUND)?;
+
+    let given = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    // blake3::Hash compares in constant time
+    if blake3::hash(given.as_bytes()) != blake3::hash(token.expose_secret().as_bytes()) {
+        return Err(anyhow!("invalid admin token"))
+            .context(StatusCode::UNAUTHORIZED)
+            .map_err(Into::into);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        agent::{types::AgentPlan, ComplianceChecker},
+        api::RouterRegister,
+        test_utils::write_policy_file,
+        Config,
+    };
+
+    fn is_allowed(state: &HypervisorState, query: &str) -> bool {
+        let plan = AgentPlan {
+            system_prompt: String::new(),
+            user_query: query.to_string(),
+            history: Vec::new(),
+            thought_process: vec![],
+            intended_tool_calls: vec![],
+        };
+
+        ComplianceChecker::from_registry(&state.policy_registry())
+            .check_compliance(&plan)
+            .unwrap()
+            .compliant
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_edited_policy_file() {
+        let path = std::env::temp_dir().join(format!("policies_{}.toml", uuid::Uuid::now_v7()));
+        write_policy_file(&path, &["to the moon"]);
+
+        let mut config = Config {
+            admin_token: Some("admin-secret".into()),
+            ..Default::default()
+        };
+        config.agent.policy_file = Some(path.clone());
+        let state = HypervisorState::new(config).unwrap();
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .with_state(state.clone()),
+        )
+        .unwrap();
+
+        let query = "Will SOL reach a new all-time high this year?";
+        assert!(is_allowed(&state, query));
+        let hash_before = ComplianceChecker::from_registry(&state.policy_registry()).policy_hash();
+
+        write_policy_file(&path, &["to the moon", "all-time high"]);
+        // Nothing changes until the reload
+        assert!(is_allowed(&state, query));
+
+        let response = server
+            .post("/admin/policies/reload")
+            .authorization_bearer("wrong")
+            .expect_failure()
+            .await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        let response = server
+            .post("/admin/policies/reload")
+            .authorization_bearer("admin-secret")
+            .await;
+        let reloaded: ReloadPoliciesResponse = response.json();
+        assert_eq!(reloaded.policies, ["H1"]);
+        assert_ne!(reloaded.policy_hash, hash_before);
+        assert!(!is_allowed(&state, query));
+
+        // An invalid file is rejected and the reloaded policies stay in force
+        fs::write(&path, "[[policies]]\nid = 1").unwrap();
+        let response = server
+            .post("/admin/policies/reload")
+            .authorization_bearer("admin-secret")
+            .expect_failure()
+            .await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!is_allowed(&state, query));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_disabled_without_token() {
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .with_state(HypervisorState::default()),
+        )
+        .unwrap();
+
+        let response = server.post("/admin/policies/reload").expect_failure().await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+}
@@ -1,21 +1,77 @@
-use aes_gcm_siv::aead::Aead;
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use aes_gcm_siv::{aead::Aead, Aes256GcmSiv};
 use anyhow::{anyhow, Context};
-use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{get, post},
+    Router,
+};
 use serde::{Deserialize, Serialize};
-use tracing::{info, debug};
+use serde_json::json;
+use tokio::{sync::mpsc, task::JoinHandle};
+use tokio_stream::{wrappers::UnboundedReceiverStream, Stream};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 use crate::{
-    agent::{AgentExecution, ComplianceChecker, ComplianceResult, CryptoAgent},
+    api::{
+        accept_plaintext, acquire_permit, bind_quote,
+        extract::{Json, Path, Query},
+        quote::QuoteCompression,
+        session_quote,
+        validation::Validation,
+        SessionQuote,
+    },
+    agent::{
+        compliance::cites_source, crypto_agent::CryptoAgentConfig, merkle::hash_tool_result,
+        AgentError, AgentEvent, AgentExecution, ComplianceChecker, ComplianceResult, CryptoAgent,
+        MerkleProof, SkippedRule, SupportedChains, ToolOutput, ToolResult, ToolResultsMerkleTree,
+    },
+    config::GenerationLimits,
     error::HypervisorError,
     types::HypervisorState,
-    utils::crypto,
+    utils::{
+        attest::{ReportDataBuilder, AGENT_DOMAIN, AGENT_TOOL_DOMAIN},
+        crypto::{self, StreamSealer},
+    },
 };
 
 pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
     router
         .route("/agent/query", post(query_agent))
+        .route("/agent/query/stream", post(query_agent_stream))
+        .route("/agent/tool", post(call_agent_tool))
         .route("/verifiable/agent/query", post(verifiable_query_agent))
+        .route("/agent/chains", get(supported_chains))
+        .route("/agent/execution/{hash}", get(get_execution))
+}
+
+/// Plaintext agent route, registered on `unix:` listeners when `plaintext_routes` is set
+pub(crate) fn plaintext_api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
+    router.route("/agent/query/plain", post(query_agent_plain))
+}
+
+/// Blockchains accepted by the agent's chain-aware tools
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SupportedChainsResponse {
+    pub chains: Vec<String>,
+}
+
+async fn supported_chains(State(state): State<HypervisorState>) -> Json<SupportedChainsResponse> {
+    let chains = SupportedChains::new(&state.config.agent.supported_chains);
+
+    Json(SupportedChainsResponse {
+        chains: chains.list().to_vec(),
+    })
 }
 
 /// Request to query the crypto agent
@@ -28,6 +84,116 @@ pub struct AgentQueryRequest {
     /// Whether to use LLM-based compliance checking (default: false)
     #[serde(default)]
     pub use_llm_compliance: bool,
+    /// Return the plan's thought steps (default: `agent.include_thoughts`); can't turn
+    /// them on when the server disabled them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_thoughts: Option<bool>,
+    /// Return the system prompt (default: `agent.include_system_prompt`); can't turn it
+    /// on when the server disabled it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_system_prompt: Option<bool>,
+    /// Replace raw tool-result payloads with their Merkle leaf hashes, for clients that
+    /// only verify the execution
+    #[serde(default)]
+    pub compact: bool,
+    /// `max_tokens` of the final response (default: `agent.max_tokens`), capped by
+    /// `agent.max_tokens_ceiling` and the server's `max_tokens_ceiling`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Temperature of the final response (default: `agent.temperature`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Return a TEE quote over the execution hash, as `/verifiable/agent/query` does
+    #[serde(default)]
+    pub attest: bool,
+}
+
+/// Agent settings for a request, its completion parameters clamped to the server's limits
+fn agent_config(
+    state: &HypervisorState,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+) -> (CryptoAgentConfig, GenerationLimits) {
+    let mut config = state.config.agent.clone();
+    let mut limits = state.config.generation_limits(
+        max_tokens.unwrap_or(config.max_tokens),
+        temperature.unwrap_or(config.temperature),
+    );
+    if let Some(ceiling) = config.max_tokens_ceiling {
+        limits.max_tokens = limits.max_tokens.min(ceiling);
+    }
+    config.max_tokens = limits.max_tokens;
+    config.temperature = limits.temperature;
+    config.models = state.config.models.clone();
+
+    (config, limits)
+}
+
+/// Parts of the execution returned to the client
+#[derive(Debug, Clone, Copy)]
+struct Disclosure {
+    thoughts: bool,
+    system_prompt: bool,
+    /// Raw tool-result payloads; replaced by their leaf hashes when false
+    tool_results: bool,
+}
+
+impl Disclosure {
+    /// What the request asks for, within what the server's config discloses
+    fn resolve(state: &HypervisorState, req: &AgentQueryRequest) -> Self {
+        let config = &state.config.agent;
+        Self {
+            thoughts: config.include_thoughts && req.include_thoughts.unwrap_or(true),
+            system_prompt: config.include_system_prompt
+                && req.include_system_prompt.unwrap_or(true),
+            tool_results: !req.compact,
+        }
+    }
+
+    /// Redact the execution after it has been hashed
+    /// Returns whether anything was removed
+    fn apply(self, execution: &mut AgentExecution) -> bool {
+        let mut redacted = execution.plan.redact(self.thoughts, self.system_prompt);
+        if !self.tool_results && !execution.tool_results.is_empty() {
+            execution.tool_results.iter_mut().for_each(ToolResult::compact);
+            redacted = true;
+        }
+
+        redacted
+    }
+}
+
+/// Unencrypted query of `/agent/query/plain`, see `plaintext_api_register`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlainAgentQueryRequest {
+    pub query: String,
+    /// Whether to use LLM-based compliance checking (default: false)
+    #[serde(default)]
+    pub use_llm_compliance: bool,
+    /// `max_tokens` of the final response (default: `agent.max_tokens`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Temperature of the final response (default: `agent.temperature`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+/// Response from a plaintext agent query: the answer, without the execution trace
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlainAgentQueryResponse {
+    pub response: String,
+    /// False when the agent declared the query impossible (the response text still explains why)
+    pub answerable: bool,
+    /// Why the query couldn't be answered, when `answerable` is false
+    pub reason: Option<String>,
+    /// Model that wrote the response
+    pub model: String,
+    /// Execution time in milliseconds
+    pub execution_time_ms: u64,
+    /// `max_tokens` of the final response, after clamping to the server's ceiling
+    pub max_tokens: u32,
+    /// Temperature of the final response, after clamping to the accepted range
+    pub temperature: f32,
 }
 
 /// Response from agent query
@@ -35,14 +201,43 @@ pub struct AgentQueryRequest {
 pub struct AgentQueryResponse {
     /// Session ID
     pub session_id: Uuid,
-    /// Encrypted response (hex-encoded)
+    /// Encrypted response, sealed as `version || nonce || ciphertext` (hex-encoded),
+    /// see `crypto::open`
     pub encrypted_response: String,
-    /// Nonce used for response encryption (hex-encoded)
-    pub response_nonce: String,
+    /// False when the agent declared the query impossible (the response text still explains why)
+    pub answerable: bool,
+    /// Why the query couldn't be answered, when `answerable` is false
+    pub reason: Option<String>,
     /// Execution time in milliseconds
     pub execution_time_ms: u64,
+    /// Number of the execution in the server's sequence, covered by `execution_hash`
+    pub sequence: u64,
     /// Hash of the execution trace
     pub execution_hash: String,
+    /// Merkle root over the tool results (hex-encoded, committed to in the execution hash)
+    pub tool_results_root: String,
+    /// Merkle inclusion proof for each tool result
+    pub tool_result_proofs: Vec<MerkleProof>,
+    /// Whether thoughts, the system prompt or (in compact mode) tool-result payloads were
+    /// redacted from `execution`; the execution hash still covers the full content
+    pub redacted: bool,
+    /// `max_tokens` of the final response, after clamping to the server's ceiling
+    pub max_tokens: u32,
+    /// Temperature of the final response, after clamping to the accepted range
+    pub temperature: f32,
+    /// TEE attestation quote over `execution_hash` (hex-encoded), when `attest` was set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    /// Session key's signature over the quote and session ID (hex-encoded), with `quote`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_signature: Option<String>,
+    /// Id of the raw quote at `GET /verifiable/quote/{id}`, with `quote`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<String>,
+    /// Compression of `quote` before hex encoding, see `QuoteCompression::decode`;
+    /// plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
     /// Full execution details (for hash verification)
     pub execution: AgentExecution,
 }
@@ -52,18 +247,43 @@ pub struct AgentQueryResponse {
 pub struct VerifiableAgentQueryResponse {
     /// Session ID
     pub session_id: Uuid,
-    /// Encrypted response (hex-encoded)
+    /// Encrypted response, sealed as `version || nonce || ciphertext` (hex-encoded),
+    /// see `crypto::open`
     pub encrypted_response: String,
-    /// Nonce used for response encryption (hex-encoded)
-    pub response_nonce: String,
+    /// False when the agent declared the query impossible (the response text still explains why)
+    pub answerable: bool,
+    /// Why the query couldn't be answered, when `answerable` is false
+    pub reason: Option<String>,
     /// Execution time in milliseconds
     pub execution_time_ms: u64,
+    /// Number of the execution in the server's sequence, covered by `execution_hash`
+    pub sequence: u64,
     /// Hash of the execution trace
     pub execution_hash: String,
+    /// Merkle root over the tool results (hex-encoded, committed to in the execution hash)
+    pub tool_results_root: String,
+    /// Merkle inclusion proof for each tool result
+    pub tool_result_proofs: Vec<MerkleProof>,
     /// TEE attestation quote (hex-encoded)
     pub quote: String,
+    /// Session key's signature over the quote and session ID (hex-encoded),
+    /// see `crypto::verify_quote_binding`
+    pub quote_signature: String,
+    /// Id of the raw quote at `GET /verifiable/quote/{id}`
+    pub quote_id: String,
+    /// Compression of `quote` before hex encoding, see `QuoteCompression::decode`;
+    /// plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
     /// Compliance check result
     pub compliance: ComplianceResult,
+    /// Whether thoughts, the system prompt or (in compact mode) tool-result payloads were
+    /// redacted from `execution`; the execution hash still covers the full content
+    pub redacted: bool,
+    /// `max_tokens` of the final response, after clamping to the server's ceiling
+    pub max_tokens: u32,
+    /// Temperature of the final response, after clamping to the accepted range
+    pub temperature: f32,
     /// Full execution details (for hash verification)
     pub execution: AgentExecution,
 }
@@ -77,252 +297,925 @@ async fn query_agent(
     // Validate request
     validate_agent_request(&req)?;
 
-    // Decode user's public key
-    let user_pk = crypto::pk_from_hex(&req.public_key)
-        .context(StatusCode::BAD_REQUEST)
-        .context("decode request pubkey")?;
+    let (session_id, cipher, decrypted_query) = open_agent_query(&state, &req)?;
 
-    // Get session keypair
-    let (session_sk, session_id) = state
-        .get_session_keypair(&user_pk)
-        .ok_or(anyhow!("session not found"))
-        .context(StatusCode::UNAUTHORIZED)?;
+    info!(
+        session_id = %session_id,
+        public_key = req.public_key,
+        query_length = decrypted_query.len(),
+        use_llm_compliance = req.use_llm_compliance,
+        "processing crypto agent query"
+    );
 
-    // Create cipher for this session
-    let cipher = crypto::create_encrypt_key(&session_sk, &user_pk, session_id)
-        .context(StatusCode::INTERNAL_SERVER_ERROR)
-        .context("create encrypt key")?;
+    let (config, limits) = agent_config(&state, req.max_tokens, req.temperature);
+    let execution =
+        execute_agent_query(&state, config, session_id, decrypted_query, req.use_llm_compliance)
+            .await?;
 
-    let msg_nonce = crypto::derive_msg_nonce(session_id);
+    let disclosure = Disclosure::resolve(&state, &req);
+    let mut resp = build_agent_response(session_id, &cipher, execution, disclosure, limits)?;
+    if req.attest {
+        attest_agent_response(&state, &req.public_key, &mut resp)?;
+    }
+    state.execution_store.insert(
+        session_id,
+        &resp.execution_hash,
+        resp.quote.as_deref(),
+        resp.quote_compression,
+        resp.redacted,
+        &resp.execution,
+    );
 
-    // Decrypt the query
-    let decrypted_query = {
-        let encrypted_bytes = const_hex::decode(&req.encrypted_query)
-            .context(StatusCode::BAD_REQUEST)
-            .context("invalid query hex")?;
+    info!(
+        session_id = %session_id,
+        execution_time_ms = resp.execution_time_ms,
+        status = "success",
+        msg = "Agent query completed successfully"
+    );
 
-        debug!(
-            session_id = %session_id,
-            public_key = %req.public_key,
-            encrypted_len = encrypted_bytes.len(),
-            "attempting to decrypt query"
-        );
+    Ok(Json(resp))
+}
 
-        let decrypted = cipher
-            .decrypt(&msg_nonce, encrypted_bytes.as_slice())
-            .map_err(|e| anyhow!(e.to_string()))
-            .context(StatusCode::BAD_REQUEST)
-            .context("decrypt query")?;
+/// Query the crypto agent in plaintext, with the same compliance checks
+///
+/// Each query runs in a session of its own, so cross-turn policies see a single turn.
+#[tracing::instrument(skip(state, req), err)]
+async fn query_agent_plain(
+    State(state): State<HypervisorState>,
+    Json(req): Json<PlainAgentQueryRequest>,
+) -> Result<Json<PlainAgentQueryResponse>, HypervisorError> {
+    let query = accept_plaintext(&state, "/agent/query/plain", req.query, "query")?;
+
+    let (config, limits) = agent_config(&state, req.max_tokens, req.temperature);
+    let execution =
+        execute_agent_query(&state, config, Uuid::now_v7(), query, req.use_llm_compliance)
+            .await?;
+
+    Ok(Json(PlainAgentQueryResponse {
+        response: execution.final_response,
+        answerable: execution.answerable,
+        reason: execution.reason,
+        model: execution.model,
+        execution_time_ms: execution.execution_time_ms,
+        max_tokens: limits.max_tokens,
+        temperature: limits.temperature,
+    }))
+}
 
-        String::from_utf8(decrypted)
-            .context(StatusCode::BAD_REQUEST)
-            .context("query isn't valid UTF-8")?
-    };
+/// Query the crypto agent, streaming progress as server-sent events
+///
+/// Emits a `stream_started` event carrying the stream id, then `planning_started`,
+/// `thought`, `tool_approved`, `tool_rejected` and `tool_result` events while the agent
+/// runs, then a `final` event carrying the same payload as `/agent/query` (or an `error`
+/// event). Events whose content is derived from the query are sealed in order with a
+/// `StreamSealer` of the stream id, so a dropped or reordered event fails to open.
+#[tracing::instrument(skip(state, req), err)]
+async fn query_agent_stream(
+    State(state): State<HypervisorState>,
+    Json(req): Json<AgentQueryRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, HypervisorError> {
+    validate_agent_request(&req)?;
+
+    let (session_id, cipher, decrypted_query) = open_agent_query(&state, &req)?;
 
     info!(
         session_id = %session_id,
         public_key = req.public_key,
         query_length = decrypted_query.len(),
         use_llm_compliance = req.use_llm_compliance,
-        "processing crypto agent query"
+        "processing streaming crypto agent query"
     );
 
-    // Get OpenAI API key
     let api_key = std::env::var("OPENAI_API_KEY")
         .context("OPENAI_API_KEY not set")
         .context(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    // Execute agent with per-tool compliance checking
-    let agent = CryptoAgent::new()
-        .context("Failed to initialize agent")
-        .context(StatusCode::INTERNAL_SERVER_ERROR)?;
-    let checker = ComplianceChecker::default_crypto_policy();
-    
-    let execution = if req.use_llm_compliance {
-        agent
-            .execute_with_llm_compliance(&decrypted_query, session_id, &api_key, &checker)
-            .await
-            .context("agent execution failed")
-            .context(StatusCode::INTERNAL_SERVER_ERROR)?
-    } else {
-        agent
-            .execute_with_compliance(&decrypted_query, session_id, &api_key, &checker)
-            .await
-            .context("agent execution failed")
-            .context(StatusCode::INTERNAL_SERVER_ERROR)?
+    let permit = acquire_permit(&state)?;
+    let (config, limits) = agent_config(&state, req.max_tokens, req.temperature);
+    let policy_registry = state.policy_registry();
+    let checker = ComplianceChecker::from_registry(&policy_registry);
+    let history = check_conversation(&state, &checker, session_id, &decrypted_query)?;
+    let agent = CryptoAgent::with_tools(config, policy_registry.clone(), state.tool_registry())
+        .with_history(history);
+    let disclosure = Disclosure::resolve(&state, &req);
+    let execution_store = state.execution_store.clone();
+    let public_key = req.public_key.clone();
+
+    let stream_id = Uuid::now_v7();
+    let mut sealer = StreamSealer::new(&cipher, stream_id);
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    let started = json!({ "stream_id": stream_id });
+    let _ = event_tx.send(Ok(Event::default().event("stream_started").data(started.to_string())));
+    // The permit is held by the run, which outlives this handler
+    tokio::spawn(async move {
+        let _permit = permit;
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+
+        let run = agent.execute_with_progress(
+            &decrypted_query,
+            session_id,
+            &api_key,
+            &checker,
+            req.use_llm_compliance,
+            progress_tx,
+        );
+        let forward = async {
+            while let Some(event) = progress_rx.recv().await {
+                if matches!(event, AgentEvent::Thought(_)) && !disclosure.thoughts {
+                    continue;
+                }
+                let _ = event_tx.send(Ok(agent_event_to_sse(&mut sealer, &event)));
+            }
+        };
+
+        let result = tokio::select! {
+            (result, ()) = async { tokio::join!(run, forward) } => result,
+            _ = event_tx.closed() => {
+                warn!(session_id = %session_id, "client disconnected, aborted streaming agent run");
+                return;
+            }
+        };
+
+        let final_event = result
+            .map_err(HypervisorError::from)
+            .and_then(|mut execution| {
+                execution.sequence = state.next_execution_sequence();
+                build_agent_response(session_id, &cipher, execution, disclosure, limits)
+            })
+            .and_then(|mut resp| {
+                if req.attest {
+                    attest_agent_response(&state, &public_key, &mut resp)?;
+                }
+                Ok(resp)
+            })
+            .and_then(|resp| {
+                execution_store.insert(
+                    session_id,
+                    &resp.execution_hash,
+                    resp.quote.as_deref(),
+                    resp.quote_compression,
+                    resp.redacted,
+                    &resp.execution,
+                );
+                Event::default()
+                    .event("final")
+                    .json_data(resp)
+                    .context("serialize final event")
+                    .map_err(HypervisorError::from)
+            })
+            .unwrap_or_else(|e| {
+                info!(session_id = %session_id, error = %e, "Streaming agent query failed");
+                Event::default()
+                    .event("error")
+                    .data(json!({ "msg": e.to_string() }).to_string())
+            });
+
+        let _ = event_tx.send(Ok(final_event));
+    });
+
+    Ok(Sse::new(UnboundedReceiverStream::new(event_rx)).keep_alive(KeepAlive::default()))
+}
+
+/// Convert an agent progress event into an SSE event, sealing sensitive payloads as the
+/// stream's next chunk
+fn agent_event_to_sse(sealer: &mut StreamSealer, event: &AgentEvent) -> Event {
+    let payload = serde_json::to_vec(event).expect("agent event is serializable");
+    let data = match event {
+        AgentEvent::PlanningStarted | AgentEvent::ToolApproved { .. } => {
+            String::from_utf8(payload).expect("json is valid UTF-8")
+        }
+        AgentEvent::Thought(_) | AgentEvent::ToolRejected { .. } | AgentEvent::ToolResult(_) => {
+            match sealer.seal(&payload) {
+                Ok(sealed) => json!({ "encrypted_payload": const_hex::encode(sealed) }).to_string(),
+                Err(e) => json!({ "msg": format!("encrypt event: {e}") }).to_string(),
+            }
+        }
     };
 
+    Event::default().event(event.name()).data(data)
+}
+
+/// Query the crypto agent with verification and compliance check
+#[tracing::instrument(skip(state, req), err)]
+async fn verifiable_query_agent(
+    State(state): State<HypervisorState>,
+    Json(req): Json<AgentQueryRequest>,
+) -> Result<Json<VerifiableAgentQueryResponse>, HypervisorError> {
+    validate_agent_request(&req)?;
+
+    let (session_id, cipher, decrypted_query) = open_agent_query(&state, &req)?;
+    let disclosure = Disclosure::resolve(&state, &req);
+
+    info!(
+        session_id = %session_id,
+        public_key = req.public_key,
+        query_length = decrypted_query.len(),
+        use_llm_compliance = req.use_llm_compliance,
+        "processing verifiable crypto agent query"
+    );
+
+    let (config, limits) = agent_config(&state, req.max_tokens, req.temperature);
+    let mut execution =
+        execute_agent_query(&state, config, session_id, decrypted_query, req.use_llm_compliance)
+            .await?;
+
+    // Generate compliance summary for attestation
+    // (compliance already checked during execute_with_compliance)
+    let compliance = generate_compliance_summary(&execution);
+
     // Hash the execution
+    let results_tree = ToolResultsMerkleTree::build(&execution.tool_results);
     let execution_hash = hash_execution(&execution);
 
+    // Generate attestation quote
+    let SessionQuote {
+        quote,
+        quote_compression,
+        quote_signature,
+        quote_id,
+    } = quote_execution(&state, &req.public_key, session_id, &execution_hash)?;
+
     // Encrypt the response
-    let response_nonce = crypto::derive_msg_nonce(execution.final_response.as_bytes());
     let encrypted_response = {
-        let encrypted = cipher
-            .encrypt(&response_nonce, execution.final_response.as_bytes())
-            .map_err(|e| anyhow!(e.to_string()))
+        let sealed = crypto::seal(&cipher, execution.final_response.as_bytes())
             .context("encrypt response")
             .context(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        const_hex::encode(encrypted)
+        const_hex::encode(sealed)
     };
 
+    // Redact only after hashing, so the quote covers the full plan
+    let redacted = disclosure.apply(&mut execution);
+
     info!(
         session_id = %session_id,
         execution_time_ms = execution.execution_time_ms,
+        compliance = compliance.compliant,
         status = "success",
-        msg = "Agent query completed successfully"
+        msg = "Verifiable agent query completed successfully"
     );
 
-    Ok(Json(AgentQueryResponse {
+    let resp = VerifiableAgentQueryResponse {
         session_id,
         encrypted_response,
-        response_nonce: const_hex::encode(response_nonce),
+        answerable: execution.answerable,
+        reason: execution.reason.clone(),
         execution_time_ms: execution.execution_time_ms,
+        sequence: execution.sequence,
         execution_hash: const_hex::encode(execution_hash),
+        tool_results_root: const_hex::encode(results_tree.root()),
+        tool_result_proofs: results_tree.proofs(),
+        quote,
+        quote_signature,
+        quote_id,
+        quote_compression,
+        compliance,
+        redacted,
+        max_tokens: limits.max_tokens,
+        temperature: limits.temperature,
         execution,
-    }))
+    };
+    state.execution_store.insert(
+        session_id,
+        &resp.execution_hash,
+        Some(&resp.quote),
+        resp.quote_compression,
+        resp.redacted,
+        &resp.execution,
+    );
+
+    Ok(Json(resp))
 }
 
-/// Query the crypto agent with verification and compliance check
+/// Request to run one tool call directly, without LLM planning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentToolRequest {
+    /// Encrypted tool call (hex-encoded), `{"tool": <name>, "arguments": {...}}` as planned
+    /// by the agent
+    pub encrypted_call: String,
+    /// User's public key (hex-encoded compressed SECP256K1 public key)
+    pub public_key: String,
+    /// Attest the call and its result with a TEE quote (default: false)
+    #[serde(default)]
+    pub include_quote: bool,
+}
+
+/// Result of a direct tool call
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgentToolResponse {
+    /// Session ID
+    pub session_id: Uuid,
+    /// The call as executed, with its compliance quote: `ToolCall` JSON sealed with the
+    /// session key (hex-encoded), see `crypto::open`
+    pub encrypted_tool_call: String,
+    /// Result of the call: `ToolResult` JSON sealed with the session key (hex-encoded)
+    pub encrypted_tool_result: String,
+    /// Rules not evaluated; LLM rules are never run on this path
+    pub skipped_rules: Vec<SkippedRule>,
+    /// TEE attestation quote over the call and its result (hex-encoded), when requested
+    pub quote: Option<String>,
+    /// Session key's signature over the quote and session ID (hex-encoded)
+    pub quote_signature: Option<String>,
+    /// Compression of `quote` before hex encoding, see `QuoteCompression::decode`;
+    /// plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
+}
+
+/// Run one tool call with deterministic compliance only: a cheap, predictable path for
+/// SDKs that know which tool they need, with no OpenAI call
 #[tracing::instrument(skip(state, req), err)]
-async fn verifiable_query_agent(
+async fn call_agent_tool(
     State(state): State<HypervisorState>,
-    Json(req): Json<AgentQueryRequest>,
-) -> Result<Json<VerifiableAgentQueryResponse>, HypervisorError> {
+    Json(req): Json<AgentToolRequest>,
+) -> Result<Json<AgentToolResponse>, HypervisorError> {
+    Validation::default()
+        .ciphertext("encrypted_call", &req.encrypted_call)
+        .public_key("public_key", &req.public_key)
+        .finish()?;
+
+    let (session_id, cipher, call) =
+        open_session_message(&state, &req.public_key, &req.encrypted_call, "call")?;
+    let call: serde_json::Value = serde_json::from_str(&call)
+        .context("invalid call json")
+        .context(StatusCode::BAD_REQUEST)?;
+    let (Some(tool_name), Some(arguments)) = (call["tool"].as_str(), call.get("arguments")) else {
+        return Err(anyhow::Error::msg(StatusCode::BAD_REQUEST)
+            .context("call needs a \"tool\" name and \"arguments\"")
+            .into());
+    };
+
+    info!(session_id = %session_id, tool_name, "processing direct tool call");
+
+    let tools = state.tool_registry();
+    if tools.get_tool(tool_name).is_none() {
+        return Err(anyhow::Error::msg(StatusCode::NOT_FOUND)
+            .context(tools.missing_tool_reason(tool_name))
+            .into());
+    }
+
+    let policy_registry = state.policy_registry();
+    let agent = CryptoAgent::with_tools(state.config.agent.clone(), policy_registry.clone(), tools);
+    let checker = ComplianceChecker::from_registry(&policy_registry);
+
+    let (tool_call, tool_result, skipped_rules) =
+        agent.execute_tool_call(tool_name, session_id, arguments, &checker).await?;
+
+    let (quote, quote_signature, quote_compression) = if req.include_quote {
+        let report = ReportDataBuilder::new(AGENT_TOOL_DOMAIN)
+            .field(&tool_call.tool_name)
+            .field(&tool_call.arguments)
+            .field(hash_tool_result(&tool_result))
+            .build();
+        let quote = state
+            .tee_quote(report)
+            .context("get tool call quote")
+            .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+        let signature = bind_quote(&state, &req.public_key, session_id, &quote)?;
+        let compression = state.config.quote_compression;
+        let quote = compression
+            .encode(&quote)
+            .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+        (Some(quote), Some(signature), compression.field())
+    } else {
+        (None, None, None)
+    };
+
+    Ok(Json(AgentToolResponse {
+        session_id,
+        encrypted_tool_call: seal_json(&cipher, &tool_call)?,
+        encrypted_tool_result: seal_json(&cipher, &tool_result)?,
+        skipped_rules,
+        quote,
+        quote_signature,
+        quote_compression,
+    }))
+}
+
+/// Settings of the opt-in store of finished executions, see `GET /agent/execution/{hash}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionStoreConfig {
+    /// How long an execution can be fetched again, in seconds
+    #[serde(default = "default_store_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Maximum number of stored executions
+    #[serde(default = "default_store_capacity")]
+    pub capacity: usize,
+}
+
+fn default_store_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_store_capacity() -> usize {
+    100
+}
+
+/// Executions as returned to their session, keyed by execution hash (hex-encoded)
+///
+/// Executions are sensitive, so nothing is kept unless the store is configured.
+#[derive(Default)]
+pub(crate) struct ExecutionStore {
+    config: Option<ExecutionStoreConfig>,
+    entries: Mutex<HashMap<String, StoredExecution>>,
+}
+
+#[derive(Clone)]
+struct StoredExecution {
+    stored_at: Instant,
+    session_id: Uuid,
+    execution_hash: String,
+    quote: Option<String>,
+    quote_compression: Option<QuoteCompression>,
+    redacted: bool,
+    execution: AgentExecution,
+}
+
+impl ExecutionStore {
+    pub fn new(config: Option<ExecutionStoreConfig>) -> Self {
+        Self {
+            config,
+            entries: Mutex::default(),
+        }
+    }
+
+    fn insert(
+        &self,
+        session_id: Uuid,
+        execution_hash: &str,
+        quote: Option<&str>,
+        quote_compression: Option<QuoteCompression>,
+        redacted: bool,
+        execution: &AgentExecution,
+    ) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        if config.capacity == 0 {
+            return;
+        }
+
+        let ttl = Duration::from_secs(config.ttl_secs);
+        let mut entries = self.entries.lock().expect("execution store poisoned");
+        entries.retain(|_, entry| entry.stored_at.elapsed() < ttl);
+        if entries.len() >= config.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            execution_hash.to_string(),
+            StoredExecution {
+                stored_at: Instant::now(),
+                session_id,
+                execution_hash: execution_hash.to_string(),
+                quote: quote.map(ToString::to_string),
+                quote_compression,
+                redacted,
+                execution: execution.clone(),
+            },
+        );
+    }
+
+    /// The execution stored under `execution_hash`, if it belongs to `session_id`
+    fn get(&self, session_id: Uuid, execution_hash: &str) -> Option<StoredExecution> {
+        let ttl = Duration::from_secs(self.config.as_ref()?.ttl_secs);
+        let entries = self.entries.lock().expect("execution store poisoned");
+        entries
+            .get(&execution_hash.to_lowercase())
+            .filter(|entry| entry.stored_at.elapsed() < ttl && entry.session_id == session_id)
+            .cloned()
+    }
+}
+
+/// Retention of agent queries for the policies checked across turns
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationMemoryConfig {
+    /// How long a session's queries are kept after its last one, in seconds
+    #[serde(default = "default_conversation_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Latest queries kept per session
+    #[serde(default = "default_conversation_max_turns")]
+    pub max_turns: usize,
+    /// Maximum number of sessions remembered
+    #[serde(default = "default_conversation_capacity")]
+    pub capacity: usize,
+}
+
+fn default_conversation_ttl_secs() -> u64 {
+    1800
+}
+
+fn default_conversation_max_turns() -> usize {
+    8
+}
+
+fn default_conversation_capacity() -> usize {
+    1024
+}
+
+impl Default for ConversationMemoryConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_conversation_ttl_secs(),
+            max_turns: default_conversation_max_turns(),
+            capacity: default_conversation_capacity(),
+        }
+    }
+}
+
+/// Earlier agent queries of each session, checked together with the current one
+///
+/// Queries are sensitive, so they're only kept while a policy is checked across turns or
+/// the agent plans with earlier queries (`max_history_turns`), whose window `max_turns` also
+/// bounds. A rotated session starts a new conversation.
+#[derive(Default)]
+pub(crate) struct ConversationMemory {
+    config: ConversationMemoryConfig,
+    sessions: Mutex<HashMap<Uuid, Conversation>>,
+}
+
+struct Conversation {
+    updated_at: Instant,
+    turns: VecDeque<String>,
+}
+
+impl ConversationMemory {
+    pub fn new(config: ConversationMemoryConfig) -> Self {
+        Self {
+            config,
+            sessions: Mutex::default(),
+        }
+    }
+
+    /// Earlier queries of the session, oldest first
+    fn turns(&self, session_id: Uuid) -> Vec<String> {
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let sessions = self.sessions.lock().expect("conversation memory poisoned");
+        sessions
+            .get(&session_id)
+            .filter(|conversation| conversation.updated_at.elapsed() < ttl)
+            .map(|conversation| conversation.turns.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Remember a query of the session, forgetting its oldest beyond `max_turns`
+    fn record(&self, session_id: Uuid, query: &str) {
+        if self.config.capacity == 0 || self.config.max_turns == 0 {
+            return;
+        }
+
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let mut sessions = self.sessions.lock().expect("conversation memory poisoned");
+        sessions.retain(|_, conversation| conversation.updated_at.elapsed() < ttl);
+        if sessions.len() >= self.config.capacity && !sessions.contains_key(&session_id) {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, conversation)| conversation.updated_at)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                sessions.remove(&oldest);
+            }
+        }
+
+        let conversation = sessions.entry(session_id).or_insert_with(|| Conversation {
+            updated_at: Instant::now(),
+            turns: VecDeque::new(),
+        });
+        conversation.updated_at = Instant::now();
+        conversation.turns.push_back(query.to_string());
+        while conversation.turns.len() > self.config.max_turns {
+            conversation.turns.pop_front();
+        }
+    }
+}
+
+/// Check the query together with the session's earlier ones against the policies checked
+/// across turns, remembering it once they pass
+///
+/// Returns the earlier queries, oldest first, for planning. Nothing is kept when no policy
+/// is checked across turns and the agent plans without history.
+fn check_conversation(
+    state: &HypervisorState,
+    checker: &ComplianceChecker,
+    session_id: Uuid,
+    query: &str,
+) -> Result<Vec<String>, HypervisorError> {
+    let cross_turn = checker.has_cross_turn_policies();
+    if !cross_turn && state.config.agent.max_history_turns == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut turns = state.conversations.turns(session_id);
+    if cross_turn {
+        turns.push(query.to_string());
+        checker
+            .check_conversation(&turns)
+            .map_err(AgentError::Compliance)?;
+        turns.pop();
+    }
+    state.conversations.record(session_id, query);
+
+    Ok(turns)
+}
+
+/// Owner of a stored execution
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecutionLookup {
+    /// User's public key (hex-encoded compressed SECP256K1 public key)
+    pub public_key: String,
+}
+
+/// A previously returned execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredExecutionResponse {
+    /// Hash of the execution trace
+    pub execution_hash: String,
+    /// TEE attestation quote over the execution hash (verifiable queries only)
+    pub quote: Option<String>,
+    /// Compression of `quote` before hex encoding, see `QuoteCompression::decode`;
+    /// plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
+    /// Whether the execution was redacted when first returned
+    pub redacted: bool,
+    /// The execution as first returned to the session: `AgentExecution` JSON sealed with
+    /// the session key (hex-encoded), see `crypto::open`
+    pub encrypted_execution: String,
+}
+
+/// Fetch an execution previously returned to the caller's session
+///
+/// Unknown, expired and foreign executions are all reported as not found. The public key
+/// naming the session is no secret, so the execution is sealed with the session key.
+#[tracing::instrument(skip(state, lookup), err)]
+async fn get_execution(
+    State(state): State<HypervisorState>,
+    Path(execution_hash): Path<String>,
+    Query(lookup): Query<ExecutionLookup>,
+) -> Result<Json<StoredExecutionResponse>, HypervisorError> {
+    let user_pk = crypto::pk_from_hex(&lookup.public_key)
+        .context("decode request pubkey")
+        .context(StatusCode::BAD_REQUEST)?;
+    let (session_sk, session_id) = state
+        .clone()
+        .get_session_keypair(&user_pk)
+        .ok_or(anyhow!("session not found"))
+        .context(StatusCode::UNAUTHORIZED)?;
+
+    let stored = state
+        .execution_store
+        .get(session_id, &execution_hash)
+        .ok_or(anyhow!("execution not found"))
+        .context(StatusCode::NOT_FOUND)?;
+    let cipher = crypto::create_encrypt_key(&session_sk, &user_pk, session_id)?;
+
+    Ok(Json(StoredExecutionResponse {
+        execution_hash: stored.execution_hash,
+        quote: stored.quote,
+        quote_compression: stored.quote_compression,
+        redacted: stored.redacted,
+        encrypted_execution: seal_json(&cipher, &stored.execution)?,
+    }))
+}
+
+/// Run the agent with per-tool compliance checking on its own task
+///
+/// The task is aborted if the handler is dropped (axum drops it when the client
+/// disconnects), so no further OpenAI calls or compliance quotes are made for it.
+async fn execute_agent_query(
+    state: &HypervisorState,
+    config: CryptoAgentConfig,
+    session_id: Uuid,
+    query: String,
+    use_llm_compliance: bool,
+) -> Result<AgentExecution, HypervisorError> {
+    // Get OpenAI API key
+    let api_key = std::env::var("OPENAI_API_KEY")
+        .context("OPENAI_API_KEY not set")
+        .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let _permit = acquire_permit(state)?;
+    let policy_registry = state.policy_registry();
+    let checker = ComplianceChecker::from_registry(&policy_registry);
+    let history = check_conversation(state, &checker, session_id, &query)?;
+    let agent = CryptoAgent::with_tools(config, policy_registry.clone(), state.tool_registry())
+        .with_history(history);
+
+    let mut execution = run_until_disconnect(session_id, async move {
+        if use_llm_compliance {
+            agent
+                .execute_with_llm_compliance(&query, session_id, &api_key, &checker)
+                .await
+        } else {
+            agent
+                .execute_with_compliance(&query, session_id, &api_key, &checker)
+                .await
+        }
+    })
+    .await??;
+    execution.sequence = state.next_execution_sequence();
+
+    Ok(execution)
+}
+
+/// Agent work spawned for a request, aborted if dropped before it finished
+struct RequestTask<T> {
+    session_id: Uuid,
+    handle: JoinHandle<T>,
+}
+
+impl<T> Drop for RequestTask<T> {
+    fn drop(&mut self) {
+        if !self.handle.is_finished() {
+            self.handle.abort();
+            warn!(session_id = %self.session_id, "client disconnected, aborted agent run");
+        }
+    }
+}
+
+/// Spawn `work` and wait for it; dropping the returned future aborts the work
+async fn run_until_disconnect<T, F>(session_id: Uuid, work: F) -> Result<T, HypervisorError>
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let mut task = RequestTask {
+        session_id,
+        handle: tokio::spawn(work),
+    };
+
+    Ok((&mut task.handle)
+        .await
+        .context("agent task failed")
+        .context(StatusCode::INTERNAL_SERVER_ERROR)?)
+}
+
+/// Resolve the caller's session and decrypt the query
+/// Returns the session id, the session cipher and the plaintext query
+fn open_agent_query(
+    state: &HypervisorState,
+    req: &AgentQueryRequest,
+) -> Result<(Uuid, Aes256GcmSiv, String), HypervisorError> {
+    let (session_id, cipher, query) =
+        open_session_message(state, &req.public_key, &req.encrypted_query, "query")?;
+    state.config.prompt_denylist.check(&query)?;
+
+    Ok((session_id, cipher, query))
+}
+
+/// Resolve the session of `public_key` and decrypt a message encrypted for it,
+/// `what` naming the message in errors
+/// Returns the session id, the session cipher and the plaintext
+fn open_session_message(
+    state: &HypervisorState,
+    public_key: &str,
+    encrypted: &str,
+    what: &str,
+) -> Result<(Uuid, Aes256GcmSiv, String), HypervisorError> {
     // Decode user's public key
-    let user_pk = crypto::pk_from_hex(&req.public_key)
+    let user_pk = crypto::pk_from_hex(public_key)
         .context(StatusCode::BAD_REQUEST)
         .context("decode request pubkey")?;
 
     // Get session keypair
     let (session_sk, session_id) = state
+        .clone()
         .get_session_keypair(&user_pk)
         .ok_or(anyhow!("session not found"))
         .context(StatusCode::UNAUTHORIZED)?;
 
     // Create cipher for this session
-    let cipher = crypto::create_encrypt_key(&session_sk, &user_pk, session_id)
-        .context(StatusCode::INTERNAL_SERVER_ERROR)
-        .context("create encrypt key")?;
+    let cipher = crypto::create_encrypt_key(&session_sk, &user_pk, session_id)?;
 
     let msg_nonce = crypto::derive_msg_nonce(session_id);
 
-    // Decrypt the query
-    let decrypted_query = {
-        let encrypted_bytes = const_hex::decode(&req.encrypted_query)
+    // Decrypt the message
+    let decrypted = {
+        let encrypted_bytes = const_hex::decode(encrypted)
             .context(StatusCode::BAD_REQUEST)
-            .context("invalid query hex")?;
+            .context(format!("invalid {what} hex"))?;
+
+        debug!(
+            session_id = %session_id,
+            public_key = %public_key,
+            encrypted_len = encrypted_bytes.len(),
+            "attempting to decrypt {what}"
+        );
 
         let decrypted = cipher
             .decrypt(&msg_nonce, encrypted_bytes.as_slice())
             .map_err(|e| anyhow!(e.to_string()))
             .context(StatusCode::BAD_REQUEST)
-            .context("decrypt query")?;
+            .context(format!("decrypt {what}"))?;
 
-        String::from_utf8(decrypted)
-            .context(StatusCode::BAD_REQUEST)
-            .context("query isn't valid UTF-8")?
+        crypto::decode_plaintext(decrypted, state.config.max_prompt_bytes).map_err(|reason| {
+            anyhow::Error::msg(StatusCode::BAD_REQUEST).context(format!("{what} {reason}"))
+        })?
     };
 
-    info!(
-        session_id = %session_id,
-        public_key = req.public_key,
-        query_length = decrypted_query.len(),
-        use_llm_compliance = req.use_llm_compliance,
-        "processing verifiable crypto agent query"
-    );
-
-    // Get OpenAI API key
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .context("OPENAI_API_KEY not set")
-        .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((session_id, cipher, decrypted))
+}
 
-    // Execute agent with per-tool compliance checking
-    let agent = CryptoAgent::new()
-        .context("Failed to initialize agent")
+/// `value` as JSON, sealed with the session cipher and hex-encoded
+fn seal_json(cipher: &Aes256GcmSiv, value: &impl Serialize) -> Result<String, HypervisorError> {
+    let json = serde_json::to_vec(value).context("serialize sealed value")?;
+    let sealed = crypto::seal(cipher, &json)
+        .context("encrypt response")
         .context(StatusCode::INTERNAL_SERVER_ERROR)?;
-    let checker = ComplianceChecker::default_crypto_policy();
-    
-    let execution = if req.use_llm_compliance {
-        agent
-            .execute_with_llm_compliance(&decrypted_query, session_id, &api_key, &checker)
-            .await
-            .context("agent execution failed")
-            .context(StatusCode::INTERNAL_SERVER_ERROR)?
-    } else {
-        agent
-            .execute_with_compliance(&decrypted_query, session_id, &api_key, &checker)
-            .await
-            .context("agent execution failed")
-            .context(StatusCode::INTERNAL_SERVER_ERROR)?
-    };
 
-    // Generate compliance summary for attestation
-    // (compliance already checked during execute_with_compliance)
-    let compliance = generate_compliance_summary(&execution);
+    Ok(const_hex::encode(sealed))
+}
 
+/// Hash, prove and encrypt a finished execution into the `/agent/query` response
+/// The plan is redacted per `disclosure` after hashing
+fn build_agent_response(
+    session_id: Uuid,
+    cipher: &Aes256GcmSiv,
+    mut execution: AgentExecution,
+    disclosure: Disclosure,
+    limits: GenerationLimits,
+) -> Result<AgentQueryResponse, HypervisorError> {
     // Hash the execution
+    let results_tree = ToolResultsMerkleTree::build(&execution.tool_results);
     let execution_hash = hash_execution(&execution);
 
-    // Generate attestation quote
-    let quote = attest::get_quote(crate::utils::attest::generate_raw_report_from_hash(
-        execution_hash,
-    ))
-    .context("get agent query quote")
-    .context(StatusCode::INTERNAL_SERVER_ERROR)?;
-
     // Encrypt the response
-    let response_nonce = crypto::derive_msg_nonce(execution.final_response.as_bytes());
     let encrypted_response = {
-        let encrypted = cipher
-            .encrypt(&response_nonce, execution.final_response.as_bytes())
-            .map_err(|e| anyhow!(e.to_string()))
+        let sealed = crypto::seal(cipher, execution.final_response.as_bytes())
             .context("encrypt response")
             .context(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        const_hex::encode(encrypted)
+        const_hex::encode(sealed)
     };
 
-    info!(
-        session_id = %session_id,
-        execution_time_ms = execution.execution_time_ms,
-        compliance = compliance.compliant,
-        status = "success",
-        msg = "Verifiable agent query completed successfully"
-    );
+    let redacted = disclosure.apply(&mut execution);
 
-    Ok(Json(VerifiableAgentQueryResponse {
+    Ok(AgentQueryResponse {
         session_id,
         encrypted_response,
-        response_nonce: const_hex::encode(response_nonce),
+        answerable: execution.answerable,
+        reason: execution.reason.clone(),
         execution_time_ms: execution.execution_time_ms,
+        sequence: execution.sequence,
         execution_hash: const_hex::encode(execution_hash),
-        quote: const_hex::encode(quote.to_bytes()),
-        compliance,
+        tool_results_root: const_hex::encode(results_tree.root()),
+        tool_result_proofs: results_tree.proofs(),
+        redacted,
+        max_tokens: limits.max_tokens,
+        temperature: limits.temperature,
+        quote: None,
+        quote_signature: None,
+        quote_id: None,
+        quote_compression: None,
         execution,
-    }))
+    })
 }
 
-/// Validate agent request
-fn validate_agent_request(request: &AgentQueryRequest) -> Result<(), HypervisorError> {
-    let validate = || -> anyhow::Result<()> {
-        anyhow::ensure!(
-            !request.encrypted_query.trim().is_empty(),
-            "encrypted_query cannot be empty"
-        );
-
-        anyhow::ensure!(
-            !request.public_key.trim().is_empty(),
-            "public_key cannot be empty"
-        );
+/// Add the quote over the response's execution hash, for requests setting `attest`
+fn attest_agent_response(
+    state: &HypervisorState,
+    public_key: &str,
+    resp: &mut AgentQueryResponse,
+) -> Result<(), HypervisorError> {
+    let execution_hash = const_hex::decode(&resp.execution_hash)
+        .context("decode execution hash")
+        .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let quote = quote_execution(state, public_key, resp.session_id, &execution_hash)?;
+    resp.quote = Some(quote.quote);
+    resp.quote_signature = Some(quote.quote_signature);
+    resp.quote_id = Some(quote.quote_id);
+    resp.quote_compression = quote.quote_compression;
 
-        Ok(())
-    };
+    Ok(())
+}
 
-    validate().context(StatusCode::BAD_REQUEST)?;
+/// Quote over an execution hash and its session binding
+fn quote_execution(
+    state: &HypervisorState,
+    public_key: &str,
+    session_id: Uuid,
+    execution_hash: &[u8],
+) -> Result<SessionQuote, HypervisorError> {
+    let report = ReportDataBuilder::new(AGENT_DOMAIN)
+        .field(execution_hash)
+        .build();
+
+    session_quote(state, public_key, session_id, report, |report| {
+        state.tee_quote(report).context("get agent query quote")
+    })
+}
 
-    Ok(())
+/// Validate agent request
+fn validate_agent_request(request: &AgentQueryRequest) -> Result<(), HypervisorError> {
+    Validation::default()
+        .ciphertext("encrypted_query", &request.encrypted_query)
+        .public_key("public_key", &request.public_key)
+        .finish()
 }
 
 /// Generate a compliance summary for a completed execution
@@ -349,12 +1242,38 @@ fn generate_compliance_summary(execution: &AgentExecution) -> ComplianceResult {
         .filter(|result| !result.success)
         .collect();
 
-    let compliant = failed_tools.is_empty();
-    let reason = if compliant {
+    // Approved tools whose data the answer attributes neither to their source nor to the
+    // tool, which is what the system prompt asks to cite
+    let response = &execution.final_response;
+    let unattributed: Vec<String> = execution
+        .tool_results
+        .iter()
+        .filter(|result| result.success)
+        .filter_map(|result| serde_json::from_str::<ToolOutput>(&result.result).ok())
+        .filter(|output| {
+            !output.source.is_empty()
+                && !cites_source(response, &output.source)
+                && !cites_source(response, &output.tool)
+        })
+        .map(|output| format!("{} ({})", output.tool, output.source))
+        .collect();
+
+    let no_tools = execution.tool_calls.is_empty();
+    let compliant = failed_tools.is_empty() && unattributed.is_empty();
+    let reason = if no_tools {
+        "No tool calls were planned; answered without tool data".to_string()
+    } else if compliant {
         format!(
             "All {} tool calls passed per-tool compliance checks during execution",
             execution.tool_calls.len()
         )
+    } else if failed_tools.is_empty() {
+        format!(
+            "The answer doesn't attribute the data of {} of {} tool calls to its source: [{}]",
+            unattributed.len(),
+            execution.tool_calls.len(),
+            unattributed.join(", ")
+        )
     } else {
         let failed_ids: Vec<String> = failed_tools
             .iter()
@@ -371,21 +1290,32 @@ fn generate_compliance_summary(execution: &AgentExecution) -> ComplianceResult {
     ComplianceResult {
         compliant,
         reason,
+        no_tools,
         policy_hash: "per-tool-validation".to_string(),
         plan_hash: const_hex::encode(plan_hash.as_bytes()),
+        skipped_rules: execution.skipped_rules.clone(),
     }
 }
 
 /// Hash an agent execution for attestation
-fn hash_execution(execution: &AgentExecution) -> [u8; 32] {
+///
+/// The `execution_hash` of agent responses, bound into the quote of verifiable queries.
+pub fn hash_execution(execution: &AgentExecution) -> [u8; 32] {
     let mut hasher = blake3::Hasher::new();
 
-    // Hash session ID
+    // Hash session ID and the execution's sequence number
     hasher.update(execution.session_id.as_bytes());
+    hasher.update(&execution.sequence.to_le_bytes());
 
     // Hash plan
     hasher.update(execution.plan.system_prompt.as_bytes());
     hasher.update(execution.plan.user_query.as_bytes());
+    // Length-prefixed, so turns can't be shifted into each other
+    for turn in &execution.plan.history {
+        hasher.update(b"history");
+        hasher.update(&(turn.len() as u64).to_le_bytes());
+        hasher.update(turn.as_bytes());
+    }
 
     for step in &execution.plan.thought_process {
         hasher.update(step.content.as_bytes());
@@ -396,25 +1326,781 @@ fn hash_execution(execution: &AgentExecution) -> [u8; 32] {
         hasher.update(call.id.as_bytes());
         hasher.update(call.tool_name.as_bytes());
         hasher.update(call.arguments.as_bytes());
+        // Steps start at 1, so 0 stands for a call that follows no thought
+        hasher.update(&(call.thought_step.unwrap_or(0) as u64).to_le_bytes());
     }
 
-    // Hash tool results
-    for result in &execution.tool_results {
-        hasher.update(result.call_id.as_bytes());
-        hasher.update(&[result.success as u8]);
-        hasher.update(result.result.as_bytes());
-    }
+    // Hash tool results via their Merkle root, so single results can be proven
+    hasher.update(&ToolResultsMerkleTree::build(&execution.tool_results).root());
 
-    // Hash final response
+    // Hash final response and the model that wrote it
     hasher.update(execution.final_response.as_bytes());
+    hasher.update(execution.model.as_bytes());
 
     hasher.finalize().into()
 }
 
+/// Check that `expected` (hex) is the `execution_hash` of `execution`
+///
+/// Redacted executions don't match, since the hash covers the removed fields.
+pub fn verify_execution_hash(execution: &AgentExecution, expected: &str) -> bool {
+    const_hex::decode(expected).is_ok_and(|expected| expected == hash_execution(execution))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use axum::http::StatusCode;
+
     use super::*;
-    use crate::{api::RouterRegister, types::SessionKeyPairs, utils::crypto};
+    use crate::{
+        agent::{types::ThoughtStep, AgentPlan, ToolCall},
+        api::RouterRegister,
+        client::{open_stream_event, StreamOpener},
+        test_utils::{chat_completion, data_dir, mock_attest_quote, serve, MockOpenAI},
+        types::SessionKeyPairs,
+        utils::crypto,
+    };
+
+    #[test]
+    fn test_conversation_memory_rejects_spread_request() {
+        let mut config = crate::Config::default();
+        config.agent.cross_turn_policies = vec!["L3".to_string()];
+        config.conversation_memory.max_turns = 2;
+        let state = HypervisorState::new(config).unwrap();
+        let checker = ComplianceChecker::from_registry(&state.policy_registry());
+        let (session, other) = (Uuid::now_v7(), Uuid::now_v7());
+
+        check_conversation(&state, &checker, session, "Check the inflows of this wallet").unwrap();
+        check_conversation(&state, &checker, other, "What is the price of BTC?").unwrap();
+        let err = check_conversation(&state, &checker, session, "belongs to whom?").unwrap_err();
+        assert!(err.to_string().contains("'L3'"), "{err}");
+        // Rejected turns aren't remembered, and other sessions are unaffected
+        assert_eq!(state.conversations.turns(session), ["Check the inflows of this wallet"]);
+        check_conversation(&state, &checker, other, "belongs to whom?").unwrap();
+
+        // Only the latest `max_turns` queries are kept
+        check_conversation(&state, &checker, session, "And the price of ETH?").unwrap();
+        check_conversation(&state, &checker, session, "belongs to whom?").unwrap();
+        assert_eq!(state.conversations.turns(session).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_supported_chains() {
+        let mut state = HypervisorState::default();
+        state.config.agent.supported_chains = vec!["Ethereum".to_string(), "solana".to_string()];
+
+        let server =
+            axum_test::TestServer::new(Router::new().register_api(api_register).with_state(state))
+                .unwrap();
+
+        let response = server.get("/agent/chains").await;
+        response.assert_status_ok();
+        assert_eq!(
+            response.json::<SupportedChainsResponse>().chains,
+            ["ethereum", "solana"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_aborts_work() {
+        let started = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let (s, f) = (started.clone(), finished.clone());
+        let app = Router::new().route(
+            "/slow",
+            get(move || {
+                let (started, finished) = (s.clone(), f.clone());
+                async move {
+                    run_until_disconnect(Uuid::now_v7(), async move {
+                        started.store(true, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(300)).await;
+                        finished.store(true, Ordering::SeqCst);
+                    })
+                    .await
+                }
+            }),
+        );
+        let base_url = serve(app).await;
+
+        // The client gives up long before the work would finish
+        let err = reqwest::Client::new()
+            .get(format!("{base_url}/slow"))
+            .timeout(Duration::from_millis(100))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout());
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(started.load(Ordering::SeqCst));
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+
+    fn sample_execution() -> AgentExecution {
+        AgentExecution {
+            session_id: Uuid::now_v7(),
+            plan: AgentPlan {
+                system_prompt: "internal system prompt".to_string(),
+                user_query: "What is the price of BTC?".to_string(),
+                history: Vec::new(),
+                thought_process: vec![ThoughtStep {
+                    step: 1,
+                    content: "I need the current BTC price".to_string(),
+                    timestamp: std::time::SystemTime::now(),
+                }],
+                intended_tool_calls: vec![],
+            },
+            tool_calls: vec![],
+            tool_results: vec![],
+            final_response: "BTC trades at $67,500.".to_string(),
+            answerable: true,
+            reason: None,
+            model: "gpt-4o".to_string(),
+            execution_time_ms: 1,
+            sequence: 1,
+            skipped_rules: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_verify_execution_hash() {
+        let mut execution = sample_execution();
+        let expected = const_hex::encode(hash_execution(&execution));
+
+        assert!(verify_execution_hash(&execution, &expected));
+        assert!(verify_execution_hash(&execution, &expected.to_uppercase()));
+        assert!(!verify_execution_hash(&execution, "not hex"));
+
+        execution.final_response = "BTC trades at $1.".to_string();
+        assert!(!verify_execution_hash(&execution, &expected));
+    }
+
+    #[test]
+    fn test_compliance_summary_without_tools() {
+        let summary = generate_compliance_summary(&sample_execution());
+        assert!(summary.compliant);
+        assert!(summary.no_tools);
+        assert!(summary.reason.starts_with("No tool calls were planned"), "{}", summary.reason);
+    }
+
+    #[test]
+    fn test_compliance_summary_flags_unattributed_tool_data() {
+        use crate::agent::SystemClock;
+
+        let call = ToolCall {
+            id: Uuid::now_v7(),
+            tool_name: "PriceFeedTool".to_string(),
+            arguments: r#"{"symbol": "BTC"}"#.to_string(),
+            timestamp: std::time::SystemTime::now(),
+            compliance_quote: None,
+            thought_step: Some(1),
+        };
+        let output = ToolOutput::new(
+            "PriceFeedTool",
+            "Market Data Feed",
+            json!({ "symbol": "BTC", "price_usd": 67500.5 }),
+            &SystemClock,
+        );
+        let mut execution = sample_execution();
+        execution.tool_results = vec![ToolResult {
+            call_id: call.id,
+            success: true,
+            result: output.to_json(),
+            error: None,
+            quote_verified: true,
+            compliance_quote: None,
+            result_hash: None,
+            injection_markers: vec![],
+            would_reject: false,
+            report_only_violations: Vec::new(),
+        }];
+        execution.tool_calls = vec![call];
+
+        // The tool succeeded, but the answer doesn't say where the price comes from
+        let summary = generate_compliance_summary(&execution);
+        assert!(!summary.compliant);
+        assert!(!summary.no_tools);
+        assert!(
+            summary.reason.contains("1 of 1 tool calls")
+                && summary.reason.contains("PriceFeedTool (Market Data Feed)"),
+            "{}",
+            summary.reason
+        );
+
+        execution.final_response =
+            "According to the market data feed, BTC trades at $67,500.50.".to_string();
+        let summary = generate_compliance_summary(&execution);
+        assert!(summary.compliant, "{}", summary.reason);
+
+        // Naming the tool, as the system prompt asks, cites it too
+        execution.final_response = "BTC trades at $67,500.50 (source: PriceFeedTool).".to_string();
+        let summary = generate_compliance_summary(&execution);
+        assert!(summary.compliant, "{}", summary.reason);
+    }
+
+    #[test]
+    fn test_redaction_keeps_execution_hash() {
+        let limits = crate::Config::default().generation_limits(100, 0.0);
+        let execution = sample_execution();
+        let full_hash = const_hex::encode(hash_execution(&execution));
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let cipher = crypto::create_encrypt_key(&sk, sk.verifying_key(), execution.session_id)
+            .unwrap();
+
+        let disclosure = Disclosure {
+            thoughts: false,
+            system_prompt: false,
+            tool_results: true,
+        };
+        let resp =
+            build_agent_response(execution.session_id, &cipher, execution, disclosure, limits)
+                .unwrap();
+
+        assert!(resp.redacted);
+        assert!(resp.execution.plan.thought_process.is_empty());
+        assert!(resp.execution.plan.system_prompt.is_empty());
+        assert_eq!(resp.execution_hash, full_hash);
+
+        // Nothing is removed when everything is disclosed
+        let disclosure = Disclosure {
+            thoughts: true,
+            system_prompt: true,
+            tool_results: true,
+        };
+        let execution = sample_execution();
+        let resp =
+            build_agent_response(execution.session_id, &cipher, execution, disclosure, limits)
+                .unwrap();
+        assert!(!resp.redacted);
+        assert_eq!(resp.execution.plan.thought_process.len(), 1);
+    }
+
+    #[test]
+    fn test_requests_only_narrow_disclosure() {
+        let mut config = crate::Config::default();
+        config.agent.include_thoughts = false;
+        let state = HypervisorState::new(config).unwrap();
+        let req = |include: Option<bool>| AgentQueryRequest {
+            encrypted_query: String::new(),
+            public_key: String::new(),
+            use_llm_compliance: false,
+            include_thoughts: include,
+            include_system_prompt: include,
+            compact: false,
+            max_tokens: None,
+            temperature: None,
+            attest: false,
+        };
+
+        let disclosure = Disclosure::resolve(&state, &req(Some(true)));
+        assert!(!disclosure.thoughts);
+        assert!(disclosure.system_prompt);
+
+        let disclosure = Disclosure::resolve(&state, &req(Some(false)));
+        assert!(!disclosure.thoughts);
+        assert!(!disclosure.system_prompt);
+    }
+
+    #[test]
+    fn test_compact_response_omits_tool_results() {
+        let mut execution = sample_execution();
+        execution.tool_results = vec![
+            ToolResult {
+                call_id: Uuid::now_v7(),
+                success: true,
+                result: r#"{"symbol":"BTC","price_usd":67500.0}"#.to_string(),
+                error: None,
+                quote_verified: true,
+                compliance_quote: None,
+                result_hash: None,
+                injection_markers: Vec::new(),
+                would_reject: false,
+                report_only_violations: Vec::new(),
+            },
+            ToolResult {
+                call_id: Uuid::now_v7(),
+                success: false,
+                result: String::new(),
+                error: Some("Policy compliance failed: L2".to_string()),
+                quote_verified: false,
+                compliance_quote: None,
+                result_hash: None,
+                injection_markers: Vec::new(),
+                would_reject: false,
+                report_only_violations: Vec::new(),
+            },
+        ];
+        let full = execution.clone();
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let cipher = crypto::create_encrypt_key(&sk, sk.verifying_key(), execution.session_id)
+            .unwrap();
+        let disclosure = Disclosure {
+            thoughts: true,
+            system_prompt: true,
+            tool_results: false,
+        };
+        let limits = crate::Config::default().generation_limits(100, 0.0);
+        let resp =
+            build_agent_response(execution.session_id, &cipher, execution, disclosure, limits)
+                .unwrap();
+
+        assert!(resp.redacted);
+        assert_eq!(resp.execution_hash, const_hex::encode(hash_execution(&full)));
+        for (compact, full) in resp.execution.tool_results.iter().zip(&full.tool_results) {
+            assert!(compact.result.is_empty());
+            assert_eq!(compact.call_id, full.call_id);
+            assert_eq!(compact.success, full.success);
+            assert_eq!(compact.error, full.error);
+            assert_eq!(
+                compact.result_hash,
+                Some(const_hex::encode(crate::agent::merkle::hash_tool_result(full)))
+            );
+        }
+    }
+
+    /// Agent backend answering planning requests with `plan` and the others with `answer`
+    async fn agent_backend(plan: &'static str, answer: &'static str) -> MockOpenAI {
+        MockOpenAI::spawn(move |body| {
+            let system = body["messages"][0]["content"].as_str().unwrap_or_default();
+            let content = match system.starts_with("You are a planning assistant") {
+                true => plan,
+                false => answer,
+            };
+            (StatusCode::OK, chat_completion(content))
+        })
+        .await
+    }
+
+    /// Server answering the agent routes from a mock backend, and a session to query it
+    struct AgentFixture {
+        server: axum_test::TestServer,
+        session_key_pairs: SessionKeyPairs,
+        cipher: aes_gcm_siv::Aes256GcmSiv,
+        user_pk: k256::ecdsa::VerifyingKey,
+        session_pk: k256::ecdsa::VerifyingKey,
+        session_id: Uuid,
+        /// Encrypted query for the session
+        req: AgentQueryRequest,
+    }
+
+    impl AgentFixture {
+        /// `plaintext` encrypted for the session (hex-encoded), as requests carry it
+        fn encrypt(&self, plaintext: &[u8]) -> String {
+            let nonce = crypto::derive_msg_nonce(self.session_id);
+            const_hex::encode(self.cipher.encrypt(&nonce, plaintext).unwrap())
+        }
+    }
+
+    /// `AgentFixture` with an agent on the crate's tool data calling `backend`
+    async fn agent_fixture(
+        config: crate::Config,
+        backend: &MockOpenAI,
+        query: &[u8],
+    ) -> AgentFixture {
+        agent_fixture_on(agent_state(config, backend), query)
+    }
+
+    /// State of an agent on the crate's tool data calling `backend`
+    fn agent_state(mut config: crate::Config, backend: &MockOpenAI) -> HypervisorState {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        config.agent.api_base = backend.base_url.clone();
+        config.agent.data_dir = data_dir();
+        HypervisorState::new(config).unwrap()
+    }
+
+    /// `AgentFixture` on a server with `state`
+    fn agent_fixture_on(mut state: HypervisorState, query: &[u8]) -> AgentFixture {
+        let session_key_pairs = SessionKeyPairs::default();
+        state.set_session_key_pairs(session_key_pairs.clone());
+        let server =
+            axum_test::TestServer::new(Router::new().register_api(api_register).with_state(state))
+                .unwrap();
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let user_pk = *sk.verifying_key();
+        let (session_pk, session_id) = session_key_pairs.clone().create(&user_pk);
+        let cipher = crypto::create_encrypt_key(&sk, &session_pk, session_id).unwrap();
+        let mut fixture = AgentFixture {
+            server,
+            session_key_pairs,
+            cipher,
+            user_pk,
+            session_pk,
+            session_id,
+            req: AgentQueryRequest {
+                encrypted_query: String::new(),
+                public_key: crypto::pk_to_hex(&user_pk),
+                use_llm_compliance: false,
+                include_thoughts: None,
+                include_system_prompt: None,
+                compact: false,
+                max_tokens: None,
+                temperature: None,
+                attest: false,
+            },
+        };
+        fixture.req.encrypted_query = fixture.encrypt(query);
+
+        fixture
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stored_execution() {
+        let backend = agent_backend(
+            r#"THOUGHT: I need the current BTC price
+TOOL_CALL: {"tool": "PriceFeedTool", "arguments": {"symbol": "BTC"}}"#,
+            "According to PriceFeedTool, BTC trades at $67,500.",
+        )
+        .await;
+        let config = crate::Config {
+            execution_store: Some(ExecutionStoreConfig {
+                ttl_secs: 60,
+                capacity: 10,
+            }),
+            ..Default::default()
+        };
+        let AgentFixture {
+            server,
+            session_key_pairs,
+            cipher,
+            req,
+            ..
+        } = agent_fixture(config, &backend, b"What is the price of BTC?").await;
+
+        let response = server.post("/agent/query").json(&req).await;
+        response.assert_status_ok();
+        let result: AgentQueryResponse = response.json();
+
+        let response = server
+            .get(&format!("/agent/execution/{}", result.execution_hash))
+            .add_query_param("public_key", &req.public_key)
+            .await;
+        response.assert_status_ok();
+        let stored: StoredExecutionResponse = response.json();
+        assert_eq!(stored.execution_hash, result.execution_hash);
+        // Only the session can read it, the public key naming it being no secret
+        let sealed = const_hex::decode(&stored.encrypted_execution).unwrap();
+        let execution: AgentExecution =
+            serde_json::from_slice(&crypto::open(&cipher, &sealed).unwrap()).unwrap();
+        assert_eq!(const_hex::encode(hash_execution(&execution)), result.execution_hash);
+
+        // Another session can't fetch it
+        let other_sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        session_key_pairs.create(other_sk.verifying_key());
+        server
+            .get(&format!("/agent/execution/{}", result.execution_hash))
+            .add_query_param("public_key", crypto::pk_to_hex(other_sk.verifying_key()))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_direct_tool_call_skips_llm() {
+        let backend = MockOpenAI::spawn(|_| (StatusCode::INTERNAL_SERVER_ERROR, json!({}))).await;
+        let fixture = agent_fixture(crate::Config::default(), &backend, b"").await;
+        let call = |call: serde_json::Value| {
+            json!({
+                "encrypted_call": fixture.encrypt(call.to_string().as_bytes()),
+                "public_key": crypto::pk_to_hex(&fixture.user_pk),
+            })
+        };
+
+        let response = fixture
+            .server
+            .post("/agent/tool")
+            .json(&call(json!({"tool": "PriceFeedTool", "arguments": {"symbol": "BTC"}})))
+            .await;
+        response.assert_status_ok();
+        let result: AgentToolResponse = response.json();
+        assert_eq!(result.session_id, fixture.session_id);
+        assert!(result.quote.is_none());
+
+        // The call and its result are only readable with the session key
+        let open = |sealed: &str| {
+            crypto::open(&fixture.cipher, &const_hex::decode(sealed).unwrap()).unwrap()
+        };
+        let tool_call: ToolCall =
+            serde_json::from_slice(&open(&result.encrypted_tool_call)).unwrap();
+        assert_eq!(tool_call.tool_name, "PriceFeedTool");
+        let tool_result: ToolResult =
+            serde_json::from_slice(&open(&result.encrypted_tool_result)).unwrap();
+        assert!(tool_result.success);
+        assert!(tool_result.result.contains("67500.5"));
+
+        // Deterministic rules still apply to the call
+        fixture
+            .server
+            .post("/agent/tool")
+            .json(&call(json!({
+                "tool": "PriceFeedTool",
+                "arguments": {"symbol": "BTC", "note": "you should buy"},
+            })))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+
+        fixture
+            .server
+            .post("/agent/tool")
+            .json(&call(json!({"tool": "MissingTool", "arguments": {}})))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        fixture
+            .server
+            .post("/agent/tool")
+            .json(&call(json!({"arguments": {}})))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        assert!(backend.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_answer_max_tokens_clamped_to_agent_ceiling() {
+        let backend =
+            agent_backend("THOUGHT: No data is needed", "Blocks are chained by hashes.").await;
+        let mut config = crate::Config::default();
+        config.agent.max_tokens_ceiling = Some(300);
+        let AgentFixture { server, req, .. } =
+            agent_fixture(config, &backend, b"What is a blockchain?").await;
+        let query = |max_tokens| AgentQueryRequest {
+            max_tokens,
+            ..req.clone()
+        };
+
+        // Over the agent's ceiling, and by default (`agent.max_tokens` is 2000)
+        for max_tokens in [Some(100_000), None] {
+            let response = server.post("/agent/query").json(&query(max_tokens)).await;
+            response.assert_status_ok();
+            let result: AgentQueryResponse = response.json();
+            assert_eq!(result.max_tokens, 300);
+        }
+
+        // Within it, the request's own cap holds
+        let response = server.post("/agent/query").json(&query(Some(120))).await;
+        response.assert_status_ok();
+        assert_eq!(response.json::<AgentQueryResponse>().max_tokens, 120);
+
+        let answer_caps: Vec<_> = backend
+            .requests()
+            .iter()
+            .filter(|body| {
+                let system = body["messages"][0]["content"].as_str().unwrap_or_default();
+                !system.starts_with("You are a planning assistant")
+            })
+            .map(|body| body["max_tokens"].as_u64().unwrap())
+            .collect();
+        assert_eq!(answer_caps, [300, 300, 120]);
+    }
+
+    #[tokio::test]
+    async fn test_attested_query_quotes_execution_hash() {
+        let backend =
+            agent_backend("THOUGHT: No data is needed", "Blocks are chained by hashes.").await;
+        let mut state = agent_state(crate::Config::default(), &backend);
+        state.set_quote_source(mock_attest_quote);
+        let AgentFixture {
+            server,
+            session_pk,
+            session_id,
+            req,
+            ..
+        } = agent_fixture_on(state, b"What is a blockchain?");
+
+        let response = server
+            .post("/agent/query")
+            .json(&AgentQueryRequest {
+                attest: true,
+                ..req
+            })
+            .await;
+        response.assert_status_ok();
+        let result: AgentQueryResponse = response.json();
+
+        // The quote is over the execution hash and bound to the caller's session
+        let quote =
+            QuoteCompression::decode(result.quote_compression, &result.quote.unwrap()).unwrap();
+        let report_data = attest::types::Quote::from_bytes(&quote).unwrap().report_data();
+        let expected = ReportDataBuilder::new(AGENT_DOMAIN)
+            .field(const_hex::decode(&result.execution_hash).unwrap())
+            .build();
+        assert_eq!(report_data, expected.to_bytes());
+        assert!(crypto::verify_quote_binding(
+            &session_pk,
+            &quote,
+            session_id,
+            &result.quote_signature.unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_planning_drops_queries_beyond_history_window() {
+        let backend =
+            agent_backend("THOUGHT: No data is needed", "Blocks are chained by hashes.").await;
+        let mut config = crate::Config::default();
+        config.agent.max_history_turns = 1;
+        let fixture = agent_fixture(config, &backend, b"What is a blockchain?").await;
+
+        let mut result = None;
+        for query in ["What is a blockchain?", "What is a block?", "What is a hash?"] {
+            let response = fixture
+                .server
+                .post("/agent/query")
+                .json(&AgentQueryRequest {
+                    encrypted_query: fixture.encrypt(query.as_bytes()),
+                    ..fixture.req.clone()
+                })
+                .await;
+            response.assert_status_ok();
+            result = Some(response.json::<AgentQueryResponse>());
+        }
+        let result = result.unwrap();
+
+        // Only the latest earlier query is planned with, and attested in the plan
+        let planning = backend
+            .requests()
+            .into_iter()
+            .rev()
+            .find(|body| {
+                let system = body["messages"][0]["content"].as_str().unwrap_or_default();
+                system.starts_with("You are a planning assistant")
+            })
+            .unwrap();
+        let prompt = planning["messages"][1]["content"].as_str().unwrap();
+        assert!(prompt.contains("- What is a block?"));
+        assert!(!prompt.contains("What is a blockchain?"));
+        assert_eq!(result.execution.plan.history, ["What is a block?"]);
+        assert!(verify_execution_hash(&result.execution, &result.execution_hash));
+    }
+
+    #[tokio::test]
+    async fn test_reports_every_invalid_field() {
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .with_state(HypervisorState::default()),
+        )
+        .unwrap();
+        let req = AgentQueryRequest {
+            encrypted_query: " ".to_string(),
+            public_key: "02abc".to_string(),
+            use_llm_compliance: false,
+            include_thoughts: None,
+            include_system_prompt: None,
+            compact: false,
+            max_tokens: None,
+            temperature: None,
+            attest: false,
+        };
+
+        for route in ["/agent/query", "/agent/query/stream", "/verifiable/agent/query"] {
+            let response = server.post(route).json(&req).expect_failure().await;
+            response.assert_status(StatusCode::BAD_REQUEST);
+            assert_eq!(
+                response.json::<serde_json::Value>()["errors"],
+                json!([
+                    { "field": "encrypted_query", "issue": "cannot be empty" },
+                    { "field": "public_key", "issue": "isn't valid hex" },
+                ]),
+                "{route}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_open_in_order() {
+        let backend = agent_backend(
+            r#"THOUGHT: I need the current BTC price
+TOOL_CALL: {"tool": "PriceFeedTool", "arguments": {"symbol": "BTC"}}
+THOUGHT: I also need the market sentiment for BTC
+TOOL_CALL: {"tool": "SentimentTool", "arguments": {"symbol": "BTC", "timeframe": "24h"}}"#,
+            "According to PriceFeedTool, BTC trades at $67,500.",
+        )
+        .await;
+        let fixture =
+            agent_fixture(crate::Config::default(), &backend, b"How is BTC doing?").await;
+
+        let response = fixture.server.post("/agent/query/stream").json(&fixture.req).await;
+        response.assert_status_ok();
+        let text = response.text();
+        let events: Vec<(&str, serde_json::Value)> = text
+            .split("\n\n")
+            .filter_map(|block| {
+                let name = block.lines().find_map(|line| line.strip_prefix("event: "))?;
+                let data = block.lines().find_map(|line| line.strip_prefix("data: "))?;
+                Some((name, serde_json::from_str(data).unwrap()))
+            })
+            .collect();
+
+        assert_eq!(events[0].0, "stream_started");
+        assert_eq!(events.last().unwrap().0, "final");
+        let stream_id: Uuid = serde_json::from_value(events[0].1["stream_id"].clone()).unwrap();
+        let sealed: Vec<_> = events
+            .iter()
+            .filter_map(|(_, data)| data["encrypted_payload"].as_str())
+            .collect();
+        assert!(sealed.len() >= 2);
+        // Each event is sealed under its own counter
+        let distinct: std::collections::HashSet<_> = sealed.iter().collect();
+        assert_eq!(distinct.len(), sealed.len());
+
+        // A dropped event is detected
+        let mut opener = StreamOpener::new(&fixture.cipher, stream_id);
+        assert!(open_stream_event(&mut opener, sealed[1]).is_err());
+
+        let mut opener = StreamOpener::new(&fixture.cipher, stream_id);
+        let opened: Vec<AgentEvent> = sealed
+            .iter()
+            .map(|payload| {
+                serde_json::from_slice(&open_stream_event(&mut opener, payload).unwrap()).unwrap()
+            })
+            .collect();
+        let results = opened.iter().filter(|event| match event {
+            AgentEvent::ToolResult(result) => result.success,
+            _ => false,
+        });
+        assert_eq!(results.count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_identical_queries_hash_apart() {
+        let backend =
+            agent_backend("THOUGHT: No data is needed", "Blocks are chained by hashes.").await;
+        let AgentFixture { server, req, .. } =
+            agent_fixture(crate::Config::default(), &backend, b"What is a blockchain?").await;
+
+        // Sent together, the same query in the same session takes distinct numbers
+        let (first, second) = tokio::join!(
+            server.post("/agent/query").json(&req),
+            server.post("/agent/query").json(&req)
+        );
+        let mut results = [first, second].map(|response| response.json::<AgentQueryResponse>());
+        results.sort_by_key(|result| result.sequence);
+        let [first, second] = results;
+
+        assert_eq!((first.sequence, second.sequence), (1, 2));
+        assert_eq!(first.execution.sequence, 1);
+        assert_eq!(first.execution.plan.user_query, second.execution.plan.user_query);
+        assert_eq!(first.execution.final_response, second.execution.final_response);
+        assert_ne!(first.execution_hash, second.execution_hash);
+        for result in [&first, &second] {
+            assert!(verify_execution_hash(&result.execution, &result.execution_hash));
+        }
+    }
 
     #[tokio::test]
     #[ignore] // Requires OPENAI_API_KEY
@@ -444,6 +2130,12 @@ mod tests {
                 encrypted_query: const_hex::encode(&encrypted_query),
                 public_key: crypto::pk_to_hex(user_pk),
                 use_llm_compliance: false,
+                include_thoughts: None,
+                include_system_prompt: None,
+                compact: false,
+                max_tokens: None,
+                temperature: None,
+                attest: false,
             })
             .await;
 
@@ -451,18 +2143,11 @@ mod tests {
 
         let result: AgentQueryResponse = response.json();
 
-        let response_nonce = *aes_gcm_siv::Nonce::from_slice(
-            &const_hex::decode(result.response_nonce).unwrap(),
-        );
-
-        let decrypted_response = cipher
-            .decrypt(
-                &response_nonce,
-                const_hex::decode(&result.encrypted_response)
-                    .unwrap()
-                    .as_slice(),
-            )
-            .unwrap();
+        let decrypted_response = crypto::open(
+            &cipher,
+            &const_hex::decode(&result.encrypted_response).unwrap(),
+        )
+        .unwrap();
 
         let response_text = String::from_utf8(decrypted_response).unwrap();
         println!("Agent Response: {}", response_text);
@@ -1,12 +1,17 @@
 use anyhow::Context;
-use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use attest::types::RawReport;
+use axum::{extract::State, http::StatusCode, routing::post, Router};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    api::{extract::Json, quote::QuoteCompression, validation::Validation},
     error::HypervisorError,
     types::HypervisorState,
-    utils::{attest::generate_raw_report, crypto},
+    utils::{
+        attest::{ReportDataBuilder, ReportDataError, KEYPAIR_DOMAIN, MAX_NONCE_LEN},
+        crypto,
+    },
 };
 
 pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
@@ -16,6 +21,8 @@ pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<Hypervisor
             "/verifiable/encrypt/create_keypair",
             post(verifiable_create_keypair),
         )
+        .route("/encrypt/rotate", post(rotate_keypair))
+        .route("/verifiable/encrypt/rotate", post(verifiable_rotate_keypair))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,33 +30,108 @@ pub struct VerifiableCreateKeyPairResponse {
     pub session_pubkey: String,
     pub session_id: Uuid,
     pub quote: String,
+    /// Compression of `quote` before hex encoding, see `QuoteCompression::decode`;
+    /// plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
+    /// Id of the raw quote at `GET /verifiable/quote/{id}`
+    pub quote_id: String,
+    /// Client challenge bound into the quote's `report_data`, echoed back hex-encoded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
 }
 
 async fn verifiable_create_keypair(
-    state: State<HypervisorState>,
-    req: Json<CreateKeyPairRequest>,
+    State(state): State<HypervisorState>,
+    Json(req): Json<CreateKeyPairRequest>,
+) -> Result<Json<VerifiableCreateKeyPairResponse>, HypervisorError> {
+    let challenge = validate_keypair_request(&req)?;
+    let raw_resp = open_session(&state, &req.pubkey)?;
+
+    attest_keypair(&state, raw_resp, challenge)
+}
+
+async fn verifiable_rotate_keypair(
+    State(state): State<HypervisorState>,
+    Json(req): Json<CreateKeyPairRequest>,
 ) -> Result<Json<VerifiableCreateKeyPairResponse>, HypervisorError> {
-    let Json(raw_resp) = create_keypair(state, req).await?;
+    let challenge = validate_keypair_request(&req)?;
+    let raw_resp = rotate_session(&state, &req.pubkey)?;
+
+    attest_keypair(&state, raw_resp, challenge)
+}
 
-    let session_pk = const_hex::decode(raw_resp.session_pubkey.as_str()).expect("impossible");
-    let report = generate_raw_report(&[session_pk.as_slice(), raw_resp.session_id.as_bytes()]);
+/// Quote over a (new) session keypair
+fn attest_keypair(
+    state: &HypervisorState,
+    raw_resp: CreateKeyPairResponse,
+    challenge: Option<Vec<u8>>,
+) -> Result<Json<VerifiableCreateKeyPairResponse>, HypervisorError> {
+    let session_pk = const_hex::decode(raw_resp.session_pubkey.as_str())
+        .context("decode session pubkey")
+        .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let report = keypair_report(&session_pk, raw_resp.session_id, challenge.as_deref())
+        .context(StatusCode::BAD_REQUEST)?;
 
-    let quote = attest::get_quote(report)
+    let quote = state
+        .tee_quote(report)
         .context("get create keypair quote")
         .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let compression = state.config.quote_compression;
 
     let verifiable_resp = VerifiableCreateKeyPairResponse {
         session_pubkey: raw_resp.session_pubkey,
         session_id: raw_resp.session_id,
-        quote: const_hex::encode(quote.to_bytes()),
+        quote: compression
+            .encode(&quote)
+            .context(StatusCode::INTERNAL_SERVER_ERROR)?,
+        quote_compression: compression.field(),
+        quote_id: state.quote_store.insert(&quote),
+        challenge: challenge.map(const_hex::encode),
     };
 
     Ok(Json(verifiable_resp))
 }
 
+/// Check the pubkey and challenge, reporting every problem at once, and return the
+/// decoded challenge
+fn validate_keypair_request(
+    req: &CreateKeyPairRequest,
+) -> Result<Option<Vec<u8>>, HypervisorError> {
+    let challenge = req.challenge.as_deref().map(const_hex::decode).transpose();
+    let too_long = matches!(&challenge, Ok(Some(c)) if c.len() > MAX_NONCE_LEN);
+
+    Validation::default()
+        .public_key("pubkey", &req.pubkey)
+        .check(challenge.is_ok(), "challenge", "isn't valid hex")
+        .check(!too_long, "challenge", format!("is longer than {MAX_NONCE_LEN} bytes"))
+        .finish()?;
+
+    Ok(challenge.ok().flatten())
+}
+
+/// Report attested for a session keypair, binding the client challenge if given
+fn keypair_report(
+    session_pk: &[u8],
+    session_id: Uuid,
+    challenge: Option<&[u8]>,
+) -> Result<RawReport, ReportDataError> {
+    let builder = ReportDataBuilder::new(KEYPAIR_DOMAIN)
+        .field(session_pk)
+        .field(session_id.as_bytes());
+
+    match challenge {
+        Some(challenge) => Ok(builder.nonce(challenge)?.build()),
+        None => Ok(builder.build()),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateKeyPairRequest {
     pub pubkey: String,
+    /// Optional hex-encoded client nonce (at most 32 bytes) bound into the attested report
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,7 +149,7 @@ async fn create_keypair(
         .context("recover request pubkey")
         .context(StatusCode::BAD_REQUEST)?;
 
-    let (session_pubkey, session_id) = state.create_session_keypair(&req_pk);
+    let (session_pubkey, session_id) = state.clone().create_session_keypair(&req_pk);
 
     let resp = CreateKeyPairResponse {
         session_pubkey: crypto::pk_to_hex(&session_pubkey),
@@ -82,7 +164,17 @@ async fn create_keypair(
     State(state): State<HypervisorState>,
     Json(req): Json<CreateKeyPairRequest>,
 ) -> Result<Json<CreateKeyPairResponse>, HypervisorError> {
-    let req_pk = crypto::pk_from_hex(&req.pubkey)
+    validate_keypair_request(&req)?;
+
+    open_session(&state, &req.pubkey).map(Json)
+}
+
+/// New session of `pubkey`
+fn open_session(
+    state: &HypervisorState,
+    pubkey: &str,
+) -> Result<CreateKeyPairResponse, HypervisorError> {
+    let req_pk = crypto::pk_from_hex(pubkey)
         .map_err(|e| {
             eprintln!("DEBUG: pk_from_hex failed: {:?}", e);
             e
@@ -90,20 +182,55 @@ async fn create_keypair(
         .context("recover request pubkey")
         .context(StatusCode::BAD_REQUEST)?;
 
-    let (session_pubkey, session_id) = state.create_session_keypair(&req_pk);
+    let (session_pubkey, session_id) = state.clone().create_session_keypair(&req_pk);
 
-    let resp = CreateKeyPairResponse {
+    Ok(CreateKeyPairResponse {
         session_pubkey: crypto::pk_to_hex(&session_pubkey),
         session_id,
-    };
+    })
+}
 
-    Ok(Json(resp))
+/// Replace the caller's session with a fresh keypair and id
+/// The previous session id stops working immediately
+async fn rotate_keypair(
+    State(state): State<HypervisorState>,
+    Json(req): Json<CreateKeyPairRequest>,
+) -> Result<Json<CreateKeyPairResponse>, HypervisorError> {
+    validate_keypair_request(&req)?;
+
+    rotate_session(&state, &req.pubkey).map(Json)
+}
+
+/// Fresh keypair and id for the session of `pubkey`
+fn rotate_session(
+    state: &HypervisorState,
+    pubkey: &str,
+) -> Result<CreateKeyPairResponse, HypervisorError> {
+    let req_pk = crypto::pk_from_hex(pubkey)
+        .context("recover request pubkey")
+        .context(StatusCode::BAD_REQUEST)?;
+
+    let (session_pubkey, session_id) = state
+        .clone()
+        .rotate_session_keypair(&req_pk)
+        .context("session not found")
+        .context(StatusCode::NOT_FOUND)?;
+
+    Ok(CreateKeyPairResponse {
+        session_pubkey: crypto::pk_to_hex(&session_pubkey),
+        session_id,
+    })
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::api::RouterRegister;
+    use attest::types::Quote;
+
+    use crate::{
+        api::{quote, RouterRegister},
+        test_utils::mock_attest_quote,
+    };
 
     use super::*;
 
@@ -121,7 +248,10 @@ mod tests {
 
         let response = server
             .post("/encrypt/create_keypair")
-            .json(&CreateKeyPairRequest { pubkey: pk })
+            .json(&CreateKeyPairRequest {
+                pubkey: pk,
+                challenge: None,
+            })
             .await;
 
         response.assert_status_ok();
@@ -132,4 +262,165 @@ mod tests {
             resp.session_pubkey, resp.session_id
         );
     }
+
+    #[tokio::test]
+    async fn test_identity_public_key_is_rejected() {
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .with_state(HypervisorState::default()),
+        )
+        .unwrap();
+
+        for pubkey in ["00".to_string(), "00".repeat(33), "00".repeat(65)] {
+            let response = server
+                .post("/encrypt/create_keypair")
+                .json(&CreateKeyPairRequest {
+                    pubkey,
+                    challenge: None,
+                })
+                .expect_failure()
+                .await;
+            response.assert_status(StatusCode::BAD_REQUEST);
+            let body: serde_json::Value = response.json();
+            assert_eq!(body["errors"][0]["field"], "pubkey");
+            assert_eq!(body["errors"][0]["issue"], "is the point at infinity");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotate_session() {
+        use aes_gcm_siv::aead::Aead;
+
+        use crate::api::openai::{self, OpenAIQueryRequest};
+        use crate::test_utils::{chat_completion, MockOpenAI};
+
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, chat_completion("4"))).await;
+        let mut config = crate::Config::default();
+        config.openai.api_base = backend.base_url.clone();
+
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .register_api(openai::api_register)
+                .with_state(HypervisorState::new(config).unwrap()),
+        )
+        .unwrap();
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let pubkey = crypto::pk_to_hex(sk.verifying_key());
+        let request = || CreateKeyPairRequest {
+            pubkey: pubkey.clone(),
+            challenge: None,
+        };
+
+        // Rotating requires an existing session
+        server
+            .post("/encrypt/rotate")
+            .json(&request())
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        let old = server
+            .post("/encrypt/create_keypair")
+            .json(&request())
+            .await
+            .json::<CreateKeyPairResponse>();
+        let new = server
+            .post("/encrypt/rotate")
+            .json(&request())
+            .await
+            .json::<CreateKeyPairResponse>();
+        assert_ne!(old.session_id, new.session_id);
+        assert_ne!(old.session_pubkey, new.session_pubkey);
+
+        let query = |session: &CreateKeyPairResponse| {
+            let session_pk = crypto::pk_from_hex(&session.session_pubkey).unwrap();
+            let cipher = crypto::create_encrypt_key(&sk, &session_pk, session.session_id).unwrap();
+            let nonce = crypto::derive_msg_nonce(session.session_id);
+            let encrypted_prompt = cipher.encrypt(&nonce, b"What is 2+2?".as_slice()).unwrap();
+
+            server.post("/openai/query").json(&OpenAIQueryRequest {
+                encrypted_prompt: const_hex::encode(encrypted_prompt),
+                public_key: pubkey.clone(),
+                temperature: Some(0.0),
+                max_tokens: Some(50),
+                n: None,
+                attest: false,
+            })
+        };
+
+        query(&old).await.assert_status(StatusCode::BAD_REQUEST);
+        query(&new).await.assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_keypair_quote_is_stored() {
+        let mut state = HypervisorState::default();
+        state.set_quote_source(mock_attest_quote);
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .register_api(quote::api_register)
+                .with_state(state),
+        )
+        .unwrap();
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let challenge = "00112233445566778899aabbccddeeff";
+        let response = server
+            .post("/verifiable/encrypt/create_keypair")
+            .json(&CreateKeyPairRequest {
+                pubkey: crypto::pk_to_hex(sk.verifying_key()),
+                challenge: Some(challenge.to_string()),
+            })
+            .await;
+        response.assert_status_ok();
+        let resp: VerifiableCreateKeyPairResponse = response.json();
+        assert_eq!(resp.challenge.as_deref(), Some(challenge));
+
+        // The quote binds the session keypair and challenge
+        let quote = QuoteCompression::decode(resp.quote_compression, &resp.quote).unwrap();
+        let session_pk = const_hex::decode(&resp.session_pubkey).unwrap();
+        let report = keypair_report(
+            &session_pk,
+            resp.session_id,
+            Some(&const_hex::decode(challenge).unwrap()),
+        )
+        .unwrap();
+        let parsed = Quote::from_bytes(&quote).unwrap();
+        assert_eq!(parsed.report_data(), report.to_bytes());
+
+        let response = server.get(&format!("/verifiable/quote/{}", resp.quote_id)).await;
+        response.assert_status_ok();
+        assert_eq!(response.as_bytes().to_vec(), quote);
+    }
+
+    #[test]
+    fn test_keypair_report_binds_challenge() {
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let request = |challenge: &str| CreateKeyPairRequest {
+            pubkey: crypto::pk_to_hex(sk.verifying_key()),
+            challenge: Some(challenge.to_string()),
+        };
+        let session_pk = [2u8; 33];
+        let session_id = Uuid::now_v7();
+        let challenge = validate_keypair_request(&request("00112233445566778899aabbccddeeff"))
+            .unwrap()
+            .unwrap();
+
+        let report_data = keypair_report(&session_pk, session_id, Some(&challenge))
+            .unwrap()
+            .to_bytes();
+        assert_eq!(&report_data[32..32 + challenge.len()], challenge.as_slice());
+        assert!(report_data[32 + challenge.len()..].iter().all(|b| *b == 0));
+
+        // The challenge also changes the commitment over the session
+        let unbound = keypair_report(&session_pk, session_id, None).unwrap().to_bytes();
+        assert_ne!(report_data[..32], unbound[..32]);
+
+        assert!(validate_keypair_request(&request(&"ab".repeat(MAX_NONCE_LEN + 1))).is_err());
+        assert!(validate_keypair_request(&request("not hex")).is_err());
+    }
 }
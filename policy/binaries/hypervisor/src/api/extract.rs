@@ -0,0 +1,47 @@
+use std::ops::{Deref, DerefMut};
+
+use axum::{
+    extract::{FromRequest, FromRequestParts},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::error::HypervisorError;
+
+/// `axum::Json`, rejecting bad bodies with the JSON error body of `HypervisorError`
+/// instead of axum's plain text
+#[derive(Debug, Clone, Copy, Default, FromRequest)]
+#[from_request(via(axum::Json), rejection(HypervisorError))]
+pub struct Json<T>(pub T);
+
+/// `axum::extract::Query`, rejecting bad query strings with the JSON error body of
+/// `HypervisorError`
+#[derive(Debug, Clone, Copy, Default, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(HypervisorError))]
+pub struct Query<T>(pub T);
+
+/// `axum::extract::Path`, rejecting bad path parameters with the JSON error body of
+/// `HypervisorError`
+#[derive(Debug, Clone, Copy, Default, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(HypervisorError))]
+pub struct Path<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use axum::{extract::State, routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::types::HypervisorState;
+
+pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
+    router.route("/health", get(health))
+}
+
+/// Seconds the models-list call of a probe may take
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// Liveness and the status of the backends the server depends on
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthResponse {
+    /// Always `ok` while the server answers; a backend being down doesn't change it
+    pub status: String,
+    /// Backend statuses by name, e.g. `openai`; only probed backends are listed
+    pub components: BTreeMap<String, ComponentHealth>,
+    /// Quote providers and TEE of the instance, for debugging "no provider available"
+    pub attestation: attest::ProviderCapabilities,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+    Up,
+    Down,
+}
+
+/// Outcome of the last check of a backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub status: ComponentStatus,
+    pub last_checked: DateTime<Utc>,
+    /// Why the backend is down
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reachability of the OpenAI API, checked with a models-list call and reused for a while
+///
+/// Concurrent `/health` requests wait for the check in flight instead of starting another.
+#[derive(Default)]
+pub(crate) struct BackendProbe {
+    last: Mutex<Option<(Instant, ComponentHealth)>>,
+}
+
+impl BackendProbe {
+    /// Result of the last check when younger than `ttl`, otherwise of a new one
+    pub async fn check(&self, api_base: &str, ttl: Duration) -> ComponentHealth {
+        let mut last = self.last.lock().await;
+        if let Some((checked_at, health)) = last.as_ref() {
+            if checked_at.elapsed() < ttl {
+                return health.clone();
+            }
+        }
+
+        let error = list_models(api_base).await.err();
+        if let Some(error) = &error {
+            tracing::warn!(%error, "OpenAI health probe failed");
+        }
+        let health = ComponentHealth {
+            status: match error {
+                None => ComponentStatus::Up,
+                Some(_) => ComponentStatus::Down,
+            },
+            last_checked: Utc::now(),
+            error,
+        };
+        *last = Some((Instant::now(), health.clone()));
+
+        health
+    }
+}
+
+async fn list_models(api_base: &str) -> Result<(), String> {
+    let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_default();
+    let response = reqwest::Client::new()
+        .get(format!("{api_base}/models"))
+        .bearer_auth(api_key)
+        .timeout(Duration::from_secs(PROBE_TIMEOUT_SECS))
+        .send()
+        .await
+        .map_err(|e| format!("models list request failed: {e}"))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("models list returned {status}"));
+    }
+
+    Ok(())
+}
+
+async fn health(State(state): State<HypervisorState>) -> Json<HealthResponse> {
+    let mut components = BTreeMap::new();
+    if let Some(secs) = state.config.openai.health_probe_secs {
+        let ttl = Duration::from_secs(secs);
+        let openai = state.openai_probe.check(&state.config.openai.api_base, ttl).await;
+        components.insert("openai".to_string(), openai);
+    }
+
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        components,
+        attestation: attest::provider_capabilities(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::{api::RouterRegister, test_utils::serve};
+
+    #[tokio::test]
+    async fn test_openai_component_reports_up_then_down() {
+        let (reachable, calls) = (Arc::new(AtomicBool::new(true)), Arc::new(AtomicUsize::new(0)));
+        let (r, c) = (reachable.clone(), calls.clone());
+        let backend = serve(Router::new().route(
+            "/models",
+            get(move || {
+                c.fetch_add(1, Ordering::SeqCst);
+                let status = if r.load(Ordering::SeqCst) {
+                    StatusCode::OK
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                };
+                async move { (status, Json(serde_json::json!({ "data": [] }))) }
+            }),
+        ))
+        .await;
+
+        let mut config = crate::Config::default();
+        config.openai.api_base = backend.clone();
+        config.openai.health_probe_secs = Some(0);
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .with_state(HypervisorState::new(config).unwrap()),
+        )
+        .unwrap();
+
+        let health: HealthResponse = server.get("/health").await.json();
+        assert_eq!(health.components["openai"].status, ComponentStatus::Up);
+        let first_check = health.components["openai"].last_checked;
+
+        reachable.store(false, Ordering::SeqCst);
+        let response = server.get("/health").await;
+        // Liveness holds while the backend is down
+        response.assert_status_ok();
+        let health: HealthResponse = response.json();
+        let openai = &health.components["openai"];
+        assert_eq!(health.status, "ok");
+        assert_eq!(openai.status, ComponentStatus::Down);
+        assert_eq!(openai.error.as_deref(), Some("models list returned 503 Service Unavailable"));
+        assert!(openai.last_checked >= first_check);
+
+        // Within the TTL the last result is reused without calling the backend
+        let probe = BackendProbe::default();
+        let calls_before = calls.load(Ordering::SeqCst);
+        for _ in 0..3 {
+            let health = probe.check(&backend, Duration::from_secs(60)).await;
+            assert_eq!(health.status, ComponentStatus::Down);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), calls_before + 1);
+    }
+
+    #[tokio::test]
+    async fn test_backend_not_probed_by_default() {
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .with_state(HypervisorState::default()),
+        )
+        .unwrap();
+
+        let health: HealthResponse = server.get("/health").await.json();
+        assert_eq!(health.status, "ok");
+        assert!(health.components.is_empty());
+        assert_eq!(health.attestation.providers.len(), 3);
+    }
+}
@@ -0,0 +1,15 @@
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+
+use crate::{agent::metrics::rule_metrics, types::HypervisorState};
+
+pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
+    router.route("/metrics", get(metrics))
+}
+
+/// Compliance rule counters and timings, in the Prometheus text format
+async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        rule_metrics().render(),
+    )
+}
@@ -1,9 +1,28 @@
-use axum::Router;
+use anyhow::{anyhow, Context};
+use attest::types::RawReport;
+use axum::{http::StatusCode, Router};
+use tokio::sync::OwnedSemaphorePermit;
+use tracing::warn;
+use uuid::Uuid;
 
+use crate::{
+    api::quote::QuoteCompression, error::HypervisorError, types::HypervisorState,
+    utils::crypto,
+};
+
+pub mod admin;
 pub mod agent;
 pub mod encrypt;
+pub mod extract;
+pub mod health;
+pub mod metrics;
 pub mod openai;
 pub mod ping;
+pub mod policy;
+pub mod prompt_filter;
+pub mod quote;
+pub(crate) mod validation;
+pub mod verify;
 
 pub trait ServerState: Clone + Sync + Send + 'static {}
 impl<T: Clone + Sync + Send + 'static> ServerState for T {}
@@ -17,3 +36,224 @@ impl<V> RouterRegister<V> for Router<V> {
         f(self)
     }
 }
+
+/// Permit to call OpenAI, to hold until the request's work is done; fails with 503
+/// when `max_concurrent_requests` requests already hold one
+///
+/// Taken by the endpoints that call OpenAI, so a burst is turned away
+/// instead of queueing up and draining the quota.
+pub(crate) fn acquire_permit(
+    state: &HypervisorState,
+) -> Result<Option<OwnedSemaphorePermit>, HypervisorError> {
+    let Some(limit) = &state.expensive_requests else {
+        return Ok(None);
+    };
+
+    let permit = limit
+        .clone()
+        .try_acquire_owned()
+        .map_err(|_| anyhow!("too many concurrent requests, retry later"))
+        .context(StatusCode::SERVICE_UNAVAILABLE)?;
+
+    Ok(Some(permit))
+}
+
+/// Quote as returned by the verifiable routes, and by the others when `attest` is set
+pub(crate) struct SessionQuote {
+    /// TEE quote (hex-encoded), compressed with `quote_compression`
+    pub quote: String,
+    /// Compression of `quote`, none for plain hex
+    pub quote_compression: Option<QuoteCompression>,
+    /// Binding of the quote to the caller's session, see `crypto::verify_quote_binding`
+    pub quote_signature: String,
+    /// Id of the raw quote at `GET /verifiable/quote/{id}`
+    pub quote_id: String,
+}
+
+/// TEE quote over `report` from `get_quote` and its binding to the caller's session
+///
+/// The raw quote is also kept in the quote store for a while, under the returned id.
+pub(crate) fn session_quote(
+    state: &HypervisorState,
+    public_key: &str,
+    session_id: Uuid,
+    report: RawReport,
+    get_quote: impl FnOnce(RawReport) -> anyhow::Result<Vec<u8>>,
+) -> Result<SessionQuote, HypervisorError> {
+    let quote = get_quote(report).context(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let quote_signature = bind_quote(state, public_key, session_id, &quote)?;
+    let quote_id = state.quote_store.insert(&quote);
+    let compression = state.config.quote_compression;
+
+    Ok(SessionQuote {
+        quote: compression
+            .encode(&quote)
+            .context(StatusCode::INTERNAL_SERVER_ERROR)?,
+        quote_compression: compression.field(),
+        quote_signature,
+        quote_id,
+    })
+}
+
+/// Quote from the TEE's attestation provider
+pub(crate) fn tee_quote(report: RawReport) -> anyhow::Result<Vec<u8>> {
+    Ok(attest::get_quote(report)?.to_bytes())
+}
+
+/// Sign `quote` with the key of the caller's session, see `crypto::sign_quote_binding`
+///
+/// Fails with 409 if the session was rotated while the request ran, as the response
+/// is bound to the session it was encrypted for.
+pub(crate) fn bind_quote(
+    state: &HypervisorState,
+    public_key: &str,
+    session_id: Uuid,
+    quote: &[u8],
+) -> Result<String, HypervisorError> {
+    let user_pk = crypto::pk_from_hex(public_key)
+        .context(StatusCode::BAD_REQUEST)
+        .context("decode request pubkey")?;
+
+    let (session_sk, current_id) = state
+        .clone()
+        .get_session_keypair(&user_pk)
+        .filter(|(_, current_id)| *current_id == session_id)
+        .ok_or(anyhow!("session rotated during the request"))
+        .context(StatusCode::CONFLICT)?;
+
+    Ok(crypto::sign_quote_binding(&session_sk, quote, current_id))
+}
+
+/// Check the prompt of a plaintext route as the session routes check decrypted ones,
+/// `what` naming it in errors
+pub(crate) fn accept_plaintext(
+    state: &HypervisorState,
+    route: &str,
+    text: String,
+    what: &str,
+) -> Result<String, HypervisorError> {
+    warn!(route, "serving a plaintext query without session encryption");
+
+    let text = crypto::decode_plaintext(text.into_bytes(), state.config.max_prompt_bytes)
+        .map_err(|reason| {
+            anyhow::Error::msg(StatusCode::BAD_REQUEST).context(format!("{what} {reason}"))
+        })?;
+    state.config.prompt_denylist.check(&text)?;
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use aes_gcm_siv::aead::Aead;
+    use axum::{routing::post, Json};
+    use tokio::sync::Semaphore;
+
+    use super::*;
+    use crate::api::openai::{PlainOpenAIQueryRequest, PlainOpenAIQueryResponse};
+    use crate::test_utils::{chat_completion, data_dir, serve, MockOpenAI};
+    use crate::types::SessionKeyPairs;
+
+    #[tokio::test]
+    async fn test_concurrency_limit_holds_permit_for_the_stream() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        // The backend holds each completion until the gate opens
+        let (entered, gate) = (Arc::new(Semaphore::new(0)), Arc::new(Semaphore::new(0)));
+        let (e, g) = (entered.clone(), gate.clone());
+        let backend = serve(Router::new().route(
+            "/chat/completions",
+            post(move || {
+                let (entered, gate) = (e.clone(), g.clone());
+                async move {
+                    entered.add_permits(1);
+                    gate.acquire().await.unwrap().forget();
+                    Json(chat_completion("THOUGHT: No data is needed"))
+                }
+            }),
+        ))
+        .await;
+
+        let mut config = crate::Config::default();
+        config.agent.api_base = backend;
+        config.agent.data_dir = data_dir();
+        config.max_concurrent_requests = Some(1);
+        let session_key_pairs = SessionKeyPairs::default();
+        let mut state = HypervisorState::new(config).unwrap();
+        state.set_session_key_pairs(session_key_pairs.clone());
+        let limit = state.expensive_requests.clone().unwrap();
+        let base_url = serve(
+            Router::new()
+                .register_api(agent::api_register)
+                .register_api(openai::api_register)
+                .with_state(state),
+        )
+        .await;
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let (session_pk, session_id) = session_key_pairs.create(sk.verifying_key());
+        let cipher = crypto::create_encrypt_key(&sk, &session_pk, session_id).unwrap();
+        let encrypted_query = cipher
+            .encrypt(&crypto::derive_msg_nonce(session_id), b"What is a blockchain?".as_slice())
+            .unwrap();
+        let body = serde_json::json!({
+            "encrypted_query": const_hex::encode(&encrypted_query),
+            "public_key": crypto::pk_to_hex(sk.verifying_key()),
+        });
+
+        // The stream's handler returns at once, but its run keeps the permit
+        let client = reqwest::Client::new();
+        let stream = client
+            .post(format!("{base_url}/agent/query/stream"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(stream.status(), StatusCode::OK);
+        entered.acquire().await.unwrap().forget();
+
+        let resp = client.post(format!("{base_url}/agent/query")).json(&body).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Routes that don't call OpenAI are not limited
+        let resp = client.get(format!("{base_url}/agent/chains")).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // The permit is released once the stream's run completes
+        gate.add_permits(2);
+        assert!(stream.text().await.unwrap().contains("event: final"));
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while limit.available_permits() == 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_openai_query() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, chat_completion("4"))).await;
+        let mut config = crate::Config::default();
+        config.openai.api_base = backend.base_url.clone();
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(openai::plaintext_api_register)
+                .with_state(HypervisorState::new(config).unwrap()),
+        )
+        .unwrap();
+        let request = PlainOpenAIQueryRequest {
+            prompt: "What is 2+2?".to_string(),
+            temperature: None,
+            max_tokens: None,
+        };
+
+        let response = server.post("/openai/query/plain").json(&request).await;
+        response.assert_status_ok();
+        let result: PlainOpenAIQueryResponse = response.json();
+        assert_eq!(result.response, "4");
+        assert!(backend.requests()[0]["messages"].to_string().contains("What is 2+2?"));
+    }
+}
@@ -1,20 +1,201 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
 use aes_gcm_siv::aead::Aead;
 use anyhow::{anyhow, Context};
-use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use attest::types::RawReport;
+use axum::{extract::State, http::StatusCode, routing::post, Router};
 use serde::{Deserialize, Serialize};
 use tracing::{info, debug};
 use uuid::Uuid;
 
 use crate::{
+    api::{
+        accept_plaintext, acquire_permit, extract::Json, quote::QuoteCompression, session_quote,
+        validation::Validation, SessionQuote,
+    },
+    config::GenerationLimits,
     error::HypervisorError,
     types::HypervisorState,
-    utils::{self, commitment_openai, crypto},
+    utils::{
+        attest::ReportDataBuilder,
+        commitment_openai, crypto,
+        models::{self, Completion, FinishReason, ModelsConfig},
+    },
 };
 
 pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
     router
         .route("/openai/query", post(query_openai))
         .route("/verifiable/openai/query", post(verifiable_query_openai))
+        .route("/openai/estimate", post(estimate_openai_query))
+}
+
+/// Plaintext OpenAI route, registered on `unix:` listeners when `plaintext_routes` is set
+pub(crate) fn plaintext_api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
+    router.route("/openai/query/plain", post(query_openai_plain))
+}
+
+const DEFAULT_API_BASE: &str = "https://api.openai.com/v1";
+
+const DEFAULT_MAX_CANDIDATES: u32 = 4;
+
+/// Settings of the `/openai/query` endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpenAIConfig {
+    /// Base URL of the OpenAI-compatible API
+    pub api_base: String,
+    /// Cache of temperature-0 completions; disabled when unset
+    pub response_cache: Option<ResponseCacheConfig>,
+    /// Prices per model name, used by `/openai/estimate`
+    pub pricing: HashMap<String, ModelPricing>,
+    /// Seconds a `/health` check of the API is reused; the API isn't probed when unset
+    pub health_probe_secs: Option<u64>,
+    /// Ceiling on `n`, the candidate answers generated per query
+    pub max_candidates: u32,
+}
+
+impl Default for OpenAIConfig {
+    fn default() -> Self {
+        Self {
+            api_base: DEFAULT_API_BASE.to_string(),
+            response_cache: None,
+            pricing: HashMap::new(),
+            health_probe_secs: None,
+            max_candidates: DEFAULT_MAX_CANDIDATES,
+        }
+    }
+}
+
+impl OpenAIConfig {
+    /// Candidates generated for a requested `n`: one by default, at most `max_candidates`
+    pub fn candidates(&self, n: Option<u32>) -> u32 {
+        n.unwrap_or(1).clamp(1, self.max_candidates.max(1))
+    }
+}
+
+/// Price of a model, in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub prompt_usd_per_million: f64,
+    pub completion_usd_per_million: f64,
+}
+
+impl ModelPricing {
+    /// Cost of a completion using `prompt_tokens` and `completion_tokens`
+    pub fn cost_usd(&self, prompt_tokens: usize, completion_tokens: u32) -> f64 {
+        (prompt_tokens as f64 * self.prompt_usd_per_million
+            + completion_tokens as f64 * self.completion_usd_per_million)
+            / 1_000_000.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    /// How long a completion is served from cache, in seconds
+    #[serde(default = "default_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Maximum number of cached completions
+    #[serde(default = "default_cache_capacity")]
+    pub capacity: usize,
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    300
+}
+
+fn default_cache_capacity() -> usize {
+    1000
+}
+
+/// Plaintext completions of temperature-0 queries, keyed by `response_cache_key`
+///
+/// Responses are stored decrypted and re-encrypted for the requesting session on a hit.
+#[derive(Default)]
+pub(crate) struct ResponseCache {
+    config: Option<ResponseCacheConfig>,
+    entries: Mutex<HashMap<[u8; 32], CachedResponse>>,
+}
+
+#[derive(Clone)]
+struct CachedResponse {
+    stored_at: Instant,
+    model: String,
+    completion: Completion,
+}
+
+impl ResponseCache {
+    pub fn new(config: Option<ResponseCacheConfig>) -> Self {
+        Self {
+            config,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Only deterministic (temperature 0) queries are cached
+    fn enabled_for(&self, temperature: f32) -> bool {
+        self.config.is_some() && temperature == 0.0
+    }
+
+    fn get(&self, key: &[u8; 32]) -> Option<CachedResponse> {
+        let config = self.config.as_ref()?;
+        let ttl = Duration::from_secs(config.ttl_secs);
+        let entries = self.entries.lock().expect("response cache poisoned");
+        entries
+            .get(key)
+            .filter(|entry| entry.stored_at.elapsed() < ttl)
+            .cloned()
+    }
+
+    fn insert(&self, key: [u8; 32], model: String, completion: Completion) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        if config.capacity == 0 {
+            return;
+        }
+
+        let ttl = Duration::from_secs(config.ttl_secs);
+        let mut entries = self.entries.lock().expect("response cache poisoned");
+        entries.retain(|_, entry| entry.stored_at.elapsed() < ttl);
+        if entries.len() >= config.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(k, _)| *k);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            CachedResponse {
+                stored_at: Instant::now(),
+                model,
+                completion,
+            },
+        );
+    }
+}
+
+/// Cache key: hash(model, temperature, max_tokens, prompt)
+fn response_cache_key(model: &str, temperature: f32, max_tokens: u32, prompt: &str) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    for field in [
+        model.as_bytes(),
+        &temperature.to_le_bytes(),
+        &max_tokens.to_le_bytes(),
+        prompt.as_bytes(),
+    ] {
+        hasher.update(&(field.len() as u64).to_le_bytes());
+        hasher.update(field);
+    }
+
+    hasher.finalize().into()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,56 +208,162 @@ pub struct OpenAIQueryRequest {
     pub temperature: Option<f32>,
     #[serde(default)]
     pub max_tokens: Option<u32>,
+    /// Candidate answers to generate (default 1), capped at `openai.max_candidates`
+    #[serde(default)]
+    pub n: Option<u32>,
+    /// Return a TEE quote over the query commitment, as `/verifiable/openai/query` does
+    #[serde(default)]
+    pub attest: bool,
+}
+
+/// Candidate answer after the first, for queries generating several
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Candidate {
+    /// Encrypted candidate, sealed like `encrypted_response`
+    pub encrypted_response: String,
+    pub finish_reason: FinishReason,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIQueryResponse {
     pub session_id: Uuid,
-    /// Encrypted response (hex-encoded)
+    /// Encrypted response, sealed as `version || nonce || ciphertext` (hex-encoded),
+    /// see `crypto::open`
     pub encrypted_response: String,
-    /// Nonce used for response encryption (hex-encoded)
-    pub response_nonce: String,
     /// Model used
     pub model: String,
     /// Commitment to the query (prompt + response + metadata)
     pub query_commitment: String,
+    /// Session key's signature over the commitment (hex-encoded),
+    /// see `crypto::verify_response_signature`
+    pub response_signature: String,
+    /// `max_tokens` used, after clamping to the server's ceiling
+    pub max_tokens: u32,
+    /// Temperature used, after clamping to the accepted range
+    pub temperature: f32,
+    /// Why generation stopped; `length` when the response was cut off at `max_tokens`
+    pub finish_reason: FinishReason,
+    /// Candidates after the first, which is `encrypted_response`, when `n` > 1
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_candidates: Vec<Candidate>,
+    /// TEE attestation quote over `query_commitment` (hex-encoded), when `attest` was set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    /// Session key's signature over the quote and session ID (hex-encoded), with `quote`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_signature: Option<String>,
+    /// Id of the raw quote at `GET /verifiable/quote/{id}`, with `quote`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<String>,
+    /// Compression of `quote` before hex encoding, see `QuoteCompression::decode`;
+    /// plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
+}
+
+/// Unencrypted query of `/openai/query/plain`, see `plaintext_api_register`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlainOpenAIQueryRequest {
+    pub prompt: String,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlainOpenAIQueryResponse {
+    pub response: String,
+    /// Model used
+    pub model: String,
+    /// `max_tokens` used, after clamping to the server's ceiling
+    pub max_tokens: u32,
+    /// Temperature used, after clamping to the accepted range
+    pub temperature: f32,
+    /// Why generation stopped; `length` when the response was cut off at `max_tokens`
+    pub finish_reason: FinishReason,
+}
+
+/// Pre-flight estimate of an OpenAI query, made without calling OpenAI
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenAIEstimateResponse {
+    /// Model the query would be sent to first
+    pub model: String,
+    /// Prompt tokens, including the chat message framing
+    pub prompt_tokens: usize,
+    /// `max_tokens` the query would use, after clamping to the server's ceiling
+    pub max_tokens: u32,
+    /// Candidates the query would generate, after capping to `openai.max_candidates`
+    pub n: u32,
+    /// Cost if every candidate uses all of `max_tokens`; none when the model has no
+    /// configured pricing
+    pub max_cost_usd: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VerifiableOpenAIQueryResponse {
     pub session_id: Uuid,
-    /// Encrypted response (hex-encoded)
+    /// Encrypted response, sealed as `version || nonce || ciphertext` (hex-encoded),
+    /// see `crypto::open`
     pub encrypted_response: String,
-    /// Nonce used for response encryption (hex-encoded)
-    pub response_nonce: String,
     /// Model used
     pub model: String,
     /// Commitment to the query (prompt + response + metadata)
     pub query_commitment: String,
+    /// Session key's signature over the commitment (hex-encoded),
+    /// see `crypto::verify_response_signature`
+    pub response_signature: String,
+    /// `max_tokens` used, after clamping to the server's ceiling
+    pub max_tokens: u32,
+    /// Temperature used, after clamping to the accepted range
+    pub temperature: f32,
+    /// Why generation stopped; `length` when the response was cut off at `max_tokens`
+    pub finish_reason: FinishReason,
+    /// Candidates after the first, which is `encrypted_response`, when `n` > 1
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_candidates: Vec<Candidate>,
     /// TEE attestation quote (hex-encoded)
     pub quote: String,
+    /// Session key's signature over the quote and session ID (hex-encoded),
+    /// see `crypto::verify_quote_binding`
+    pub quote_signature: String,
+    /// Id of the raw quote at `GET /verifiable/quote/{id}`
+    pub quote_id: String,
+    /// Compression of `quote` before hex encoding, see `QuoteCompression::decode`;
+    /// plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
 }
 
 async fn verifiable_query_openai(
     state: State<HypervisorState>,
     req: Json<OpenAIQueryRequest>,
 ) -> Result<Json<VerifiableOpenAIQueryResponse>, HypervisorError> {
-    let Json(resp) = query_openai(state, req).await?;
-    
-    let commitment: [u8; 32] =
-        const_hex::decode_to_array(&resp.query_commitment).expect("impossible");
-
-    let quote = attest::get_quote(utils::attest::generate_raw_report_from_hash(commitment))
-        .context("get openai query quote")
-        .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let public_key = req.public_key.clone();
+    let (resp, commitment) = execute_openai_query(state.clone(), req).await?;
+    let SessionQuote {
+        quote,
+        quote_compression,
+        quote_signature,
+        quote_id,
+    } = quote_query(&state, &public_key, resp.session_id, &commitment, |report| {
+        state.tee_quote(report)
+    })?;
 
     let verifiable_resp = VerifiableOpenAIQueryResponse {
         session_id: resp.session_id,
         encrypted_response: resp.encrypted_response,
-        response_nonce: resp.response_nonce,
         model: resp.model,
         query_commitment: resp.query_commitment,
-        quote: const_hex::encode(quote.to_bytes()),
+        response_signature: resp.response_signature,
+        max_tokens: resp.max_tokens,
+        temperature: resp.temperature,
+        finish_reason: resp.finish_reason,
+        additional_candidates: resp.additional_candidates,
+        quote,
+        quote_signature,
+        quote_id,
+        quote_compression,
     };
 
     Ok(Json(verifiable_resp))
@@ -84,13 +371,268 @@ async fn verifiable_query_openai(
 
 #[tracing::instrument(skip(state, req), err)]
 async fn query_openai(
+    state: State<HypervisorState>,
+    req: Json<OpenAIQueryRequest>,
+) -> Result<Json<OpenAIQueryResponse>, HypervisorError> {
+    let (public_key, attest) = (req.public_key.clone(), req.attest);
+    let (mut resp, commitment) = execute_openai_query(state.clone(), req).await?;
+    if attest {
+        let quote = quote_query(&state, &public_key, resp.session_id, &commitment, |report| {
+            state.tee_quote(report)
+        })?;
+        resp.quote = Some(quote.quote);
+        resp.quote_signature = Some(quote.quote_signature);
+        resp.quote_id = Some(quote.quote_id);
+        resp.quote_compression = quote.quote_compression;
+    }
+
+    Ok(Json(resp))
+}
+
+/// Query OpenAI in plaintext, without a session, commitment or cache
+#[tracing::instrument(skip(state, req), err)]
+async fn query_openai_plain(
+    State(state): State<HypervisorState>,
+    Json(req): Json<PlainOpenAIQueryRequest>,
+) -> Result<Json<PlainOpenAIQueryResponse>, HypervisorError> {
+    let prompt = accept_plaintext(&state, "/openai/query/plain", req.prompt, "prompt")?;
+    let _permit = acquire_permit(&state)?;
+
+    let GenerationLimits {
+        max_tokens,
+        temperature,
+    } = state
+        .config
+        .generation_limits(req.max_tokens.unwrap_or(1000), req.temperature.unwrap_or(0.7));
+    let (model, mut completions) = complete_openai(
+        &state.config.openai.api_base,
+        &state.config.models,
+        &prompt,
+        temperature,
+        max_tokens,
+        1,
+    )
+    .await?;
+    let Completion {
+        content,
+        finish_reason,
+    } = completions.remove(0);
+
+    Ok(Json(PlainOpenAIQueryResponse {
+        response: content,
+        model,
+        max_tokens,
+        temperature,
+        finish_reason,
+    }))
+}
+
+/// Quote over the query commitment and its session binding
+fn quote_query(
+    state: &HypervisorState,
+    public_key: &str,
+    session_id: Uuid,
+    commitment: &ReportDataBuilder,
+    get_quote: impl FnOnce(RawReport) -> anyhow::Result<Vec<u8>>,
+) -> Result<SessionQuote, HypervisorError> {
+    session_quote(state, public_key, session_id, commitment.build(), |report| {
+        get_quote(report).context("get openai query quote")
+    })
+}
+
+/// Count the prompt tokens of a query and price its worst case, without calling OpenAI
+#[tracing::instrument(skip(state, req), err)]
+async fn estimate_openai_query(
     State(state): State<HypervisorState>,
     Json(req): Json<OpenAIQueryRequest>,
-) -> Result<Json<OpenAIQueryResponse>, HypervisorError> {
-    // Validate request
-    validate_query_request(&req)?;
+) -> Result<Json<OpenAIEstimateResponse>, HypervisorError> {
+    let DecryptedPrompt { prompt, .. } = decrypt_prompt(&state, &req)?;
+
+    let model = state.config.models.primary.clone();
+    let prompt_tokens = count_prompt_tokens(&model, &prompt);
+    let GenerationLimits { max_tokens, .. } = state
+        .config
+        .generation_limits(req.max_tokens.unwrap_or(1000), req.temperature.unwrap_or(0.7));
+    let n = state.config.openai.candidates(req.n);
+    let max_cost_usd = state
+        .config
+        .openai
+        .pricing
+        .get(&model)
+        .map(|pricing| pricing.cost_usd(prompt_tokens, max_tokens.saturating_mul(n)));
+
+    Ok(Json(OpenAIEstimateResponse {
+        model,
+        prompt_tokens,
+        max_tokens,
+        n,
+        max_cost_usd,
+    }))
+}
 
+/// Prompt tokens the chat completions API bills for `prompt` sent as the only user message
+fn count_prompt_tokens(model: &str, prompt: &str) -> usize {
+    // Every message is framed by 3 tokens around its role, and the reply is primed with 3 more
+    3 + models::count_tokens(model, "user") + models::count_tokens(model, prompt) + 3
+}
+
+/// Run the OpenAI query, returning the response and the builder of its commitment
+async fn execute_openai_query(
+    State(state): State<HypervisorState>,
+    Json(req): Json<OpenAIQueryRequest>,
+) -> Result<(OpenAIQueryResponse, ReportDataBuilder), HypervisorError> {
     let start_time = std::time::Instant::now();
+    let _permit = acquire_permit(&state)?;
+
+    let DecryptedPrompt {
+        user_pk,
+        session_sk,
+        session_id,
+        cipher,
+        prompt: decrypted_prompt,
+    } = decrypt_prompt(&state, &req)?;
+
+    info!(
+        session_id = %session_id,
+        public_key = req.public_key,
+        prompt_length = decrypted_prompt.len(),
+        "processing OpenAI query request"
+    );
+
+    let GenerationLimits {
+        max_tokens,
+        temperature,
+    } = state
+        .config
+        .generation_limits(req.max_tokens.unwrap_or(1000), req.temperature.unwrap_or(0.7));
+    let n = state.config.openai.candidates(req.n);
+    // Only single-candidate queries are cached
+    let cache = &state.openai_cache;
+    let cache_key = (n == 1 && cache.enabled_for(temperature))
+        .then(|| {
+        response_cache_key(
+            &state.config.models.primary,
+            temperature,
+            max_tokens,
+            &decrypted_prompt,
+        )
+    });
+
+    let (model, completions) = match cache_key.as_ref().and_then(|key| cache.get(key)) {
+        Some(cached) => {
+            info!(session_id = %session_id, "serving OpenAI query from cache");
+            (cached.model, vec![cached.completion])
+        }
+        None => {
+            let (model, completions) = complete_openai(
+                &state.config.openai.api_base,
+                &state.config.models,
+                &decrypted_prompt,
+                temperature,
+                max_tokens,
+                n,
+            )
+            .await?;
+            if let Some(key) = cache_key {
+                cache.insert(key, model.clone(), completions[0].clone());
+            }
+
+            (model, completions)
+        }
+    };
+    let truncated = completions
+        .iter()
+        .filter(|c| c.finish_reason == FinishReason::Length)
+        .count();
+    if truncated > 0 {
+        info!(
+            session_id = %session_id,
+            max_tokens,
+            truncated,
+            "OpenAI completion truncated at max_tokens"
+        );
+    }
+
+    info!(
+        session_id = %session_id,
+        public_key = req.public_key,
+        execution_time_ms = start_time.elapsed().as_millis(),
+        candidates = completions.len(),
+        response_length = completions.iter().map(|c| c.content.len()).sum::<usize>(),
+        status = "success",
+        msg = "OpenAI query completed successfully"
+    );
+
+    // Encrypt each candidate on its own, so each opens independently
+    let mut candidates = completions
+        .into_iter()
+        .map(|completion| {
+            let sealed = crypto::seal(&cipher, completion.content.as_bytes())
+                .context("encrypt response")
+                .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            Ok(Candidate {
+                encrypted_response: const_hex::encode(sealed),
+                finish_reason: completion.finish_reason,
+            })
+        })
+        .collect::<Result<Vec<_>, HypervisorError>>()?;
+    let Candidate {
+        encrypted_response,
+        finish_reason,
+    } = candidates.remove(0);
+
+    // Build commitment: hash(user_pk, session_pk, session_id, encrypted_prompt, model, encrypted_response)
+    let encrypted_responses: Vec<_> = std::iter::once(encrypted_response.as_str())
+        .chain(candidates.iter().map(|c| c.encrypted_response.as_str()))
+        .collect();
+    let query_commitment = commitment_openai::build_query_commitment(
+        &user_pk,
+        session_sk.verifying_key(),
+        session_id,
+        &req.encrypted_prompt,
+        &model,
+        temperature,
+        max_tokens,
+        &encrypted_responses,
+    );
+
+    let commitment = query_commitment.digest();
+    let resp = OpenAIQueryResponse {
+        session_id,
+        encrypted_response,
+        model,
+        query_commitment: const_hex::encode(commitment),
+        response_signature: crypto::sign_response(&session_sk, &commitment, session_id),
+        max_tokens,
+        temperature,
+        finish_reason,
+        additional_candidates: candidates,
+        quote: None,
+        quote_signature: None,
+        quote_id: None,
+        quote_compression: None,
+    };
+
+    Ok((resp, query_commitment))
+}
+
+/// Prompt of a query, with the session it was encrypted for
+struct DecryptedPrompt {
+    user_pk: k256::ecdsa::VerifyingKey,
+    session_sk: k256::ecdsa::SigningKey,
+    session_id: Uuid,
+    cipher: aes_gcm_siv::Aes256GcmSiv,
+    prompt: String,
+}
+
+/// Validate the request and decrypt its prompt with the caller's session key
+fn decrypt_prompt(
+    state: &HypervisorState,
+    req: &OpenAIQueryRequest,
+) -> Result<DecryptedPrompt, HypervisorError> {
+    // Validate request
+    validate_query_request(req)?;
 
     // Decode user's public key
     let user_pk = crypto::pk_from_hex(&req.public_key)
@@ -99,19 +641,18 @@ async fn query_openai(
 
     // Get session keypair
     let (session_sk, session_id) = state
+        .clone()
         .get_session_keypair(&user_pk)
         .ok_or(anyhow!("session not found"))
         .context(StatusCode::UNAUTHORIZED)?;
 
     // Create cipher for this session
-    let cipher = crypto::create_encrypt_key(&session_sk, &user_pk, session_id)
-        .context(StatusCode::INTERNAL_SERVER_ERROR)
-        .context("create encrypt key")?;
+    let cipher = crypto::create_encrypt_key(&session_sk, &user_pk, session_id)?;
 
     let msg_nonce = crypto::derive_msg_nonce(session_id);
 
     // Decrypt the prompt
-    let decrypted_prompt = {
+    let prompt = {
         let encrypted_bytes = const_hex::decode(&req.encrypted_prompt)
             .context(StatusCode::BAD_REQUEST)
             .context("invalid prompt hex")?;
@@ -137,18 +678,33 @@ async fn query_openai(
             .context(StatusCode::BAD_REQUEST)
             .context("decrypt prompt")?;
 
-        String::from_utf8(decrypted)
-            .context(StatusCode::BAD_REQUEST)
-            .context("prompt isn't valid UTF-8")?
+        crypto::decode_plaintext(decrypted, state.config.max_prompt_bytes).map_err(|reason| {
+            anyhow::Error::msg(StatusCode::BAD_REQUEST).context(format!("prompt {reason}"))
+        })?
     };
+    state.config.prompt_denylist.check(&prompt)?;
 
-    info!(
-        session_id = %session_id,
-        public_key = req.public_key,
-        prompt_length = decrypted_prompt.len(),
-        "processing OpenAI query request"
-    );
+    Ok(DecryptedPrompt {
+        user_pk,
+        session_sk,
+        session_id,
+        cipher,
+        prompt,
+    })
+}
 
+/// Send the prompt to the chat completions API, returning the model and `n` completions
+///
+/// Falls back to the next configured model while one is rate limited or failing.
+/// Completions withheld by the content filter or refused by the model fail with 422.
+async fn complete_openai(
+    api_base: &str,
+    models: &ModelsConfig,
+    prompt: &str,
+    temperature: f32,
+    max_tokens: u32,
+    n: u32,
+) -> Result<(String, Vec<Completion>), HypervisorError> {
     // Get OpenAI API key from environment
     let api_key = std::env::var("OPENAI_API_KEY")
         .context("OPENAI_API_KEY not set")
@@ -156,133 +712,570 @@ async fn query_openai(
 
     // Build OpenAI API request
     let client = reqwest::Client::new();
-    let request_body = serde_json::json!({
-        "model": "gpt-4o",
-        "messages": [
-            {
-                "role": "user",
-                "content": decrypted_prompt
-            }
-        ],
-        "temperature": req.temperature.unwrap_or(0.7),
-        "max_tokens": req.max_tokens.unwrap_or(1000)
-    });
+    let request = |model: &str| {
+        let request_body = serde_json::json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt
+                }
+            ],
+            "temperature": temperature,
+            "max_tokens": max_tokens,
+            "n": n
+        });
+
+        client
+            .post(format!("{}/chat/completions", api_base))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&request_body)
+    };
 
     // Call OpenAI API
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
+    let (model, response) = models
+        .send(request)
         .await
         .context("failed to send request to OpenAI")
         .context(StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let status = response.status();
     if !status.is_success() {
-        let error_text = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
+        let error_text = models.read_error_text(response).await;
         return Err(anyhow!("OpenAI API error {}: {}", status, error_text))
             .context(StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
     // Parse OpenAI response
-    let openai_response: serde_json::Value = response
-        .json()
+    let openai_response = models
+        .read_json(response)
         .await
         .context("failed to parse OpenAI response")
         .context(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let response_text = openai_response["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or_else(|| anyhow!("Invalid OpenAI response format"))
-        .context(StatusCode::INTERNAL_SERVER_ERROR)?
-        .to_string();
+    let completions = models::parse_completions(&openai_response).map_err(|e| {
+        if e.is_refusal() {
+            anyhow::Error::msg(StatusCode::UNPROCESSABLE_ENTITY).context(format!("OpenAI {e}"))
+        } else {
+            anyhow::Error::msg(StatusCode::INTERNAL_SERVER_ERROR)
+                .context(format!("invalid OpenAI response: {e}"))
+        }
+    })?;
 
     let model = openai_response["model"]
         .as_str()
-        .unwrap_or("gpt-4o")
-        .to_string();
+        .map(ToOwned::to_owned)
+        .unwrap_or(model);
 
-    info!(
-        session_id = %session_id,
-        public_key = req.public_key,
-        execution_time_ms = start_time.elapsed().as_millis(),
-        response_length = response_text.len(),
-        status = "success",
-        msg = "OpenAI query completed successfully"
-    );
+    Ok((model, completions))
+}
 
-    // Encrypt the response
-    let response_nonce = crypto::derive_msg_nonce(response_text.as_bytes());
-    let encrypted_response = {
-        let encrypted = cipher
-            .encrypt(&response_nonce, response_text.as_bytes())
-            .map_err(|e| anyhow!(e.to_string()))
-            .context("encrypt response")
-            .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+/// Validate query request
+fn validate_query_request(request: &OpenAIQueryRequest) -> Result<(), HypervisorError> {
+    Validation::default()
+        .ciphertext("encrypted_prompt", &request.encrypted_prompt)
+        .public_key("public_key", &request.public_key)
+        .finish()
+}
 
-        const_hex::encode(encrypted)
+#[cfg(test)]
+mod tests {
+    use aes_gcm_siv::aead::Aead;
+    use serde_json::json;
+
+    use crate::test_utils::{
+        chat_completion, content_filtered_completion, mock_attest_quote, truncated_completion,
+        MockOpenAI,
     };
+    use crate::utils::crypto;
+    use crate::{api::RouterRegister, types::SessionKeyPairs};
 
-    // Build commitment: hash(user_pk, session_pk, session_id, encrypted_prompt, model, response_nonce, encrypted_response)
-    let query_commitment = commitment_openai::build_query_commitment(
-        &user_pk,
-        session_sk.verifying_key(),
-        session_id,
-        &req.encrypted_prompt,
-        &model,
-        req.temperature.unwrap_or(0.7),
-        req.max_tokens.unwrap_or(1000),
-        response_nonce,
-        &encrypted_response,
-    );
+    use super::*;
 
-    let resp = OpenAIQueryResponse {
-        session_id,
-        encrypted_response,
-        response_nonce: const_hex::encode(response_nonce),
-        model,
-        query_commitment: const_hex::encode(query_commitment),
-    };
+    /// Server answering `/openai/query` from a mock backend, and an encrypted query for it
+    struct QueryFixture {
+        server: axum_test::TestServer,
+        req: OpenAIQueryRequest,
+        cipher: aes_gcm_siv::Aes256GcmSiv,
+        user_pk: k256::ecdsa::VerifyingKey,
+        session_pk: k256::ecdsa::VerifyingKey,
+        session_id: Uuid,
+    }
 
-    Ok(Json(resp))
-}
+    impl QueryFixture {
+        /// `plaintext` encrypted for the session (hex-encoded), as requests carry it
+        fn encrypt(&self, plaintext: &[u8]) -> String {
+            let nonce = crypto::derive_msg_nonce(self.session_id);
+            const_hex::encode(self.cipher.encrypt(&nonce, plaintext).unwrap())
+        }
+    }
 
-/// Validate query request
-fn validate_query_request(request: &OpenAIQueryRequest) -> Result<(), HypervisorError> {
-    let validate = || -> anyhow::Result<()> {
-        // Validate encrypted_prompt
-        anyhow::ensure!(
-            !request.encrypted_prompt.trim().is_empty(),
-            "encrypted_prompt cannot be empty"
+    async fn query_fixture(backend: &MockOpenAI, prompt: &[u8]) -> QueryFixture {
+        query_fixture_with(crate::Config::default(), backend, prompt).await
+    }
+
+    /// `query_fixture` on a server with `config`
+    async fn query_fixture_with(
+        mut config: crate::Config,
+        backend: &MockOpenAI,
+        prompt: &[u8],
+    ) -> QueryFixture {
+        config.openai.api_base = backend.base_url.clone();
+        query_fixture_on(HypervisorState::new(config).unwrap(), prompt)
+    }
+
+    /// `query_fixture` on a server with `state`, whose backend is already configured
+    fn query_fixture_on(mut state: HypervisorState, prompt: &[u8]) -> QueryFixture {
+        let session_key_pairs = SessionKeyPairs::default();
+        state.set_session_key_pairs(session_key_pairs.clone());
+        let server =
+            axum_test::TestServer::new(Router::new().register_api(api_register).with_state(state))
+                .unwrap();
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let user_pk = *sk.verifying_key();
+        let (session_pk, session_id) = session_key_pairs.create(&user_pk);
+        let cipher = crypto::create_encrypt_key(&sk, &session_pk, session_id).unwrap();
+        let nonce = crypto::derive_msg_nonce(session_id);
+        let req = OpenAIQueryRequest {
+            encrypted_prompt: const_hex::encode(cipher.encrypt(&nonce, prompt).unwrap()),
+            public_key: crypto::pk_to_hex(&user_pk),
+            temperature: None,
+            max_tokens: Some(5),
+            n: None,
+            attest: false,
+        };
+
+        QueryFixture {
+            server,
+            req,
+            cipher,
+            user_pk,
+            session_pk,
+            session_id,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_tokens_clamped_to_ceiling() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, chat_completion("4"))).await;
+
+        let config = crate::Config {
+            max_tokens_ceiling: 500,
+            ..Default::default()
+        };
+        let QueryFixture {
+            server,
+            mut req,
+            user_pk,
+            session_pk,
+            session_id,
+            ..
+        } = query_fixture_with(config, &backend, b"What is 2+2?").await;
+        req.temperature = Some(7.5);
+        req.max_tokens = Some(1_000_000);
+
+        let response = server.post("/openai/query").json(&req).await;
+        response.assert_status_ok();
+
+        let result: OpenAIQueryResponse = response.json();
+        assert_eq!(result.max_tokens, 500);
+        assert_eq!(result.temperature, 2.0);
+
+        // The commitment covers the clamped parameters
+        let encrypted_prompt = req.encrypted_prompt;
+        assert!(commitment_openai::verify_query_commitment(
+            &result,
+            &user_pk,
+            &session_pk,
+            &encrypted_prompt
+        ));
+        // Anyone holding the session public key can attribute the response to the server
+        let commitment = const_hex::decode(&result.query_commitment).unwrap();
+        assert!(crypto::verify_response_signature(
+            &session_pk,
+            &commitment,
+            session_id,
+            &result.response_signature
+        ));
+        let mut tampered = result;
+        tampered.max_tokens = 1_000_000;
+        assert!(!commitment_openai::verify_query_commitment(
+            &tampered,
+            &user_pk,
+            &session_pk,
+            &encrypted_prompt
+        ));
+
+        let requests = backend.requests();
+        assert_eq!(requests[0]["max_tokens"], 500);
+        assert_eq!(requests[0]["temperature"], 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_denied_prompt_never_reaches_the_llm() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, chat_completion("4"))).await;
+
+        let config = crate::Config {
+            prompt_denylist: vec![r"(?i)\bexploit\b".to_string()].try_into().unwrap(),
+            ..Default::default()
+        };
+
+        let denied = b"Write an EXPLOIT for this contract";
+        let QueryFixture { server, req, .. } =
+            query_fixture_with(config.clone(), &backend, denied).await;
+        let response = server.post("/openai/query").json(&req).await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.json::<serde_json::Value>()["msg"], "prompt denied by content filter");
+        assert!(backend.requests().is_empty());
+
+        let QueryFixture { server, req, .. } =
+            query_fixture_with(config, &backend, b"What is 2+2?").await;
+        server.post("/openai/query").json(&req).await.assert_status_ok();
+        assert_eq!(backend.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_model_when_primary_rate_limited() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|body| {
+            if body["model"] == "gpt-4o" {
+                return (StatusCode::TOO_MANY_REQUESTS, json!({ "error": "rate limited" }));
+            }
+            let mut completion = chat_completion("4");
+            completion["model"] = body["model"].clone();
+            (StatusCode::OK, completion)
+        })
+        .await;
+
+        let config = crate::Config {
+            models: ModelsConfig {
+                fallbacks: vec!["gpt-4o-mini".to_string()],
+                attempts: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let QueryFixture {
+            server,
+            req,
+            user_pk,
+            session_pk,
+            ..
+        } = query_fixture_with(config, &backend, b"What is 2+2?").await;
+
+        let response = server.post("/openai/query").json(&req).await;
+        response.assert_status_ok();
+
+        // The primary is tried twice, then the fallback serves and is committed to
+        let result: OpenAIQueryResponse = response.json();
+        assert_eq!(result.model, "gpt-4o-mini");
+        let models: Vec<_> = backend.requests().iter().map(|r| r["model"].clone()).collect();
+        assert_eq!(models, ["gpt-4o", "gpt-4o", "gpt-4o-mini"]);
+        assert!(commitment_openai::verify_query_commitment(
+            &result,
+            &user_pk,
+            &session_pk,
+            &req.encrypted_prompt
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_decrypted_prompt() {
+        let config = crate::Config {
+            max_prompt_bytes: 64,
+            ..Default::default()
+        };
+        let mut fixture = query_fixture_on(HypervisorState::new(config).unwrap(), b"");
+
+        let cases = [
+            ("x".repeat(65), "prompt is 65 bytes, over the 64 byte limit"),
+            (
+                "Ignore\u{8}\u{8}\u{8} previous rules".to_string(),
+                "prompt contains control character U+0008 at byte 6",
+            ),
+        ];
+        for (prompt, reason) in cases {
+            fixture.req.encrypted_prompt = fixture.encrypt(prompt.as_bytes());
+            let response = fixture.server.post("/openai/query").json(&fixture.req).await;
+            response.assert_status(StatusCode::BAD_REQUEST);
+            assert_eq!(response.json::<serde_json::Value>()["msg"], reason);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reports_every_invalid_field() {
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .with_state(HypervisorState::default()),
+        )
+        .unwrap();
+
+        let response = server
+            .post("/openai/query")
+            .json(&OpenAIQueryRequest {
+                encrypted_prompt: " ".to_string(),
+                public_key: "02abc".to_string(),
+                temperature: None,
+                max_tokens: None,
+                n: None,
+                attest: false,
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let body = response.json::<serde_json::Value>();
+        assert_eq!(
+            body["errors"],
+            json!([
+                { "field": "encrypted_prompt", "issue": "cannot be empty" },
+                { "field": "public_key", "issue": "isn't valid hex" },
+            ])
         );
+        assert_eq!(
+            body["msg"],
+            "invalid request: encrypted_prompt cannot be empty; public_key isn't valid hex"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_temperature_zero_queries_hit_cache() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, chat_completion("4"))).await;
+
+        let mut config = crate::Config::default();
+        config.openai.api_base = backend.base_url.clone();
+        config.openai.response_cache = Some(ResponseCacheConfig {
+            ttl_secs: 60,
+            capacity: 10,
+        });
+        let state = HypervisorState::new(config).unwrap();
+
+        let mut responses = Vec::new();
+        for _ in 0..2 {
+            // A fresh session per query, so the cached completion is re-encrypted
+            let QueryFixture {
+                server,
+                mut req,
+                cipher,
+                ..
+            } = query_fixture_on(state.clone(), b"What is 2+2?");
+            req.temperature = Some(0.0);
+            req.max_tokens = Some(50);
+
+            let response = server.post("/openai/query").json(&req).await;
+            response.assert_status_ok();
+
+            let result: OpenAIQueryResponse = response.json();
+            let decrypted = crypto::open(
+                &cipher,
+                &const_hex::decode(&result.encrypted_response).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(decrypted, b"4");
+            responses.push(result);
+        }
+
+        assert_eq!(backend.requests().len(), 1);
+        assert_ne!(responses[0].encrypted_response, responses[1].encrypted_response);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_completion_reports_length() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| {
+            (StatusCode::OK, truncated_completion("Quantum computers"))
+        })
+        .await;
+        let QueryFixture {
+            server, req, cipher, ..
+        } = query_fixture(&backend, b"Explain quantum computing").await;
+
+        let response = server.post("/openai/query").json(&req).await;
+        response.assert_status_ok();
+
+        let result: OpenAIQueryResponse = response.json();
+        assert_eq!(result.finish_reason, FinishReason::Length);
+        let decrypted =
+            crypto::open(&cipher, &const_hex::decode(&result.encrypted_response).unwrap());
+        assert_eq!(decrypted.unwrap(), b"Quantum computers");
+        assert_eq!(response.json::<serde_json::Value>()["finish_reason"], "length");
+    }
+
+    #[tokio::test]
+    async fn test_content_filtered_completion_is_an_error() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, content_filtered_completion())).await;
+        let QueryFixture { server, req, .. } =
+            query_fixture(&backend, b"Something disallowed").await;
 
-        // Validate public_key
-        anyhow::ensure!(
-            !request.public_key.trim().is_empty(),
-            "public_key cannot be empty"
+        let response = server.post("/openai/query").json(&req).expect_failure().await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.json::<serde_json::Value>()["msg"],
+            "OpenAI completion was blocked by the content filter"
         );
 
-        Ok(())
-    };
+        // A refusal in the message is reported the same way
+        let refusal = json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": null, "refusal": "I can't help" },
+                "finish_reason": "stop"
+            }]
+        });
+        let err = models::parse_completion(&refusal).unwrap_err();
+        assert!(err.is_refusal());
+        assert_eq!(err.to_string(), "model refused to answer: I can't help");
+    }
 
-    validate().context(StatusCode::BAD_REQUEST)?;
+    #[tokio::test]
+    async fn test_candidates_are_sealed_independently_and_committed() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        // One choice per requested candidate, listed out of order
+        let backend = MockOpenAI::spawn(|body| {
+            let choices: Vec<_> = (0..body["n"].as_u64().unwrap())
+                .rev()
+                .map(|i| {
+                    json!({
+                        "index": i,
+                        "message": { "role": "assistant", "content": format!("Answer {i}") },
+                        "finish_reason": "stop"
+                    })
+                })
+                .collect();
+            (StatusCode::OK, json!({ "model": "gpt-4o-mini", "choices": choices }))
+        })
+        .await;
 
-    Ok(())
-}
+        let mut config = crate::Config::default();
+        config.openai.max_candidates = 2;
+        let QueryFixture {
+            server,
+            mut req,
+            cipher,
+            user_pk,
+            session_pk,
+            ..
+        } = query_fixture_with(config, &backend, b"Name a color").await;
+        req.temperature = Some(1.0);
+        req.n = Some(5);
 
-#[cfg(test)]
-mod tests {
-    use aes_gcm_siv::aead::Aead;
+        let response = server.post("/openai/query").json(&req).await;
+        response.assert_status_ok();
+        // `n` is capped at max_candidates
+        assert_eq!(backend.requests()[0]["n"], 2);
 
-    use crate::utils::crypto;
-    use crate::{api::RouterRegister, types::SessionKeyPairs};
+        let mut result: OpenAIQueryResponse = response.json();
+        assert_eq!(result.additional_candidates.len(), 1);
+        let open = |encrypted: &str| crypto::open(&cipher, &const_hex::decode(encrypted).unwrap());
+        assert_eq!(open(&result.encrypted_response).unwrap(), b"Answer 0");
+        let second = &result.additional_candidates[0];
+        assert_eq!(second.finish_reason, FinishReason::Stop);
+        assert_eq!(open(&second.encrypted_response).unwrap(), b"Answer 1");
 
-    use super::*;
+        // Every candidate is covered by the commitment
+        assert!(commitment_openai::verify_query_commitment(
+            &result,
+            &user_pk,
+            &session_pk,
+            &req.encrypted_prompt
+        ));
+        result.additional_candidates.clear();
+        assert!(!commitment_openai::verify_query_commitment(
+            &result,
+            &user_pk,
+            &session_pk,
+            &req.encrypted_prompt
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_quote_only_when_attest_is_set() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, chat_completion("4"))).await;
+        let mut config = crate::Config::default();
+        config.openai.api_base = backend.base_url.clone();
+        let mut state = HypervisorState::new(config).unwrap();
+        state.set_quote_source(mock_attest_quote);
+        let QueryFixture {
+            server,
+            mut req,
+            session_pk,
+            session_id,
+            ..
+        } = query_fixture_on(state, b"2+2?");
+
+        let response = server.post("/openai/query").json(&req).await;
+        response.assert_status_ok();
+        let body = response.json::<serde_json::Value>();
+        assert!(body.get("quote").is_none() && body.get("quote_signature").is_none());
+
+        // With `attest` the quote is over the commitment and bound to the caller's session
+        req.attest = true;
+        let response = server.post("/openai/query").json(&req).await;
+        response.assert_status_ok();
+        let result: OpenAIQueryResponse = response.json();
+        let quote =
+            QuoteCompression::decode(result.quote_compression, &result.quote.unwrap()).unwrap();
+        let report_data = attest::types::Quote::from_bytes(&quote).unwrap().report_data();
+        assert_eq!(report_data[..32], const_hex::decode(&result.query_commitment).unwrap());
+        assert!(crypto::verify_quote_binding(
+            &session_pk,
+            &quote,
+            session_id,
+            &result.quote_signature.unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_counts_prompt_tokens_without_calling_openai() {
+        let mut config = crate::Config::default();
+        // Nothing listens here, so a completion request would fail the estimate
+        config.openai.api_base = "http://127.0.0.1:1".to_string();
+        config.max_tokens_ceiling = 500;
+        config.openai.pricing.insert(
+            "gpt-4o".to_string(),
+            ModelPricing {
+                prompt_usd_per_million: 2.5,
+                completion_usd_per_million: 10.0,
+            },
+        );
+        let QueryFixture {
+            server, mut req, ..
+        } = query_fixture_on(HypervisorState::new(config).unwrap(), b"Hello world");
+        req.max_tokens = Some(1_000_000);
+
+        let response = server.post("/openai/estimate").json(&req).await;
+        response.assert_status_ok();
+
+        // "Hello" and " world", plus 7 tokens of chat framing
+        let estimate: OpenAIEstimateResponse = response.json();
+        assert_eq!(estimate.model, "gpt-4o");
+        assert_eq!(estimate.prompt_tokens, 9);
+        assert_eq!(estimate.max_tokens, 500);
+        let max_cost = estimate.max_cost_usd.unwrap();
+        assert!((max_cost - (9.0 * 2.5 + 500.0 * 10.0) / 1e6).abs() < 1e-12);
+
+        // gpt-4-turbo uses cl100k_base; unknown models are counted with o200k_base
+        assert_eq!(count_prompt_tokens("gpt-4-turbo", "Hello world"), 9);
+        assert_eq!(count_prompt_tokens("unknown-model", "Hello world"), 9);
+    }
+
+    #[test]
+    fn test_response_cache_only_for_temperature_zero() {
+        assert!(!ResponseCache::default().enabled_for(0.0));
+
+        let cache = ResponseCache::new(Some(ResponseCacheConfig {
+            ttl_secs: 60,
+            capacity: 10,
+        }));
+        assert!(cache.enabled_for(0.0));
+        assert!(!cache.enabled_for(0.7));
+    }
 
     #[tokio::test]
     #[ignore] // Requires OPENAI_API_KEY
@@ -313,24 +1306,19 @@ mod tests {
                 public_key: crypto::pk_to_hex(user_pk),
                 temperature: Some(0.0),
                 max_tokens: Some(50),
+                n: None,
+                attest: false,
             })
             .await;
 
         response.assert_status_ok();
 
         let result: OpenAIQueryResponse = response.json();
-        let response_nonce = *aes_gcm_siv::Nonce::from_slice(
-            &const_hex::decode(result.response_nonce).unwrap(),
-        );
-
-        let decrypted_response = cipher
-            .decrypt(
-                &response_nonce,
-                const_hex::decode(&result.encrypted_response)
-                    .unwrap()
-                    .as_slice(),
-            )
-            .unwrap();
+        let decrypted_response = crypto::open(
+            &cipher,
+            &const_hex::decode(&result.encrypted_response).unwrap(),
+        )
+        .unwrap();
 
         let response_text = String::from_utf8(decrypted_response).unwrap();
         println!("Response: {}", response_text);
@@ -366,24 +1354,19 @@ mod tests {
                 public_key: crypto::pk_to_hex(user_pk),
                 temperature: Some(0.7),
                 max_tokens: Some(100),
+                n: None,
+                attest: false,
             })
             .await;
 
         response.assert_status_ok();
 
         let result: VerifiableOpenAIQueryResponse = response.json();
-        let response_nonce = *aes_gcm_siv::Nonce::from_slice(
-            &const_hex::decode(result.response_nonce).unwrap(),
-        );
-
-        let decrypted_response = cipher
-            .decrypt(
-                &response_nonce,
-                const_hex::decode(&result.encrypted_response)
-                    .unwrap()
-                    .as_slice(),
-            )
-            .unwrap();
+        let decrypted_response = crypto::open(
+            &cipher,
+            &const_hex::decode(&result.encrypted_response).unwrap(),
+        )
+        .unwrap();
 
         let response_text = String::from_utf8(decrypted_response).unwrap();
         println!("Response: {}", response_text);
@@ -0,0 +1,154 @@
+use anyhow::Context;
+use attest::types::RawReport;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::quote::QuoteCompression,
+    error::HypervisorError,
+    types::HypervisorState,
+    utils::attest::{ReportDataBuilder, POLICY_DOMAIN},
+};
+
+pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
+    router.route("/verifiable/policy", get(verifiable_policy))
+}
+
+/// Policies the TEE enforces, attested so clients can check them before sending queries
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifiablePolicyResponse {
+    /// Hash of the policies, the tools they apply to and the compliance methods switched
+    /// off (hex-encoded), as reported in compliance decisions
+    pub policy_hash: String,
+    /// Policy IDs
+    pub policies: Vec<String>,
+    /// TEE quote over the policy hash (hex-encoded), see `policy_report`
+    pub quote: String,
+    /// Compression of `quote` before hex encoding, see `QuoteCompression::decode`;
+    /// plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
+}
+
+#[tracing::instrument(skip_all, err)]
+async fn verifiable_policy(
+    State(state): State<HypervisorState>,
+) -> Result<Json<VerifiablePolicyResponse>, HypervisorError> {
+    policy_quote(&state, |report| state.tee_quote(report)).map(Json)
+}
+
+/// Quote from `get_quote` over the hash of the policies currently in force
+///
+/// Read per request, so a reload through `POST /admin/policies/reload` shows up here.
+fn policy_quote(
+    state: &HypervisorState,
+    get_quote: impl FnOnce(RawReport) -> anyhow::Result<Vec<u8>>,
+) -> Result<VerifiablePolicyResponse, HypervisorError> {
+    let registry = state.policy_registry();
+    let policy_hash = registry.policy_hash();
+
+    let quote = get_quote(policy_report(&policy_hash))
+        .context("get policy quote")
+        .context(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let compression = state.config.quote_compression;
+
+    Ok(VerifiablePolicyResponse {
+        policy_hash: const_hex::encode(policy_hash),
+        policies: registry.policies().iter().map(|p| p.id.clone()).collect(),
+        quote: compression
+            .encode(&quote)
+            .context(StatusCode::INTERNAL_SERVER_ERROR)?,
+        quote_compression: compression.field(),
+    })
+}
+
+/// Report attested for the enforced policies
+pub fn policy_report(policy_hash: &[u8; 32]) -> RawReport {
+    ReportDataBuilder::new(POLICY_DOMAIN).field(policy_hash).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use attest::types::Quote;
+
+    use super::*;
+    use crate::{
+        agent::{ComplianceChecker, ComplianceMethod},
+        api::RouterRegister,
+        test_utils::write_policy_file,
+        Config,
+    };
+
+    #[test]
+    fn test_quote_commits_to_the_policy_hash() {
+        let path = std::env::temp_dir().join(format!("policies_{}.toml", uuid::Uuid::now_v7()));
+        write_policy_file(&path, &["to the moon"]);
+        let mut config = Config::default();
+        config.agent.policy_file = Some(path.clone());
+        let state = HypervisorState::new(config).unwrap();
+        let mock_quote = |report: RawReport| Ok(report.to_bytes().to_vec());
+
+        let before = policy_quote(&state, mock_quote).unwrap();
+        assert_eq!(before.policies, ["H1"]);
+        let policy_hash: [u8; 32] = const_hex::decode_to_array(&before.policy_hash).unwrap();
+        assert_eq!(
+            const_hex::decode(&before.quote).unwrap(),
+            policy_report(&policy_hash).to_bytes()
+        );
+        let checker = ComplianceChecker::from_registry(&state.policy_registry());
+        assert_eq!(before.policy_hash, checker.policy_hash());
+
+        write_policy_file(&path, &["all-time high"]);
+        state.reload_policies().unwrap();
+        let after = policy_quote(&state, mock_quote).unwrap();
+        assert_ne!(after.policy_hash, before.policy_hash);
+        assert_ne!(after.quote, before.quote);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_quote_changes_when_enforcement_is_switched_off() {
+        let quote = |config: Config| {
+            let state = HypervisorState::new(config).unwrap();
+            policy_quote(&state, |report| Ok(report.to_bytes().to_vec())).unwrap()
+        };
+        let enforced = quote(Config::default());
+
+        // PriceFeedTool calls no longer checked against any policy
+        let mut unmapped = Config::default();
+        unmapped.agent.tool_policies.insert("PriceFeedTool".to_string(), vec![]);
+        let unmapped = quote(unmapped);
+
+        let mut llm_off = Config::default();
+        llm_off.agent.disabled_compliance_methods.global = vec![ComplianceMethod::LLMBased];
+        let llm_off = quote(llm_off);
+
+        // The policies themselves are unchanged, but the hash and quote are not
+        for switched_off in [&unmapped, &llm_off] {
+            assert_eq!(switched_off.policies, enforced.policies);
+            assert_ne!(switched_off.policy_hash, enforced.policy_hash);
+            assert_ne!(switched_off.quote, enforced.quote);
+        }
+        assert_ne!(unmapped.policy_hash, llm_off.policy_hash);
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires TEE environment
+    async fn test_policy_quote_from_the_tee() {
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(api_register)
+                .with_state(HypervisorState::default()),
+        )
+        .unwrap();
+
+        let response: VerifiablePolicyResponse = server.get("/verifiable/policy").await.json();
+        let quote = Quote::from_bytes(&const_hex::decode(&response.quote).unwrap()).unwrap();
+        let policy_hash = const_hex::decode_to_array(&response.policy_hash).unwrap();
+        assert_eq!(quote.report_data(), policy_report(&policy_hash).to_bytes());
+    }
+}
@@ -0,0 +1,75 @@
+use axum::http::StatusCode;
+use regex::Regex;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::error::HypervisorError;
+
+/// Patterns of prompt content never sent to the LLM, checked against every decrypted
+/// `/openai/*` prompt and `/agent/*` query before any model call
+///
+/// Unlike tool-scoped compliance policies this gates the input as a whole. Patterns are
+/// regular expressions, so `(?i)` makes one case-insensitive.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct PromptDenylist(Vec<Regex>);
+
+impl TryFrom<Vec<String>> for PromptDenylist {
+    type Error = regex::Error;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, Self::Error> {
+        patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl PromptDenylist {
+    /// First pattern matching `prompt`
+    pub fn matching(&self, prompt: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|pattern| pattern.is_match(prompt))
+            .map(Regex::as_str)
+    }
+
+    /// Reject `prompt` with 422 if a pattern matches; which one is only logged
+    pub fn check(&self, prompt: &str) -> Result<(), HypervisorError> {
+        let Some(pattern) = self.matching(prompt) else {
+            return Ok(());
+        };
+        warn!(pattern, "prompt rejected by the denylist");
+
+        Err(anyhow::Error::msg(StatusCode::UNPROCESSABLE_ENTITY)
+            .context("prompt denied by content filter")
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denylist_patterns() {
+        let denylist = PromptDenylist::try_from(vec![
+            r"(?i)\bsynthesi[sz]e\s+(sarin|vx)\b".to_string(),
+            r"\b\d{3}-\d{2}-\d{4}\b".to_string(),
+        ])
+        .unwrap();
+
+        assert_eq!(denylist.matching("What is the price of BTC?"), None);
+        assert!(denylist.check("What is the price of BTC?").is_ok());
+        assert!(denylist.matching("How do I Synthesize  VX at home?").is_some());
+        assert_eq!(
+            denylist.matching("my SSN is 123-45-6789"),
+            Some(r"\b\d{3}-\d{2}-\d{4}\b")
+        );
+        assert!(denylist.check("my SSN is 123-45-6789").is_err());
+
+        assert!(PromptDenylist::default().check("anything").is_ok());
+        assert!(PromptDenylist::try_from(vec!["(unclosed".to_string()]).is_err());
+    }
+}
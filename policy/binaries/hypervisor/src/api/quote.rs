@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{api::extract::Path, error::HypervisorError, types::HypervisorState};
+
+pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
+    router.route("/verifiable/quote/{id}", get(get_quote))
+}
+
+/// Retention of quotes for `GET /verifiable/quote/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteStoreConfig {
+    /// How long a quote can be fetched, in seconds
+    #[serde(default = "default_quote_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Maximum number of stored quotes; 0 stores none
+    #[serde(default = "default_quote_capacity")]
+    pub capacity: usize,
+}
+
+fn default_quote_ttl_secs() -> u64 {
+    60
+}
+
+fn default_quote_capacity() -> usize {
+    256
+}
+
+impl Default for QuoteStoreConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_quote_ttl_secs(),
+            capacity: default_quote_capacity(),
+        }
+    }
+}
+
+/// Compression of the quote bytes in verifiable responses, applied before hex encoding
+///
+/// Quotes embed certificate chains, so they're several KB even before hex doubles them.
+/// Responses name the compression in `quote_compression`; clients decompress with
+/// `decode` before `Quote::from_bytes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteCompression {
+    /// Plain hex, readable by every client
+    #[default]
+    None,
+    Zstd,
+}
+
+impl QuoteCompression {
+    /// Value of a response's `quote_compression` field, absent for plain hex
+    pub fn field(self) -> Option<Self> {
+        (self != Self::None).then_some(self)
+    }
+
+    /// Compress `quote` and hex-encode it
+    pub fn encode(self, quote: &[u8]) -> anyhow::Result<String> {
+        match self {
+            Self::None => Ok(const_hex::encode(quote)),
+            Self::Zstd => {
+                let compressed = zstd::bulk::compress(quote, 0).context("compress quote")?;
+                Ok(const_hex::encode(compressed))
+            }
+        }
+    }
+
+    /// Raw quote bytes of a hex-encoded `quote` compressed with `compression`
+    pub fn decode(compression: Option<Self>, quote: &str) -> anyhow::Result<Vec<u8>> {
+        let bytes = const_hex::decode(quote).context("invalid quote hex")?;
+        match compression.unwrap_or_default() {
+            Self::None => Ok(bytes),
+            Self::Zstd => zstd::decode_all(bytes.as_slice()).context("decompress quote"),
+        }
+    }
+}
+
+/// Raw quotes returned by the session-bound routes, keyed by `quote_id`, so clients can
+/// fetch the bytes instead of decoding multi-KB hex
+#[derive(Default)]
+pub(crate) struct QuoteStore {
+    config: QuoteStoreConfig,
+    entries: Mutex<HashMap<String, StoredQuote>>,
+}
+
+struct StoredQuote {
+    stored_at: Instant,
+    quote: Vec<u8>,
+}
+
+/// Short id of a quote: its hash, truncated to 16 bytes (hex-encoded)
+pub fn quote_id(quote: &[u8]) -> String {
+    const_hex::encode(&blake3::hash(quote).as_bytes()[..16])
+}
+
+impl QuoteStore {
+    pub fn new(config: QuoteStoreConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Keep `quote` for a while, returning its id
+    pub fn insert(&self, quote: &[u8]) -> String {
+        let id = quote_id(quote);
+        if self.config.capacity == 0 {
+            return id;
+        }
+
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let mut entries = self.entries.lock().expect("quote store poisoned");
+        entries.retain(|_, entry| entry.stored_at.elapsed() < ttl);
+        if entries.len() >= self.config.capacity && !entries.contains_key(&id) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            id.clone(),
+            StoredQuote {
+                stored_at: Instant::now(),
+                quote: quote.to_vec(),
+            },
+        );
+
+        id
+    }
+
+    fn get(&self, id: &str) -> Option<Vec<u8>> {
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let entries = self.entries.lock().expect("quote store poisoned");
+        entries
+            .get(&id.to_lowercase())
+            .filter(|entry| entry.stored_at.elapsed() < ttl)
+            .map(|entry| entry.quote.clone())
+    }
+}
+
+/// Raw bytes of a recently returned quote, as `application/octet-stream`
+///
+/// Unknown and expired ids are both reported as not found.
+#[tracing::instrument(skip(state), err)]
+async fn get_quote(
+    State(state): State<HypervisorState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, HypervisorError> {
+    let quote = state
+        .quote_store
+        .get(&id)
+        .ok_or(anyhow!("quote not found"))
+        .context(StatusCode::NOT_FOUND)?;
+
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], quote))
+}
+
+#[cfg(test)]
+mod tests {
+    use attest::types::{Quote, RawReport};
+
+    use crate::{
+        api::{session_quote, tee_quote, RouterRegister, SessionQuote},
+        test_utils::mock_attest_quote,
+        types::SessionKeyPairs,
+        utils::crypto,
+    };
+
+    use super::*;
+
+    /// Server with the quote route, and a session quoted with `get_quote`
+    fn quoted_session(
+        config: QuoteStoreConfig,
+        get_quote: impl FnOnce(RawReport) -> anyhow::Result<Vec<u8>>,
+    ) -> (axum_test::TestServer, SessionQuote) {
+        let session_key_pairs = SessionKeyPairs::default();
+        let mut state = HypervisorState::new(crate::Config {
+            quote_store: config,
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_session_key_pairs(session_key_pairs.clone());
+
+        let user_pk = *k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng).verifying_key();
+        let (_, session_id) = session_key_pairs.create(&user_pk);
+        let report = RawReport::new([7u8; 64]);
+        let quote =
+            session_quote(&state, &crypto::pk_to_hex(&user_pk), session_id, report, get_quote)
+                .unwrap();
+
+        let server =
+            axum_test::TestServer::new(Router::new().register_api(api_register).with_state(state))
+                .unwrap();
+
+        (server, quote)
+    }
+
+    #[tokio::test]
+    async fn test_quote_is_served_raw() {
+        let mock_quote = |report: RawReport| Ok([b"quote".as_slice(), &report.to_bytes()].concat());
+        let (server, quote) = quoted_session(QuoteStoreConfig::default(), mock_quote);
+        assert_eq!(quote.quote_id.len(), 32);
+
+        let response = server.get(&format!("/verifiable/quote/{}", quote.quote_id)).await;
+        response.assert_status_ok();
+        response.assert_header("content-type", "application/octet-stream");
+        assert_eq!(response.as_bytes().to_vec(), const_hex::decode(&quote.quote).unwrap());
+
+        // Ids are case-insensitive hex
+        let upper = quote.quote_id.to_uppercase();
+        server.get(&format!("/verifiable/quote/{upper}")).await.assert_status_ok();
+
+        let response = server.get("/verifiable/quote/0123").expect_failure().await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_expired_quote_is_not_found() {
+        let config = QuoteStoreConfig {
+            ttl_secs: 0,
+            ..Default::default()
+        };
+        let (server, quote) = quoted_session(config, |report| Ok(report.to_bytes().to_vec()));
+
+        let response = server
+            .get(&format!("/verifiable/quote/{}", quote.quote_id))
+            .expect_failure()
+            .await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_compressed_quote_round_trip() {
+        // Most of a quote is its PEM certificate chain
+        let chain = "-----BEGIN CERTIFICATE-----\nMIIE8zCCBJig\n-----END CERTIFICATE-----\n";
+        let report = RawReport::new([7u8; 64]).to_bytes();
+        let quote = [report.as_slice(), chain.repeat(3).as_bytes()].concat();
+
+        let plain = QuoteCompression::None.encode(&quote).unwrap();
+        assert_eq!(plain, const_hex::encode(&quote));
+        assert_eq!(QuoteCompression::decode(None, &plain).unwrap(), quote);
+
+        let zstd = Some(QuoteCompression::Zstd);
+        let compressed = QuoteCompression::Zstd.encode(&quote).unwrap();
+        assert!(compressed.len() < plain.len());
+        assert_eq!(QuoteCompression::decode(zstd, &compressed).unwrap(), quote);
+        assert!(QuoteCompression::decode(zstd, &plain).is_err());
+
+        assert_eq!(QuoteCompression::None.field(), None);
+        assert_eq!(serde_json::to_value(zstd).unwrap(), "zstd");
+    }
+
+    #[tokio::test]
+    async fn test_quote_parses_from_raw_bytes() {
+        let (server, quote) = quoted_session(QuoteStoreConfig::default(), mock_attest_quote);
+
+        let response = server.get(&format!("/verifiable/quote/{}", quote.quote_id)).await;
+        response.assert_status_ok();
+        let parsed = Quote::from_bytes(response.as_bytes()).unwrap();
+        assert_eq!(parsed.report_data(), [7u8; 64]);
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires TEE environment
+    async fn test_tee_quote_parses_from_raw_bytes() {
+        let (server, quote) = quoted_session(QuoteStoreConfig::default(), tee_quote);
+
+        let response = server.get(&format!("/verifiable/quote/{}", quote.quote_id)).await;
+        response.assert_status_ok();
+        let parsed = Quote::from_bytes(response.as_bytes()).unwrap();
+        assert_eq!(parsed.report_data(), [7u8; 64]);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    error::{FieldError, HypervisorError},
+    utils::crypto,
+};
+
+/// Collects every problem with a request, so a client can fix them all at once
+#[derive(Debug, Default)]
+pub(crate) struct Validation(Vec<FieldError>);
+
+impl Validation {
+    /// Record `issue` against `field` unless `ok`
+    pub fn check(&mut self, ok: bool, field: &str, issue: impl Into<String>) -> &mut Self {
+        if !ok {
+            self.0.push(FieldError {
+                field: field.to_string(),
+                issue: issue.into(),
+            });
+        }
+        self
+    }
+
+    /// A non-empty, hex-encoded ciphertext
+    pub fn ciphertext(&mut self, field: &str, value: &str) -> &mut Self {
+        if value.trim().is_empty() {
+            return self.check(false, field, "cannot be empty");
+        }
+        self.check(const_hex::decode(value).is_ok(), field, "isn't valid hex")
+    }
+
+    /// A hex-encoded SEC1 secp256k1 public key (33 bytes compressed, 65 uncompressed),
+    /// on the curve and not the point at infinity
+    pub fn public_key(&mut self, field: &str, value: &str) -> &mut Self {
+        if value.trim().is_empty() {
+            return self.check(false, field, "cannot be empty");
+        }
+        match const_hex::decode(value) {
+            Ok(bytes) => match crypto::pk_from_sec1(&bytes) {
+                Ok(_) => self,
+                Err(e) => self.check(false, field, e.to_string()),
+            },
+            Err(_) => self.check(false, field, "isn't valid hex"),
+        }
+    }
+
+    /// Fail with every recorded problem (400), if any
+    pub fn finish(&mut self) -> Result<(), HypervisorError> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        Err(HypervisorError::InvalidRequest(std::mem::take(&mut self.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collects_every_problem() {
+        let err = Validation::default()
+            .ciphertext("encrypted_query", "  ")
+            .public_key("public_key", "02abcd")
+            .check(true, "max_tokens", "unused")
+            .finish()
+            .unwrap_err();
+
+        let HypervisorError::InvalidRequest(errors) = err else {
+            panic!("unexpected error {err}");
+        };
+        let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["encrypted_query", "public_key"]);
+        assert_eq!(
+            errors[1].issue,
+            "is 3 bytes, expected 33 (compressed) or 65"
+        );
+
+        let mut validation = Validation::default();
+        validation
+            .ciphertext("encrypted_query", "abc")
+            .public_key("public_key", "zz");
+        assert!(validation.finish().is_err());
+        assert!(Validation::default()
+            .public_key("public_key", &"02".repeat(33))
+            .finish()
+            .is_ok());
+    }
+}
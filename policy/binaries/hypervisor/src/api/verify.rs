@@ -0,0 +1,134 @@
+use anyhow::Context;
+use attest::{
+    types::{QuoteVersion, TeeType},
+    verify::{Collateral, TcbStatus},
+};
+use axum::{extract::State, http::StatusCode, routing::post, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{extract::Json, quote::QuoteCompression},
+    error::HypervisorError,
+    types::HypervisorState,
+    utils::verify::{self, VerifyOutcome},
+};
+
+pub(crate) fn api_register(router: Router<HypervisorState>) -> Router<HypervisorState> {
+    router.route("/verifiable/verify", post(verify_quote))
+}
+
+/// Request to verify a quote returned by one of the verifiable endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyQuoteRequest {
+    /// Quote (hex-encoded)
+    pub quote: String,
+    /// Compression of `quote`, as in the response that carried it; plain hex when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_compression: Option<QuoteCompression>,
+    /// Collateral to evaluate the quote's TCB against
+    pub collateral: Collateral,
+}
+
+/// Result of verifying a quote
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyQuoteResponse {
+    /// Quote format version
+    pub version: QuoteVersion,
+    /// Whether an SGX enclave or a TDX guest produced the quote
+    pub tee_type: TeeType,
+    /// Report data attested by the quote (hex-encoded)
+    pub report_data: String,
+    /// TCB status of the TEE that produced the quote; `unverified` while the collateral's
+    /// signatures and the PCK chain aren't checked
+    pub tcb_status: TcbStatus,
+    /// Security advisories affecting the platform's TCB level, per the supplied collateral
+    pub advisory_ids: Vec<String>,
+    /// Date of the matched TCB level
+    pub tcb_date: String,
+}
+
+#[tracing::instrument(skip(state, req), err)]
+async fn verify_quote(
+    State(state): State<HypervisorState>,
+    Json(req): Json<VerifyQuoteRequest>,
+) -> Result<Json<VerifyQuoteResponse>, HypervisorError> {
+    let quote = QuoteCompression::decode(req.quote_compression, &req.quote)
+        .context(StatusCode::BAD_REQUEST)?;
+    let quote = match verify::verify_quote(&quote, None, &state.config.expected_measurements) {
+        VerifyOutcome::Verified(quote) => quote,
+        VerifyOutcome::BadFormat(e) => {
+            return Err(anyhow::Error::msg(StatusCode::BAD_REQUEST)
+                .context(format!("parse quote {e}"))
+                .into())
+        }
+        outcome => {
+            return Err(anyhow::Error::msg(StatusCode::UNPROCESSABLE_ENTITY)
+                .context(format!("check quote {outcome}"))
+                .into())
+        }
+    };
+
+    let result = quote
+        .verify(&req.collateral)
+        .context("verify quote")
+        .context(StatusCode::UNPROCESSABLE_ENTITY)?;
+
+    Ok(Json(VerifyQuoteResponse {
+        version: result.version,
+        tee_type: result.tee_type,
+        report_data: const_hex::encode(result.report_data),
+        tcb_status: result.tcb_status,
+        advisory_ids: result.advisory_ids,
+        tcb_date: result.tcb_date,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{api::RouterRegister, test_utils::synthetic_td_quote, Config};
+
+    #[tokio::test]
+    async fn test_tcb_status_from_supplied_collateral_is_unverified() {
+        let state = HypervisorState::new(Config::default()).unwrap();
+        let server =
+            axum_test::TestServer::new(Router::new().register_api(api_register).with_state(state))
+                .unwrap();
+        let quote = QuoteCompression::Zstd.encode(&synthetic_td_quote([7; 64])).unwrap();
+
+        // Collateral claiming the platform is up to date
+        let components = |svn: u8| vec![json!({ "svn": svn }); 16];
+        let collateral = json!({
+            "tcb_info": {
+                "tcbInfo": {
+                    "id": "TDX",
+                    "version": 3,
+                    "fmspc": "00806f050000",
+                    "tcbLevels": [{
+                        "tcb": {
+                            "sgxtcbcomponents": components(0),
+                            "pcesvn": 0,
+                            "tdxtcbcomponents": components(0),
+                        },
+                        "tcbDate": "2024-03-13T00:00:00Z",
+                        "tcbStatus": "UpToDate",
+                    }],
+                },
+                "signature": "00",
+            },
+            "pck_tcb": { "sgx_tcb_components": vec![0u8; 16], "pce_svn": 0 },
+        });
+
+        let response = server
+            .post("/verifiable/verify")
+            .json(&json!({ "quote": quote, "quote_compression": "zstd", "collateral": collateral }))
+            .await;
+        response.assert_status_ok();
+        let verified: VerifyQuoteResponse = response.json();
+        assert_eq!(verified.tee_type, TeeType::Tdx);
+        assert_eq!(verified.report_data, const_hex::encode([7; 64]));
+        assert_eq!(verified.tcb_status, TcbStatus::Unverified);
+    }
+}
@@ -0,0 +1,145 @@
+//! Client side of the session protocol, for SDKs talking to the hypervisor
+//!
+//! After `POST /encrypt/create_keypair` returns the session's public key and id, a client
+//! derives the session cipher, seals its query into `encrypted_prompt` (or
+//! `encrypted_query`) and opens the `encrypted_response` it gets back. Events of
+//! `/agent/query/stream` are opened in order with a `StreamOpener`, see `open_stream_event`.
+
+use aes_gcm_siv::aead::Aead;
+use anyhow::{anyhow, Context};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use uuid::Uuid;
+
+use crate::utils::crypto;
+
+pub use aes_gcm_siv::Aes256GcmSiv as SessionCipher;
+
+pub use crate::utils::crypto::{StreamError, StreamOpener};
+
+/// Cipher shared with the session: ECDH of `user_sk` and `session_pk`, expanded with
+/// HKDF salted by `session_id`
+pub fn create_cipher(
+    user_sk: &SigningKey,
+    session_pk: &VerifyingKey,
+    session_id: Uuid,
+) -> anyhow::Result<SessionCipher> {
+    Ok(crypto::create_encrypt_key(user_sk, session_pk, session_id)?)
+}
+
+/// Encrypt `query` under the session's message nonce, hex-encoded for the request
+pub fn seal_query(
+    cipher: &SessionCipher,
+    session_id: Uuid,
+    query: impl AsRef<[u8]>,
+) -> anyhow::Result<String> {
+    let encrypted = cipher
+        .encrypt(&crypto::derive_msg_nonce(session_id), query.as_ref())
+        .map_err(|e| anyhow!(e.to_string()))
+        .context("encrypt query")?;
+
+    Ok(const_hex::encode(encrypted))
+}
+
+/// Decrypt a hex-encoded `encrypted_response` (a blob of `crypto::seal`)
+pub fn open_response(cipher: &SessionCipher, encrypted_response: &str) -> anyhow::Result<Vec<u8>> {
+    let sealed = const_hex::decode(encrypted_response).context("invalid response hex")?;
+
+    crypto::open(cipher, &sealed).context("open response")
+}
+
+/// Decrypt the hex-encoded `encrypted_payload` of a `/agent/query/stream` event
+///
+/// `opener` is made from the stream id of the `stream_started` event, and must see the
+/// sealed events in the order they arrived; a dropped or reordered event fails here.
+pub fn open_stream_event(
+    opener: &mut StreamOpener,
+    encrypted_payload: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let chunk = const_hex::decode(encrypted_payload).context("invalid event payload hex")?;
+
+    opener.open(&chunk).context("open stream event")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, Router};
+
+    use super::*;
+    use crate::api::{
+        encrypt::{self, CreateKeyPairRequest, CreateKeyPairResponse},
+        openai::{self, OpenAIQueryRequest, OpenAIQueryResponse},
+        RouterRegister,
+    };
+    use crate::test_utils::{chat_completion, MockOpenAI};
+    use crate::types::HypervisorState;
+
+    #[test]
+    fn test_seal_open_round_trip() {
+        let user_sk = SigningKey::random(&mut rand::rngs::OsRng);
+        let session_sk = SigningKey::random(&mut rand::rngs::OsRng);
+        let session_id = Uuid::now_v7();
+        let cipher = create_cipher(&user_sk, session_sk.verifying_key(), session_id).unwrap();
+
+        // What the server does with the query and its answer
+        let server_cipher =
+            crypto::create_encrypt_key(&session_sk, user_sk.verifying_key(), session_id).unwrap();
+        let sealed = const_hex::decode(seal_query(&cipher, session_id, "What is BTC?").unwrap());
+        let query = server_cipher
+            .decrypt(&crypto::derive_msg_nonce(session_id), sealed.unwrap().as_slice())
+            .unwrap();
+        assert_eq!(query, b"What is BTC?");
+        let response = crypto::seal(&server_cipher, b"A cryptocurrency").unwrap();
+        let response = const_hex::encode(response);
+        assert_eq!(open_response(&cipher, &response).unwrap(), b"A cryptocurrency");
+
+        assert!(open_response(&cipher, "not hex").is_err());
+        let other = create_cipher(&user_sk, session_sk.verifying_key(), Uuid::now_v7()).unwrap();
+        assert!(open_response(&other, &response).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_through_the_server() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, chat_completion("4"))).await;
+        let mut config = crate::Config::default();
+        config.openai.api_base = backend.base_url.clone();
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(encrypt::api_register)
+                .register_api(openai::api_register)
+                .with_state(HypervisorState::new(config).unwrap()),
+        )
+        .unwrap();
+
+        let user_sk = SigningKey::random(&mut rand::rngs::OsRng);
+        let public_key = crypto::pk_to_hex(user_sk.verifying_key());
+        let session: CreateKeyPairResponse = server
+            .post("/encrypt/create_keypair")
+            .json(&CreateKeyPairRequest {
+                pubkey: public_key.clone(),
+                challenge: None,
+            })
+            .await
+            .json();
+
+        let session_pk = crypto::pk_from_hex(&session.session_pubkey).unwrap();
+        let cipher = create_cipher(&user_sk, &session_pk, session.session_id).unwrap();
+        let response = server
+            .post("/openai/query")
+            .json(&OpenAIQueryRequest {
+                encrypted_prompt: seal_query(&cipher, session.session_id, "What is 2+2?").unwrap(),
+                public_key,
+                temperature: None,
+                max_tokens: None,
+                n: None,
+                attest: false,
+            })
+            .await;
+        response.assert_status_ok();
+
+        let result: OpenAIQueryResponse = response.json();
+        assert_eq!(open_response(&cipher, &result.encrypted_response).unwrap(), b"4");
+        let prompt = &backend.requests()[0]["messages"];
+        assert!(prompt.to_string().contains("What is 2+2?"));
+    }
+}
@@ -1,12 +1,196 @@
-use std::{net::SocketAddr, path::PathBuf};
+use std::{fmt, net::SocketAddr, ops::RangeInclusive, path::PathBuf, str::FromStr};
 
-use serde::Deserialize;
+use attest::verify::ExpectedMeasurement;
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::crypto_agent::CryptoAgentConfig,
+    api::{
+        agent::{ConversationMemoryConfig, ExecutionStoreConfig},
+        openai::OpenAIConfig, prompt_filter::PromptDenylist,
+        quote::{QuoteCompression, QuoteStoreConfig},
+    },
+    utils::{logging::LoggingConfig, models::ModelsConfig},
+};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub executor_path: PathBuf,
     pub app_path: PathBuf,
-    pub listening: SocketAddr,
+    /// Addresses served, a TCP address or `unix:<path>`; the latter add `plaintext_routes`
+    #[serde(deserialize_with = "one_or_many")]
+    pub listening: Vec<ListenSpec>,
+    /// Crypto agent settings
+    #[serde(default)]
+    pub agent: CryptoAgentConfig,
+    /// OpenAI query endpoint settings
+    #[serde(default)]
+    pub openai: OpenAIConfig,
+    /// Startup self-test settings
+    #[serde(default)]
+    pub self_test: SelfTestConfig,
+    /// Upper bound on `max_tokens` for every completion, whoever requested it
+    #[serde(default = "default_max_tokens_ceiling")]
+    pub max_tokens_ceiling: u32,
+    /// Largest decrypted prompt or agent query accepted, in bytes
+    #[serde(default = "default_max_prompt_bytes")]
+    pub max_prompt_bytes: usize,
+    /// Patterns of prompts and agent queries rejected with 422 before reaching the LLM
+    #[serde(default)]
+    pub prompt_denylist: PromptDenylist,
+    /// TD measurements accepted when verifying quotes; empty accepts any
+    #[serde(default)]
+    pub expected_measurements: Vec<ExpectedMeasurement>,
+    /// Store of returned agent executions; disabled when unset
+    #[serde(default)]
+    pub execution_store: Option<ExecutionStoreConfig>,
+    /// Retention of agent queries for the policies checked across turns
+    #[serde(default)]
+    pub conversation_memory: ConversationMemoryConfig,
+    /// Chat completion models, with fallbacks for when the primary is rate limited or down
+    #[serde(default)]
+    pub models: ModelsConfig,
+    /// Cap on concurrent agent and OpenAI queries, a stream counting until it ends;
+    /// excess get 503. Unlimited when unset
+    #[serde(default)]
+    pub max_concurrent_requests: Option<usize>,
+    /// Bearer token of the `/admin/*` routes, which are disabled when unset
+    #[serde(default)]
+    pub admin_token: Option<SecretString>,
+    /// Seconds a quote is reused for identical report data; every request is
+    /// quoted afresh when unset
+    #[serde(default)]
+    pub quote_cache_secs: Option<u64>,
+    /// Retention of returned quotes for `GET /verifiable/quote/{id}`
+    #[serde(default)]
+    pub quote_store: QuoteStoreConfig,
+    /// Compression of the quotes in responses; plain hex by default, for older clients
+    #[serde(default)]
+    pub quote_compression: QuoteCompression,
+    /// Quote providers tried in order, each falling back to the next; `ATTEST_PROVIDERS`
+    /// or coco then ioctl when unset
+    #[serde(default)]
+    pub attestation_providers: Option<Vec<attest::Provider>>,
+    /// Redaction of sensitive log fields
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Serve `POST /agent/query/plain` and `/openai/query/plain`, which take and return
+    /// plaintext without a session, on the `unix:` listeners only
+    #[serde(default)]
+    pub plaintext_routes: bool,
+}
+
+/// Where the server listens
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ListenSpec {
+    Tcp(SocketAddr),
+    /// Unix domain socket, reachable by local processes allowed to open its path; it serves
+    /// the routes of the TCP addresses, admin routes included, plus `plaintext_routes`
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(ListenSpec::Unix(path.into()));
+        }
+
+        s.parse()
+            .map(ListenSpec::Tcp)
+            .map_err(|e| format!("listen address {s}: {e}"))
+    }
+}
+
+impl TryFrom<String> for ListenSpec {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ListenSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenSpec::Tcp(addr) => write!(f, "{addr}"),
+            ListenSpec::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Accept a single listen address as well as a list
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<ListenSpec>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(ListenSpec),
+        Many(Vec<ListenSpec>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(spec) => vec![spec],
+        OneOrMany::Many(specs) => specs,
+    })
+}
+
+fn default_max_tokens_ceiling() -> u32 {
+    4000
+}
+
+fn default_max_prompt_bytes() -> usize {
+    32 * 1024
+}
+
+/// Temperatures accepted by the OpenAI API
+pub const TEMPERATURE_RANGE: RangeInclusive<f32> = 0.0..=2.0;
+
+/// Completion parameters after applying the server's limits
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GenerationLimits {
+    pub max_tokens: u32,
+    pub temperature: f32,
+}
+
+impl Config {
+    /// Clamp requested completion parameters to `max_tokens_ceiling` and `TEMPERATURE_RANGE`
+    pub fn generation_limits(&self, max_tokens: u32, temperature: f32) -> GenerationLimits {
+        GenerationLimits {
+            max_tokens: max_tokens.min(self.max_tokens_ceiling),
+            temperature: temperature.clamp(*TEMPERATURE_RANGE.start(), *TEMPERATURE_RANGE.end()),
+        }
+    }
+}
+
+/// Which startup self-test failures abort startup
+///
+/// Policy registry failures always do; the others can be downgraded to
+/// warnings, e.g. when running outside a TEE.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SelfTestConfig {
+    /// Fail startup if `OPENAI_API_KEY` is not set
+    pub require_api_key: bool,
+    /// Fail startup if no attestation provider can produce a quote
+    pub require_attestation: bool,
+    /// Fail startup if a tool's data can't be loaded; otherwise the tool is left out
+    pub require_tool_data: bool,
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        Self {
+            require_api_key: true,
+            require_attestation: true,
+            require_tool_data: false,
+        }
+    }
 }
 
 impl Default for Config {
@@ -14,7 +198,25 @@ impl Default for Config {
         Config {
             executor_path: "./data/executor".parse().expect("executor path"),
             app_path: "./data/apps".parse().expect("app path"),
-            listening: "0.0.0.0:3000".parse().expect("hypervisor listen address"),
+            listening: vec!["0.0.0.0:3000".parse().expect("hypervisor listen address")],
+            agent: CryptoAgentConfig::default(),
+            openai: OpenAIConfig::default(),
+            self_test: SelfTestConfig::default(),
+            max_tokens_ceiling: default_max_tokens_ceiling(),
+            max_prompt_bytes: default_max_prompt_bytes(),
+            prompt_denylist: PromptDenylist::default(),
+            expected_measurements: Vec::new(),
+            execution_store: None,
+            conversation_memory: ConversationMemoryConfig::default(),
+            max_concurrent_requests: None,
+            admin_token: None,
+            quote_cache_secs: None,
+            quote_store: QuoteStoreConfig::default(),
+            quote_compression: QuoteCompression::default(),
+            attestation_providers: None,
+            logging: LoggingConfig::default(),
+            plaintext_routes: false,
+            models: ModelsConfig::default(),
         }
     }
 }
@@ -1,8 +1,11 @@
 use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use crate::{agent::AgentError, utils::crypto::CipherError};
 
 #[derive(thiserror::Error, Debug)]
 pub enum HypervisorError {
@@ -11,10 +14,62 @@ pub enum HypervisorError {
 
     #[error(transparent)]
     Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Agent(#[from] AgentError),
+
+    #[error(transparent)]
+    Cipher(#[from] CipherError),
+
+    #[error(transparent)]
+    Json(#[from] JsonRejection),
+
+    #[error(transparent)]
+    Query(#[from] QueryRejection),
+
+    #[error(transparent)]
+    Path(#[from] PathRejection),
+
+    #[error("invalid request: {}", join_field_errors(.0))]
+    InvalidRequest(Vec<FieldError>),
+}
+
+/// Machine-readable kind of a rejected JSON body
+fn json_rejection_code(rejection: &JsonRejection) -> &'static str {
+    match rejection {
+        JsonRejection::JsonSyntaxError(_) => "invalid_json",
+        JsonRejection::JsonDataError(_) => "invalid_json_data",
+        JsonRejection::MissingJsonContentType(_) => "missing_json_content_type",
+        _ => "invalid_body",
+    }
+}
+
+fn join_field_errors(errors: &[FieldError]) -> String {
+    errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
+/// A problem with one field of a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[error("{field} {issue}")]
+pub struct FieldError {
+    pub field: String,
+    pub issue: String,
 }
 
 impl IntoResponse for HypervisorError {
     fn into_response(self) -> Response {
+        let errors = match &self {
+            HypervisorError::InvalidRequest(errors) => errors.clone(),
+            _ => Vec::new(),
+        };
+        let code = match &self {
+            HypervisorError::Cipher(e) => Some(e.code()),
+            HypervisorError::Json(e) => Some(json_rejection_code(e)),
+            HypervisorError::Query(_) => Some("invalid_query"),
+            HypervisorError::Path(_) => Some("invalid_path"),
+            _ => None,
+        };
+
         let (status_code, err_msg) = match self {
             HypervisorError::Any(e) => {
                 let status_code = e
@@ -33,14 +88,60 @@ impl IntoResponse for HypervisorError {
 
                 (status_code, msg)
             }
+            HypervisorError::Agent(e) => {
+                let status_code = match e {
+                    AgentError::PlanningFailed(_)
+                    | AgentError::LlmRequest(_)
+                    | AgentError::LlmParse(_) => StatusCode::BAD_GATEWAY,
+                    AgentError::LlmRefused(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                    AgentError::Compliance(_) => StatusCode::FORBIDDEN,
+                    AgentError::ToolExecution(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                // The reason may quote the query or the LLM's compliance explanation
+                tracing::error!(code = e.code(), reason = %e, "Agent error ({})", status_code);
+
+                (status_code, e.to_string())
+            }
+            HypervisorError::Cipher(e) => {
+                // Can't happen with the validated keys of a request
+                let status_code = StatusCode::INTERNAL_SERVER_ERROR;
+                tracing::error!("Cipher error ({}): {}", status_code, e);
+
+                (status_code, e.to_string())
+            }
+            HypervisorError::Json(e) => {
+                tracing::warn!("Client error ({}): {}", e.status(), e.body_text());
+
+                (e.status(), e.body_text())
+            }
+            HypervisorError::Query(e) => {
+                tracing::warn!("Client error ({}): {}", e.status(), e.body_text());
+
+                (e.status(), e.body_text())
+            }
+            HypervisorError::Path(e) => {
+                tracing::warn!("Client error ({}): {}", e.status(), e.body_text());
+
+                (e.status(), e.body_text())
+            }
             #[rustfmt::skip]
             HypervisorError::Io(e) => {
                 tracing::error!("IO error: {:?}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
             }
+            HypervisorError::InvalidRequest(_) => {
+                let msg = self.to_string();
+                tracing::warn!("Client error ({}): {}", StatusCode::BAD_REQUEST, msg);
+
+                (StatusCode::BAD_REQUEST, msg)
+            }
         };
 
-        let err_resp = ErrorResponse { msg: err_msg };
+        let err_resp = ErrorResponse {
+            msg: err_msg,
+            code,
+            errors,
+        };
 
         (status_code, axum::Json(err_resp)).into_response()
     }
@@ -49,4 +150,103 @@ impl IntoResponse for HypervisorError {
 #[derive(Serialize)]
 struct ErrorResponse {
     msg: String,
+    /// Machine-readable kind of the error, where one is defined
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    /// Every problem found with the request's fields, for invalid requests
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn response_of(err: CipherError) -> (StatusCode, serde_json::Value) {
+        let response = HypervisorError::from(err).into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_cipher_errors_map_to_status_and_code() {
+        let (status, body) = response_of(CipherError::KeyDerivation("invalid length".into())).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "key_derivation_failed");
+        assert_eq!(body["msg"], "key derivation failed: invalid length");
+
+        let (status, body) = response_of(CipherError::CipherInit("invalid length".into())).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "cipher_init_failed");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_gets_a_json_error_body() {
+        use axum::Router;
+
+        use crate::api::{encrypt, RouterRegister};
+        use crate::types::HypervisorState;
+
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(encrypt::api_register)
+                .with_state(HypervisorState::default()),
+        )
+        .unwrap();
+
+        let response = server
+            .post("/encrypt/create_keypair")
+            .bytes(r#"{"pubkey": "#.into())
+            .content_type("application/json")
+            .expect_failure()
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "invalid_json");
+        assert!(body["msg"].as_str().unwrap().contains("Failed to parse the request body"));
+
+        let response = server
+            .post("/encrypt/create_keypair")
+            .json(&serde_json::json!({ "challenge": "00" }))
+            .expect_failure()
+            .await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.json::<serde_json::Value>()["code"], "invalid_json_data");
+
+        let response = server
+            .post("/encrypt/create_keypair")
+            .text(r#"{"pubkey": "00"}"#)
+            .expect_failure()
+            .await;
+        response.assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(response.json::<serde_json::Value>()["code"], "missing_json_content_type");
+    }
+
+    #[tokio::test]
+    async fn test_bad_query_and_path_get_a_json_error_body() {
+        use axum::Router;
+
+        use crate::api::{agent, quote, RouterRegister};
+        use crate::types::HypervisorState;
+
+        let server = axum_test::TestServer::new(
+            Router::new()
+                .register_api(agent::api_register)
+                .register_api(quote::api_register)
+                .with_state(HypervisorState::default()),
+        )
+        .unwrap();
+
+        let response = server.get(&format!("/agent/execution/{}", "00".repeat(32))).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "invalid_query");
+        assert!(body["msg"].as_str().unwrap().contains("public_key"), "{body}");
+
+        let response = server.get("/verifiable/quote/%FF").expect_failure().await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert_eq!(response.json::<serde_json::Value>()["code"], "invalid_path");
+    }
 }
@@ -1,12 +1,20 @@
 pub mod agent;
 pub mod api;
+pub mod client;
 
 mod config;
 mod error;
 mod server;
+#[cfg(test)]
+mod test_utils;
 mod types;
 mod utils;
 
-pub use config::Config;
+pub use api::agent::{hash_execution, verify_execution_hash};
+pub use config::{Config, GenerationLimits, ListenSpec, SelfTestConfig};
 pub use server::Server;
-pub use utils::crypto;
+pub use utils::{
+    attest::{identity_quote, IdentityTarget},
+    commitment_openai::verify_query_commitment,
+    crypto, logging,
+};
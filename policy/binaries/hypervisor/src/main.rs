@@ -1,9 +1,12 @@
 use std::path::PathBuf;
 
-use clap::Parser;
-use hypervisor::{Config, Server};
-use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
+use hypervisor::{
+    agent::{ComplianceChecker, PolicyRegistry},
+    crypto, identity_quote, logging, Config, IdentityTarget, Server,
+};
+use serde::Deserialize;
 
 /// Compute node
 #[derive(Parser, Debug)]
@@ -12,26 +15,90 @@ struct Args {
     /// Path to config file
     #[arg(short, long)]
     config: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run example queries through the configured policies' deterministic rules
+    /// and report the ones decided differently than expected
+    CheckPolicies {
+        /// JSON array of `{"query": "...", "compliant": true}` cases
+        corpus: PathBuf,
+    },
+    /// Print a quote (hex) over a hash or an identity public key, to register the
+    /// deployment without starting the server
+    Attest {
+        /// 32-byte hash (hex), bound in the `identity` report-data domain
+        #[arg(long, required_unless_present = "public_key", conflicts_with = "public_key")]
+        hash: Option<String>,
+        /// Compressed secp256k1 public key (hex), placed at the start of report_data
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+struct CorpusCase {
+    query: String,
+    compliant: bool,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .with(
-            tracing_subscriber::EnvFilter::builder()
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env_lossy(),
-        )
-        .init();
-
     let args = Args::parse();
     let config: Config = {
         let config_str = tokio::fs::read_to_string(args.config).await?;
         toml::from_str(&config_str)?
     };
+    logging::init(&config.logging);
+
+    match args.command {
+        Some(Command::CheckPolicies { corpus }) => return check_policies(&config, corpus).await,
+        Some(Command::Attest { hash, public_key }) => return attest(hash, public_key),
+        None => {}
+    }
 
     let server = Server::build(config)?;
 
     server.start().await
 }
+
+fn attest(hash: Option<String>, public_key: Option<String>) -> anyhow::Result<()> {
+    let target = match (hash, public_key) {
+        (Some(hash), _) => IdentityTarget::Hash(
+            const_hex::decode_to_array(hash).context("hash must be 32 hex-encoded bytes")?,
+        ),
+        (None, Some(public_key)) => IdentityTarget::PublicKey(
+            crypto::pk_from_hex(&public_key).context("invalid public key")?,
+        ),
+        (None, None) => bail!("either --hash or --public-key is required"),
+    };
+
+    let quote = identity_quote(&target, |report| Ok(attest::get_quote(report)?.to_bytes()))?;
+    println!("{quote}");
+
+    Ok(())
+}
+
+async fn check_policies(config: &Config, corpus: PathBuf) -> anyhow::Result<()> {
+    let cases: Vec<CorpusCase> = serde_json::from_str(&tokio::fs::read_to_string(corpus).await?)?;
+    let cases: Vec<_> = cases.into_iter().map(|c| (c.query, c.compliant)).collect();
+
+    let checker =
+        ComplianceChecker::from_registry(&PolicyRegistry::from_agent_config(&config.agent)?);
+    let report = checker.evaluate_corpus(&cases);
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if report.failed > 0 {
+        bail!(
+            "{} of {} cases decided differently than expected",
+            report.failed,
+            report.total
+        );
+    }
+
+    Ok(())
+}
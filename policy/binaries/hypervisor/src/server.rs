@@ -1,47 +1,379 @@
+use std::{os::unix::fs::FileTypeExt, time::Duration};
+
+use anyhow::{bail, Context};
 use axum::http::HeaderValue;
 use axum::{http::Method, Router};
-use tower_http::cors::CorsLayer;
+use tokio::net::{TcpListener, UnixListener};
+use tokio::task::JoinSet;
+use tower_http::{compression::CompressionLayer, cors::CorsLayer};
 
+use crate::agent::tools::ToolRegistry;
 use crate::api::{self, RouterRegister};
 use crate::types::{HypervisorState, ServerContext};
-use crate::Config;
+use crate::utils::attest::{ReportDataBuilder, SELF_TEST_DOMAIN};
+use crate::{Config, ListenSpec};
 
 pub struct Server {
+    /// Routes of the TCP listeners
     app: Router,
+    /// Routes of the `unix:` listeners, the plaintext ones included when enabled
+    local_app: Router,
     ctx: ServerContext,
 }
 
 impl Server {
     pub fn build(config: Config) -> anyhow::Result<Self> {
-        let state = HypervisorState::new(config);
+        if let Some(secs) = config.quote_cache_secs {
+            attest::enable_quote_cache(Duration::from_secs(secs));
+        }
+        if let Some(order) = &config.attestation_providers {
+            attest::set_provider_order(order.clone());
+        }
+
+        if config.plaintext_routes {
+            tracing::warn!(
+                "plaintext_routes is set: /agent/query/plain and /openai/query/plain accept \
+                 unencrypted queries on the unix: listeners; only let trusted callers open them"
+            );
+            if !config.listening.iter().any(|spec| matches!(spec, ListenSpec::Unix(_))) {
+                tracing::warn!("plaintext_routes is set, but no unix: listener serves them");
+            }
+        }
+
+        let state = HypervisorState::new(config)?;
 
         let ctx = ServerContext {
             state: state.clone(),
         };
 
-        let app = Router::new()
+        let routes = Router::new()
             .register_api(api::ping::api_register)
+            .register_api(api::health::api_register)
+            .register_api(api::metrics::api_register)
             .register_api(api::encrypt::api_register)
             .register_api(api::openai::api_register)
             .register_api(api::agent::api_register)
-            .with_state(state)
-            .layer(
-                CorsLayer::new()
-                    .allow_origin("*".parse::<HeaderValue>()?)
-                    .allow_methods([Method::GET, Method::POST]),
+            .register_api(api::verify::api_register)
+            .register_api(api::quote::api_register)
+            .register_api(api::policy::api_register)
+            .register_api(api::admin::api_register);
+        // Only local callers allowed to open the socket can reach the plaintext routes
+        let local_routes = if state.config.plaintext_routes {
+            routes
+                .clone()
+                .register_api(api::agent::plaintext_api_register)
+                .register_api(api::openai::plaintext_api_register)
+        } else {
+            routes.clone()
+        };
+
+        let finish = |routes: Router<HypervisorState>| -> anyhow::Result<Router> {
+            Ok(routes
+                .with_state(state.clone())
+                .layer(
+                    CorsLayer::new()
+                        .allow_origin("*".parse::<HeaderValue>()?)
+                        .allow_methods([Method::GET, Method::POST]),
+                )
+                // Agent executions can be large; compress when the client accepts gzip or br
+                .layer(CompressionLayer::new()))
+        };
+
+        let server = Server {
+            app: finish(routes)?,
+            local_app: finish(local_routes)?,
+            ctx,
+        };
+        server.self_test()?;
+
+        Ok(server)
+    }
+
+    /// Check the configuration before serving: tool data, policy registry,
+    /// API key and attestation provider
+    ///
+    /// Fails with every problem found, so a misconfiguration is fixed in one pass
+    /// instead of being discovered request by request.
+    pub fn self_test(&self) -> anyhow::Result<()> {
+        let config = &self.ctx.state.config;
+        let mut failures = Vec::new();
+        let mut check = |name: &str, required: bool, result: Result<(), String>| match result {
+            Ok(()) => tracing::debug!("self-test {name}: ok"),
+            Err(e) if required => failures.push(format!("{name}: {e}")),
+            Err(e) => tracing::warn!("self-test {name}: {e}"),
+        };
+
+        for error in ToolRegistry::check_crypto_tool_data(&config.agent.data_dir) {
+            check("tool data", config.self_test.require_tool_data, Err(error));
+        }
+
+        for (tool_name, policy_id) in self.ctx.state.policy_registry().unknown_mapped_policies() {
+            check(
+                "policy registry",
+                true,
+                Err(format!("tool '{tool_name}' is mapped to unknown policy '{policy_id}'")),
             );
+        }
+
+        for conflict in self.ctx.state.policy_registry().lint() {
+            check("policy lint", false, Err(conflict.to_string()));
+        }
 
-        Ok(Server { app, ctx })
+        for expected in &config.expected_measurements {
+            check(
+                "expected measurements",
+                true,
+                expected.validate().map_err(|e| e.to_string()),
+            );
+        }
+
+        check(
+            "api key",
+            config.self_test.require_api_key,
+            std::env::var("OPENAI_API_KEY")
+                .map(drop)
+                .map_err(|_| "OPENAI_API_KEY not set".to_string()),
+        );
+
+        check(
+            "attestation",
+            config.self_test.require_attestation,
+            attest::get_quote(ReportDataBuilder::new(SELF_TEST_DOMAIN).build())
+                .map(drop)
+                .map_err(|e| e.to_string()),
+        );
+
+        if !failures.is_empty() {
+            bail!("startup self-test failed:\n  - {}", failures.join("\n  - "));
+        }
+
+        tracing::info!("startup self-test passed");
+        Ok(())
+    }
+
+    /// Bind every `listening` address, failing if any of them can't be bound
+    pub async fn bind(self) -> anyhow::Result<BoundServer> {
+        let mut listeners = Vec::new();
+        for spec in &self.ctx.state.config.listening {
+            let listener = match spec {
+                ListenSpec::Tcp(addr) => Listener::Tcp(TcpListener::bind(addr).await?),
+                ListenSpec::Unix(path) => {
+                    // A socket left behind by a previous run would fail the bind
+                    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                        std::fs::remove_file(path)?;
+                    }
+                    Listener::Unix(UnixListener::bind(path)?)
+                }
+            };
+            listeners.push(listener);
+        }
+
+        Ok(BoundServer {
+            app: self.app,
+            local_app: self.local_app,
+            listeners,
+        })
     }
 
     pub async fn start(self) -> anyhow::Result<()> {
-        let config = &self.ctx.state.config;
+        self.bind().await?.serve().await
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// A server listening on all its addresses, not serving yet
+pub struct BoundServer {
+    app: Router,
+    local_app: Router,
+    listeners: Vec<Listener>,
+}
+
+impl BoundServer {
+    /// Bound addresses, in `listening` order; TCP ports are resolved when 0 was configured
+    pub fn local_addrs(&self) -> anyhow::Result<Vec<ListenSpec>> {
+        self.listeners
+            .iter()
+            .map(|listener| match listener {
+                Listener::Tcp(l) => Ok(ListenSpec::Tcp(l.local_addr()?)),
+                Listener::Unix(l) => l
+                    .local_addr()?
+                    .as_pathname()
+                    .map(|path| ListenSpec::Unix(path.to_path_buf()))
+                    .context("unnamed unix socket"),
+            })
+            .collect()
+    }
 
-        let listener = tokio::net::TcpListener::bind(config.listening).await?;
-        tracing::info!("listening on {}", config.listening);
+    /// Serve every listener concurrently until one of them fails
+    pub async fn serve(self) -> anyhow::Result<()> {
+        let addrs = self.local_addrs()?;
+        let mut servers = JoinSet::new();
+        for (listener, addr) in self.listeners.into_iter().zip(addrs) {
+            tracing::info!("listening on {addr}");
+            match listener {
+                Listener::Tcp(l) => {
+                    let app = self.app.clone();
+                    servers.spawn(async move { axum::serve(l, app).await })
+                }
+                Listener::Unix(l) => {
+                    let app = self.local_app.clone();
+                    servers.spawn(async move { axum::serve(l, app).await })
+                }
+            };
+        }
 
-        axum::serve(listener, self.app).await?;
+        while let Some(result) = servers.join_next().await {
+            result??;
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::{
+        agent::tools::SentimentTool,
+        test_utils::{chat_completion, data_dir, MockOpenAI},
+        SelfTestConfig,
+    };
+
+    fn config(data_dir: std::path::PathBuf) -> Config {
+        let mut config = Config {
+            self_test: SelfTestConfig {
+                require_api_key: false,
+                require_attestation: false,
+                require_tool_data: true,
+            },
+            ..Default::default()
+        };
+        config.agent.data_dir = data_dir;
+        config
+    }
+
+    #[test]
+    fn test_self_test_passes() {
+        Server::build(config(data_dir())).unwrap();
+    }
+
+    #[test]
+    fn test_self_test_names_missing_tool_data() {
+        let dir = std::env::temp_dir().join(format!("self-test-{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for entry in std::fs::read_dir(data_dir()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name().unwrap() != SentimentTool::DATA_FILE {
+                std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+            }
+        }
+
+        let err = Server::build(config(dir.clone())).err().unwrap().to_string();
+
+        // Not required: the other tools are served without it
+        let mut lenient = config(dir.clone());
+        lenient.self_test.require_tool_data = false;
+        Server::build(lenient).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(err.starts_with("startup self-test failed"), "{err}");
+        assert!(err.contains(SentimentTool::DATA_FILE), "{err}");
+        assert_eq!(err.matches("tool data").count(), 1, "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_serves_every_listen_address() {
+        let mut config = config(data_dir());
+        config.listening = vec!["127.0.0.1:0".parse().unwrap(), "127.0.0.1:0".parse().unwrap()];
+
+        let server = Server::build(config).unwrap().bind().await.unwrap();
+        let addrs = server.local_addrs().unwrap();
+        assert_eq!(addrs.len(), 2);
+        assert_ne!(addrs[0], addrs[1]);
+        tokio::spawn(server.serve());
+
+        for addr in addrs {
+            let resp = reqwest::get(format!("http://{addr}/ping")).await.unwrap();
+            assert_eq!(resp.status(), reqwest::StatusCode::OK, "{addr}");
+        }
+    }
+
+    /// Status line of a bare HTTP/1.1 POST of `body` to `route` over the unix socket at `path`
+    async fn post_unix(path: &std::path::Path, route: &str, body: &str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::UnixStream::connect(path).await.unwrap();
+        let request = format!(
+            "POST {route} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_routes_only_on_unix_listeners() {
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        let backend = MockOpenAI::spawn(|_| (StatusCode::OK, chat_completion("4"))).await;
+        let body = r#"{"prompt": "What is 2+2?"}"#;
+
+        for plaintext_routes in [true, false] {
+            let socket = std::env::temp_dir().join(format!("plain-{}.sock", uuid::Uuid::now_v7()));
+            let mut config = config(data_dir());
+            config.openai.api_base = backend.base_url.clone();
+            config.plaintext_routes = plaintext_routes;
+            config.listening =
+                vec!["127.0.0.1:0".parse().unwrap(), ListenSpec::Unix(socket.clone())];
+            let server = Server::build(config).unwrap().bind().await.unwrap();
+            let tcp = server.local_addrs().unwrap()[0].clone();
+            tokio::spawn(server.serve());
+
+            // Absent from TCP listeners, with axum's empty 404
+            let resp = reqwest::Client::new()
+                .post(format!("http://{tcp}/openai/query/plain"))
+                .header("content-type", "application/json")
+                .body(body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert_eq!(resp.text().await.unwrap(), "");
+
+            let status = post_unix(&socket, "/openai/query/plain", body).await;
+            let expected = if plaintext_routes { "200 OK" } else { "404 Not Found" };
+            assert!(status.ends_with(expected), "{status}");
+            std::fs::remove_file(&socket).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_listening_accepts_one_or_many() {
+        let parse = |listening: &str| {
+            let toml = format!("executor_path = \"e\"\napp_path = \"a\"\nlistening = {listening}");
+            toml::from_str::<Config>(&toml).map(|c| c.listening)
+        };
+
+        assert_eq!(
+            parse(r#""0.0.0.0:3000""#).unwrap(),
+            ["0.0.0.0:3000".parse().unwrap()]
+        );
+        assert_eq!(
+            parse(r#"["[::]:3000", "unix:/run/hypervisor.sock"]"#).unwrap(),
+            [
+                ListenSpec::Tcp("[::]:3000".parse().unwrap()),
+                ListenSpec::Unix("/run/hypervisor.sock".into())
+            ]
+        );
+        assert!(parse(r#""localhost""#).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Helpers shared by unit tests
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use attest::types::RawReport;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde_json::json;
+
+type Responder = dyn Fn(&serde_json::Value) -> (StatusCode, serde_json::Value) + Send + Sync;
+
+/// Directory with the synthetic tool data shipped with the crate
+pub(crate) fn data_dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/data"))
+}
+
+/// Write a policy file whose only policy, H1, prohibits `keywords` and covers every tool
+pub(crate) fn write_policy_file(path: &Path, keywords: &[&str]) {
+    let policies = format!(
+        r#"
+        [[policies]]
+        id = "H1"
+        name = "No hype"
+        text = "The agent must not make price predictions"
+        [[policies.methods]]
+        method = "Deterministic"
+        [[policies.methods.rules]]
+        id = "no_hype_keywords"
+        rule_type = {{ type = "ProhibitedKeywords", keywords = {keywords:?} }}
+
+        [tool_policies]
+        PriceFeedTool = ["H1"]
+        OnChainHistoryTool = ["H1"]
+        SentimentTool = ["H1"]
+        PortfolioTool = ["H1"]
+        "#
+    );
+    fs::write(path, policies).unwrap();
+}
+
+/// Build a chat completion body with a single assistant message
+pub(crate) fn chat_completion(content: &str) -> serde_json::Value {
+    json!({
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content },
+            "finish_reason": "stop"
+        }]
+    })
+}
+
+/// Chat completion cut off at `max_tokens`
+pub(crate) fn truncated_completion(content: &str) -> serde_json::Value {
+    let mut completion = chat_completion(content);
+    completion["choices"][0]["finish_reason"] = json!("length");
+    completion
+}
+
+/// Chat completion withheld by the content filter, which leaves no content
+pub(crate) fn content_filtered_completion() -> serde_json::Value {
+    json!({
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": null },
+            "finish_reason": "content_filter"
+        }]
+    })
+}
+
+/// Synthetic TDX v4 quote over `report_data`, laid out as a TEE produces one
+///
+/// Parses with `Quote::from_bytes`, but its signature is zeros and it carries no
+/// certification data, so it only stands in where signatures aren't checked.
+pub(crate) fn synthetic_td_quote(report_data: [u8; 64]) -> Vec<u8> {
+    const TDX_TEE_TYPE: u32 = 0x81;
+    const TD10_REPORT_LEN: usize = 584;
+
+    let mut header = [0u8; 48];
+    header[0..2].copy_from_slice(&4u16.to_le_bytes());
+    // ECDSA-256 attestation key
+    header[2..4].copy_from_slice(&2u16.to_le_bytes());
+    header[4..8].copy_from_slice(&TDX_TEE_TYPE.to_le_bytes());
+
+    let mut body = [0u8; TD10_REPORT_LEN];
+    body[TD10_REPORT_LEN - 64..].copy_from_slice(&report_data);
+
+    // Signature and attestation key, then empty QE report certification data (type 6)
+    let mut signature = vec![0u8; 128];
+    signature.extend(6u16.to_le_bytes());
+    signature.extend(0u32.to_le_bytes());
+
+    [&header[..], &body, &(signature.len() as u32).to_le_bytes(), &signature].concat()
+}
+
+/// Quote `report` through the attest mock provider, for `HypervisorState::set_quote_source`
+///
+/// The mock provider serves a captured quote whatever the report, so each call captures
+/// `synthetic_td_quote` over this report first; the lock keeps concurrent tests from
+/// swapping each other's capture.
+pub(crate) fn mock_attest_quote(report: RawReport) -> anyhow::Result<Vec<u8>> {
+    static CAPTURE: Mutex<()> = Mutex::new(());
+
+    let _capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    let path = std::env::temp_dir().join(format!("hypervisor-mock-quote-{}", std::process::id()));
+    std::fs::write(&path, synthetic_td_quote(report.to_bytes()))?;
+    std::env::set_var(attest::MOCK_QUOTE_ENV, &path);
+
+    Ok(attest::provider::mock::get_raw_quote(report)?)
+}
+
+/// Serve `app` on an ephemeral local port and return its base URL
+pub(crate) async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    format!("http://{addr}")
+}
+
+/// Local OpenAI-compatible backend answering `/chat/completions` with a responder
+pub(crate) struct MockOpenAI {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+#[derive(Clone)]
+struct MockState {
+    responder: Arc<Responder>,
+    requests: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl MockOpenAI {
+    pub async fn spawn<F>(responder: F) -> Self
+    where
+        F: Fn(&serde_json::Value) -> (StatusCode, serde_json::Value) + Send + Sync + 'static,
+    {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let state = MockState {
+            responder: Arc::new(responder),
+            requests: requests.clone(),
+        };
+
+        let app = Router::new()
+            .route("/chat/completions", post(chat_completions))
+            .with_state(state);
+
+        MockOpenAI {
+            base_url: serve(app).await,
+            requests,
+        }
+    }
+
+    /// Request bodies received so far, in arrival order
+    pub fn requests(&self) -> Vec<serde_json::Value> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn chat_completions(
+    State(state): State<MockState>,
+    Json(body): Json<serde_json::Value>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    state.requests.lock().unwrap().push(body.clone());
+    let (status, resp) = (state.responder)(&body);
+
+    (status, Json(resp))
+}
@@ -1,25 +1,122 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
+use arc_swap::ArcSwap;
+use attest::types::RawReport;
 use k256::{
     ecdsa::{SigningKey, VerifyingKey},
     EncodedPoint,
 };
+use tokio::sync::Semaphore;
 use uuid::Uuid;
 
-use crate::Config;
+use crate::{
+    agent::{tools::ToolRegistry, PolicyRegistry},
+    api::{
+        agent::{ConversationMemory, ExecutionStore},
+        health::BackendProbe,
+        openai::ResponseCache,
+        quote::QuoteStore,
+        tee_quote,
+    },
+    Config,
+};
+
+/// Produces the raw quote over a report
+pub(crate) type QuoteSource = fn(RawReport) -> anyhow::Result<Vec<u8>>;
 
 #[derive(Clone, Default)]
 pub(crate) struct HypervisorState {
     pub config: Config,
+    /// Policies and tool-policy mapping shared by all agent requests, swapped on reload
+    policy_registry: Arc<ArcSwap<PolicyRegistry>>,
+    /// Agent tools shared by all agent requests, rebuilt along with the policy registry
+    tool_registry: Arc<ArcSwap<ToolRegistry>>,
+    /// Completions of temperature-0 OpenAI queries
+    pub openai_cache: Arc<ResponseCache>,
+    /// Executions returned by the agent endpoints, when enabled
+    pub execution_store: Arc<ExecutionStore>,
+    /// Permits for the OpenAI-backed endpoints; unlimited when None
+    pub expensive_requests: Option<Arc<Semaphore>>,
+    /// Last `/health` check of the OpenAI API
+    pub openai_probe: Arc<BackendProbe>,
+    /// Quotes recently returned, served raw by `GET /verifiable/quote/{id}`
+    pub quote_store: Arc<QuoteStore>,
+    /// Earlier agent queries of each session, for the policies checked across turns
+    pub conversations: Arc<ConversationMemory>,
+    /// Agent executions numbered so far, see `next_execution_sequence`
+    execution_sequence: Arc<AtomicU64>,
+    /// Quotes returned by the endpoints; the TEE's, see `api::tee_quote`, unless replaced
+    quote_source: Option<QuoteSource>,
     session_key_pairs: SessionKeyPairs,
 }
 
 impl HypervisorState {
-    pub fn new(config: Config) -> Self {
-        HypervisorState {
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        let policy_registry = Arc::new(PolicyRegistry::from_agent_config(&config.agent)?);
+        let tool_registry =
+            ToolRegistry::from_agent_config(&config.agent, policy_registry.clone())?;
+
+        let openai_cache = ResponseCache::new(config.openai.response_cache.clone());
+        let execution_store = ExecutionStore::new(config.execution_store.clone());
+        let quote_store = QuoteStore::new(config.quote_store.clone());
+        let conversations = ConversationMemory::new(config.conversation_memory.clone());
+        let expensive_requests = config
+            .max_concurrent_requests
+            .map(|limit| Arc::new(Semaphore::new(limit)));
+
+        Ok(HypervisorState {
             config,
+            policy_registry: Arc::new(ArcSwap::new(policy_registry)),
+            tool_registry: Arc::new(ArcSwap::from_pointee(tool_registry)),
+            openai_cache: Arc::new(openai_cache),
+            execution_store: Arc::new(execution_store),
+            quote_store: Arc::new(quote_store),
+            conversations: Arc::new(conversations),
+            expensive_requests,
             ..Default::default()
-        }
+        })
+    }
+
+    /// Current policy registry; requests keep the one they started with across a reload
+    pub fn policy_registry(&self) -> Arc<PolicyRegistry> {
+        self.policy_registry.load_full()
+    }
+
+    /// Current agent tools, built against the policy registry of the same load
+    pub fn tool_registry(&self) -> Arc<ToolRegistry> {
+        self.tool_registry.load_full()
+    }
+
+    /// Rebuild the policy registry from the agent config and its policy file, and swap it in
+    /// along with tools resolving their policies through it
+    ///
+    /// The current registries stay in place if the new ones fail to load.
+    pub fn reload_policies(&self) -> anyhow::Result<Arc<PolicyRegistry>> {
+        let registry = Arc::new(PolicyRegistry::from_agent_config(&self.config.agent)?);
+        let tools = ToolRegistry::from_agent_config(&self.config.agent, registry.clone())?;
+        self.tool_registry.store(Arc::new(tools));
+        self.policy_registry.store(registry.clone());
+
+        Ok(registry)
+    }
+
+    /// Number of the next agent execution, starting at 1, so identical executions still
+    /// hash apart; the count restarts with the server, whose sessions are all new then
+    pub fn next_execution_sequence(&self) -> u64 {
+        self.execution_sequence.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Quote over `report` for an endpoint response
+    pub fn tee_quote(&self, report: RawReport) -> anyhow::Result<Vec<u8>> {
+        self.quote_source.unwrap_or(tee_quote)(report)
+    }
+
+    #[cfg(test)]
+    pub fn set_quote_source(&mut self, quote_source: QuoteSource) {
+        self.quote_source = Some(quote_source);
     }
 
     #[cfg(test)]
@@ -31,6 +128,12 @@ impl HypervisorState {
         self.session_key_pairs.create(pubkey)
     }
 
+    /// Replace the session of `pubkey` with a fresh keypair and id
+    /// Returns None if the pubkey has no session
+    pub fn rotate_session_keypair(self, pubkey: &VerifyingKey) -> Option<(VerifyingKey, Uuid)> {
+        self.session_key_pairs.rotate(pubkey)
+    }
+
     pub fn get_session_keypair(self, pubkey: &VerifyingKey) -> Option<(SigningKey, Uuid)> {
         self.session_key_pairs
             .0
@@ -56,4 +159,17 @@ impl SessionKeyPairs {
 
         (pk, uuid)
     }
+
+    /// Swap in a new keypair and id under the existing entry's lock, so the old
+    /// session stops working as soon as the new one is visible
+    pub fn rotate(self, pubkey: &VerifyingKey) -> Option<(VerifyingKey, Uuid)> {
+        let mut session = self.0.get_mut(&pubkey.to_encoded_point(true))?;
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let pk = sk.verifying_key().to_owned();
+        let uuid = Uuid::now_v7();
+        *session = (sk, uuid);
+
+        Some((pk, uuid))
+    }
 }
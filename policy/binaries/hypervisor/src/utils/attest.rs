@@ -1,23 +1,286 @@
-use attest::types::RawReport;
+use attest::types::{K256PkReport, RawReport};
+use k256::ecdsa::VerifyingKey;
 
-pub fn generate_raw_report(data: &[impl AsRef<[u8]>]) -> RawReport {
-    let mut hasher = blake3::Hasher::new();
+/// Version tag prefixed to every report_data digest
+pub const REPORT_DATA_TAG: &[u8] = b"XFN_REPORT_V1";
 
-    for d in data {
-        hasher.update(d.as_ref());
+/// Maximum length of a nonce placed in the upper half of report_data
+pub const MAX_NONCE_LEN: usize = 32;
+
+/// Domain of session keypair quotes: fields `session_pk`, `session_id`, nonce = client challenge
+pub const KEYPAIR_DOMAIN: &str = "keypair";
+/// Domain of OpenAI query commitments, see `commitment_openai::build_query_commitment`
+pub const OPENAI_DOMAIN: &str = "openai";
+/// Domain of agent execution quotes: field `execution_hash`
+pub const AGENT_DOMAIN: &str = "agent";
+/// Domain of direct tool call quotes: fields `tool_name`, `arguments`, `result_hash`
+pub const AGENT_TOOL_DOMAIN: &str = "agent_tool";
+/// Domain of per-tool compliance quotes, see `quote_utils::hash_compliance_data`
+pub const COMPLIANCE_DOMAIN: &str = "compliance";
+/// Domain of the startup attestation probe, see `Server::self_test`
+pub const SELF_TEST_DOMAIN: &str = "self_test";
+/// Domain of identity quotes over a hash, see `IdentityTarget`: field `hash`
+pub const IDENTITY_DOMAIN: &str = "identity";
+/// Domain of enforced policy quotes: field `policy_hash`
+pub const POLICY_DOMAIN: &str = "policy";
+
+/// Why report_data couldn't be built
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ReportDataError {
+    #[error("nonce is {0} bytes, over the {MAX_NONCE_LEN} byte limit")]
+    NonceTooLong(usize),
+}
+
+/// The 64-byte report_data bound into every quote the hypervisor requests
+///
+/// Layout:
+/// - `[0..32]` `hash`: `blake3(REPORT_DATA_TAG || frame(domain) || frame(field)* ||
+///   nonce_part)`, where `frame(x) = u64_le(len(x)) || x` and `nonce_part` is `0x00`
+///   without a nonce or `0x01 || frame(nonce)` with one
+/// - `[32..64]` `nonce`: the nonce, zero-padded, or all zeros without a nonce
+///
+/// Built by `ReportDataBuilder`; verifiers recompute `hash` from the domain and fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportData {
+    pub hash: [u8; 32],
+    pub nonce: [u8; 32],
+}
+
+impl ReportData {
+    pub const LEN: usize = 64;
+
+    pub fn to_bytes(self) -> [u8; 64] {
+        let mut bytes = [0u8; Self::LEN];
+        bytes[..32].copy_from_slice(&self.hash);
+        bytes[32..].copy_from_slice(&self.nonce);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; 64]) -> Self {
+        let (hash, nonce) = bytes.split_at(32);
+        Self {
+            hash: hash.try_into().expect("32-byte half"),
+            nonce: nonce.try_into().expect("32-byte half"),
+        }
+    }
+
+    pub fn to_raw(self) -> RawReport {
+        RawReport::new(self.to_bytes())
+    }
+}
+
+/// Builds the report_data bound into a quote, laid out as `ReportData`
+#[derive(Clone)]
+pub struct ReportDataBuilder {
+    hasher: blake3::Hasher,
+    nonce: Option<Vec<u8>>,
+}
+
+impl ReportDataBuilder {
+    pub fn new(domain: &str) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(REPORT_DATA_TAG);
+        frame(&mut hasher, domain.as_bytes());
+
+        Self {
+            hasher,
+            nonce: None,
+        }
+    }
+
+    /// Append a length-framed field
+    pub fn field(mut self, data: impl AsRef<[u8]>) -> Self {
+        frame(&mut self.hasher, data.as_ref());
+        self
+    }
+
+    /// Place a nonce (at most `MAX_NONCE_LEN` bytes) in the upper half of the report
+    pub fn nonce(mut self, nonce: &[u8]) -> Result<Self, ReportDataError> {
+        if nonce.len() > MAX_NONCE_LEN {
+            return Err(ReportDataError::NonceTooLong(nonce.len()));
+        }
+        self.nonce = Some(nonce.to_vec());
+        Ok(self)
+    }
+
+    /// Digest stored in the lower half of the report
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = self.hasher.clone();
+        match &self.nonce {
+            Some(nonce) => {
+                hasher.update(&[1]);
+                frame(&mut hasher, nonce);
+            }
+            None => {
+                hasher.update(&[0]);
+            }
+        }
+
+        hasher.finalize().into()
+    }
+
+    pub fn report_data(&self) -> ReportData {
+        let mut padded = [0u8; 32];
+        if let Some(nonce) = &self.nonce {
+            padded[..nonce.len()].copy_from_slice(nonce);
+        }
+
+        ReportData {
+            hash: self.digest(),
+            nonce: padded,
+        }
     }
 
-    let h: [u8; 32] = hasher.finalize().into();
+    pub fn build(&self) -> RawReport {
+        self.report_data().to_raw()
+    }
+}
+
+/// What an identity quote, printed by `hypervisor attest` for registering a deployment,
+/// is generated over
+#[derive(Debug, Clone)]
+pub enum IdentityTarget {
+    /// A 32-byte hash, bound in the `identity` domain
+    Hash([u8; 32]),
+    /// A secp256k1 public key, laid out in report_data as `K256PkReport` does
+    PublicKey(VerifyingKey),
+}
+
+impl IdentityTarget {
+    pub fn report(&self) -> RawReport {
+        match self {
+            IdentityTarget::Hash(hash) => {
+                ReportDataBuilder::new(IDENTITY_DOMAIN).field(hash).build()
+            }
+            IdentityTarget::PublicKey(pk) => K256PkReport::new(*pk).to_raw(),
+        }
+    }
+}
 
-    let mut report = [0u8; 64];
-    report[..32].copy_from_slice(&h);
+/// Generate a quote over `target` with `get_quote` (`attest::get_quote` outside tests),
+/// hex-encoded
+pub fn identity_quote(
+    target: &IdentityTarget,
+    get_quote: impl FnOnce(RawReport) -> anyhow::Result<Vec<u8>>,
+) -> anyhow::Result<String> {
+    Ok(const_hex::encode(get_quote(target.report())?))
+}
 
-    RawReport::new(report)
+fn frame(hasher: &mut blake3::Hasher, data: &[u8]) {
+    hasher.update(&(data.len() as u64).to_le_bytes());
+    hasher.update(data);
 }
 
-pub fn generate_raw_report_from_hash(h: [u8; 32]) -> RawReport {
-    let mut report = [0u8; 64];
-    report[..32].copy_from_slice(&h);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_data_layout() {
+        let report = ReportDataBuilder::new(AGENT_DOMAIN)
+            .field([0xaa; 32])
+            .build()
+            .to_bytes();
+
+        let mut expected = blake3::Hasher::new();
+        expected.update(b"XFN_REPORT_V1");
+        expected.update(&5u64.to_le_bytes());
+        expected.update(b"agent");
+        expected.update(&32u64.to_le_bytes());
+        expected.update(&[0xaa; 32]);
+        expected.update(&[0]);
+
+        assert_eq!(report[..32], *expected.finalize().as_bytes());
+        assert_eq!(report[32..], [0u8; 32]);
+    }
+
+    #[test]
+    fn test_report_data_nonce_layout() {
+        let nonce = [7u8; 16];
+        let builder = ReportDataBuilder::new(KEYPAIR_DOMAIN)
+            .field(b"pk")
+            .nonce(&nonce)
+            .unwrap();
+        let report = builder.build().to_bytes();
+
+        let mut expected = blake3::Hasher::new();
+        expected.update(b"XFN_REPORT_V1");
+        expected.update(&7u64.to_le_bytes());
+        expected.update(b"keypair");
+        expected.update(&2u64.to_le_bytes());
+        expected.update(b"pk");
+        expected.update(&[1]);
+        expected.update(&16u64.to_le_bytes());
+        expected.update(&nonce);
 
-    RawReport::new(report)
+        assert_eq!(report[..32], *expected.finalize().as_bytes());
+        assert_eq!(report[..32], builder.digest());
+        assert_eq!(report[32..48], nonce);
+        assert_eq!(report[48..], [0u8; 16]);
+    }
+
+    #[test]
+    fn test_report_data_round_trip() {
+        let builder = ReportDataBuilder::new(KEYPAIR_DOMAIN)
+            .field(b"pk")
+            .nonce(&[7u8; MAX_NONCE_LEN])
+            .unwrap();
+        let report_data = builder.report_data();
+        let bytes = builder.build().to_bytes();
+
+        assert_eq!(report_data.to_bytes(), bytes);
+        assert_eq!(ReportData::from_bytes(&bytes), report_data);
+        assert_eq!(report_data.hash, builder.digest());
+        assert_eq!(report_data.nonce, [7u8; 32]);
+
+        let overlong = ReportDataBuilder::new(KEYPAIR_DOMAIN).nonce(&[7u8; MAX_NONCE_LEN + 1]);
+        assert_eq!(overlong.err(), Some(ReportDataError::NonceTooLong(33)));
+    }
+
+    #[test]
+    fn test_identity_quote_with_mock_provider() {
+        // Stands in for a TEE: a fixed header followed by the report data
+        let mock = |report: RawReport| Ok([b"QUOTE".as_slice(), &report.to_bytes()].concat());
+
+        let hash = [9u8; 32];
+        let quote = identity_quote(&IdentityTarget::Hash(hash), mock).unwrap();
+        let quote = const_hex::decode(quote).unwrap();
+        assert_eq!(&quote[..5], b"QUOTE");
+        assert_eq!(
+            quote[5..37],
+            ReportDataBuilder::new(IDENTITY_DOMAIN).field(hash).digest()
+        );
+
+        let sk = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let pk = *sk.verifying_key();
+        let quote = identity_quote(&IdentityTarget::PublicKey(pk), mock).unwrap();
+        let quote = const_hex::decode(quote).unwrap();
+        assert_eq!(quote[5..38], *pk.to_encoded_point(true).as_bytes());
+
+        let err = identity_quote(&IdentityTarget::Hash(hash), |_| {
+            Err(anyhow::anyhow!("no attestation provider"))
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "no attestation provider");
+    }
+
+    #[test]
+    fn test_report_data_separates_domains_and_fields() {
+        let digest = |domain: &str, fields: &[&[u8]]| {
+            fields
+                .iter()
+                .fold(ReportDataBuilder::new(domain), |b, f| b.field(f))
+                .digest()
+        };
+
+        assert_ne!(
+            digest(AGENT_DOMAIN, &[b"data"]),
+            digest(OPENAI_DOMAIN, &[b"data"])
+        );
+        // Framing keeps field boundaries unambiguous
+        assert_ne!(
+            digest(AGENT_DOMAIN, &[b"ab", b"c"]),
+            digest(AGENT_DOMAIN, &[b"a", b"bc"])
+        );
+    }
 }
@@ -1,11 +1,15 @@
-use aes_gcm_siv::Nonce;
 use k256::ecdsa::VerifyingKey;
 use uuid::Uuid;
 
-use crate::utils;
+use crate::{
+    api::openai::OpenAIQueryResponse,
+    utils::attest::{ReportDataBuilder, OPENAI_DOMAIN},
+};
 
 /// Build commitment for OpenAI query
-/// Commitment = hash(user_pk, session_pk, session_id, encrypted_prompt, model, temperature, max_tokens, response_nonce, encrypted_response)
+/// Commitment = report_data digest over (user_pk, session_pk, session_id, encrypted_prompt, model, temperature, max_tokens, encrypted_response)
+/// in the `openai` domain, so the quote's `report_data[..32]` equals the commitment.
+/// With several candidates, each encrypted response is a field, in candidate order.
 pub fn build_query_commitment(
     user_pk: &VerifyingKey,
     session_pk: &VerifyingKey,
@@ -14,20 +18,47 @@ pub fn build_query_commitment(
     model: &str,
     temperature: f32,
     max_tokens: u32,
-    response_nonce: Nonce,
-    encrypted_response: &str,
-) -> [u8; 32] {
-    let entries = vec![
-        user_pk.to_encoded_point(true).to_bytes(),
-        session_pk.to_encoded_point(true).to_bytes(),
-        Box::new(*session_id.as_bytes()),
-        encrypted_prompt.as_bytes().into(),
-        model.as_bytes().into(),
-        temperature.to_le_bytes().to_vec().into(),
-        max_tokens.to_le_bytes().to_vec().into(),
-        response_nonce.to_vec().into(),
-        encrypted_response.as_bytes().into(),
-    ];
+    encrypted_responses: &[&str],
+) -> ReportDataBuilder {
+    let builder = ReportDataBuilder::new(OPENAI_DOMAIN)
+        .field(user_pk.to_encoded_point(true))
+        .field(session_pk.to_encoded_point(true))
+        .field(session_id.as_bytes())
+        .field(encrypted_prompt)
+        .field(model)
+        .field(temperature.to_le_bytes())
+        .field(max_tokens.to_le_bytes());
 
-    utils::hasher::hash_multi(&entries)
+    encrypted_responses
+        .iter()
+        .fold(builder, |builder, response| builder.field(response))
+}
+
+/// Recompute the commitment of an OpenAI query response and check it against `query_commitment`
+///
+/// `session_pk` is the key from the session key exchange and `encrypted_prompt`
+/// the hex string sent in the request.
+pub fn verify_query_commitment(
+    response: &OpenAIQueryResponse,
+    user_pk: &VerifyingKey,
+    session_pk: &VerifyingKey,
+    encrypted_prompt: &str,
+) -> bool {
+    let encrypted_responses: Vec<_> = std::iter::once(&response.encrypted_response)
+        .chain(response.additional_candidates.iter().map(|c| &c.encrypted_response))
+        .map(String::as_str)
+        .collect();
+    let commitment = build_query_commitment(
+        user_pk,
+        session_pk,
+        response.session_id,
+        encrypted_prompt,
+        &response.model,
+        response.temperature,
+        response.max_tokens,
+        &encrypted_responses,
+    );
+
+    const_hex::decode(&response.query_commitment)
+        .is_ok_and(|expected| expected == commitment.digest())
 }
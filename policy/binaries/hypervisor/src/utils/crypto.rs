@@ -1,36 +1,249 @@
-use aes_gcm_siv::{Aes256GcmSiv, KeyInit, Nonce};
+use aes_gcm_siv::{aead::Aead, Aes256GcmSiv, KeyInit, Nonce};
 use anyhow::anyhow;
 use k256::{
-    ecdh::diffie_hellman,
-    ecdsa::{SigningKey, VerifyingKey},
+    ecdh::{diffie_hellman, SharedSecret},
+    ecdsa::{
+        signature::{Signer, Verifier},
+        Signature, SigningKey, VerifyingKey,
+    },
 };
 use secrecy::{ExposeSecret, ExposeSecretMut, SecretSlice};
 use uuid::Uuid;
 
+/// Why a session cipher couldn't be created, by the step that failed
+///
+/// Peer keys are checked when requests are validated, so neither step fails on one.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CipherError {
+    /// HKDF expansion of the shared secret
+    #[error("key derivation failed: {0}")]
+    KeyDerivation(String),
+    /// AES-GCM-SIV setup with the derived key
+    #[error("cipher init failed: {0}")]
+    CipherInit(String),
+}
+
+impl CipherError {
+    /// Machine-readable `code` of the error response
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::KeyDerivation(_) => "key_derivation_failed",
+            Self::CipherInit(_) => "cipher_init_failed",
+        }
+    }
+}
+
+/// Length of the derived AES-256 key
+const SESSION_KEY_LEN: usize = 32;
+
+/// Session cipher: ECDH of `sk` and `pk`, expanded with HKDF salted by `session_id`
 pub fn create_encrypt_key(
     sk: &SigningKey,
     pk: &VerifyingKey,
     session_id: Uuid,
-) -> anyhow::Result<Aes256GcmSiv> {
+) -> Result<Aes256GcmSiv, CipherError> {
     let shared_sk = diffie_hellman(sk.as_nonzero_scalar(), pk.as_affine());
-    
+    let msg_key = derive_session_key(&shared_sk, session_id, SESSION_KEY_LEN)?;
+
+    cipher_from_key(msg_key.expose_secret())
+}
+
+fn derive_session_key(
+    shared_sk: &SharedSecret,
+    session_id: Uuid,
+    len: usize,
+) -> Result<SecretSlice<u8>, CipherError> {
     let hkdf = shared_sk.extract::<k256::sha2::Sha256>(Some(session_id.as_bytes()));
 
-    let mut msg_key = SecretSlice::new(Box::new([0u8; 32]));
+    let mut msg_key = SecretSlice::new(vec![0u8; len].into_boxed_slice());
     hkdf.expand(&[], msg_key.expose_secret_mut())
-        .map_err(|e| anyhow!(e.to_string()))?;
+        .map_err(|e| CipherError::KeyDerivation(e.to_string()))?;
 
-    let key = aes_gcm_siv::Key::<Aes256GcmSiv>::from_slice(msg_key.expose_secret());
+    Ok(msg_key)
+}
 
-    Ok(Aes256GcmSiv::new(key))
+fn cipher_from_key(key: &[u8]) -> Result<Aes256GcmSiv, CipherError> {
+    Aes256GcmSiv::new_from_slice(key).map_err(|e| CipherError::CipherInit(e.to_string()))
 }
 
+/// Domain tag of message nonces, keeping them apart from commitment and report_data hashes
+pub const NONCE_TAG: &[u8] = b"XFN_NONCE_V1";
+
+/// Nonce = blake3(NONCE_TAG || data)[..12]
 pub fn derive_msg_nonce(data: impl AsRef<[u8]>) -> Nonce {
-    let hash: [u8; 32] = blake3::hash(data.as_ref()).into();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(NONCE_TAG);
+    hasher.update(data.as_ref());
+    let hash: [u8; 32] = hasher.finalize().into();
 
     Nonce::from_iter(hash[..12].iter().map(|u| *u))
 }
 
+/// Version byte leading every sealed blob
+pub const SEALED_VERSION: u8 = 1;
+
+/// Length of the nonce following the version byte of a sealed blob
+const SEALED_NONCE_LEN: usize = 12;
+
+/// Why a sealed blob couldn't be opened
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OpenError {
+    #[error("sealed blob is truncated")]
+    Truncated,
+    #[error("unsupported sealed blob version {0}, expected {SEALED_VERSION}")]
+    UnsupportedVersion(u8),
+    #[error("sealed blob failed to decrypt")]
+    Decrypt,
+}
+
+/// Encrypt `plaintext` into the on-wire blob `version || nonce || ciphertext`
+///
+/// The nonce travels with the ciphertext, so clients never re-derive it. It is derived
+/// from the plaintext, which AES-GCM-SIV tolerates should it repeat.
+pub fn seal(cipher: &Aes256GcmSiv, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let nonce = derive_msg_nonce(plaintext);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| anyhow!(e.to_string()))?;
+
+    Ok([&[SEALED_VERSION], nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Decrypt a blob made by `seal`
+pub fn open(cipher: &Aes256GcmSiv, sealed: &[u8]) -> Result<Vec<u8>, OpenError> {
+    let (&version, rest) = sealed.split_first().ok_or(OpenError::Truncated)?;
+    if version != SEALED_VERSION {
+        return Err(OpenError::UnsupportedVersion(version));
+    }
+    if rest.len() < SEALED_NONCE_LEN {
+        return Err(OpenError::Truncated);
+    }
+
+    let (nonce, ciphertext) = rest.split_at(SEALED_NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| OpenError::Decrypt)
+}
+
+/// Domain tag of stream key derivation
+pub const STREAM_KEY_TAG: &[u8] = b"XFN_STREAM_KEY_V1";
+
+/// Version byte leading every sealed stream chunk
+pub const STREAM_CHUNK_VERSION: u8 = 1;
+
+/// Length of the counter following the version byte of a stream chunk
+const STREAM_COUNTER_LEN: usize = 8;
+
+/// Why a stream chunk couldn't be sealed or opened
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StreamError {
+    #[error("stream chunk is truncated")]
+    Truncated,
+    #[error("unsupported stream chunk version {0}, expected {STREAM_CHUNK_VERSION}")]
+    UnsupportedVersion(u8),
+    #[error("stream chunk {found} is out of order, expected {expected}")]
+    OutOfOrder { expected: u64, found: u64 },
+    #[error("stream chunk failed to encrypt")]
+    Encrypt,
+    #[error("stream chunk failed to decrypt")]
+    Decrypt,
+    #[error("stream counter is exhausted, the stream has ended")]
+    Ended,
+}
+
+/// Cipher of one stream: its key is the session cipher's encryption of zeros under
+/// `derive_msg_nonce(STREAM_KEY_TAG || stream_id)`, a PRF of the session key and stream id
+///
+/// Streams never share a key, so their counter nonces can't collide with each other's, nor
+/// with the content-derived nonces of `seal` under the session key.
+fn stream_cipher(cipher: &Aes256GcmSiv, stream_id: Uuid) -> Aes256GcmSiv {
+    let nonce = derive_msg_nonce([STREAM_KEY_TAG, stream_id.as_bytes()].concat());
+    let keystream = cipher
+        .encrypt(&nonce, [0u8; SESSION_KEY_LEN].as_slice())
+        .expect("a key's worth of zeros is always encryptable");
+
+    cipher_from_key(&keystream[..SESSION_KEY_LEN]).expect("the stream key is 32 bytes")
+}
+
+/// Nonce = 0u32 || counter (big-endian), distinct for every chunk under the stream's key
+fn stream_nonce(counter: u64) -> Nonce {
+    Nonce::from_iter([0u8; 4].into_iter().chain(counter.to_be_bytes()))
+}
+
+/// Seals the chunks of one session stream, each under the next counter nonce
+///
+/// Chunks are `version || counter || ciphertext`. Once the counter is spent the stream has
+/// ended and every further chunk is refused.
+pub struct StreamSealer {
+    cipher: Aes256GcmSiv,
+    next: Option<u64>,
+}
+
+impl StreamSealer {
+    pub fn new(cipher: &Aes256GcmSiv, stream_id: Uuid) -> Self {
+        Self {
+            cipher: stream_cipher(cipher, stream_id),
+            next: Some(0),
+        }
+    }
+
+    /// Encrypt the next chunk
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, StreamError> {
+        let counter = self.next.ok_or(StreamError::Ended)?;
+        let ciphertext = self
+            .cipher
+            .encrypt(&stream_nonce(counter), plaintext)
+            .map_err(|_| StreamError::Encrypt)?;
+        self.next = counter.checked_add(1);
+
+        Ok([&[STREAM_CHUNK_VERSION], counter.to_be_bytes().as_slice(), &ciphertext].concat())
+    }
+}
+
+/// Opens the chunks of a `StreamSealer`, in the order they were sealed
+///
+/// A replayed, dropped or reordered chunk is refused, as its counter isn't the next one.
+pub struct StreamOpener {
+    cipher: Aes256GcmSiv,
+    next: Option<u64>,
+}
+
+impl StreamOpener {
+    pub fn new(cipher: &Aes256GcmSiv, stream_id: Uuid) -> Self {
+        Self {
+            cipher: stream_cipher(cipher, stream_id),
+            next: Some(0),
+        }
+    }
+
+    /// Decrypt the next chunk
+    pub fn open(&mut self, chunk: &[u8]) -> Result<Vec<u8>, StreamError> {
+        let expected = self.next.ok_or(StreamError::Ended)?;
+        let (&version, rest) = chunk.split_first().ok_or(StreamError::Truncated)?;
+        if version != STREAM_CHUNK_VERSION {
+            return Err(StreamError::UnsupportedVersion(version));
+        }
+        if rest.len() < STREAM_COUNTER_LEN {
+            return Err(StreamError::Truncated);
+        }
+
+        let (counter, ciphertext) = rest.split_at(STREAM_COUNTER_LEN);
+        let found = u64::from_be_bytes(counter.try_into().expect("counter is 8 bytes"));
+        if found != expected {
+            return Err(StreamError::OutOfOrder { expected, found });
+        }
+
+        // Decrypting under our own counter, not the chunk's, is what binds the order
+        let plaintext = self
+            .cipher
+            .decrypt(&stream_nonce(expected), ciphertext)
+            .map_err(|_| StreamError::Decrypt)?;
+        self.next = expected.checked_add(1);
+
+        Ok(plaintext)
+    }
+}
+
 pub fn pk_to_hex(pk: &VerifyingKey) -> String {
     pk.to_encoded_point(true).to_string()
 }
@@ -38,8 +251,350 @@ pub fn pk_to_hex(pk: &VerifyingKey) -> String {
 pub fn pk_from_hex(pk_hex: &str) -> anyhow::Result<VerifyingKey> {
     let pk_bytes = const_hex::decode(pk_hex)?;
 
-    let point = k256::EncodedPoint::from_bytes(pk_bytes)?;
-    let pk = k256::ecdsa::VerifyingKey::from_encoded_point(&point)?;
+    Ok(pk_from_sec1(&pk_bytes)?)
+}
+
+/// Why bytes aren't a usable secp256k1 public key
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PublicKeyError {
+    #[error("is the point at infinity")]
+    Identity,
+    #[error("is {0} bytes, expected 33 (compressed) or 65")]
+    Length(usize),
+    #[error("isn't a point on secp256k1")]
+    NotOnCurve,
+}
+
+/// A SEC1-encoded secp256k1 public key (33 bytes compressed, 65 uncompressed)
+///
+/// The point at infinity is refused whatever its length, including all-zero encodings.
+pub fn pk_from_sec1(bytes: &[u8]) -> Result<VerifyingKey, PublicKeyError> {
+    // 0x00 is the SEC1 tag of the identity
+    if bytes.first().is_none_or(|&tag| tag == 0) {
+        return Err(PublicKeyError::Identity);
+    }
+    if !matches!(bytes.len(), 33 | 65) {
+        return Err(PublicKeyError::Length(bytes.len()));
+    }
+
+    let point = k256::EncodedPoint::from_bytes(bytes).map_err(|_| PublicKeyError::NotOnCurve)?;
+    if point.is_identity() {
+        return Err(PublicKeyError::Identity);
+    }
+
+    VerifyingKey::from_encoded_point(&point).map_err(|_| PublicKeyError::NotOnCurve)
+}
+
+/// Domain tag of quote bindings, keeping the signed message apart from other uses of the key
+pub const QUOTE_BINDING_TAG: &[u8] = b"XFN_QUOTE_BINDING_V1";
+
+/// Message = QUOTE_BINDING_TAG || session_id || quote
+fn quote_binding_message(quote: &[u8], session_id: Uuid) -> Vec<u8> {
+    [QUOTE_BINDING_TAG, session_id.as_bytes(), quote].concat()
+}
+
+/// Sign `(quote, session_id)` with the session key, binding the quote to that session
+///
+/// Returns the ECDSA signature, hex-encoded.
+pub fn sign_quote_binding(session_sk: &SigningKey, quote: &[u8], session_id: Uuid) -> String {
+    let signature: Signature = session_sk.sign(&quote_binding_message(quote, session_id));
+    const_hex::encode(signature.to_bytes())
+}
+
+/// Check that `signature` (hex) was made by the session key over `(quote, session_id)`,
+/// i.e. the quote was issued to this session rather than copied from another response
+pub fn verify_quote_binding(
+    session_pk: &VerifyingKey,
+    quote: &[u8],
+    session_id: Uuid,
+    signature: &str,
+) -> bool {
+    let Some(signature) = const_hex::decode(signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        return false;
+    };
+
+    session_pk
+        .verify(&quote_binding_message(quote, session_id), &signature)
+        .is_ok()
+}
+
+/// Domain tag of response signatures
+pub const RESPONSE_SIGNATURE_TAG: &[u8] = b"XFN_RESPONSE_SIGNATURE_V1";
+
+/// Message = RESPONSE_SIGNATURE_TAG || session_id || commitment
+fn response_signature_message(commitment: &[u8], session_id: Uuid) -> Vec<u8> {
+    [RESPONSE_SIGNATURE_TAG, session_id.as_bytes(), commitment].concat()
+}
+
+/// Sign a response's commitment with the session key, so anyone holding only the
+/// session public key can attribute the response to the server
+///
+/// Returns the ECDSA signature, hex-encoded.
+pub fn sign_response(session_sk: &SigningKey, commitment: &[u8], session_id: Uuid) -> String {
+    let signature: Signature = session_sk.sign(&response_signature_message(commitment, session_id));
+    const_hex::encode(signature.to_bytes())
+}
+
+/// Check that `signature` (hex) was made by the session key over the response
+/// `commitment` (raw bytes) of `session_id`
+pub fn verify_response_signature(
+    session_pk: &VerifyingKey,
+    commitment: &[u8],
+    session_id: Uuid,
+    signature: &str,
+) -> bool {
+    let Some(signature) = const_hex::decode(signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        return false;
+    };
+
+    session_pk
+        .verify(&response_signature_message(commitment, session_id), &signature)
+        .is_ok()
+}
+
+/// Turn decrypted bytes into text fit for the LLM
+///
+/// Rejects plaintext over `max_len` bytes, invalid UTF-8 and control characters
+/// other than tab, newline and carriage return. The error completes "<input> ...".
+pub fn decode_plaintext(bytes: Vec<u8>, max_len: usize) -> Result<String, String> {
+    if bytes.len() > max_len {
+        return Err(format!("is {} bytes, over the {max_len} byte limit", bytes.len()));
+    }
+
+    let text = String::from_utf8(bytes).map_err(|_| "isn't valid UTF-8".to_string())?;
+    if let Some((i, c)) = text
+        .char_indices()
+        .find(|(_, c)| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+    {
+        return Err(format!("contains control character U+{:04X} at byte {i}", c as u32));
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::attest::{ReportDataBuilder, OPENAI_DOMAIN};
+
+    #[test]
+    fn test_cipher_errors_name_the_failed_step() {
+        let sk = SigningKey::random(&mut rand::rngs::OsRng);
+        let peer = SigningKey::random(&mut rand::rngs::OsRng);
+        let session_id = Uuid::now_v7();
+        assert!(create_encrypt_key(&sk, peer.verifying_key(), session_id).is_ok());
+
+        // HKDF-SHA256 expands to at most 255 * 32 bytes
+        let shared_sk = diffie_hellman(sk.as_nonzero_scalar(), peer.verifying_key().as_affine());
+        assert!(derive_session_key(&shared_sk, session_id, 255 * 32).is_ok());
+        let err = derive_session_key(&shared_sk, session_id, 255 * 32 + 1).unwrap_err();
+        assert!(matches!(err, CipherError::KeyDerivation(_)), "{err}");
+        assert_eq!(err.code(), "key_derivation_failed");
+
+        // AES-256 takes a 32-byte key only
+        let err = cipher_from_key(&[7u8; 16]).err().unwrap();
+        assert!(matches!(err, CipherError::CipherInit(_)), "{err}");
+        assert_eq!(err.code(), "cipher_init_failed");
+    }
+
+    #[test]
+    fn test_nonce_and_commitment_contexts_differ() {
+        let data = b"same bytes in two contexts";
+
+        let nonce = derive_msg_nonce(data);
+        let commitment = ReportDataBuilder::new(OPENAI_DOMAIN).field(data).digest();
+        let bare: [u8; 32] = blake3::hash(data).into();
+
+        assert_ne!(nonce.as_slice(), &commitment[..12]);
+        assert_ne!(nonce.as_slice(), &bare[..12]);
+        assert_ne!(commitment, bare);
+    }
+
+    fn session_cipher() -> Aes256GcmSiv {
+        let user_sk = SigningKey::random(&mut rand::rngs::OsRng);
+        let session_sk = SigningKey::random(&mut rand::rngs::OsRng);
+        create_encrypt_key(&user_sk, session_sk.verifying_key(), Uuid::now_v7()).unwrap()
+    }
+
+    #[test]
+    fn test_seal_open_round_trip() {
+        let cipher = session_cipher();
+        let plaintext = b"BTC trades at $67,500.50";
+
+        let sealed = seal(&cipher, plaintext).unwrap();
+        assert_eq!(sealed[0], SEALED_VERSION);
+        assert_eq!(&sealed[1..13], derive_msg_nonce(plaintext).as_slice());
+        assert_eq!(open(&cipher, &sealed).unwrap(), plaintext);
+
+        let empty = seal(&cipher, b"").unwrap();
+        assert_eq!(open(&cipher, &empty).unwrap(), b"");
+
+        // Only the session's key opens it
+        assert_eq!(open(&session_cipher(), &sealed), Err(OpenError::Decrypt));
+    }
+
+    #[test]
+    fn test_open_rejects_malformed_blobs() {
+        let cipher = session_cipher();
+        let sealed = seal(&cipher, b"BTC trades at $67,500.50").unwrap();
+
+        let mut other_version = sealed.clone();
+        other_version[0] = SEALED_VERSION + 1;
+        assert_eq!(
+            open(&cipher, &other_version),
+            Err(OpenError::UnsupportedVersion(SEALED_VERSION + 1))
+        );
+
+        assert_eq!(open(&cipher, &[]), Err(OpenError::Truncated));
+        assert_eq!(open(&cipher, &sealed[..8]), Err(OpenError::Truncated));
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(open(&cipher, &tampered), Err(OpenError::Decrypt));
+    }
+
+    #[test]
+    fn test_stream_chunks_open_in_order_only() {
+        let cipher = session_cipher();
+        let stream_id = Uuid::now_v7();
+        let mut sealer = StreamSealer::new(&cipher, stream_id);
+        let mut opener = StreamOpener::new(&cipher, stream_id);
+
+        // Same content, distinct chunks
+        let chunks: Vec<_> = ["BTC", "BTC", "trades at $67,500.50", ""]
+            .iter()
+            .map(|text| sealer.seal(text.as_bytes()).unwrap())
+            .collect();
+        assert_ne!(chunks[0], chunks[1]);
+        assert_eq!(&chunks[1][1..9], 1u64.to_be_bytes().as_slice());
+        // Nor does another stream of the session seal it alike
+        let other_first = StreamSealer::new(&cipher, Uuid::now_v7()).seal(b"BTC").unwrap();
+        assert_ne!(other_first, chunks[0]);
+
+        assert_eq!(opener.open(&chunks[0]).unwrap(), b"BTC");
+        // Replayed chunk
+        assert_eq!(
+            opener.open(&chunks[0]),
+            Err(StreamError::OutOfOrder { expected: 1, found: 0 })
+        );
+        assert_eq!(opener.open(&chunks[1]).unwrap(), b"BTC");
+
+        // A chunk relabelled with the expected counter still fails to decrypt
+        let mut relabelled = chunks[3].clone();
+        relabelled[1..9].copy_from_slice(&2u64.to_be_bytes());
+        assert_eq!(opener.open(&relabelled), Err(StreamError::Decrypt));
+        assert_eq!(opener.open(&chunks[2]).unwrap(), b"trades at $67,500.50");
+        assert_eq!(opener.open(&chunks[3]).unwrap(), b"");
+
+        // Another stream of the session doesn't open these chunks
+        let mut other = StreamOpener::new(&cipher, Uuid::now_v7());
+        assert_eq!(other.open(&chunks[0]), Err(StreamError::Decrypt));
+        assert_eq!(other.open(&[]), Err(StreamError::Truncated));
+        assert_eq!(
+            other.open(&[STREAM_CHUNK_VERSION + 1]),
+            Err(StreamError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn test_stream_ends_when_the_counter_is_spent() {
+        let cipher = session_cipher();
+        let stream_id = Uuid::now_v7();
+        let mut sealer = StreamSealer::new(&cipher, stream_id);
+        let mut opener = StreamOpener::new(&cipher, stream_id);
+        sealer.next = Some(u64::MAX);
+        opener.next = Some(u64::MAX);
+
+        let last = sealer.seal(b"last").unwrap();
+        assert_eq!(sealer.seal(b"one too many"), Err(StreamError::Ended));
+        assert_eq!(opener.open(&last).unwrap(), b"last");
+        assert_eq!(opener.open(&last), Err(StreamError::Ended));
+    }
+
+    #[test]
+    fn test_identity_and_off_curve_keys_are_rejected() {
+        let sk = SigningKey::random(&mut rand::rngs::OsRng);
+        let compressed = sk.verifying_key().to_encoded_point(true);
+        let uncompressed = sk.verifying_key().to_encoded_point(false);
+        assert_eq!(pk_from_sec1(compressed.as_bytes()).unwrap(), *sk.verifying_key());
+        assert_eq!(pk_from_sec1(uncompressed.as_bytes()).unwrap(), *sk.verifying_key());
+
+        for identity in [vec![], vec![0u8], vec![0u8; 33], vec![0u8; 65]] {
+            assert_eq!(pk_from_sec1(&identity), Err(PublicKeyError::Identity));
+        }
+        assert!(pk_from_hex(&"00".repeat(33)).is_err());
+
+        // x = 0 has no point on secp256k1 (7 isn't a square mod p)
+        let mut off_curve = vec![0u8; 33];
+        off_curve[0] = 0x02;
+        assert_eq!(pk_from_sec1(&off_curve), Err(PublicKeyError::NotOnCurve));
+        assert_eq!(pk_from_sec1(&[0x02; 20]), Err(PublicKeyError::Length(20)));
+    }
+
+    #[test]
+    fn test_decode_plaintext() {
+        let text = "What is the price of BTC?\n\tAnd ETH?\r\n";
+        assert_eq!(decode_plaintext(text.into(), 64).unwrap(), text);
+
+        let err = decode_plaintext(vec![b'a'; 65], 64).unwrap_err();
+        assert_eq!(err, "is 65 bytes, over the 64 byte limit");
+
+        let err = decode_plaintext(b"price\x1b[2J of BTC".to_vec(), 64).unwrap_err();
+        assert_eq!(err, "contains control character U+001B at byte 5");
+        assert!(decode_plaintext("a\u{0085}b".into(), 64).is_err());
+
+        assert_eq!(decode_plaintext(vec![0xff, 0xfe], 64).unwrap_err(), "isn't valid UTF-8");
+    }
+
+    #[test]
+    fn test_quote_binding() {
+        let session_sk = SigningKey::random(&mut rand::rngs::OsRng);
+        let session_pk = *session_sk.verifying_key();
+        let (quote, session_id) = (b"quote bytes".as_slice(), Uuid::now_v7());
+
+        let signature = sign_quote_binding(&session_sk, quote, session_id);
+        assert!(verify_quote_binding(&session_pk, quote, session_id, &signature));
+
+        // A quote copied into another session's response fails binding
+        assert!(!verify_quote_binding(&session_pk, quote, Uuid::now_v7(), &signature));
+        assert!(!verify_quote_binding(&session_pk, b"other quote", session_id, &signature));
+
+        let other_pk = *SigningKey::random(&mut rand::rngs::OsRng).verifying_key();
+        assert!(!verify_quote_binding(&other_pk, quote, session_id, &signature));
+        assert!(!verify_quote_binding(&session_pk, quote, session_id, "not hex"));
+    }
+
+    #[test]
+    fn test_response_signature() {
+        let session_sk = SigningKey::random(&mut rand::rngs::OsRng);
+        let session_pk = *session_sk.verifying_key();
+        let session_id = Uuid::now_v7();
+        let commitment = ReportDataBuilder::new(OPENAI_DOMAIN).field("response").digest();
+
+        let signature = sign_response(&session_sk, &commitment, session_id);
+        assert!(verify_response_signature(&session_pk, &commitment, session_id, &signature));
+
+        let mut tampered = commitment;
+        tampered[0] ^= 1;
+        assert!(!verify_response_signature(&session_pk, &tampered, session_id, &signature));
+        assert!(!verify_response_signature(&session_pk, &commitment, Uuid::now_v7(), &signature));
+
+        let mut forged = const_hex::decode(&signature).unwrap();
+        forged[10] ^= 1;
+        let forged = const_hex::encode(forged);
+        assert!(!verify_response_signature(&session_pk, &commitment, session_id, &forged));
+
+        // A quote binding by the same key doesn't pass as a response signature
+        let binding = sign_quote_binding(&session_sk, &commitment, session_id);
+        assert!(!verify_response_signature(&session_pk, &commitment, session_id, &binding));
 
-    Ok(pk)
+        let other_pk = *SigningKey::random(&mut rand::rngs::OsRng).verifying_key();
+        assert!(!verify_response_signature(&other_pk, &commitment, session_id, &signature));
+    }
 }
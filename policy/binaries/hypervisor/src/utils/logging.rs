@@ -0,0 +1,252 @@
+use std::{collections::HashSet, fmt, str::FromStr, sync::Arc};
+
+use serde::{Deserialize, Deserializer};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    Subscriber,
+};
+use tracing_subscriber::{
+    field::{MakeVisitor, VisitFmt, VisitOutput},
+    filter::filter_fn,
+    fmt::{
+        format::{DefaultVisitor, Writer},
+        MakeWriter,
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+/// Printed in place of a redacted field value
+pub const REDACTED: &str = "[redacted]";
+
+/// Fields carrying user data: keys, decrypted queries and prompts, LLM output and
+/// compliance explanations that may quote them
+const DEFAULT_REDACTED_FIELDS: &[&str] = &[
+    "public_key",
+    "query",
+    "prompt",
+    "system_prompt",
+    "response",
+    "arguments",
+    "reason",
+    "explanation",
+];
+
+/// Which log fields are masked, and for which events
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Events at this level or more severe have their sensitive fields masked;
+    /// more verbose events log them as-is. `off` disables redaction
+    #[serde(deserialize_with = "level_filter")]
+    pub redact_level: LevelFilter,
+    /// Field names treated as sensitive
+    pub redacted_fields: Vec<String>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            redact_level: LevelFilter::TRACE,
+            redacted_fields: DEFAULT_REDACTED_FIELDS.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+fn level_filter<'de, D>(deserializer: D) -> Result<LevelFilter, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let level = String::deserialize(deserializer)?;
+    LevelFilter::from_str(&level).map_err(serde::de::Error::custom)
+}
+
+/// Install the global subscriber: `RUST_LOG` filtering (info by default) and
+/// redacted output on stdout
+pub fn init(config: &LoggingConfig) {
+    tracing_subscriber::registry()
+        .with(layer(config, std::io::stdout))
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .init();
+}
+
+/// Formatting layer writing to `make_writer`, masking the sensitive fields of events
+/// at or above `redact_level`
+pub fn layer<S, W>(config: &LoggingConfig, make_writer: W) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Clone + Send + Sync + 'static,
+{
+    let level = config.redact_level;
+    let redacted = tracing_subscriber::fmt::layer()
+        .fmt_fields(RedactFields::new(&config.redacted_fields))
+        .with_writer(make_writer.clone())
+        .with_filter(filter_fn(move |meta| !meta.is_event() || *meta.level() <= level));
+    let plain = tracing_subscriber::fmt::layer()
+        .with_writer(make_writer)
+        .with_filter(filter_fn(move |meta| meta.is_event() && *meta.level() > level));
+
+    redacted.and_then(plain)
+}
+
+/// Field formatter printing `REDACTED` for the configured field names
+struct RedactFields {
+    fields: Arc<HashSet<String>>,
+}
+
+impl RedactFields {
+    fn new(fields: &[String]) -> Self {
+        Self {
+            fields: Arc::new(fields.iter().cloned().collect()),
+        }
+    }
+}
+
+impl<'a> MakeVisitor<Writer<'a>> for RedactFields {
+    type Visitor = RedactVisitor<'a>;
+
+    fn make_visitor(&self, target: Writer<'a>) -> Self::Visitor {
+        RedactVisitor {
+            inner: DefaultVisitor::new(target, true),
+            fields: self.fields.clone(),
+        }
+    }
+}
+
+struct RedactVisitor<'a> {
+    inner: DefaultVisitor<'a>,
+    fields: Arc<HashSet<String>>,
+}
+
+impl RedactVisitor<'_> {
+    fn redacts(&self, field: &Field) -> bool {
+        self.fields.contains(field.name())
+    }
+}
+
+impl Visit for RedactVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if self.redacts(field) {
+            self.inner.record_debug(field, &format_args!("{REDACTED}"));
+        } else {
+            self.inner.record_str(field, value);
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        if self.redacts(field) {
+            self.inner.record_debug(field, &format_args!("{REDACTED}"));
+        } else {
+            self.inner.record_error(field, value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.redacts(field) {
+            self.inner.record_debug(field, &format_args!("{REDACTED}"));
+        } else {
+            self.inner.record_debug(field, value);
+        }
+    }
+}
+
+impl VisitOutput<fmt::Result> for RedactVisitor<'_> {
+    fn finish(self) -> fmt::Result {
+        self.inner.finish()
+    }
+}
+
+impl VisitFmt for RedactVisitor<'_> {
+    fn writer(&mut self) -> &mut dyn fmt::Write {
+        self.inner.writer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::Mutex};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn capture_logs(config: &LoggingConfig, log: impl FnOnce()) -> String {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(layer(config, move || writer.clone()))
+            .with(LevelFilter::DEBUG);
+        tracing::subscriber::with_default(subscriber, log);
+
+        let output = capture.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn log_query() {
+        tracing::info!(
+            public_key = "02deadbeef",
+            query_length = 28,
+            "processing crypto agent query"
+        );
+        tracing::debug!(query = %"Should I buy BTC right now?", "Planning execution");
+    }
+
+    #[test]
+    fn test_sensitive_fields_are_redacted_by_default() {
+        let output = capture_logs(&LoggingConfig::default(), log_query);
+
+        assert!(output.contains("processing crypto agent query"), "{output}");
+        assert!(output.contains("Planning execution"), "{output}");
+        assert!(output.contains("28"), "{output}");
+        assert_eq!(output.matches(REDACTED).count(), 2, "{output}");
+        assert!(!output.contains("02deadbeef"), "{output}");
+        assert!(!output.contains("buy BTC"), "{output}");
+    }
+
+    #[test]
+    fn test_verbose_events_are_revealed_below_redact_level() {
+        let config = LoggingConfig {
+            redact_level: LevelFilter::INFO,
+            ..Default::default()
+        };
+        let output = capture_logs(&config, log_query);
+
+        assert!(!output.contains("02deadbeef"), "{output}");
+        assert!(output.contains("Should I buy BTC right now?"), "{output}");
+    }
+
+    #[test]
+    fn test_agent_error_reason_is_redacted() {
+        use axum::response::IntoResponse;
+
+        use crate::{agent::AgentError, error::HypervisorError};
+
+        let output = capture_logs(&LoggingConfig::default(), || {
+            let e = AgentError::Compliance("the user asked to buy BTC with 0xabc".to_string());
+            let _ = HypervisorError::from(e).into_response();
+        });
+
+        assert!(output.contains("compliance_failed"), "{output}");
+        assert!(output.contains(REDACTED), "{output}");
+        assert!(!output.contains("buy BTC"), "{output}");
+    }
+}
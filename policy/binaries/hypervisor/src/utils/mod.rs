@@ -1,4 +1,6 @@
 pub mod attest;
 pub mod commitment_openai;
 pub mod crypto;
-pub mod hasher;
+pub mod logging;
+pub mod models;
+pub mod verify;
@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tiktoken_rs::tokenizer::Tokenizer;
+use tracing::warn;
+
+/// Model used when no other is configured
+pub const DEFAULT_MODEL: &str = "gpt-4o";
+
+/// Seconds a chat completion call may take, reading its body included
+pub const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// Largest chat completion body read before giving up
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 4 << 20;
+
+/// Why a chat completion body could not be read
+#[derive(Debug, thiserror::Error)]
+pub enum ResponseError {
+    #[error("response body exceeds {0} bytes")]
+    TooLarge(usize),
+
+    #[error("failed to read response body: {0}")]
+    Read(#[from] reqwest::Error),
+
+    #[error("response body is not JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Read a JSON body, stopping as soon as it grows past `max_bytes`
+pub async fn read_json(
+    response: Response,
+    max_bytes: usize,
+) -> Result<serde_json::Value, ResponseError> {
+    Ok(serde_json::from_slice(&read_body(response, max_bytes).await?)?)
+}
+
+/// Body of an error response, read within `max_bytes` like a completion
+pub async fn read_error_text(response: Response, max_bytes: usize) -> String {
+    match read_body(response, max_bytes).await {
+        Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+        Err(e) => format!("<{e}>"),
+    }
+}
+
+async fn read_body(mut response: Response, max_bytes: usize) -> Result<Vec<u8>, ResponseError> {
+    if response.content_length().is_some_and(|len| len > max_bytes as u64) {
+        return Err(ResponseError::TooLarge(max_bytes));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(ResponseError::TooLarge(max_bytes));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
+
+/// Why the model stopped generating, from `finish_reason` of the first choice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Stop,
+    /// Cut off at `max_tokens`
+    Length,
+    /// Withheld by the content filter
+    ContentFilter,
+    ToolCalls,
+    #[serde(other)]
+    Other,
+}
+
+/// First choice of a chat completion
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub content: String,
+    pub finish_reason: FinishReason,
+}
+
+/// Why a chat completion has no usable answer
+#[derive(Debug, thiserror::Error)]
+pub enum CompletionError {
+    #[error("completion was blocked by the content filter")]
+    ContentFiltered,
+
+    #[error("model refused to answer: {0}")]
+    Refused(String),
+
+    #[error("completion has no content")]
+    NoContent,
+}
+
+impl CompletionError {
+    /// The model declined the prompt, as opposed to answering in an unexpected shape
+    pub fn is_refusal(&self) -> bool {
+        matches!(self, CompletionError::ContentFiltered | CompletionError::Refused(_))
+    }
+}
+
+/// Tokens of `text` in the encoding of `model`
+///
+/// Models tiktoken doesn't know are counted with the `o200k_base` encoding of the gpt-4o family.
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    let bpe = match tiktoken_rs::tokenizer::get_tokenizer(model) {
+        Some(Tokenizer::Cl100kBase) => tiktoken_rs::cl100k_base_singleton(),
+        _ => tiktoken_rs::o200k_base_singleton(),
+    };
+
+    bpe.encode_ordinary(text).len()
+}
+
+/// Content of the first choice of a chat completion body
+///
+/// A missing `finish_reason` counts as `stop`; filtered and refused completions are
+/// errors rather than empty answers.
+pub fn parse_completion(response: &serde_json::Value) -> Result<Completion, CompletionError> {
+    parse_choice(&response["choices"][0])
+}
+
+/// Content of every choice of a chat completion body requested with `n` > 1, by `index`
+///
+/// Fails as `parse_completion` does if any choice has no usable answer.
+pub fn parse_completions(
+    response: &serde_json::Value,
+) -> Result<Vec<Completion>, CompletionError> {
+    let mut choices: Vec<_> = response["choices"].as_array().into_iter().flatten().collect();
+    if choices.is_empty() {
+        return Err(CompletionError::NoContent);
+    }
+    choices.sort_by_key(|choice| choice["index"].as_u64());
+
+    choices.into_iter().map(parse_choice).collect()
+}
+
+fn parse_choice(choice: &serde_json::Value) -> Result<Completion, CompletionError> {
+    let finish_reason = FinishReason::deserialize(&choice["finish_reason"])
+        .unwrap_or(FinishReason::Stop);
+    if finish_reason == FinishReason::ContentFilter {
+        return Err(CompletionError::ContentFiltered);
+    }
+    if let Some(refusal) = choice["message"]["refusal"].as_str() {
+        return Err(CompletionError::Refused(refusal.to_string()));
+    }
+
+    let content = choice["message"]["content"]
+        .as_str()
+        .ok_or(CompletionError::NoContent)?;
+
+    Ok(Completion {
+        content: content.to_string(),
+        finish_reason,
+    })
+}
+
+/// Models of the chat completion calls, tried in order while they are unavailable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelsConfig {
+    pub primary: String,
+    /// Models tried, in order, once the previous one keeps failing
+    pub fallbacks: Vec<String>,
+    /// Attempts per model on 429 or 5xx before moving to the next one
+    pub attempts: u32,
+    /// Seconds each request may take, reading its body included
+    pub timeout_secs: u64,
+    /// Largest response body read, in bytes
+    pub max_response_bytes: usize,
+}
+
+impl Default for ModelsConfig {
+    fn default() -> Self {
+        Self {
+            primary: DEFAULT_MODEL.to_string(),
+            fallbacks: Vec::new(),
+            attempts: 1,
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+        }
+    }
+}
+
+impl ModelsConfig {
+    /// Send the request built for each model in turn until one is available
+    ///
+    /// Returns the model of the last request and its response, which is an error
+    /// response when every model was unavailable.
+    pub async fn send(
+        &self,
+        request: impl Fn(&str) -> RequestBuilder,
+    ) -> reqwest::Result<(String, Response)> {
+        let tries: Vec<&str> = std::iter::once(&self.primary)
+            .chain(&self.fallbacks)
+            .flat_map(|model| std::iter::repeat_n(model.as_str(), self.attempts.max(1) as usize))
+            .collect();
+
+        let mut tries = tries.into_iter().peekable();
+        loop {
+            let model = tries.next().expect("at least one try");
+            let response = request(model)
+                .timeout(Duration::from_secs(self.timeout_secs))
+                .send()
+                .await?;
+
+            let status = response.status();
+            let unavailable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            match tries.peek() {
+                Some(next) if unavailable => {
+                    warn!(model = %model, next = %next, status = %status, "model unavailable");
+                }
+                _ => return Ok((model.to_string(), response)),
+            }
+        }
+    }
+
+    /// Read a chat completion body within `max_response_bytes`
+    pub async fn read_json(&self, response: Response) -> Result<serde_json::Value, ResponseError> {
+        read_json(response, self.max_response_bytes).await
+    }
+
+    /// Read an error body within `max_response_bytes`
+    pub async fn read_error_text(&self, response: Response) -> String {
+        read_error_text(response, self.max_response_bytes).await
+    }
+}
@@ -0,0 +1,144 @@
+//! Trust policy applied to quotes returned by the verifiable endpoints
+
+use attest::{
+    types::Quote,
+    verify::{ExpectedMeasurement, Measurements},
+};
+
+use crate::utils::attest::ReportData;
+
+/// Outcome of checking a quote against the trust policy
+#[derive(Debug, Clone)]
+pub enum VerifyOutcome {
+    /// The quote parsed and matched the expected report_data and measurements
+    Verified(Box<Quote>),
+    /// The bytes are not a quote
+    BadFormat(String),
+    /// The lower half of report_data isn't the expected digest (both hex-encoded)
+    ReportDataMismatch { expected: String, actual: String },
+    /// The TD measurements are not in the accepted list
+    MeasurementMismatch(String),
+}
+
+impl std::fmt::Display for VerifyOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyOutcome::Verified(_) => write!(f, "verified"),
+            VerifyOutcome::BadFormat(e) => write!(f, "invalid quote {e}"),
+            VerifyOutcome::ReportDataMismatch { expected, actual } => {
+                write!(f, "report_data {actual} doesn't match {expected}")
+            }
+            VerifyOutcome::MeasurementMismatch(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Parse a quote and check its report_data and TD measurements
+///
+/// `expected_report_data` is compared with the lower half of report_data, skipped when
+/// `None`; an empty `expected_measurements` accepts any measurements.
+/// The quote's signature and TCB are not checked here, see `Quote::verify`.
+pub fn verify_quote(
+    quote_bytes: &[u8],
+    expected_report_data: Option<&[u8; 32]>,
+    expected_measurements: &[ExpectedMeasurement],
+) -> VerifyOutcome {
+    let quote = match Quote::from_bytes(quote_bytes) {
+        Ok(quote) => quote,
+        Err(e) => return VerifyOutcome::BadFormat(e.to_string()),
+    };
+
+    match check_report(
+        &quote.report_data(),
+        quote.measurements(),
+        expected_report_data,
+        expected_measurements,
+    ) {
+        Ok(()) => VerifyOutcome::Verified(Box::new(quote)),
+        Err(outcome) => outcome,
+    }
+}
+
+/// Check the parsed parts of a quote, the failing outcome as error
+fn check_report(
+    report_data: &[u8; 64],
+    measurements: Option<Measurements>,
+    expected_report_data: Option<&[u8; 32]>,
+    expected_measurements: &[ExpectedMeasurement],
+) -> Result<(), VerifyOutcome> {
+    if let Some(expected) = expected_report_data {
+        let actual = ReportData::from_bytes(report_data).hash;
+        if actual != *expected {
+            return Err(VerifyOutcome::ReportDataMismatch {
+                expected: const_hex::encode(expected),
+                actual: const_hex::encode(actual),
+            });
+        }
+    }
+
+    if expected_measurements.is_empty() {
+        return Ok(());
+    }
+
+    measurements
+        .ok_or_else(|| "SGX quote has no TD measurements".to_string())
+        .and_then(|m| m.check(expected_measurements).map_err(|e| e.to_string()))
+        .map_err(VerifyOutcome::MeasurementMismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurements() -> Measurements {
+        Measurements {
+            mrtd: [1; 48],
+            rtmr3: [2; 48],
+        }
+    }
+
+    fn report_data() -> [u8; 64] {
+        let mut data = [0; 64];
+        data[..32].copy_from_slice(&[7; 32]);
+        data
+    }
+
+    #[test]
+    fn test_bad_format() {
+        let outcome = verify_quote(&[0; 8], None, &[]);
+        assert!(matches!(outcome, VerifyOutcome::BadFormat(_)), "{outcome:?}");
+    }
+
+    #[test]
+    fn test_wrong_report_data() {
+        let outcome = check_report(&report_data(), Some(measurements()), Some(&[8; 32]), &[]);
+        assert!(matches!(
+            outcome,
+            Err(VerifyOutcome::ReportDataMismatch { ref actual, .. })
+                if *actual == const_hex::encode([7; 32])
+        ));
+
+        assert!(check_report(&report_data(), None, Some(&[7; 32]), &[]).is_ok());
+        assert!(check_report(&report_data(), None, None, &[]).is_ok());
+    }
+
+    #[test]
+    fn test_wrong_measurement() {
+        let pinned = [ExpectedMeasurement {
+            mrtd: Some(const_hex::encode([1; 48])),
+            rtmr3: Some(const_hex::encode([3; 48])),
+        }];
+
+        let outcome = check_report(&report_data(), Some(measurements()), None, &pinned);
+        assert!(matches!(outcome, Err(VerifyOutcome::MeasurementMismatch(_))));
+
+        let outcome = check_report(&report_data(), None, None, &pinned);
+        assert!(matches!(outcome, Err(VerifyOutcome::MeasurementMismatch(_))));
+
+        let accepted = [ExpectedMeasurement {
+            rtmr3: Some(const_hex::encode([2; 48])),
+            ..Default::default()
+        }];
+        assert!(check_report(&report_data(), Some(measurements()), None, &accepted).is_ok());
+    }
+}
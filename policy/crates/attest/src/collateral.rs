@@ -0,0 +1,275 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    errors::CollateralError,
+    verify::{PcsCollateral, SignedQeIdentity, SignedTcbInfo},
+};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// CA that issued the platform's PCK certificate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PckCa {
+    Processor,
+    Platform,
+}
+
+impl PckCa {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PckCa::Processor => "processor",
+            PckCa::Platform => "platform",
+        }
+    }
+}
+
+struct CacheEntry {
+    /// `None` for preloaded bundles, which never expire
+    fetched_at: Option<Instant>,
+    collateral: PcsCollateral,
+}
+
+/// Fetches TDX collateral from a PCCS (v4 API) and caches it per FMSPC and CA
+///
+/// Without a base URL the client is offline and only serves preloaded bundles.
+pub struct CollateralClient {
+    base_url: Option<String>,
+    ttl: Duration,
+    http: reqwest::Client,
+    cache: Mutex<HashMap<(String, PckCa), CacheEntry>>,
+}
+
+impl CollateralClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_base_url(Some(base_url.into()))
+    }
+
+    /// Client that never fetches, see `preload`
+    pub fn offline() -> Self {
+        Self::with_base_url(None)
+    }
+
+    fn with_base_url(base_url: Option<String>) -> Self {
+        Self {
+            base_url: base_url.map(|url| url.trim_end_matches('/').to_string()),
+            ttl: DEFAULT_TTL,
+            http: reqwest::Client::new(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How long fetched collateral is served from cache
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Serve `collateral` for the FMSPC and CA without fetching it
+    pub fn preload(&self, fmspc: &str, ca: PckCa, collateral: PcsCollateral) {
+        self.cache
+            .lock()
+            .expect("collateral cache poisoned")
+            .insert(
+                (fmspc.to_lowercase(), ca),
+                CacheEntry {
+                    fetched_at: None,
+                    collateral,
+                },
+            );
+    }
+
+    /// Collateral for a platform FMSPC (hex) whose PCK certificate was issued by `ca`
+    pub async fn collateral(
+        &self,
+        fmspc: &str,
+        ca: PckCa,
+    ) -> Result<PcsCollateral, CollateralError> {
+        let key = (fmspc.to_lowercase(), ca);
+        if let Some(collateral) = self.cached(&key) {
+            return Ok(collateral);
+        }
+
+        let Some(base_url) = &self.base_url else {
+            return Err(CollateralError::Offline {
+                fmspc: key.0,
+                ca: ca.as_str(),
+            });
+        };
+
+        tracing::debug!("fetching collateral for FMSPC {} ({})", key.0, ca.as_str());
+        let collateral = PcsCollateral {
+            tcb_info: self
+                .get_json::<SignedTcbInfo>(format!(
+                    "{base_url}/tdx/certification/v4/tcb?fmspc={}",
+                    key.0
+                ))
+                .await?,
+            qe_identity: self
+                .get_json::<SignedQeIdentity>(format!(
+                    "{base_url}/tdx/certification/v4/qe/identity"
+                ))
+                .await?,
+            pck_crl: self
+                .get_hex(format!(
+                    "{base_url}/sgx/certification/v4/pckcrl?ca={}&encoding=der",
+                    ca.as_str()
+                ))
+                .await?,
+            root_ca_crl: self
+                .get_hex(format!("{base_url}/sgx/certification/v4/rootcacrl"))
+                .await?,
+        };
+
+        self.cache
+            .lock()
+            .expect("collateral cache poisoned")
+            .insert(
+                key,
+                CacheEntry {
+                    fetched_at: Some(Instant::now()),
+                    collateral: collateral.clone(),
+                },
+            );
+
+        Ok(collateral)
+    }
+
+    fn cached(&self, key: &(String, PckCa)) -> Option<PcsCollateral> {
+        let cache = self.cache.lock().expect("collateral cache poisoned");
+        cache
+            .get(key)
+            .filter(|entry| match entry.fetched_at {
+                Some(fetched_at) => fetched_at.elapsed() < self.ttl,
+                None => true,
+            })
+            .map(|entry| entry.collateral.clone())
+    }
+
+    async fn get(&self, url: String) -> Result<reqwest::Response, CollateralError> {
+        let resp = self.http.get(&url).send().await?;
+        if !resp.status().is_success() {
+            return Err(CollateralError::Status {
+                url,
+                status: resp.status(),
+            });
+        }
+
+        Ok(resp)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: String) -> Result<T, CollateralError> {
+        let body = self.get(url.clone()).await?.bytes().await?;
+        serde_json::from_slice(&body).map_err(|e| CollateralError::Parse(format!("{url}: {e}")))
+    }
+
+    /// CRLs are served as DER; a PCCS may also return them hex-encoded
+    async fn get_hex(&self, url: String) -> Result<String, CollateralError> {
+        let body = self.get(url).await?.bytes().await?;
+        match std::str::from_utf8(&body) {
+            Ok(text) if const_hex::decode(text.trim()).is_ok() => Ok(text.trim().to_lowercase()),
+            _ => Ok(const_hex::encode(&body)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{extract::State, routing::get, Router};
+
+    use super::*;
+
+    const TDX_TCB_INFO: &str = include_str!("../fixtures/tdx_tcb_info.json");
+    const TDX_QE_IDENTITY: &str = include_str!("../fixtures/tdx_qe_identity.json");
+    const PCK_CRL: &[u8] = &[0x30, 0x82, 0x01, 0x0a];
+
+    /// Serve the fixture collateral, counting TCB info requests
+    async fn mock_pccs() -> (String, Arc<AtomicUsize>) {
+        async fn tcb_info(State(hits): State<Arc<AtomicUsize>>) -> &'static str {
+            hits.fetch_add(1, Ordering::SeqCst);
+            TDX_TCB_INFO
+        }
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route("/tdx/certification/v4/tcb", get(tcb_info))
+            .route(
+                "/tdx/certification/v4/qe/identity",
+                get(|| async { TDX_QE_IDENTITY }),
+            )
+            .route("/sgx/certification/v4/pckcrl", get(|| async { PCK_CRL }))
+            .route(
+                "/sgx/certification/v4/rootcacrl",
+                get(|| async { "308201AB" }),
+            )
+            .with_state(hits.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        (format!("http://{addr}"), hits)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_cache_collateral() {
+        let (base_url, hits) = mock_pccs().await;
+        let client = CollateralClient::new(base_url);
+
+        let collateral = client
+            .collateral("00806F050000", PckCa::Platform)
+            .await
+            .unwrap();
+        assert_eq!(collateral.tcb_info.tcb_info.fmspc, "00806f050000");
+        assert_eq!(collateral.qe_identity.enclave_identity.id, "TD_QE");
+        assert_eq!(collateral.pck_crl, "3082010a");
+        assert_eq!(collateral.root_ca_crl, "308201ab");
+
+        // The second lookup is served from cache
+        client
+            .collateral("00806f050000", PckCa::Platform)
+            .await
+            .unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // An expired entry is fetched again
+        let client = client.with_ttl(Duration::ZERO);
+        client
+            .collateral("00806f050000", PckCa::Platform)
+            .await
+            .unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_offline_serves_preloaded_collateral() {
+        let (base_url, _) = mock_pccs().await;
+        let bundle = CollateralClient::new(base_url)
+            .collateral("00806f050000", PckCa::Processor)
+            .await
+            .unwrap();
+
+        let client = CollateralClient::offline();
+        assert!(matches!(
+            client.collateral("00806f050000", PckCa::Processor).await,
+            Err(CollateralError::Offline { .. })
+        ));
+
+        client.preload("00806f050000", PckCa::Processor, bundle);
+        let collateral = client
+            .collateral("00806f050000", PckCa::Processor)
+            .await
+            .unwrap();
+        assert_eq!(collateral.tcb_info.tcb_info.tcb_levels.len(), 3);
+    }
+}
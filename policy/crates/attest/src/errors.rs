@@ -6,8 +6,20 @@ pub enum QuoteError {
     #[error("unknown version {0}")]
     UnknownQuote(u16),
 
+    #[error("unknown TEE type {0:#x}")]
+    UnknownTeeType(u32),
+
     #[error("report data {0}")]
     ReportData(String),
+
+    #[error("collateral {0}")]
+    Collateral(String),
+
+    #[error("measurements mrtd {mrtd}, rtmr3 {rtmr3} are not in the accepted list")]
+    MeasurementMismatch { mrtd: String, rtmr3: String },
+
+    #[error("measurements {0}")]
+    Measurements(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -15,6 +27,9 @@ pub enum AttestationError {
     #[error("ioctl {0}")]
     Ioctl(String),
 
+    #[error("mock {0}")]
+    Mock(String),
+
     #[error("coco {0}")]
     Coco(#[from] tdx_attestation_sdk::error::TdxError),
 
@@ -24,3 +39,21 @@ pub enum AttestationError {
     #[error("no provider available, should run inside guest vm")]
     NoProviderAvailable,
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum CollateralError {
+    #[error("request {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("PCCS returned {status} for {url}")]
+    Status {
+        url: String,
+        status: reqwest::StatusCode,
+    },
+
+    #[error("parse {0}")]
+    Parse(String),
+
+    #[error("no collateral preloaded for FMSPC {fmspc} ({ca}) in offline mode")]
+    Offline { fmspc: String, ca: &'static str },
+}
@@ -1,47 +1,198 @@
+pub mod collateral;
 pub mod errors;
 pub mod provider;
+pub mod quote_cache;
 pub mod types;
+pub mod verify;
 
-use std::path::Path;
+use std::{path::Path, str::FromStr, sync::OnceLock, time::Duration};
 
 use errors::AttestationError;
-use types::{K256PkReport, Quote, RawReport};
+use quote_cache::QuoteCache;
+use serde::{Deserialize, Serialize};
+use types::{Ed25519PkReport, K256PkReport, Quote, RawReport, TeeType};
 
-#[derive(Debug)]
+/// Source of TDX quotes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Provider {
+    /// Legacy /dev/tdx_guest, available on patched 5.x kernels (e.g. alinux3 from aliyun)
     Ioctl,
+    /// configfs-tsm
     Coco,
+    /// Captured quote file at `ATTEST_MOCK_QUOTE`, with the `mock` feature; never tried
+    /// unless put in the order
+    Mock,
 }
 
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ioctl" => Ok(Provider::Ioctl),
+            "coco" => Ok(Provider::Coco),
+            "mock" => Ok(Provider::Mock),
+            other => Err(format!("unknown attestation provider '{other}'")),
+        }
+    }
+}
+
+/// Providers tried by `get_quote` unless another order is set
+pub const DEFAULT_PROVIDER_ORDER: &[Provider] = &[Provider::Coco, Provider::Ioctl];
+
+/// Comma-separated provider order (e.g. `ioctl,coco`), used when none is set in code
+pub const PROVIDER_ORDER_ENV: &str = "ATTEST_PROVIDERS";
+
+/// Path of the captured raw quote served by the mock provider
+pub const MOCK_QUOTE_ENV: &str = "ATTEST_MOCK_QUOTE";
+
 const IOCTL_DEVICE_PATH: &str = "/dev/tdx_guest";
 
-/*
-pub fn get_quote(report: RawReport) -> Result<Quote, AttestationError> {
-    let provider = match tdx_attestation_sdk::device::Device::default() {
-        Ok(_) => Provider::Coco,
-        // Fallback to legacy /dev/tdx_guest, which is available on
-        // patched kernel 5.x. For example, alinux3 from aliyun
-        Err(_) if Path::new(IOCTL_DEVICE_PATH).exists() => Provider::Ioctl,
-        Err(_) => return Err(AttestationError::NoProviderAvailable),
+const CONFIGFS_TSM_PATH: &str = "/sys/kernel/config/tsm/report";
+
+const SGX_DEVICE_PATH: &str = "/dev/sgx_enclave";
+
+/// Cargo features of this crate that change which providers work
+const COMPILED_FEATURES: &[(&str, bool)] =
+    &[("ioctl", cfg!(feature = "ioctl")), ("mock", cfg!(feature = "mock"))];
+
+static QUOTE_CACHE: OnceLock<QuoteCache> = OnceLock::new();
+
+static PROVIDER_ORDER: OnceLock<Vec<Provider>> = OnceLock::new();
+
+/// Reuse the quote over identical report data for `ttl` in `get_quote`
+///
+/// Off unless called; returns false when the cache was already enabled.
+pub fn enable_quote_cache(ttl: Duration) -> bool {
+    QUOTE_CACHE.set(QuoteCache::new(ttl)).is_ok()
+}
+
+/// Try the providers in `order` in `get_quote`, each falling back to the next
+///
+/// Returns false when the order was already set or used.
+pub fn set_provider_order(order: Vec<Provider>) -> bool {
+    PROVIDER_ORDER.set(order).is_ok()
+}
+
+/// Parse a comma-separated provider order
+pub fn parse_provider_order(order: &str) -> Result<Vec<Provider>, String> {
+    order.split(',').map(str::parse).collect()
+}
+
+fn provider_order() -> &'static [Provider] {
+    PROVIDER_ORDER.get_or_init(|| match std::env::var(PROVIDER_ORDER_ENV) {
+        Ok(order) => parse_provider_order(&order).unwrap_or_else(|e| {
+            tracing::warn!("{PROVIDER_ORDER_ENV}: {e}, using the default order");
+            DEFAULT_PROVIDER_ORDER.to_vec()
+        }),
+        Err(_) => DEFAULT_PROVIDER_ORDER.to_vec(),
+    })
+}
+
+/// What this instance can attest with, for telling why no provider is available
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    /// TEE whose attestation device is present; none outside a guest VM or enclave
+    pub tee_type: Option<TeeType>,
+    /// Every provider, in the order `get_quote` tries them, then the untried ones
+    pub providers: Vec<ProviderCapability>,
+    /// Cargo features the crate was built with
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderCapability {
+    pub provider: Provider,
+    /// Whether `get_quote` tries it
+    pub enabled: bool,
+    /// Built in, i.e. not behind a missing feature
+    pub compiled: bool,
+    /// Built in and its device (or, for the mock, quote file) is present
+    pub available: bool,
+}
+
+/// Attestation capabilities of the running instance
+pub fn provider_capabilities() -> ProviderCapabilities {
+    let order = provider_order();
+    let untried = [Provider::Coco, Provider::Ioctl, Provider::Mock]
+        .into_iter()
+        .filter(|provider| !order.contains(provider));
+    let providers = order
+        .iter()
+        .copied()
+        .chain(untried)
+        .map(|provider| {
+            let (compiled, present) = match provider {
+                Provider::Ioctl => (cfg!(feature = "ioctl"), Path::new(IOCTL_DEVICE_PATH).exists()),
+                Provider::Coco => (true, Path::new(CONFIGFS_TSM_PATH).exists()),
+                Provider::Mock => (
+                    cfg!(feature = "mock"),
+                    std::env::var_os(MOCK_QUOTE_ENV).is_some_and(|path| Path::new(&path).exists()),
+                ),
+            };
+            ProviderCapability {
+                provider,
+                enabled: order.contains(&provider),
+                compiled,
+                available: compiled && present,
+            }
+        })
+        .collect();
+
+    let tee_type = if [IOCTL_DEVICE_PATH, CONFIGFS_TSM_PATH].iter().any(|p| Path::new(p).exists()) {
+        Some(TeeType::Tdx)
+    } else if Path::new(SGX_DEVICE_PATH).exists() {
+        Some(TeeType::Sgx)
+    } else {
+        None
     };
 
-    get_quote_with_provider(report, provider)
+    ProviderCapabilities {
+        tee_type,
+        providers,
+        features: COMPILED_FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(feature, _)| feature.to_string())
+            .collect(),
+    }
 }
-*/
 
 pub fn get_quote(report: RawReport) -> Result<Quote, AttestationError> {
-    let provider = match tdx_attestation_sdk::device::Device::default() {
-        Ok(_) => Provider::Coco,
-        Err(e) => {
-            tracing::warn!("Coco provider failed: {:?}, falling back", e);
-            if Path::new(IOCTL_DEVICE_PATH).exists() {
-                Provider::Ioctl
-            } else {
-                return Err(AttestationError::NoProviderAvailable);
+    let raw_quote = match QUOTE_CACHE.get() {
+        Some(cache) => cache.get_or_generate(report, get_raw_quote)?,
+        None => get_raw_quote(report)?,
+    };
+
+    Ok(Quote::from_bytes(&raw_quote)?)
+}
+
+fn get_raw_quote(report: RawReport) -> Result<Vec<u8>, AttestationError> {
+    get_raw_quote_in_order(report, provider_order(), get_raw_quote_with_provider)
+}
+
+/// Raw quote of the first provider in `order` producing one; the last error when none does
+fn get_raw_quote_in_order(
+    report: RawReport,
+    order: &[Provider],
+    get_raw_quote: impl Fn(RawReport, Provider) -> Result<Vec<u8>, AttestationError>,
+) -> Result<Vec<u8>, AttestationError> {
+    let mut last_err = AttestationError::NoProviderAvailable;
+    for &provider in order {
+        match get_raw_quote(report.clone(), provider) {
+            Ok(raw_quote) => {
+                tracing::info!("quote produced by {provider:?} provider");
+                return Ok(raw_quote);
+            }
+            Err(e) => {
+                tracing::warn!("{provider:?} provider failed: {e}, falling back");
+                last_err = e;
             }
         }
-    };
-    get_quote_with_provider(report, provider)
+    }
+
+    Err(last_err)
 }
 
 pub fn get_quote_for_k256_pk(report: K256PkReport) -> Result<Quote, AttestationError> {
@@ -50,12 +201,21 @@ pub fn get_quote_for_k256_pk(report: K256PkReport) -> Result<Quote, AttestationE
     get_quote(report.to_raw())
 }
 
-fn get_quote_with_provider(
+pub fn get_quote_for_ed25519_pk(report: Ed25519PkReport) -> Result<Quote, AttestationError> {
+    tracing::info!("quote report {}", report);
+
+    get_quote(report.to_raw())
+}
+
+fn get_raw_quote_with_provider(
     report: RawReport,
     provider: Provider,
-) -> Result<Quote, AttestationError> {
+) -> Result<Vec<u8>, AttestationError> {
     let raw_quote = match provider {
         Provider::Ioctl => {
+            if !Path::new(IOCTL_DEVICE_PATH).exists() {
+                return Err(AttestationError::NoProviderAvailable);
+            }
             #[cfg(feature = "ioctl")]
             {
                 provider::ioctl::get_raw_quote(report)?
@@ -65,10 +225,126 @@ fn get_quote_with_provider(
                 return Err(AttestationError::Ioctl("feature isn't enabled".to_string()));
             }
         }
-        Provider::Coco => provider::coco::get_raw_quote(report)?,
+        Provider::Coco => {
+            tdx_attestation_sdk::device::Device::default()?;
+            provider::coco::get_raw_quote(report)?
+        }
+        Provider::Mock => {
+            #[cfg(feature = "mock")]
+            {
+                provider::mock::get_raw_quote(report)?
+            }
+            #[cfg(not(feature = "mock"))]
+            {
+                return Err(AttestationError::Mock("feature isn't enabled".to_string()));
+            }
+        }
     };
 
-    let quote = Quote::from_bytes(&raw_quote)?;
+    Ok(raw_quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Provider standing in for the devices, recording the order it was asked in
+    fn mock_provider<'a>(
+        working: &'static [Provider],
+        calls: &'a Mutex<Vec<Provider>>,
+    ) -> impl Fn(RawReport, Provider) -> Result<Vec<u8>, AttestationError> + 'a {
+        move |report, provider| {
+            calls.lock().unwrap().push(provider);
+            if !working.contains(&provider) {
+                return Err(AttestationError::Ioctl(format!("{provider:?} is down")));
+            }
+            let mut raw_quote = format!("{provider:?}").into_bytes();
+            raw_quote.extend(report.to_bytes());
+            Ok(raw_quote)
+        }
+    }
+
+    #[test]
+    fn test_configured_provider_order_is_honored() {
+        let report = RawReport::new([9; 64]);
+        let order = parse_provider_order("ioctl, coco").unwrap();
+        assert_eq!(order, [Provider::Ioctl, Provider::Coco]);
+
+        let calls = Mutex::new(Vec::new());
+        let both = &[Provider::Coco, Provider::Ioctl];
+        let raw_quote =
+            get_raw_quote_in_order(report.clone(), &order, mock_provider(both, &calls)).unwrap();
+        assert!(raw_quote.starts_with(b"Ioctl"));
+        assert_eq!(*calls.lock().unwrap(), [Provider::Ioctl]);
+
+        // The default order still prefers Coco
+        let calls = Mutex::new(Vec::new());
+        let raw_quote =
+            get_raw_quote_in_order(report, DEFAULT_PROVIDER_ORDER, mock_provider(both, &calls))
+                .unwrap();
+        assert!(raw_quote.starts_with(b"Coco"));
+        assert_eq!(*calls.lock().unwrap(), [Provider::Coco]);
+
+        assert!(parse_provider_order("coco,sgx").is_err());
+    }
+
+    #[test]
+    fn test_failed_provider_falls_back_to_the_next() {
+        let report = RawReport::new([9; 64]);
+        let order = [Provider::Ioctl, Provider::Coco];
+
+        let calls = Mutex::new(Vec::new());
+        let raw_quote =
+            get_raw_quote_in_order(report.clone(), &order, mock_provider(&[Provider::Coco], &calls))
+                .unwrap();
+        assert!(raw_quote.starts_with(b"Coco"));
+        assert_eq!(*calls.lock().unwrap(), order);
+
+        // With every provider down, the last error is returned
+        let calls = Mutex::new(Vec::new());
+        let err = get_raw_quote_in_order(report.clone(), &order, mock_provider(&[], &calls))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "ioctl Coco is down");
+
+        let err = get_raw_quote_in_order(report, &[], mock_provider(&[], &calls)).unwrap_err();
+        assert!(matches!(err, AttestationError::NoProviderAvailable));
+    }
+
+    #[test]
+    fn test_capabilities_report_compiled_features() {
+        let capabilities = provider_capabilities();
+        let providers: Vec<_> = capabilities.providers.iter().map(|p| p.provider).collect();
+        assert_eq!(providers.len(), 3);
+        assert!([Provider::Coco, Provider::Ioctl, Provider::Mock]
+            .iter()
+            .all(|provider| providers.contains(provider)));
+
+        let mock = capabilities.providers.iter().find(|p| p.provider == Provider::Mock).unwrap();
+        assert_eq!(mock.compiled, cfg!(feature = "mock"));
+        let ioctl = capabilities.providers.iter().find(|p| p.provider == Provider::Ioctl).unwrap();
+        assert_eq!(ioctl.compiled, cfg!(feature = "ioctl"));
+        assert!(ioctl.compiled || !ioctl.available);
+
+        assert_eq!(capabilities.features.contains(&"mock".to_string()), cfg!(feature = "mock"));
+        assert_eq!(capabilities.features.contains(&"ioctl".to_string()), cfg!(feature = "ioctl"));
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_mock_build_reports_the_mock_capability() {
+        let path = std::env::temp_dir().join(format!("attest-mock-quote-{}", std::process::id()));
+        std::fs::write(&path, b"captured quote").unwrap();
+        std::env::set_var(MOCK_QUOTE_ENV, &path);
+
+        let capabilities = provider_capabilities();
+        assert!(capabilities.features.contains(&"mock".to_string()));
+        let mock = capabilities.providers.iter().find(|p| p.provider == Provider::Mock).unwrap();
+        assert!(mock.compiled && mock.available);
 
-    Ok(quote)
+        let raw_quote = get_raw_quote_with_provider(RawReport::new([9; 64]), Provider::Mock);
+        assert_eq!(raw_quote.unwrap(), b"captured quote");
+        std::fs::remove_file(path).unwrap();
+    }
 }
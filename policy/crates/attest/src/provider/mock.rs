@@ -0,0 +1,11 @@
+use crate::{errors::AttestationError, types::RawReport, MOCK_QUOTE_ENV};
+
+/// The raw quote captured in the file at `ATTEST_MOCK_QUOTE`
+///
+/// The report data is ignored, so the quote doesn't bind it; never use this in production.
+pub fn get_raw_quote(_report: RawReport) -> Result<Vec<u8>, AttestationError> {
+    let path = std::env::var(MOCK_QUOTE_ENV)
+        .map_err(|_| AttestationError::Mock(format!("{MOCK_QUOTE_ENV} isn't set")))?;
+
+    std::fs::read(&path).map_err(|e| AttestationError::Mock(format!("reading {path}: {e}")))
+}
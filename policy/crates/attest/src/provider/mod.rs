@@ -1,3 +1,5 @@
 pub mod coco;
 #[cfg(feature = "ioctl")]
 pub mod ioctl;
+#[cfg(feature = "mock")]
+pub mod mock;
@@ -0,0 +1,126 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::types::RawReport;
+
+struct CacheEntry {
+    created_at: Instant,
+    /// Filled by the first caller; the others wait on the lock instead of
+    /// invoking the provider themselves
+    quote: Mutex<Option<Vec<u8>>>,
+}
+
+/// Raw quotes per report data, reused for `ttl`
+///
+/// A quote over identical report data is equally valid, so concurrent requests
+/// binding the same digest share one provider call.
+pub struct QuoteCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<[u8; 64], Arc<CacheEntry>>>,
+}
+
+impl QuoteCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Quote cached for `report`, or the one `generate` returns for it
+    ///
+    /// Errors are not cached; the next caller tries the provider again.
+    pub fn get_or_generate<E>(
+        &self,
+        report: RawReport,
+        generate: impl FnOnce(RawReport) -> Result<Vec<u8>, E>,
+    ) -> Result<Vec<u8>, E> {
+        let entry = {
+            let mut entries = self.entries.lock().expect("quote cache poisoned");
+            entries.retain(|_, entry| entry.created_at.elapsed() < self.ttl);
+            entries
+                .entry(report.to_bytes())
+                .or_insert_with(|| {
+                    Arc::new(CacheEntry {
+                        created_at: Instant::now(),
+                        quote: Mutex::new(None),
+                    })
+                })
+                .clone()
+        };
+
+        let mut quote = entry.quote.lock().expect("quote cache poisoned");
+        if let Some(quote) = quote.as_ref() {
+            return Ok(quote.clone());
+        }
+
+        let generated = generate(report)?;
+        *quote = Some(generated.clone());
+
+        Ok(generated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn report(byte: u8) -> RawReport {
+        RawReport::new([byte; 64])
+    }
+
+    #[test]
+    fn test_identical_report_data_is_quoted_once() {
+        let cache = Arc::new(QuoteCache::new(Duration::from_secs(60)));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider = |calls: Arc<AtomicUsize>| {
+            move |report: RawReport| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(20));
+                Ok::<_, String>(report.to_bytes().to_vec())
+            }
+        };
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let (cache, calls) = (cache.clone(), calls.clone());
+                std::thread::spawn(move || cache.get_or_generate(report(1), provider(calls)))
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().unwrap(), [1; 64]);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Other report data gets its own quote
+        let quote = cache.get_or_generate(report(2), provider(calls.clone()));
+        assert_eq!(quote.unwrap(), [2; 64]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_expired_and_failed_quotes_are_regenerated() {
+        let cache = QuoteCache::new(Duration::from_millis(50));
+        let calls = AtomicUsize::new(0);
+        let provider = |report: RawReport| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, String>(report.to_bytes().to_vec())
+        };
+
+        let failed = cache.get_or_generate(report(1), |_| Err("provider down".to_string()));
+        assert_eq!(failed.unwrap_err(), "provider down");
+
+        cache.get_or_generate(report(1), provider).unwrap();
+        cache.get_or_generate(report(1), provider).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        std::thread::sleep(Duration::from_millis(60));
+        cache.get_or_generate(report(1), provider).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
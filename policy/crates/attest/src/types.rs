@@ -7,15 +7,63 @@ use dcap_rs::{
     },
 };
 use k256::ecdsa::VerifyingKey;
+use serde::{Deserialize, Serialize};
 
-use crate::errors::QuoteError;
+use crate::{errors::QuoteError, verify::Measurements};
 
 #[derive(Clone, Debug)]
 pub struct Quote {
     raw: Vec<u8>,
+    version: QuoteVersion,
+    tee_type: TeeType,
     report: QuoteReport,
 }
 
+/// Quote format version, from the quote header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuoteVersion {
+    V3,
+    V4,
+    V5,
+}
+
+impl TryFrom<u16> for QuoteVersion {
+    type Error = QuoteError;
+
+    fn try_from(version: u16) -> Result<Self, Self::Error> {
+        match version {
+            3 => Ok(QuoteVersion::V3),
+            4 => Ok(QuoteVersion::V4),
+            5 => Ok(QuoteVersion::V5),
+            _ => Err(QuoteError::UnknownQuote(version)),
+        }
+    }
+}
+
+/// TEE that produced the quote, from the quote header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TeeType {
+    Sgx,
+    Tdx,
+}
+
+impl TeeType {
+    const SGX: u32 = 0x00;
+    const TDX: u32 = 0x81;
+}
+
+impl TryFrom<u32> for TeeType {
+    type Error = QuoteError;
+
+    fn try_from(tee_type: u32) -> Result<Self, Self::Error> {
+        match tee_type {
+            TeeType::SGX => Ok(TeeType::Sgx),
+            TeeType::TDX => Ok(TeeType::Tdx),
+            _ => Err(QuoteError::UnknownTeeType(tee_type)),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum QuoteReport {
     V3(QuoteV3),
@@ -30,32 +78,41 @@ impl Quote {
         }
 
         let header = QuoteHeader::from_bytes(&bytes[0..HEADER_LEN]);
-        let report = match header.version {
-            3 => QuoteReport::V3(QuoteV3::from_bytes(bytes)),
-            4 => QuoteReport::V4(QuoteV4::from_bytes(bytes)),
-            5 => QuoteReport::V5(QuoteV5::from_bytes(bytes)),
-            _ => return Err(QuoteError::UnknownQuote(header.version)),
+        let version = QuoteVersion::try_from(header.version)?;
+        let tee_type = TeeType::try_from(header.tee_type)?;
+        let report = match version {
+            QuoteVersion::V3 => QuoteReport::V3(QuoteV3::from_bytes(bytes)),
+            QuoteVersion::V4 => QuoteReport::V4(QuoteV4::from_bytes(bytes)),
+            QuoteVersion::V5 => QuoteReport::V5(QuoteV5::from_bytes(bytes)),
         };
 
         Ok(Quote {
             raw: bytes.to_vec(),
+            version,
+            tee_type,
             report,
         })
     }
 
+    pub fn version(&self) -> QuoteVersion {
+        self.version
+    }
+
+    pub fn tee_type(&self) -> TeeType {
+        self.tee_type
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         self.raw.clone()
     }
 
     pub fn k256_pk_report(&self) -> Result<K256PkReport, QuoteError> {
-        let report_data = self.report_data();
-
-        let point = k256::EncodedPoint::from_bytes(&report_data[0..33])
-            .map_err(|e| QuoteError::ReportData(format!("invalid secp pk {e}")))?;
-        let pk = k256::ecdsa::VerifyingKey::from_encoded_point(&point)
-            .map_err(|e| QuoteError::ReportData(format!("invalid secp pk {e}")))?;
+        K256PkReport::from_report_data(&self.report_data())
+    }
 
-        Ok(K256PkReport { pk })
+    /// Public key bound into report_data, of whichever type its layout byte names
+    pub fn pk_report(&self) -> Result<PkReport, QuoteError> {
+        PkReport::from_report_data(&self.report_data())
     }
 
     pub fn quote_report(&self) -> &QuoteReport {
@@ -79,6 +136,44 @@ impl Quote {
             QuoteBody::TD15QuoteBody(report) => report.report_data,
         }
     }
+
+    /// Whether the lower half of report_data equals `expected`
+    pub fn check_report_data(&self, expected: &[u8; 32]) -> bool {
+        RawReport(self.report_data()).check_digest(expected)
+    }
+
+    /// Upper half of report_data, where the nonce is placed
+    pub fn report_nonce(&self) -> [u8; 32] {
+        RawReport(self.report_data()).nonce()
+    }
+}
+
+impl Quote {
+    /// TEE_TCB_SVN of TDX quotes, `None` for SGX quotes
+    pub fn tee_tcb_svn(&self) -> Option<[u8; 16]> {
+        self.report.tee_tcb_svn()
+    }
+
+    /// MRTD and RTMR3 of TDX quotes, `None` for SGX quotes
+    pub fn measurements(&self) -> Option<Measurements> {
+        let body = match &self.report {
+            QuoteReport::V3(_) => return None,
+            QuoteReport::V4(quote) => quote.quote_body,
+            QuoteReport::V5(quote) => quote.quote_body,
+        };
+
+        match body {
+            QuoteBody::SGXQuoteBody(_) => None,
+            QuoteBody::TD10QuoteBody(report) => Some(Measurements {
+                mrtd: report.mrtd,
+                rtmr3: report.rtmr3,
+            }),
+            QuoteBody::TD15QuoteBody(report) => Some(Measurements {
+                mrtd: report.mrtd,
+                rtmr3: report.rtmr3,
+            }),
+        }
+    }
 }
 
 impl QuoteReport {
@@ -99,9 +194,174 @@ impl QuoteReport {
             QuoteBody::TD15QuoteBody(report) => report.rtmr3,
         }
     }
+
+    /// Report body, the enclave report for V3 quotes
+    fn body(&self) -> QuoteBody {
+        match self {
+            QuoteReport::V3(quote) => QuoteBody::SGXQuoteBody(quote.isv_enclave_report),
+            QuoteReport::V4(quote) => quote.quote_body,
+            QuoteReport::V5(quote) => quote.quote_body,
+        }
+    }
+
+    /// Attributes and SVNs of TDX reports, `None` for SGX reports
+    pub fn td_report(&self) -> Option<TdReport> {
+        TdReport::from_body(&self.body())
+    }
+
+    /// Attributes and SVNs of SGX reports, `None` for TDX reports
+    pub fn sgx_report(&self) -> Option<SgxReport> {
+        SgxReport::from_body(&self.body())
+    }
+
+    pub fn td_attributes(&self) -> Option<TdAttributes> {
+        self.td_report().map(|report| report.td_attributes)
+    }
+
+    /// Extended features available to the TD, `None` for SGX reports
+    pub fn xfam(&self) -> Option<u64> {
+        self.td_report().map(|report| report.xfam)
+    }
+
+    pub fn tee_tcb_svn(&self) -> Option<[u8; 16]> {
+        self.td_report().map(|report| report.tee_tcb_svn)
+    }
+
+    /// TEE_TCB_SVN_2 of TD 1.5 reports
+    pub fn tee_tcb_svn2(&self) -> Option<[u8; 16]> {
+        self.td_report().and_then(|report| report.tee_tcb_svn2)
+    }
+
+    pub fn sgx_attributes(&self) -> Option<SgxAttributes> {
+        self.sgx_report().map(|report| report.attributes)
+    }
+
+    pub fn isv_svn(&self) -> Option<u16> {
+        self.sgx_report().map(|report| report.isv_svn)
+    }
 }
 
-#[derive(Debug)]
+/// TD_ATTRIBUTES of a TDX report
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TdAttributes(pub u64);
+
+impl TdAttributes {
+    const DEBUG: u64 = 1;
+    const SEPT_VE_DISABLE: u64 = 1 << 28;
+    const PKS: u64 = 1 << 30;
+    const PERFMON: u64 = 1 << 63;
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Whether the TD is debuggable, so its memory and state are visible to the host
+    pub fn debug(&self) -> bool {
+        self.0 & Self::DEBUG != 0
+    }
+
+    pub fn sept_ve_disable(&self) -> bool {
+        self.0 & Self::SEPT_VE_DISABLE != 0
+    }
+
+    pub fn pks(&self) -> bool {
+        self.0 & Self::PKS != 0
+    }
+
+    pub fn perfmon(&self) -> bool {
+        self.0 & Self::PERFMON != 0
+    }
+}
+
+/// ATTRIBUTES of an SGX enclave report: the flags and the XSAVE feature mask
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SgxAttributes {
+    pub flags: u64,
+    pub xfrm: u64,
+}
+
+impl SgxAttributes {
+    const DEBUG: u64 = 1 << 1;
+    const MODE64BIT: u64 = 1 << 2;
+
+    fn from_bytes(bytes: [u8; 16]) -> Self {
+        let (flags, xfrm) = bytes.split_at(8);
+        Self {
+            flags: u64::from_le_bytes(flags.try_into().unwrap()),
+            xfrm: u64::from_le_bytes(xfrm.try_into().unwrap()),
+        }
+    }
+
+    /// Whether the enclave is debuggable, so its memory is visible to the host
+    pub fn debug(&self) -> bool {
+        self.flags & Self::DEBUG != 0
+    }
+
+    pub fn mode64bit(&self) -> bool {
+        self.flags & Self::MODE64BIT != 0
+    }
+}
+
+/// Attributes and SVNs of a TD 1.0 or 1.5 report body
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TdReport {
+    pub tee_tcb_svn: [u8; 16],
+    /// Only in TD 1.5 reports
+    pub tee_tcb_svn2: Option<[u8; 16]>,
+    pub seam_attributes: u64,
+    pub td_attributes: TdAttributes,
+    pub xfam: u64,
+}
+
+impl TdReport {
+    fn from_body(body: &QuoteBody) -> Option<Self> {
+        match body {
+            QuoteBody::SGXQuoteBody(_) => None,
+            QuoteBody::TD10QuoteBody(report) => Some(Self {
+                tee_tcb_svn: report.tee_tcb_svn,
+                tee_tcb_svn2: None,
+                seam_attributes: report.seam_attributes,
+                td_attributes: TdAttributes(report.td_attributes),
+                xfam: report.xfam,
+            }),
+            QuoteBody::TD15QuoteBody(report) => Some(Self {
+                tee_tcb_svn: report.tee_tcb_svn,
+                tee_tcb_svn2: Some(report.tee_tcb_svn2),
+                seam_attributes: report.seam_attributes,
+                td_attributes: TdAttributes(report.td_attributes),
+                xfam: report.xfam,
+            }),
+        }
+    }
+}
+
+/// Attributes and SVNs of an SGX enclave report
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SgxReport {
+    pub cpu_svn: [u8; 16],
+    pub misc_select: u32,
+    pub attributes: SgxAttributes,
+    pub isv_prod_id: u16,
+    pub isv_svn: u16,
+}
+
+impl SgxReport {
+    fn from_body(body: &QuoteBody) -> Option<Self> {
+        let QuoteBody::SGXQuoteBody(report) = body else {
+            return None;
+        };
+
+        Some(Self {
+            cpu_svn: report.cpu_svn,
+            misc_select: u32::from_le_bytes(report.misc_select),
+            attributes: SgxAttributes::from_bytes(report.attributes),
+            isv_prod_id: report.isv_prod_id,
+            isv_svn: report.isv_svn,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct RawReport([u8; 64]);
 
 impl RawReport {
@@ -112,6 +372,20 @@ impl RawReport {
     pub fn to_bytes(&self) -> [u8; 64] {
         self.0
     }
+
+    /// Lower half of the report, the digest bound into the quote
+    pub fn digest(&self) -> [u8; 32] {
+        self.0[..32].try_into().expect("32 bytes")
+    }
+
+    /// Upper half of the report, zero-padded nonce
+    pub fn nonce(&self) -> [u8; 32] {
+        self.0[32..].try_into().expect("32 bytes")
+    }
+
+    pub fn check_digest(&self, expected: &[u8; 32]) -> bool {
+        self.digest() == *expected
+    }
 }
 
 #[derive(Debug)]
@@ -124,6 +398,17 @@ impl K256PkReport {
         K256PkReport { pk }
     }
 
+    /// Parse the SEC1 compressed key at `report_data[0..33]`, its tag byte (0x02/0x03)
+    /// doubling as the layout byte
+    pub fn from_report_data(report_data: &[u8; 64]) -> Result<Self, QuoteError> {
+        let point = k256::EncodedPoint::from_bytes(&report_data[0..33])
+            .map_err(|e| QuoteError::ReportData(format!("invalid secp pk {e}")))?;
+        let pk = k256::ecdsa::VerifyingKey::from_encoded_point(&point)
+            .map_err(|e| QuoteError::ReportData(format!("invalid secp pk {e}")))?;
+
+        Ok(K256PkReport { pk })
+    }
+
     pub fn pubkey(&self) -> &VerifyingKey {
         &self.pk
     }
@@ -145,3 +430,223 @@ impl Display for K256PkReport {
         write!(f, "report: pk {point}")
     }
 }
+
+#[derive(Debug)]
+pub struct Ed25519PkReport {
+    pk: ed25519_dalek::VerifyingKey,
+}
+
+impl Ed25519PkReport {
+    /// Layout byte at `report_data[0]`, followed by the 32-byte key; it can't be mistaken
+    /// for the tag of a SEC1 compressed secp256k1 key
+    pub const LAYOUT: u8 = 0xed;
+
+    pub fn new(pk: ed25519_dalek::VerifyingKey) -> Self {
+        Ed25519PkReport { pk }
+    }
+
+    pub fn pubkey(&self) -> &ed25519_dalek::VerifyingKey {
+        &self.pk
+    }
+
+    pub fn from_report_data(report_data: &[u8; 64]) -> Result<Self, QuoteError> {
+        if report_data[0] != Self::LAYOUT {
+            return Err(QuoteError::ReportData(format!(
+                "layout byte {:#04x} isn't an ed25519 pk",
+                report_data[0]
+            )));
+        }
+
+        let bytes = report_data[1..33].try_into().expect("32 bytes");
+        let pk = ed25519_dalek::VerifyingKey::from_bytes(bytes)
+            .map_err(|e| QuoteError::ReportData(format!("invalid ed25519 pk {e}")))?;
+
+        Ok(Ed25519PkReport { pk })
+    }
+
+    pub fn to_raw(&self) -> RawReport {
+        let mut buf = [0u8; 64];
+        buf[0] = Self::LAYOUT;
+        buf[1..33].copy_from_slice(self.pk.as_bytes());
+
+        RawReport(buf)
+    }
+}
+
+impl Display for Ed25519PkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pk = const_hex::encode(self.pk.as_bytes());
+
+        write!(f, "report: ed25519 pk {pk}")
+    }
+}
+
+/// Public key bound into a quote, by key type
+#[derive(Debug)]
+pub enum PkReport {
+    K256(K256PkReport),
+    Ed25519(Ed25519PkReport),
+}
+
+impl PkReport {
+    /// Detect the key type from the layout byte at `report_data[0]` and parse the key
+    pub fn from_report_data(report_data: &[u8; 64]) -> Result<Self, QuoteError> {
+        match report_data[0] {
+            0x02 | 0x03 => K256PkReport::from_report_data(report_data).map(PkReport::K256),
+            Ed25519PkReport::LAYOUT => {
+                Ed25519PkReport::from_report_data(report_data).map(PkReport::Ed25519)
+            }
+            layout => Err(QuoteError::ReportData(format!(
+                "unknown pk layout byte {layout:#04x}"
+            ))),
+        }
+    }
+
+    pub fn to_raw(&self) -> RawReport {
+        match self {
+            PkReport::K256(report) => report.to_raw(),
+            PkReport::Ed25519(report) => report.to_raw(),
+        }
+    }
+}
+
+impl Display for PkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PkReport::K256(report) => report.fmt(f),
+            PkReport::Ed25519(report) => report.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> RawReport {
+        let mut raw = [0u8; 64];
+        raw[..32].copy_from_slice(&[7u8; 32]);
+        raw[32..36].copy_from_slice(b"abcd");
+        RawReport::new(raw)
+    }
+
+    #[test]
+    fn test_check_digest() {
+        assert!(report().check_digest(&[7u8; 32]));
+
+        let mut expected = [7u8; 32];
+        expected[31] = 8;
+        assert!(!report().check_digest(&expected));
+        assert!(!report().check_digest(&[0u8; 32]));
+    }
+
+    #[test]
+    fn test_quote_kind_from_header() {
+        // Leading version and TEE type fields of a V4 TDX quote header
+        let header = const_hex::decode("0400020081000000").unwrap();
+        let version = u16::from_le_bytes([header[0], header[1]]);
+        let tee_type = u32::from_le_bytes(header[4..8].try_into().unwrap());
+
+        assert_eq!(QuoteVersion::try_from(version).unwrap(), QuoteVersion::V4);
+        assert_eq!(TeeType::try_from(tee_type).unwrap(), TeeType::Tdx);
+        assert_eq!(TeeType::try_from(0).unwrap(), TeeType::Sgx);
+
+        assert!(matches!(QuoteVersion::try_from(2), Err(QuoteError::UnknownQuote(2))));
+        assert!(matches!(TeeType::try_from(1), Err(QuoteError::UnknownTeeType(1))));
+    }
+
+    #[test]
+    fn test_pk_report_round_trip() {
+        let ed25519_pk = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]).verifying_key();
+        let raw = Ed25519PkReport::new(ed25519_pk).to_raw().to_bytes();
+        assert_eq!(raw[0], Ed25519PkReport::LAYOUT);
+        match PkReport::from_report_data(&raw).unwrap() {
+            PkReport::Ed25519(report) => assert_eq!(*report.pubkey(), ed25519_pk),
+            other => panic!("{other}"),
+        }
+
+        let k256_pk = *k256::ecdsa::SigningKey::from_slice(&[7u8; 32]).unwrap().verifying_key();
+        let raw = K256PkReport::new(k256_pk).to_raw().to_bytes();
+        match PkReport::from_report_data(&raw).unwrap() {
+            PkReport::K256(report) => assert_eq!(*report.pubkey(), k256_pk),
+            other => panic!("{other}"),
+        }
+        assert!(Ed25519PkReport::from_report_data(&raw).is_err());
+    }
+
+    #[test]
+    fn test_pk_report_rejects_unknown_layout() {
+        let mut raw = [0u8; 64];
+        let err = PkReport::from_report_data(&raw).unwrap_err();
+        assert_eq!(err.to_string(), "report data unknown pk layout byte 0x00");
+
+        raw[0] = 0x04;
+        assert!(PkReport::from_report_data(&raw).is_err());
+    }
+
+    #[test]
+    fn test_nonce() {
+        let nonce = report().nonce();
+        assert_eq!(&nonce[..4], b"abcd");
+        assert!(nonce[4..].iter().all(|b| *b == 0));
+        assert_eq!(RawReport::new([0u8; 64]).nonce(), [0u8; 32]);
+    }
+
+    /// TD 1.5 report body of a V4 TDX quote: a production TD with SEPT_VE_DISABLE set,
+    /// the common x87/SSE/AVX/AVX-512/PK/AMX xfam, and the SVNs of the TCB info fixture
+    fn td15_body() -> Vec<u8> {
+        let mut body = vec![0u8; 648];
+        body[..3].copy_from_slice(&[5, 0, 3]);
+        body[120..128].copy_from_slice(&0x1000_0000u64.to_le_bytes());
+        body[128..136].copy_from_slice(&0x0006_02e7u64.to_le_bytes());
+        body[584..587].copy_from_slice(&[6, 0, 4]);
+        body
+    }
+
+    #[test]
+    fn test_td_report_fields() {
+        use dcap_rs::types::quotes::body::{TD10ReportBody, TD15ReportBody};
+
+        let bytes = td15_body();
+        let body = QuoteBody::TD15QuoteBody(TD15ReportBody::from_bytes(&bytes));
+        let td15 = TdReport::from_body(&body).unwrap();
+        assert_eq!(td15.tee_tcb_svn[..3], [5, 0, 3]);
+        assert_eq!(td15.tee_tcb_svn2.unwrap()[..3], [6, 0, 4]);
+        assert_eq!(td15.xfam, 0x0006_02e7);
+        assert_eq!(td15.seam_attributes, 0);
+        assert_eq!(td15.td_attributes.bits(), 0x1000_0000);
+        assert!(td15.td_attributes.sept_ve_disable());
+        assert!(!td15.td_attributes.debug());
+        assert!(!td15.td_attributes.pks());
+        assert!(!td15.td_attributes.perfmon());
+
+        // TD 1.0 bodies share the layout up to report_data, without a second SVN
+        let body = QuoteBody::TD10QuoteBody(TD10ReportBody::from_bytes(&bytes[..584]));
+        let td10 = TdReport::from_body(&body).unwrap();
+        assert_eq!(td10.tee_tcb_svn2, None);
+        assert_eq!(TdReport { tee_tcb_svn2: None, ..td15 }, td10);
+        assert_eq!(SgxReport::from_body(&body), None);
+    }
+
+    #[test]
+    fn test_sgx_report_fields() {
+        use dcap_rs::types::quotes::body::EnclaveReport;
+
+        let mut bytes = vec![0u8; 384];
+        bytes[..2].copy_from_slice(&[0x0c, 0x0c]);
+        // INIT | DEBUG | MODE64BIT, xfrm of x87 and SSE
+        bytes[48..56].copy_from_slice(&0x07u64.to_le_bytes());
+        bytes[56..64].copy_from_slice(&0x03u64.to_le_bytes());
+        bytes[256..258].copy_from_slice(&1u16.to_le_bytes());
+        bytes[258..260].copy_from_slice(&9u16.to_le_bytes());
+
+        let body = QuoteBody::SGXQuoteBody(EnclaveReport::from_bytes(&bytes));
+        let sgx = SgxReport::from_body(&body).unwrap();
+        assert_eq!(sgx.cpu_svn[..2], [0x0c, 0x0c]);
+        assert_eq!(sgx.attributes, SgxAttributes { flags: 0x07, xfrm: 0x03 });
+        assert!(sgx.attributes.debug());
+        assert!(sgx.attributes.mode64bit());
+        assert_eq!((sgx.isv_prod_id, sgx.isv_svn), (1, 9));
+        assert_eq!(TdReport::from_body(&body), None);
+    }
+}
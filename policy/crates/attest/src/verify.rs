@@ -0,0 +1,404 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::QuoteError,
+    types::{Quote, QuoteVersion, TeeType},
+};
+
+/// TCB status of a platform, as reported by Intel TCB info
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TcbStatus {
+    UpToDate,
+    SWHardeningNeeded,
+    ConfigurationNeeded,
+    ConfigurationAndSWHardeningNeeded,
+    OutOfDate,
+    OutOfDateConfigurationNeeded,
+    Revoked,
+    /// Taken from collateral whose signatures weren't checked, so not a verdict
+    #[serde(rename = "unverified")]
+    Unverified,
+    /// Status string not known to this version
+    #[serde(other)]
+    Unrecognized,
+}
+
+/// TCB info as served by Intel PCS / PCCS (`{"tcbInfo": ..., "signature": ...}`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedTcbInfo {
+    pub tcb_info: TcbInfo,
+    /// Signature over `tcb_info` by the TCB signing key (hex-encoded)
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbInfo {
+    /// `SGX` or `TDX` (TCB info v3)
+    #[serde(default)]
+    pub id: Option<String>,
+    pub version: u32,
+    pub fmspc: String,
+    /// TCB levels, ordered from the newest to the oldest
+    pub tcb_levels: Vec<TcbLevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbLevel {
+    pub tcb: Tcb,
+    pub tcb_date: String,
+    pub tcb_status: TcbStatus,
+    #[serde(default, rename = "advisoryIDs")]
+    pub advisory_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tcb {
+    pub sgxtcbcomponents: Vec<TcbComponent>,
+    pub pcesvn: u16,
+    #[serde(default)]
+    pub tdxtcbcomponents: Vec<TcbComponent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcbComponent {
+    pub svn: u8,
+}
+
+/// SGX TCB of the platform, as carried in the PCK certificate's SGX extension
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PckTcb {
+    pub sgx_tcb_components: [u8; 16],
+    pub pce_svn: u16,
+}
+
+/// QE identity as served by Intel PCS / PCCS (`{"enclaveIdentity": ..., "signature": ...}`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedQeIdentity {
+    pub enclave_identity: QeIdentity,
+    /// Signature over `enclave_identity` by the TCB signing key (hex-encoded)
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QeIdentity {
+    /// `QE`, `QVE` or `TD_QE`
+    pub id: String,
+    pub version: u32,
+    pub mrsigner: String,
+    pub isvprodid: u16,
+    /// QE TCB levels, ordered from the newest to the oldest
+    pub tcb_levels: Vec<QeTcbLevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QeTcbLevel {
+    pub tcb: QeTcb,
+    pub tcb_date: String,
+    pub tcb_status: TcbStatus,
+    #[serde(default, rename = "advisoryIDs")]
+    pub advisory_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QeTcb {
+    pub isvsvn: u16,
+}
+
+/// Platform-independent collateral for an FMSPC, as fetched from a PCCS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcsCollateral {
+    pub tcb_info: SignedTcbInfo,
+    pub qe_identity: SignedQeIdentity,
+    /// PCK CRL of the issuing CA (hex-encoded DER)
+    pub pck_crl: String,
+    /// Intel root CA CRL (hex-encoded DER)
+    pub root_ca_crl: String,
+}
+
+impl PcsCollateral {
+    /// Combine with the platform's SGX TCB into the collateral `Quote::verify` consumes
+    pub fn for_platform(self, pck_tcb: PckTcb) -> Collateral {
+        Collateral {
+            tcb_info: self.tcb_info,
+            qe_identity: Some(self.qe_identity),
+            pck_crl: Some(self.pck_crl),
+            root_ca_crl: Some(self.root_ca_crl),
+            pck_tcb,
+        }
+    }
+}
+
+/// Collateral a quote is verified against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collateral {
+    pub tcb_info: SignedTcbInfo,
+    #[serde(default)]
+    pub qe_identity: Option<SignedQeIdentity>,
+    /// PCK CRL of the issuing CA (hex-encoded DER)
+    #[serde(default)]
+    pub pck_crl: Option<String>,
+    /// Intel root CA CRL (hex-encoded DER)
+    #[serde(default)]
+    pub root_ca_crl: Option<String>,
+    /// Platform SGX TCB; supplied alongside the TCB info until PCK certificate parsing lands
+    pub pck_tcb: PckTcb,
+}
+
+/// Outcome of verifying a quote
+#[derive(Debug, Clone)]
+pub struct VerificationResult {
+    pub version: QuoteVersion,
+    pub tee_type: TeeType,
+    pub report_data: [u8; 64],
+    /// `Unverified` until the collateral signatures and the PCK chain are checked
+    pub tcb_status: TcbStatus,
+    /// Security advisories affecting the matched TCB level, per the collateral
+    pub advisory_ids: Vec<String>,
+    pub tcb_date: String,
+}
+
+impl TcbInfo {
+    /// Find the newest TCB level the platform meets
+    ///
+    /// All SGX components and the PCE SVN must be at least the level's values, and for
+    /// TDX platforms (`tdx_tcb_svn` is the quote's TEE_TCB_SVN) the TDX components too.
+    pub fn matching_level(
+        &self,
+        pck: &PckTcb,
+        tdx_tcb_svn: Option<&[u8; 16]>,
+    ) -> Option<&TcbLevel> {
+        self.tcb_levels.iter().find(|level| {
+            let sgx_ok = components_at_least(&pck.sgx_tcb_components, &level.tcb.sgxtcbcomponents)
+                && pck.pce_svn >= level.tcb.pcesvn;
+            let tdx_ok = match tdx_tcb_svn {
+                Some(svn) => components_at_least(svn, &level.tcb.tdxtcbcomponents),
+                None => true,
+            };
+
+            sgx_ok && tdx_ok
+        })
+    }
+}
+
+fn components_at_least(platform: &[u8; 16], level: &[TcbComponent]) -> bool {
+    level
+        .iter()
+        .zip(platform.iter())
+        .all(|(component, svn)| *svn >= component.svn)
+}
+
+/// TD measurements pinned by `ExpectedMeasurement`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurements {
+    pub mrtd: [u8; 48],
+    pub rtmr3: [u8; 48],
+}
+
+/// One accepted set of TD measurements (hex-encoded); unset fields match any value
+///
+/// Several entries can be accepted at once, e.g. the old and new image during a rolling upgrade.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpectedMeasurement {
+    #[serde(default)]
+    pub mrtd: Option<String>,
+    #[serde(default)]
+    pub rtmr3: Option<String>,
+}
+
+impl ExpectedMeasurement {
+    /// Check that every set field is a 48-byte hex value
+    pub fn validate(&self) -> Result<(), QuoteError> {
+        for (name, value) in [("mrtd", &self.mrtd), ("rtmr3", &self.rtmr3)] {
+            let Some(value) = value else { continue };
+            match const_hex::decode(value) {
+                Ok(bytes) if bytes.len() == 48 => {}
+                _ => {
+                    return Err(QuoteError::Measurements(format!(
+                        "expected {name} {value} isn't 48 hex-encoded bytes"
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, measurements: &Measurements) -> bool {
+        let field_matches = |expected: &Option<String>, actual: &[u8; 48]| {
+            expected
+                .as_deref()
+                .is_none_or(|e| const_hex::decode(e).is_ok_and(|e| e == actual))
+        };
+
+        field_matches(&self.mrtd, &measurements.mrtd)
+            && field_matches(&self.rtmr3, &measurements.rtmr3)
+    }
+}
+
+impl Measurements {
+    /// Accept the measurements if `expected` is empty (nothing pinned) or any entry matches
+    pub fn check(&self, expected: &[ExpectedMeasurement]) -> Result<(), QuoteError> {
+        if expected.is_empty() || expected.iter().any(|e| e.matches(self)) {
+            return Ok(());
+        }
+
+        Err(QuoteError::MeasurementMismatch {
+            mrtd: const_hex::encode(self.mrtd),
+            rtmr3: const_hex::encode(self.rtmr3),
+        })
+    }
+}
+
+impl Quote {
+    /// Check the quote's TD measurements against the accepted list, see `Measurements::check`
+    pub fn check_measurements(&self, expected: &[ExpectedMeasurement]) -> Result<(), QuoteError> {
+        if expected.is_empty() {
+            return Ok(());
+        }
+
+        self.measurements()
+            .ok_or_else(|| QuoteError::Measurements("SGX quote has no TD measurements".into()))?
+            .check(expected)
+    }
+
+    /// Evaluate the quote's TCB against the collateral
+    ///
+    /// The signature chains over the quote and the collateral are not checked yet, so the
+    /// status is always `TcbStatus::Unverified`: anyone can supply collateral claiming any
+    /// level. The platform must still match a level of the collateral.
+    pub fn verify(&self, collateral: &Collateral) -> Result<VerificationResult, QuoteError> {
+        let tcb_info = &collateral.tcb_info.tcb_info;
+        let tdx_tcb_svn = self.tee_tcb_svn();
+
+        let expected_id = match self.tee_type() {
+            TeeType::Tdx => "TDX",
+            TeeType::Sgx => "SGX",
+        };
+        if let Some(id) = &tcb_info.id {
+            if id != expected_id {
+                return Err(QuoteError::Collateral(format!(
+                    "{expected_id} quote checked against {id} TCB info"
+                )));
+            }
+        }
+
+        let level = tcb_info
+            .matching_level(&collateral.pck_tcb, tdx_tcb_svn.as_ref())
+            .ok_or_else(|| QuoteError::Collateral("no TCB level matches the platform".into()))?;
+
+        Ok(VerificationResult {
+            version: self.version(),
+            tee_type: self.tee_type(),
+            report_data: self.report_data(),
+            tcb_status: TcbStatus::Unverified,
+            advisory_ids: level.advisory_ids.clone(),
+            tcb_date: level.tcb_date.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TDX_TCB_INFO: &str = include_str!("../fixtures/tdx_tcb_info.json");
+
+    fn tcb_info() -> TcbInfo {
+        serde_json::from_str::<SignedTcbInfo>(TDX_TCB_INFO)
+            .unwrap()
+            .tcb_info
+    }
+
+    fn pck() -> PckTcb {
+        PckTcb {
+            sgx_tcb_components: [2, 2, 2, 2, 3, 1, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0],
+            pce_svn: 11,
+        }
+    }
+
+    #[test]
+    fn test_up_to_date_tcb() {
+        let tdx_svn = [5, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let tcb_info = tcb_info();
+        let level = tcb_info.matching_level(&pck(), Some(&tdx_svn)).unwrap();
+
+        assert_eq!(level.tcb_status, TcbStatus::UpToDate);
+        assert!(level.advisory_ids.is_empty());
+    }
+
+    #[test]
+    fn test_out_of_date_tcb() {
+        let tdx_svn = [4, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let tcb_info = tcb_info();
+        let level = tcb_info.matching_level(&pck(), Some(&tdx_svn)).unwrap();
+
+        assert_eq!(level.tcb_status, TcbStatus::OutOfDate);
+        assert_eq!(level.advisory_ids, ["INTEL-SA-00960", "INTEL-SA-00982"]);
+        assert_eq!(level.tcb_date, "2023-08-09T00:00:00Z");
+
+        // A platform below every level isn't recognized at all
+        let mut old_pck = pck();
+        old_pck.pce_svn = 1;
+        assert!(tcb_info.matching_level(&old_pck, Some(&tdx_svn)).is_none());
+    }
+
+    fn measurements() -> Measurements {
+        Measurements {
+            mrtd: [0x11; 48],
+            rtmr3: [0x33; 48],
+        }
+    }
+
+    #[test]
+    fn test_matching_measurement() {
+        let current = ExpectedMeasurement {
+            mrtd: Some(const_hex::encode([0x11; 48])),
+            rtmr3: Some(const_hex::encode_upper([0x33; 48])),
+        };
+        current.validate().unwrap();
+        measurements().check(std::slice::from_ref(&current)).unwrap();
+
+        // Any entry may match, e.g. during a rolling upgrade
+        let previous = ExpectedMeasurement {
+            mrtd: Some(const_hex::encode([0x10; 48])),
+            rtmr3: None,
+        };
+        measurements().check(&[previous, current]).unwrap();
+
+        // Nothing pinned
+        measurements().check(&[]).unwrap();
+    }
+
+    #[test]
+    fn test_mismatched_measurement() {
+        let expected = ExpectedMeasurement {
+            mrtd: Some(const_hex::encode([0x11; 48])),
+            rtmr3: Some(const_hex::encode([0x34; 48])),
+        };
+        let err = measurements().check(&[expected]).unwrap_err();
+
+        assert!(matches!(err, QuoteError::MeasurementMismatch { .. }));
+        assert!(err.to_string().contains(&const_hex::encode([0x33; 48])));
+
+        let malformed = ExpectedMeasurement {
+            mrtd: Some("abcd".into()),
+            rtmr3: None,
+        };
+        assert!(matches!(malformed.validate(), Err(QuoteError::Measurements(_))));
+    }
+
+    #[test]
+    fn test_unknown_status_is_unrecognized() {
+        let status: TcbStatus = serde_json::from_str(r#""TDRelaunchAdvised""#).unwrap();
+        assert_eq!(status, TcbStatus::Unrecognized);
+
+        let unverified = serde_json::to_value(TcbStatus::Unverified).unwrap();
+        assert_eq!(unverified, "unverified");
+    }
+}
@@ -14,13 +14,16 @@ import os
 import uuid
 import requests
 import json
-from typing import Tuple
+from typing import Optional, Tuple
 import hashlib
 
 from coincurve import PrivateKey, PublicKey
 from cryptography.hazmat.primitives.ciphers.aead import AESGCMSIV
 from cryptography.hazmat.primitives import hashes
 from cryptography.hazmat.primitives.kdf.hkdf import HKDF
+from cryptography.hazmat.primitives.asymmetric import ec
+from cryptography.hazmat.primitives.asymmetric.utils import encode_dss_signature
+from cryptography.exceptions import InvalidSignature
 from cryptography.hazmat.backends import default_backend
 
 
@@ -55,18 +58,123 @@ def derive_shared_secret(
     return derived_key
 
 
+NONCE_TAG = b"XFN_NONCE_V1"
+
+
 def derive_nonce(data: bytes) -> bytes:
-    """Derive a 12-byte nonce using BLAKE3 (or SHA256 fallback)."""
+    """Derive a 12-byte nonce as BLAKE3(NONCE_TAG || data) (or SHA256 fallback)."""
     try:
         import blake3
-        hash_result = blake3.blake3(data).digest()
+        hash_result = blake3.blake3(NONCE_TAG + data).digest()
     except ImportError:
         # Fallback to SHA256 if blake3 not available
-        hash_result = hashlib.sha256(data).digest()
+        hash_result = hashlib.sha256(NONCE_TAG + data).digest()
     
     return hash_result[:12]
 
 
+SEALED_VERSION = 1
+
+
+def open_sealed(cipher, sealed_hex: str) -> bytes:
+    """Decrypt a sealed blob `version || nonce (12 bytes) || ciphertext` (hex)."""
+    sealed = bytes.fromhex(sealed_hex)
+    if not sealed or sealed[0] != SEALED_VERSION:
+        raise ValueError(f"unsupported sealed blob version: {sealed[:1].hex()}")
+    return cipher.decrypt(sealed[1:13], sealed[13:], None)
+
+
+QUOTE_BINDING_TAG = b"XFN_QUOTE_BINDING_V1"
+
+
+def decode_quote(quote_hex: str, quote_compression: Optional[str] = None) -> bytes:
+    """
+    Raw quote bytes of a response's `quote`, decompressed per its `quote_compression`.
+    Must match QuoteCompression::decode() in the Rust implementation.
+    """
+    quote = bytes.fromhex(quote_hex)
+    if quote_compression is None:
+        return quote
+    if quote_compression == "zstd":
+        import zstandard
+
+        return zstandard.ZstdDecompressor().decompressobj().decompress(quote)
+    raise ValueError(f"unsupported quote compression: {quote_compression}")
+
+
+def verify_quote_binding(
+    session_pk_hex: str,
+    quote_hex: str,
+    session_id: uuid.UUID,
+    signature_hex: str,
+    quote_compression: Optional[str] = None,
+) -> bool:
+    """
+    Check the session key signed (quote, session_id), so the quote was issued to this session.
+    The signature covers the raw quote, so a compressed `quote_hex` is decompressed first.
+    Must match verify_quote_binding() in the Rust implementation.
+    """
+    session_pk = ec.EllipticCurvePublicKey.from_encoded_point(
+        ec.SECP256K1(), bytes.fromhex(session_pk_hex)
+    )
+    signature = bytes.fromhex(signature_hex)
+    der = encode_dss_signature(
+        int.from_bytes(signature[:32], "big"), int.from_bytes(signature[32:], "big")
+    )
+    message = QUOTE_BINDING_TAG + session_id.bytes + decode_quote(quote_hex, quote_compression)
+
+    try:
+        session_pk.verify(der, message, ec.ECDSA(hashes.SHA256()))
+        return True
+    except InvalidSignature:
+        return False
+
+
+def tool_results_root(tool_results: list, new_hasher) -> bytes:
+    """
+    Merkle root over tool results.
+    Must match ToolResultsMerkleTree in the Rust implementation.
+    """
+    if not tool_results:
+        return bytes(32)
+
+    level = []
+    for result in tool_results:
+        # Compact responses carry the leaf hash instead of the raw result
+        if result.get("result_hash"):
+            level.append(bytes.fromhex(result["result_hash"]))
+            continue
+        leaf = new_hasher()
+        leaf.update(b"\x00")
+        leaf.update(uuid.UUID(result["call_id"]).bytes)
+        leaf.update(bytes([1 if result["success"] else 0]))
+        leaf.update(bytes([1 if result.get("would_reject") else 0]))
+        violations = result.get("report_only_violations", [])
+        leaf.update(len(violations).to_bytes(8, "little"))
+        for violation in violations:
+            encoded = violation.encode()
+            leaf.update(len(encoded).to_bytes(8, "little"))
+            leaf.update(encoded)
+        leaf.update(result["result"].encode())
+        level.append(leaf.digest())
+
+    while len(level) > 1:
+        next_level = []
+        for i in range(0, len(level), 2):
+            if i + 1 < len(level):
+                node = new_hasher()
+                node.update(b"\x01")
+                node.update(level[i])
+                node.update(level[i + 1])
+                next_level.append(node.digest())
+            else:
+                # Odd node is promoted unchanged
+                next_level.append(level[i])
+        level = next_level
+
+    return level[0]
+
+
 def hash_execution(execution: dict) -> str:
     """
     Hash an agent execution to verify integrity.
@@ -74,20 +182,27 @@ def hash_execution(execution: dict) -> str:
     """
     try:
         import blake3
-        hasher = blake3.blake3()
+        new_hasher = blake3.blake3
     except ImportError:
         # Fallback to SHA256 if blake3 not available
         import hashlib
-        hasher = hashlib.sha256()
+        new_hasher = hashlib.sha256
+    hasher = new_hasher()
     
-    # Hash session ID
+    # Hash session ID and the execution's sequence number
     session_id = uuid.UUID(execution["session_id"])
     hasher.update(session_id.bytes)
+    hasher.update(execution.get("sequence", 0).to_bytes(8, "little"))
     
     # Hash plan
     plan = execution["plan"]
     hasher.update(plan["system_prompt"].encode())
     hasher.update(plan["user_query"].encode())
+    for turn in plan.get("history", []):
+        turn = turn.encode()
+        hasher.update(b"history")
+        hasher.update(len(turn).to_bytes(8, "little"))
+        hasher.update(turn)
     
     for step in plan["thought_process"]:
         hasher.update(step["content"].encode())
@@ -98,16 +213,15 @@ def hash_execution(execution: dict) -> str:
         hasher.update(call_id.bytes)
         hasher.update(call["tool_name"].encode())
         hasher.update(call["arguments"].encode())
+        # Steps start at 1, so 0 stands for a call that follows no thought
+        hasher.update((call.get("thought_step") or 0).to_bytes(8, "little"))
     
-    # Hash tool results
-    for result in execution["tool_results"]:
-        call_id = uuid.UUID(result["call_id"])
-        hasher.update(call_id.bytes)
-        hasher.update(bytes([1 if result["success"] else 0]))
-        hasher.update(result["result"].encode())
+    # Hash tool results via their Merkle root
+    hasher.update(tool_results_root(execution["tool_results"], new_hasher))
     
-    # Hash final response
+    # Hash final response and the model that wrote it
     hasher.update(execution["final_response"].encode())
+    hasher.update(execution.get("model", "").encode())
     
     return hasher.hexdigest()
 
@@ -154,7 +268,8 @@ def print_execution_details(execution: dict, indent: int = 2):
 def create_session_keypair(
     base_url: str, 
     user_public_key: str, 
-    verifiable: bool = False
+    verifiable: bool = False,
+    challenge: Optional[bytes] = None
 ) -> Tuple[str, str, str]:
     """
     Create a session keypair with the hypervisor.
@@ -163,16 +278,17 @@ def create_session_keypair(
         base_url: Hypervisor base URL
         user_public_key: User's hex-encoded public key
         verifiable: If True, use /verifiable/encrypt/create_keypair to get attestation
+        challenge: Optional nonce (<= 32 bytes) bound into the quote's report_data[32:]
         
     Returns:
         Tuple of (session_pubkey, session_id, quote)
         Note: quote will be empty string if verifiable=False
     """
     endpoint = "/verifiable/encrypt/create_keypair" if verifiable else "/encrypt/create_keypair"
-    response = requests.post(
-        f"{base_url}{endpoint}",
-        json={"pubkey": user_public_key}
-    )
+    body = {"pubkey": user_public_key}
+    if challenge is not None:
+        body["challenge"] = challenge.hex()
+    response = requests.post(f"{base_url}{endpoint}", json=body)
     
     if not response.ok:
         try:
@@ -249,19 +365,27 @@ class CryptoAgentClient:
         data = response.json()
         
         # Decrypt the response
-        response_nonce = bytes.fromhex(data["response_nonce"])
-        encrypted_response = bytes.fromhex(data["encrypted_response"])
-        decrypted_response = self.cipher.decrypt(response_nonce, encrypted_response, None)
+        decrypted_response = open_sealed(self.cipher, data["encrypted_response"])
         
         result = {
             "response": decrypted_response.decode(),
+            "answerable": data["answerable"],
+            "reason": data["reason"],
             "execution_time_ms": data["execution_time_ms"],
             "execution_hash": data["execution_hash"],
+            "redacted": data["redacted"],
             "execution": data["execution"]
         }
         
         if verifiable:
             result["quote"] = data["quote"]
+            result["quote_bound"] = verify_quote_binding(
+                self.session_pk,
+                data["quote"],
+                self.session_id,
+                data["quote_signature"],
+                data.get("quote_compression"),
+            )
             result["compliance"] = data["compliance"]
         
         return result
@@ -296,11 +420,14 @@ def main():
             try:
                 result = client.query_agent(query, verifiable=True)
                 print(f"Response: {result['response']}")
+                if not result['answerable']:
+                    print(f"Unanswerable: {result['reason']}")
                 print(f"Execution time: {result['execution_time_ms']}ms")
                 print(f"Execution hash: {result['execution_hash']}")
                 print(f"Compliance: {result['compliance']['compliant']}")
                 print(f"Compliance reason: {result['compliance']['reason']}")
                 print(f"Quote length: {len(result['quote'])} chars")
+                print(f"Quote bound to session: {result['quote_bound']}")
                 
                 # Print execution details
                 print(f"\nExecution Details:")
@@ -308,6 +435,9 @@ def main():
                 
                 # Verify hash
                 print(f"\nHash Verification:")
+                if result['redacted']:
+                    print(f"  Skipped: thoughts or system prompt were redacted by the server")
+                    continue
                 computed_hash = hash_execution(result['execution'])
                 matches = computed_hash == result['execution_hash']
                 status = "✓ VERIFIED" if matches else "✗ MISMATCH"